@@ -1,3 +1,6 @@
 fn main() {
-    slint_build::compile("ui/main.slint").unwrap();
+    // Translations (lang/<language>/LC_MESSAGES/lado.po) are compiled in
+    let config = slint_build::CompilerConfiguration::new().with_bundled_translations("lang");
+    slint_build::compile_with_config("ui/main.slint", config).unwrap();
+    println!("cargo:rerun-if-changed=lang");
 }
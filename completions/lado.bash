@@ -14,9 +14,46 @@ _lado_completions() {
         return 0
     fi
 
+    if [[ "$prev" == "--export" ]]; then
+        COMPREPLY=($(compgen -W "html pdf patch" -- "$cur"))
+        return 0
+    fi
+
+    if [[ "$prev" == "--file" || "$prev" == "--config" || "$prev" == "--output" || "$prev" == "-o" || "$prev" == "--log-file" ]]; then
+        COMPREPLY=($(compgen -f -- "$cur"))
+        return 0
+    fi
+
+    # --files and --dirs take two paths each
+    local prev2="${COMP_WORDS[COMP_CWORD-2]}"
+    if [[ "$prev" == "--files" || "$prev2" == "--files" ]]; then
+        COMPREPLY=($(compgen -f -- "$cur"))
+        return 0
+    fi
+
+    if [[ "$prev" == "--dirs" || "$prev2" == "--dirs" ]]; then
+        COMPREPLY=($(compgen -d -- "$cur"))
+        return 0
+    fi
+
+    if [[ "$prev" == "--theme" || "$prev" == "--syntax-theme" ]]; then
+        COMPREPLY=($(compgen -W "dark light solarized-dark solarized-light" -- "$cur"))
+        return 0
+    fi
+
+    if [[ "$prev" == "--renderer" ]]; then
+        COMPREPLY=($(compgen -W "auto skia femtovg software" -- "$cur"))
+        return 0
+    fi
+
+    if [[ "$prev" == "--windowing" ]]; then
+        COMPREPLY=($(compgen -W "auto wayland x11" -- "$cur"))
+        return 0
+    fi
+
     # Options
     if [[ "$cur" == -* ]]; then
-        COMPREPLY=($(compgen -W "--completions --help --version" -- "$cur"))
+        COMPREPLY=($(compgen -W "--stdin --files --dirs --file --line --register-url-handler --completions --offline --no-github --config --config-profile --theme --syntax-theme --font-size --line-wrap --renderer --windowing --export --output --print --check --stat --verbose --log-file --profile --help --version" -- "$cur"))
         return 0
     fi
 
@@ -32,7 +69,15 @@ _lado_completions() {
         prs=$(gh pr list --limit 20 --json number --jq '.[].number' 2>/dev/null | sed 's/^/#/')
     fi
 
-    COMPREPLY=($(compgen -W "$branches $prs" -- "$cur"))
+    # Subcommands only in first position
+    local subcommands=""
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        subcommands="diff pr show stash log recent"
+    elif [[ $COMP_CWORD -eq 2 && "${COMP_WORDS[1]}" == "pr" ]]; then
+        subcommands="list"
+    fi
+
+    COMPREPLY=($(compgen -W "$subcommands $branches $prs" -- "$cur"))
 }
 
 complete -F _lado_completions lado
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+use syntect::highlighting::ThemeSet;
+
+fn main() {
+    dump_themes();
+}
+
+/// Bundle syntect's default themes and our custom ones into a single binary
+/// dump, so no .tmTheme has to be parsed at runtime.
+fn dump_themes() {
+    println!("cargo:rerun-if-changed=themes");
+
+    let mut theme_set = ThemeSet::load_defaults();
+    let doom = ThemeSet::get_theme("themes/doom-solarized-light.tmTheme")
+        .expect("Failed to parse themes/doom-solarized-light.tmTheme");
+    theme_set
+        .themes
+        .insert("Doom Solarized Light".to_string(), doom);
+
+    let out = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("themes.themedump");
+    syntect::dumps::dump_to_uncompressed_file(&theme_set, out).unwrap();
+}
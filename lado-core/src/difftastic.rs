@@ -0,0 +1,234 @@
+//! Structural diffs by [difftastic](https://difftastic.wilfred.me.uk/),
+//! for files where a line diff hides what changed (moved arguments,
+//! re-wrapped expressions). Runs `difft --display json` on a file's two
+//! versions and turns its output into hunks like those of the line diff.
+
+use crate::git::{DiffHunk, DiffLine, DiffLineType};
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Whether `difft` is installed.
+pub fn is_available() -> bool {
+    Command::new("difft")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// The structural diff of `path` from `old` to `new` contents.
+#[tracing::instrument(skip(old, new))]
+pub fn diff(path: &str, old: &[u8], new: &[u8]) -> Result<Vec<DiffHunk>> {
+    // difft picks the language by file name, so both sides keep it
+    let scratch = Scratch::new()?;
+    let name = Path::new(path)
+        .file_name()
+        .ok_or_else(|| anyhow!("{} has no file name", path))?;
+    let old_path = scratch.write("old", name, old)?;
+    let new_path = scratch.write("new", name, new)?;
+
+    let output = Command::new("difft")
+        .args(["--display", "json", "--color", "never"])
+        .arg(&old_path)
+        .arg(&new_path)
+        // Not wherever the process happens to be
+        .current_dir(&scratch.dir)
+        // The JSON output is still marked unstable
+        .env("DFT_UNSTABLE", "yes")
+        .output()
+        .context("Failed to execute difft. Is difftastic installed?")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "difft failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    parse(
+        &String::from_utf8_lossy(&output.stdout),
+        &String::from_utf8_lossy(old),
+        &String::from_utf8_lossy(new),
+    )
+}
+
+/// Turn difft's JSON into hunks, one per chunk. difft only lists changed
+/// lines (by number), so their text comes from `old` and `new`.
+fn parse(json: &str, old: &str, new: &str) -> Result<Vec<DiffHunk>> {
+    let value: Value = serde_json::from_str(json).context("difft printed invalid JSON")?;
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    let chunks = value["chunks"].as_array().map_or(&[][..], Vec::as_slice);
+    if chunks.is_empty() {
+        return Ok(vec![DiffHunk {
+            header: "No syntactic changes".to_string(),
+            old_start: 0,
+            old_lines: 0,
+            new_start: 0,
+            new_lines: 0,
+            lines: Vec::new(),
+        }]);
+    }
+
+    let mut hunks = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        let mut lines = Vec::new();
+        for entry in chunk.as_array().into_iter().flatten() {
+            let lhs = side(&entry["lhs"]);
+            let rhs = side(&entry["rhs"]);
+            let changed = lhs.is_some_and(|(_, c)| c) || rhs.is_some_and(|(_, c)| c);
+            if !changed {
+                // Matched on both sides without a change
+                if let (Some((old_num, _)), Some((new_num, _))) = (lhs, rhs) {
+                    lines.push(line(
+                        DiffLineType::Context,
+                        Some(old_num),
+                        Some(new_num),
+                        &new_lines,
+                    ));
+                }
+                continue;
+            }
+            if let Some((num, _)) = lhs {
+                lines.push(line(DiffLineType::Remove, Some(num), None, &old_lines));
+            }
+            if let Some((num, _)) = rhs {
+                lines.push(line(DiffLineType::Add, None, Some(num), &new_lines));
+            }
+        }
+        hunks.push(hunk(lines));
+    }
+    Ok(hunks)
+}
+
+/// Line number (1-based) of one side of an entry, and whether anything on
+/// it changed.
+fn side(value: &Value) -> Option<(u32, bool)> {
+    let number = value["line_number"].as_u64()?;
+    let changed = value["changes"]
+        .as_array()
+        .is_some_and(|changes| !changes.is_empty());
+    Some((number as u32 + 1, changed))
+}
+
+fn line(
+    line_type: DiffLineType,
+    old_line_num: Option<u32>,
+    new_line_num: Option<u32>,
+    text: &[&str],
+) -> DiffLine {
+    let num = new_line_num.or(old_line_num).unwrap_or(1);
+    DiffLine {
+        line_type,
+        old_line_num,
+        new_line_num,
+        content: text
+            .get(num as usize - 1)
+            .copied()
+            .unwrap_or_default()
+            .into(),
+        comment: None,
+    }
+}
+
+/// A hunk with a header like git's for `lines`.
+fn hunk(lines: Vec<DiffLine>) -> DiffHunk {
+    let range = |nums: Vec<u32>| match (nums.iter().min(), nums.iter().max()) {
+        (Some(&min), Some(&max)) => (min, max - min + 1),
+        _ => (0, 0),
+    };
+    let (old_start, old_lines) = range(lines.iter().filter_map(|l| l.old_line_num).collect());
+    let (new_start, new_lines) = range(lines.iter().filter_map(|l| l.new_line_num).collect());
+    DiffHunk {
+        header: format!(
+            "@@ -{},{} +{},{} @@",
+            old_start, old_lines, new_start, new_lines
+        ),
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        lines,
+    }
+}
+
+/// A temporary directory for the two versions, removed when dropped.
+struct Scratch {
+    dir: PathBuf,
+}
+
+impl Scratch {
+    fn new() -> Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "lado-difft-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    fn write(&self, side: &str, name: &std::ffi::OsStr, content: &[u8]) -> Result<PathBuf> {
+        let dir = self.dir.join(side);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(name);
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "fn main() {\n    run(a, b);\n}\n";
+    const NEW: &str = "fn main() {\n    run(b, a);\n    done();\n}\n";
+
+    #[test]
+    fn test_parse() {
+        let json = r#"{
+            "chunks": [[
+                {"lhs": {"line_number": 1, "changes": [{"start": 8, "end": 9, "content": "a", "highlight": "normal"}]},
+                 "rhs": {"line_number": 1, "changes": [{"start": 11, "end": 12, "content": "a", "highlight": "normal"}]}},
+                {"rhs": {"line_number": 2, "changes": [{"start": 4, "end": 8, "content": "done", "highlight": "normal"}]}}
+            ]],
+            "language": "Rust",
+            "path": "main.rs",
+            "status": "changed"
+        }"#;
+        let hunks = parse(json, OLD, NEW).unwrap();
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].header, "@@ -2,1 +2,2 @@");
+        let lines: Vec<(DiffLineType, &str)> = hunks[0]
+            .lines
+            .iter()
+            .map(|l| (l.line_type, l.content.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (DiffLineType::Remove, "    run(a, b);"),
+                (DiffLineType::Add, "    run(b, a);"),
+                (DiffLineType::Add, "    done();"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_without_changes() {
+        let json = r#"{"language": "Rust", "path": "main.rs", "status": "unchanged"}"#;
+        let hunks = parse(json, OLD, OLD).unwrap();
+        assert_eq!(hunks[0].header, "No syntactic changes");
+        assert!(hunks[0].lines.is_empty());
+    }
+}
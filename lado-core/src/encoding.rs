@@ -0,0 +1,58 @@
+//! Files that aren't UTF-8: a Latin-1 config file or a Shift-JIS source
+//! would otherwise show up as rows of replacement characters. Their encoding
+//! is guessed from a byte order mark or, failing that, by `chardetng`, and
+//! they are decoded with `encoding_rs` before they are diffed.
+
+use encoding_rs::{Encoding, UTF_8};
+use std::borrow::Cow;
+
+/// The encoding of a file, guessed from the contents of all its sides.
+/// `None` if they are UTF-8 (or empty), or binary: NUL bytes without a byte
+/// order mark that could explain them.
+pub fn detect(contents: &[&[u8]]) -> Option<&'static str> {
+    if let Some((encoding, _)) = contents.iter().find_map(|bytes| Encoding::for_bom(bytes)) {
+        return (encoding != UTF_8).then(|| encoding.name());
+    }
+    let utf8 = contents.iter().all(|b| std::str::from_utf8(b).is_ok());
+    if utf8 || contents.iter().any(|bytes| bytes.contains(&0)) {
+        return None;
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    for bytes in contents {
+        detector.feed(bytes, false);
+    }
+    detector.feed(&[], true);
+    Some(detector.guess(None, false).name())
+}
+
+/// `bytes` as text, decoded from `encoding` (a name `detect` returned) or
+/// from UTF-8 if there is none. A byte order mark is dropped.
+pub fn decode<'a>(bytes: &'a [u8], encoding: Option<&str>) -> Cow<'a, str> {
+    match encoding.and_then(|name| Encoding::for_label(name.as_bytes())) {
+        Some(encoding) => encoding.decode(bytes).0,
+        None => String::from_utf8_lossy(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(&[b"plain ascii\n", "gr\u{fc}n\n".as_bytes()]), None);
+        assert_eq!(detect(&[b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\xff"]), None);
+        assert_eq!(detect(&[b"", b"\xff\xfea\0b\0"]), Some("UTF-16LE"));
+
+        let text = "日本語のテキストです。これは文字コードのテストです。\n";
+        let (shift_jis, _, _) = encoding_rs::SHIFT_JIS.encode(text);
+        let encoding = detect(&[&shift_jis]);
+        assert_eq!(encoding, Some("Shift_JIS"));
+        assert_eq!(decode(&shift_jis, encoding), text);
+
+        let latin1 = b"caf\xe9 cr\xe8me br\xfbl\xe9e\n";
+        let encoding = detect(&[b"plain ascii\n", latin1]);
+        assert_eq!(encoding, Some("windows-1252"));
+        assert_eq!(decode(latin1, encoding), "café crème brûlée\n");
+    }
+}
@@ -0,0 +1,95 @@
+//! `exclude` globs of the config file: files like `vendor/**`, `*.snap` or
+//! `pnpm-lock.yaml` that are left out of the file tree and `--stat` unless
+//! the toolbar reveals them.
+//!
+//! As in `.gitignore`, a glob without a `/` matches the file name in any
+//! directory; one with a `/` matches the whole path.
+
+use crate::git::FileChange;
+use glob::{MatchOptions, Pattern};
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// The compiled `exclude` globs
+#[derive(Debug, Default)]
+pub struct ExcludeFilter {
+    patterns: Vec<Pattern>,
+}
+
+impl ExcludeFilter {
+    /// Compile `globs`, warning about (and skipping) invalid ones.
+    pub fn new(globs: &[String]) -> Self {
+        let mut filter = Self::default();
+        for glob in globs {
+            filter.add(glob);
+        }
+        filter
+    }
+
+    /// Exclude the files matching `glob` as well, warning if it's invalid.
+    pub fn add(&mut self, glob: &str) {
+        // A leading slash anchors the glob at the root, as in .gitignore
+        let glob = glob.strip_prefix('/').unwrap_or(glob);
+        match Pattern::new(glob) {
+            Ok(pattern) => self.patterns.push(pattern),
+            Err(e) => eprintln!("Warning: Invalid exclude glob {:?}: {}", glob, e),
+        }
+    }
+
+    /// Whether `path` matches one of the globs.
+    pub fn is_excluded(&self, path: &str) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        self.patterns.iter().any(|pattern| {
+            let subject = if pattern.as_str().contains('/') {
+                path
+            } else {
+                name
+            };
+            pattern.matches_with(subject, MATCH_OPTIONS)
+        })
+    }
+
+    /// `files` without the excluded ones, and how many those were.
+    pub fn filter(&self, files: &[FileChange]) -> (Vec<FileChange>, usize) {
+        let kept: Vec<FileChange> = files
+            .iter()
+            .filter(|f| !self.is_excluded(&f.path))
+            .cloned()
+            .collect();
+        let excluded = files.len() - kept.len();
+        (kept, excluded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_excluded() {
+        let filter = ExcludeFilter::new(&[
+            "vendor/**".to_string(),
+            "*.snap".to_string(),
+            "pnpm-lock.yaml".to_string(),
+            "/docs/*.md".to_string(),
+        ]);
+        assert!(filter.is_excluded("vendor/github.com/x/y.go"));
+        assert!(filter.is_excluded("src/__snapshots__/app.snap"));
+        assert!(filter.is_excluded("pnpm-lock.yaml"));
+        assert!(filter.is_excluded("packages/web/pnpm-lock.yaml"));
+        assert!(filter.is_excluded("docs/intro.md"));
+        assert!(!filter.is_excluded("docs/api/intro.md"));
+        assert!(!filter.is_excluded("src/vendor.rs"));
+        assert!(!filter.is_excluded("src/main.rs"));
+    }
+
+    #[test]
+    fn test_invalid_globs_are_skipped() {
+        let filter = ExcludeFilter::new(&["[".to_string(), "*.lock".to_string()]);
+        assert!(filter.is_excluded("Cargo.lock"));
+    }
+}
@@ -0,0 +1,196 @@
+//! Unchanged regions of a file its diff leaves out, shown folded as
+//! `… 37 unchanged lines …` above each hunk and unfolded on request.
+
+use crate::git::{DiffHunk, DiffLine, DiffLineType};
+
+/// Unchanged lines above a hunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fold {
+    /// Index of the hunk the lines are above
+    pub hunk: usize,
+    /// First line in the old and the new version of the file
+    pub old_start: u32,
+    pub new_start: u32,
+    pub lines: u32,
+}
+
+/// First line of a hunk's side; an empty side starts after its line
+fn first_line(start: u32, lines: u32) -> u32 {
+    if lines == 0 {
+        start + 1
+    } else {
+        start
+    }
+}
+
+/// The unchanged regions above the hunks of a line diff, in order. Lines
+/// below the last hunk aren't included, as the diff doesn't tell how many
+/// there are.
+pub fn folds(hunks: &[DiffHunk]) -> Vec<Fold> {
+    let mut next_new = 1;
+    let mut folds = Vec::new();
+    for (index, hunk) in hunks.iter().enumerate() {
+        let first_new = first_line(hunk.new_start, hunk.new_lines);
+        let first_old = first_line(hunk.old_start, hunk.old_lines);
+        if let Some(lines) = first_new.checked_sub(next_new).filter(|&n| n > 0) {
+            folds.push(Fold {
+                hunk: index,
+                old_start: first_old.saturating_sub(lines),
+                new_start: next_new,
+                lines,
+            });
+        }
+        next_new = first_new + hunk.new_lines;
+    }
+    folds
+}
+
+/// Show the lines of `fold` as context, taken from `new_text` (the new
+/// version of the file, tabs expanded as in the hunks), merging the hunks
+/// around them.
+pub fn unfold(hunks: &mut Vec<DiffHunk>, fold: &Fold, new_text: &str) {
+    if fold.hunk >= hunks.len() {
+        return;
+    }
+    let context: Vec<DiffLine> = new_text
+        .split('\n')
+        .skip(fold.new_start as usize - 1)
+        .take(fold.lines as usize)
+        .zip(0..)
+        .map(|(content, offset)| DiffLine {
+            line_type: DiffLineType::Context,
+            old_line_num: Some(fold.old_start + offset),
+            new_line_num: Some(fold.new_start + offset),
+            content: content.into(),
+            comment: None,
+        })
+        .collect();
+    let lines = context.len() as u32;
+
+    let next = hunks.remove(fold.hunk);
+    let merged = match fold.hunk.checked_sub(1) {
+        Some(above) => {
+            let mut hunk = hunks.remove(above);
+            hunk.old_start = first_line(hunk.old_start, hunk.old_lines);
+            hunk.new_start = first_line(hunk.new_start, hunk.new_lines);
+            hunk.old_lines += lines + next.old_lines;
+            hunk.new_lines += lines + next.new_lines;
+            hunk.lines.extend(context);
+            hunk.lines.extend(next.lines);
+            hunk
+        }
+        None => {
+            let mut hunk = next;
+            hunk.old_start = fold.old_start;
+            hunk.new_start = fold.new_start;
+            hunk.old_lines += lines;
+            hunk.new_lines += lines;
+            hunk.lines.splice(0..0, context);
+            hunk
+        }
+    };
+    let index = fold.hunk.saturating_sub(1);
+    hunks.insert(index, merged);
+    let hunk = &mut hunks[index];
+    hunk.header = header(hunk);
+}
+
+/// `@@ -1,5 +1,6 @@` for the lines of `hunk`, keeping the section heading
+/// (e.g. the enclosing function) of its current header
+fn header(hunk: &DiffHunk) -> String {
+    let section = hunk
+        .header
+        .strip_prefix("@@")
+        .and_then(|rest| rest.split_once("@@"))
+        .map_or("", |(_, section)| section.trim_end());
+    format!(
+        "@@ -{},{} +{},{} @@{}",
+        hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines, section
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(header: &str, lines: &[(DiffLineType, Option<u32>, Option<u32>)]) -> DiffHunk {
+        let numbers: Vec<u32> = header
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|n| n.parse().ok())
+            .collect();
+        DiffHunk {
+            header: header.to_string(),
+            old_start: numbers[0],
+            old_lines: numbers[1],
+            new_start: numbers[2],
+            new_lines: numbers[3],
+            lines: lines
+                .iter()
+                .map(|&(line_type, old_line_num, new_line_num)| DiffLine {
+                    line_type,
+                    old_line_num,
+                    new_line_num,
+                    content: format!("line {}", new_line_num.or(old_line_num).unwrap()).into(),
+                    comment: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_fold_and_unfold() {
+        use DiffLineType::{Add, Context, Remove};
+        let new_text: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        let mut hunks = vec![
+            // Line 5 changed
+            hunk(
+                "@@ -4,3 +4,3 @@ fn main() {",
+                &[
+                    (Context, Some(4), Some(4)),
+                    (Remove, Some(5), None),
+                    (Add, None, Some(5)),
+                    (Context, Some(6), Some(6)),
+                ],
+            ),
+            // Old line 12 removed
+            hunk(
+                "@@ -11,3 +11,2 @@",
+                &[
+                    (Context, Some(11), Some(11)),
+                    (Remove, Some(12), None),
+                    (Context, Some(13), Some(12)),
+                ],
+            ),
+        ];
+        let found = folds(&hunks);
+        assert_eq!(
+            found,
+            [
+                Fold {
+                    hunk: 0,
+                    old_start: 1,
+                    new_start: 1,
+                    lines: 3,
+                },
+                Fold {
+                    hunk: 1,
+                    old_start: 7,
+                    new_start: 7,
+                    lines: 4,
+                },
+            ]
+        );
+
+        unfold(&mut hunks, &found[1], &new_text);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].header, "@@ -4,10 +4,9 @@ fn main() {");
+        assert_eq!(hunks[0].lines[4].content.as_str(), "line 7");
+        assert_eq!(hunks[0].lines[4].old_line_num, Some(7));
+        assert_eq!(folds(&hunks), [found[0]]);
+
+        unfold(&mut hunks, &found[0], &new_text);
+        assert_eq!(hunks[0].header, "@@ -1,13 +1,12 @@ fn main() {");
+        assert_eq!(hunks[0].lines[0].content.as_str(), "line 1");
+        assert_eq!(folds(&hunks), []);
+    }
+}
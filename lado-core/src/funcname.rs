@@ -0,0 +1,164 @@
+//! The function (or class, `impl`, module …) a hunk is in, shown after the
+//! line ranges of its header like `git diff -p` does:
+//! `@@ -10,4 +10,6 @@ fn parse_args() -> Args {`.
+//!
+//! git's default funcname pattern takes the closest line above the hunk that
+//! starts at the left margin, which for a method is usually its `impl` or
+//! `class` line. Files of a language with a tree-sitter grammar get the
+//! innermost section around the hunk instead; the others keep the context
+//! libgit2 wrote.
+
+use tree_sitter::{Language, Node, Parser, Point, Tree};
+
+/// Larger files aren't parsed, to keep diffing them fast
+const MAX_BYTES: usize = 1_000_000;
+
+/// Node kinds that start a section, across the grammars below
+const SECTION_KINDS: &[&str] = &[
+    // Rust
+    "function_item",
+    "impl_item",
+    "trait_item",
+    "mod_item",
+    // Python, C, C++, Bash
+    "function_definition",
+    "class_definition",
+    "class_specifier",
+    "namespace_definition",
+    // JavaScript, TypeScript, Go, Java
+    "function_declaration",
+    "generator_function_declaration",
+    "method_definition",
+    "method_declaration",
+    "constructor_declaration",
+    "class_declaration",
+    "abstract_class_declaration",
+    "interface_declaration",
+    "enum_declaration",
+    // Ruby
+    "method",
+    "singleton_method",
+    "class",
+    "module",
+];
+
+/// The grammar for files named `path`, if there is one with sections
+fn language(path: &str) -> Option<Language> {
+    let ext = path.rsplit_once('.')?.1;
+    let language = match ext {
+        "rs" => tree_sitter_rust::LANGUAGE,
+        "py" | "pyi" => tree_sitter_python::LANGUAGE,
+        "js" | "mjs" | "cjs" | "jsx" => tree_sitter_javascript::LANGUAGE,
+        "ts" | "mts" | "cts" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+        "tsx" => tree_sitter_typescript::LANGUAGE_TSX,
+        "go" => tree_sitter_go::LANGUAGE,
+        "c" | "h" => tree_sitter_c::LANGUAGE,
+        "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => tree_sitter_cpp::LANGUAGE,
+        "sh" | "bash" => tree_sitter_bash::LANGUAGE,
+        "java" => tree_sitter_java::LANGUAGE,
+        "rb" => tree_sitter_ruby::LANGUAGE,
+        _ => return None,
+    };
+    Some(language.into())
+}
+
+/// The sections of one version of a file
+pub struct Sections<'a> {
+    source: &'a str,
+    tree: Tree,
+}
+
+impl<'a> Sections<'a> {
+    /// Parse `source`, the contents of `path`. `None` if there is no grammar
+    /// for it or it is too large.
+    pub fn parse(path: &str, source: &'a str) -> Option<Self> {
+        if source.len() > MAX_BYTES {
+            return None;
+        }
+        let mut parser = Parser::new();
+        parser.set_language(&language(path)?).ok()?;
+        let tree = parser.parse(source, None)?;
+        Some(Self { source, tree })
+    }
+
+    /// The first line, trimmed, of the innermost section that starts above
+    /// `line` (1-based) and goes on past it.
+    pub fn around(&self, line: u32) -> Option<&'a str> {
+        let row = (line as usize).checked_sub(1)?;
+        let text = self.source.lines().nth(row)?;
+        let column = text.len() - text.trim_start().len();
+        let point = Point { row, column };
+        let node = self
+            .tree
+            .root_node()
+            .descendant_for_point_range(point, point)?;
+        let section = std::iter::successors(Some(node), Node::parent).find(|node| {
+            node.is_named()
+                && node.start_position().row < row
+                && SECTION_KINDS.contains(&node.kind())
+        })?;
+        let first = self.source.lines().nth(section.start_position().row)?;
+        Some(first.trim())
+    }
+}
+
+/// A hunk header split into its line ranges (`@@ -1,3 +1,4 @@`) and the
+/// context after them, which is empty if there is none.
+pub fn split_header(header: &str) -> (&str, &str) {
+    let header = header.trim_end();
+    match header.match_indices("@@").nth(1) {
+        Some((end, _)) => {
+            let (ranges, context) = header.split_at(end + 2);
+            (ranges, context.trim_start())
+        }
+        None => (header, ""),
+    }
+}
+
+/// `header` with `context` in place of the context git wrote.
+pub fn with_context(header: &str, context: &str) -> String {
+    format!("{} {}\n", split_header(header).0, context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_around() {
+        let source = "\
+use std::fmt;
+
+impl Parser {
+    fn new() -> Self {
+        let a = 1;
+        let b = 2;
+        Self { a, b }
+    }
+}
+";
+        let sections = Sections::parse("src/parser.rs", source).unwrap();
+        assert_eq!(sections.around(6), Some("fn new() -> Self {"));
+        // The line a section starts on belongs to the one around it
+        assert_eq!(sections.around(4), Some("impl Parser {"));
+        assert_eq!(sections.around(1), None);
+
+        let source = "class Shape:\n    def area(self):\n        return 0\n";
+        let sections = Sections::parse("shape.py", source).unwrap();
+        assert_eq!(sections.around(3), Some("def area(self):"));
+        assert!(Sections::parse("notes.txt", source).is_none());
+    }
+
+    #[test]
+    fn test_split_header() {
+        assert_eq!(
+            split_header("@@ -1,3 +1,4 @@ impl Parser {\n"),
+            ("@@ -1,3 +1,4 @@", "impl Parser {")
+        );
+        assert_eq!(split_header("@@ -1 +1 @@\n"), ("@@ -1 +1 @@", ""));
+        assert_eq!(
+            with_context("@@ -1 +1 @@ impl Parser {\n", "fn new() -> Self {"),
+            "@@ -1 +1 @@ fn new() -> Self {\n"
+        );
+    }
+}
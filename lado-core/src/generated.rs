@@ -0,0 +1,149 @@
+//! Generated files, as GitHub's linguist tells them apart: those
+//! `.gitattributes` marks `linguist-generated`, and otherwise lock files,
+//! protobuf output, minified code and bundles, and files that say so in
+//! their first lines. Their diffs are rarely worth reading line by line.
+
+use crate::git::{DiffHunk, DiffLineType};
+
+/// File names of lock files, written by package managers
+const LOCK_FILES: [&str; 11] = [
+    "Cargo.lock",
+    "Gemfile.lock",
+    "Pipfile.lock",
+    "composer.lock",
+    "flake.lock",
+    "go.sum",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "uv.lock",
+    "yarn.lock",
+];
+
+/// Endings of file names compilers and minifiers write
+const GENERATED_SUFFIXES: [&str; 12] = [
+    ".min.js",
+    ".min.css",
+    "-min.js",
+    ".js.map",
+    ".css.map",
+    ".pb.go",
+    ".pb.cc",
+    ".pb.h",
+    ".pb.swift",
+    "_pb2.py",
+    "_pb2_grpc.py",
+    ".designer.cs",
+];
+
+/// What generated files say about themselves in their first lines, in
+/// lower case
+const MARKERS: [&str; 5] = [
+    "do not edit",
+    "@generated",
+    "auto-generated",
+    "autogenerated",
+    "generated by",
+];
+
+/// Lines of a file looked through for a marker
+const MARKER_LINES: u32 = 5;
+
+/// Average line length above which JavaScript or CSS is minified
+const MINIFIED_LINE_LENGTH: usize = 110;
+
+/// Whether the file at `path`, changed by `hunks`, is generated.
+/// `attribute` is its `linguist-generated` attribute if `.gitattributes`
+/// sets (or unsets) it, which goes before any heuristic.
+pub fn is_generated(path: &str, attribute: Option<bool>, hunks: &[DiffHunk]) -> bool {
+    if let Some(generated) = attribute {
+        return generated;
+    }
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if LOCK_FILES.contains(&name) || GENERATED_SUFFIXES.iter().any(|s| name.ends_with(s)) {
+        return true;
+    }
+    let bundled = ["js", "mjs", "cjs", "css"]
+        .iter()
+        .any(|ext| name.ends_with(&format!(".{}", ext)));
+    if bundled && (path.starts_with("dist/") || path.contains("/dist/")) {
+        return true;
+    }
+    has_marker(hunks) || (bundled && is_minified(hunks))
+}
+
+/// Whether the first lines of the file, if the diff has them, say it is
+/// generated. Either side will do, for deleted files.
+fn has_marker(hunks: &[DiffHunk]) -> bool {
+    let first_lines = hunks.iter().flat_map(|hunk| &hunk.lines).filter(|line| {
+        let number = match line.line_type {
+            DiffLineType::Remove => line.old_line_num,
+            _ => line.new_line_num,
+        };
+        number.is_some_and(|number| number <= MARKER_LINES)
+    });
+    first_lines
+        .map(|line| line.content.to_lowercase())
+        .any(|text| MARKERS.iter().any(|marker| text.contains(marker)))
+}
+
+/// Whether the lines of the new file the diff shows are long enough on
+/// average to be minified
+fn is_minified(hunks: &[DiffHunk]) -> bool {
+    let lengths: Vec<usize> = hunks
+        .iter()
+        .flat_map(|hunk| &hunk.lines)
+        .filter(|line| matches!(line.line_type, DiffLineType::Add | DiffLineType::Context))
+        .map(|line| line.content.len())
+        .collect();
+    !lengths.is_empty() && lengths.iter().sum::<usize>() / lengths.len() > MINIFIED_LINE_LENGTH
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffLine;
+
+    fn added(lines: &[&str]) -> Vec<DiffHunk> {
+        vec![DiffHunk {
+            header: format!("@@ -0,0 +1,{} @@", lines.len()),
+            old_start: 0,
+            old_lines: 0,
+            new_start: 1,
+            new_lines: lines.len() as u32,
+            lines: lines
+                .iter()
+                .zip(1..)
+                .map(|(content, number)| DiffLine {
+                    line_type: DiffLineType::Add,
+                    old_line_num: None,
+                    new_line_num: Some(number),
+                    content: (*content).into(),
+                    comment: None,
+                })
+                .collect(),
+        }]
+    }
+
+    #[test]
+    fn test_is_generated() {
+        let code = added(&["fn main() {", "    println!(\"hi\");", "}"]);
+        assert!(!is_generated("src/main.rs", None, &code));
+        assert!(is_generated("src/main.rs", Some(true), &code));
+        assert!(!is_generated("Cargo.lock", Some(false), &code));
+
+        assert!(is_generated("Cargo.lock", None, &code));
+        assert!(is_generated("web/package-lock.json", None, &code));
+        assert!(is_generated("api/v1/user.pb.go", None, &code));
+        assert!(is_generated("static/app.min.js", None, &code));
+        assert!(is_generated("dist/app.js", None, &code));
+        assert!(is_generated("web/dist/style.css", None, &code));
+        assert!(!is_generated("docs/dist/README.md", None, &code));
+
+        let marked = added(&["// Code generated by stringer. DO NOT EDIT.", "package x"]);
+        assert!(is_generated("kind_string.go", None, &marked));
+        let long = "x".repeat(500);
+        assert!(is_generated("vendor.js", None, &added(&[&long, &long])));
+        assert!(!is_generated("vendor.rs", None, &added(&[&long, &long])));
+    }
+}
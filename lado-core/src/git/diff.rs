@@ -0,0 +1,534 @@
+use super::line_text::{LineArena, LineText};
+use super::repository::FileDelta;
+use git2::Oid;
+use std::collections::{HashMap, HashSet};
+
+/// Status of a file in the diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+impl FileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileStatus::Added => "added",
+            FileStatus::Modified => "modified",
+            FileStatus::Deleted => "deleted",
+            FileStatus::Renamed => "renamed",
+        }
+    }
+}
+
+/// A changed file in the diff
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub path: String,
+    pub status: FileStatus,
+    pub additions: usize,
+    pub deletions: usize,
+    /// Where a renamed file was moved from
+    pub rename: Option<Rename>,
+    /// Encoding the file was decoded from if it isn't UTF-8, e.g.
+    /// `windows-1252`
+    pub encoding: Option<&'static str>,
+    /// Written by a tool rather than by hand, e.g. a lock file or minified
+    /// code (see [`crate::generated`])
+    pub generated: bool,
+}
+
+/// The old side of a renamed file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rename {
+    pub from: String,
+    /// Share of the lines both sides have in common, in percent (100 for
+    /// a pure move)
+    pub similarity: u8,
+}
+
+impl Rename {
+    /// The rename of a file of `old_lines` lines to one of `new_lines`,
+    /// after the diff between them removes `deletions` of the old ones.
+    pub fn new(from: String, old_lines: usize, new_lines: usize, deletions: usize) -> Self {
+        let kept = old_lines.saturating_sub(deletions);
+        let similarity = match old_lines + new_lines {
+            0 => 100,
+            total => 200 * kept / total,
+        };
+        Self {
+            from,
+            similarity: similarity.min(100) as u8,
+        }
+    }
+}
+
+/// Type of a diff line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineType {
+    Add,
+    Remove,
+    Context,
+    Hunk,
+    Comment,
+}
+
+/// Data for a comment line
+#[derive(Debug, Clone, Default)]
+pub struct CommentData {
+    pub author: String,
+    pub body: String,
+    /// When it was written, e.g. `2024-01-15 11:30`
+    pub timestamp: String,
+    /// The same relative to now, e.g. `3 hours ago`
+    pub relative_time: String,
+    pub is_reply: bool,
+    /// Id of the comment that started its thread, which replies are posted to
+    pub thread_id: u64,
+    /// URLs of the image attachments referenced by the comment body
+    pub images: Vec<String>,
+}
+
+/// A single line in a diff
+#[derive(Debug, Clone)]
+pub struct DiffLine {
+    pub line_type: DiffLineType,
+    pub old_line_num: Option<u32>,
+    pub new_line_num: Option<u32>,
+    pub content: LineText,
+    /// Boxed since almost every line has none
+    pub comment: Option<Box<CommentData>>,
+}
+
+/// A row of a side-by-side diff: indexes of the line shown in the old
+/// column and of the one shown in the new column. Context lines, hunk
+/// headers and comments span both columns, so both indexes are the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinePair {
+    pub old: Option<usize>,
+    pub new: Option<usize>,
+}
+
+/// Align `lines` into side-by-side rows: each run of removed lines is paired
+/// with the run of added lines right after it, line by line, and the lines
+/// left over in the longer run get a row with an empty other column.
+pub fn align_side_by_side(lines: &[DiffLine]) -> Vec<LinePair> {
+    let mut pairs = Vec::with_capacity(lines.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let removes = i..i + run_length(&lines[i..], DiffLineType::Remove);
+        let adds = removes.end..removes.end + run_length(&lines[removes.end..], DiffLineType::Add);
+        if removes.is_empty() && adds.is_empty() {
+            pairs.push(LinePair {
+                old: Some(i),
+                new: Some(i),
+            });
+            i += 1;
+            continue;
+        }
+        for row in 0..removes.len().max(adds.len()) {
+            pairs.push(LinePair {
+                old: Some(removes.start + row).filter(|l| removes.contains(l)),
+                new: Some(adds.start + row).filter(|l| adds.contains(l)),
+            });
+        }
+        i = adds.end;
+    }
+    pairs
+}
+
+/// How many of the lines at the start of `lines` are of type `line_type`
+fn run_length(lines: &[DiffLine], line_type: DiffLineType) -> usize {
+    lines
+        .iter()
+        .take_while(|l| l.line_type == line_type)
+        .count()
+}
+
+/// A hunk in a diff
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct DiffHunk {
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+/// The diffed contents of one changed file.
+#[derive(Debug)]
+pub struct FileDiff {
+    pub file: FileChange,
+    pub hunks: Vec<DiffHunk>,
+    /// Old and new blob, as in `DiffData::blobs`
+    pub blobs: (Option<Oid>, Option<Oid>),
+}
+
+/// Complete diff data
+#[derive(Debug, Clone)]
+pub struct DiffData {
+    pub files: Vec<FileChange>,
+    pub file_hunks: HashMap<String, Vec<DiffHunk>>,
+    /// Old and new blob of each file (`None` where the file doesn't exist)
+    pub blobs: HashMap<String, (Option<Oid>, Option<Oid>)>,
+    /// Structural diff (by difftastic) of files shown that way instead of
+    /// their `file_hunks`
+    pub structural_hunks: HashMap<String, Vec<DiffHunk>>,
+    /// Renamed files shown as the deletion of the old file and the addition
+    /// of the new one rather than by what changed between them
+    pub split_renames: HashMap<String, Vec<DiffHunk>>,
+}
+
+impl DiffData {
+    /// The files of `changes` before their contents have been diffed: no
+    /// line counts or hunks until `set_file` fills them in.
+    pub fn listed(changes: &[FileDelta]) -> Self {
+        Self {
+            files: changes
+                .iter()
+                .map(|change| FileChange {
+                    path: change.path.clone(),
+                    status: change.status,
+                    additions: 0,
+                    deletions: 0,
+                    rename: None,
+                    encoding: None,
+                    generated: false,
+                })
+                .collect(),
+            file_hunks: HashMap::new(),
+            blobs: HashMap::new(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+        }
+    }
+
+    /// Append a diffed file.
+    pub fn add_file(&mut self, diff: FileDiff) {
+        self.files.push(diff.file.clone());
+        self.set_file(self.files.len() - 1, diff);
+    }
+
+    /// Fill in the contents of `files[index]`, which was listed before it
+    /// had been diffed.
+    pub fn set_file(&mut self, index: usize, diff: FileDiff) {
+        // Binary files have no hunks and no entry in `file_hunks`
+        if !diff.hunks.is_empty() {
+            self.file_hunks.insert(diff.file.path.clone(), diff.hunks);
+        }
+        self.blobs.insert(diff.file.path.clone(), diff.blobs);
+        self.files[index] = diff.file;
+    }
+
+    /// The hunks to show for `path`: its structural diff if it has one,
+    /// all of its old and new lines if it is a split rename, otherwise the
+    /// line diff.
+    pub fn shown_hunks(&self, path: &str) -> &[DiffHunk] {
+        self.structural_hunks
+            .get(path)
+            .or_else(|| self.split_renames.get(path))
+            .or_else(|| self.file_hunks.get(path))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Show `path` as the structural diff `hunks`, with tabs expanded as in
+    /// `expand_tabs`.
+    pub fn set_structural_hunks(
+        &mut self,
+        path: String,
+        mut hunks: Vec<DiffHunk>,
+        tab_width: usize,
+    ) {
+        expand_tabs_in(&mut hunks, &" ".repeat(tab_width));
+        self.structural_hunks.insert(path, hunks);
+    }
+
+    /// Show the renamed file `path` as the deletion of `old` and the
+    /// addition of `new`, with tabs expanded as in `expand_tabs`.
+    pub fn set_split_rename(&mut self, path: String, old: &str, new: &str, tab_width: usize) {
+        let mut hunks = delete_add_hunks(old, new);
+        expand_tabs_in(&mut hunks, &" ".repeat(tab_width));
+        self.split_renames.insert(path, hunks);
+    }
+
+    /// Paths whose diff may differ from `previous`: files that entered or
+    /// left the diff and files whose blobs changed on either side.
+    pub fn changed_files(&self, previous: &DiffData) -> HashSet<String> {
+        let mut changed: HashSet<String> = self
+            .blobs
+            .iter()
+            .filter(|(path, blobs)| previous.blobs.get(*path) != Some(blobs))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.extend(
+            previous
+                .blobs
+                .keys()
+                .filter(|path| !self.blobs.contains_key(*path))
+                .cloned(),
+        );
+        changed
+    }
+
+    /// Replace tab characters with spaces in all line content.
+    ///
+    /// Slint's Text element renders raw `\t` as a replacement glyph,
+    /// so tabs must be expanded before reaching the UI.
+    pub fn expand_tabs(&mut self, tab_width: usize) {
+        let spaces = " ".repeat(tab_width);
+        for hunks in self.file_hunks.values_mut() {
+            expand_tabs_in(hunks, &spaces);
+        }
+    }
+}
+
+impl FileDiff {
+    /// Replace tab characters with spaces, as `DiffData::expand_tabs` does.
+    pub fn expand_tabs(&mut self, tab_width: usize) {
+        expand_tabs_in(&mut self.hunks, &" ".repeat(tab_width));
+    }
+}
+
+/// A hunk removing every line of `old` and one adding every line of `new`
+fn delete_add_hunks(old: &str, new: &str) -> Vec<DiffHunk> {
+    let mut arena = LineArena::default();
+    let mut lines = Vec::new();
+    let mut hunks = Vec::new();
+    for (text, removed) in [(old, true), (new, false)] {
+        let count = text.lines().count() as u32;
+        if count == 0 {
+            continue;
+        }
+        let header = if removed {
+            format!("@@ -1,{} +0,0 @@", count)
+        } else {
+            format!("@@ -0,0 +1,{} @@", count)
+        };
+        for (i, line) in text.lines().enumerate() {
+            arena.push(line);
+            let number = Some(i as u32 + 1);
+            lines.push((hunks.len(), removed, number));
+        }
+        hunks.push(DiffHunk {
+            header,
+            old_start: if removed { 1 } else { 0 },
+            old_lines: if removed { count } else { 0 },
+            new_start: if removed { 0 } else { 1 },
+            new_lines: if removed { 0 } else { count },
+            lines: Vec::new(),
+        });
+    }
+    for ((hunk, removed, number), content) in lines.into_iter().zip(arena.finish()) {
+        hunks[hunk].lines.push(DiffLine {
+            line_type: if removed {
+                DiffLineType::Remove
+            } else {
+                DiffLineType::Add
+            },
+            old_line_num: if removed { number } else { None },
+            new_line_num: if removed { None } else { number },
+            content,
+            comment: None,
+        });
+    }
+    hunks
+}
+
+fn expand_tabs_in(hunks: &mut [DiffHunk], spaces: &str) {
+    if !hunks
+        .iter()
+        .flat_map(|h| &h.lines)
+        .any(|l| l.content.contains('\t'))
+    {
+        return;
+    }
+
+    // Lines share their file's buffer, so rebuild it as a whole
+    let mut arena = LineArena::default();
+    for line in hunks.iter().flat_map(|h| &h.lines) {
+        arena.push(&line.content.replace('\t', spaces));
+    }
+    let lines = hunks.iter_mut().flat_map(|h| &mut h.lines);
+    for (line, text) in lines.zip(arena.finish()) {
+        line.content = text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_similarity() {
+        assert_eq!(Rename::new("a".into(), 10, 10, 0).similarity, 100);
+        assert_eq!(Rename::new("a".into(), 10, 10, 5).similarity, 50);
+        assert_eq!(Rename::new("a".into(), 0, 0, 0).similarity, 100);
+        assert_eq!(Rename::new("a".into(), 4, 0, 4).similarity, 0);
+    }
+
+    #[test]
+    fn test_align_side_by_side() {
+        let line = |line_type| DiffLine {
+            line_type,
+            old_line_num: None,
+            new_line_num: None,
+            content: Default::default(),
+            comment: None,
+        };
+        use DiffLineType::{Add, Context, Remove};
+        let lines: Vec<DiffLine> = [Context, Remove, Remove, Add, Context, Add, Remove, Add, Add]
+            .into_iter()
+            .map(line)
+            .collect();
+        let pair = |old, new| LinePair { old, new };
+        assert_eq!(
+            align_side_by_side(&lines),
+            vec![
+                pair(Some(0), Some(0)),
+                pair(Some(1), Some(3)),
+                pair(Some(2), None),
+                pair(Some(4), Some(4)),
+                pair(None, Some(5)),
+                pair(Some(6), Some(7)),
+                pair(None, Some(8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_rename() {
+        let mut data = DiffData {
+            files: vec![],
+            blobs: HashMap::new(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+            file_hunks: HashMap::new(),
+        };
+        data.set_split_rename("new.rs".into(), "a\n\tb\n", "a\n", 2);
+        let hunks = data.shown_hunks("new.rs");
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].header, "@@ -1,2 +0,0 @@");
+        assert_eq!(hunks[0].lines[1].content, "  b");
+        assert_eq!(hunks[0].lines[1].old_line_num, Some(2));
+        assert_eq!(hunks[1].lines[0].line_type, DiffLineType::Add);
+        assert_eq!(hunks[1].lines[0].new_line_num, Some(1));
+    }
+
+    #[test]
+    fn test_expand_tabs_replaces_tabs_with_spaces() {
+        let mut data = DiffData {
+            files: vec![],
+            blobs: HashMap::new(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+            file_hunks: HashMap::from([(
+                "test.go".to_string(),
+                vec![DiffHunk {
+                    header: String::new(),
+                    old_start: 1,
+                    old_lines: 3,
+                    new_start: 1,
+                    new_lines: 3,
+                    lines: vec![
+                        DiffLine {
+                            line_type: DiffLineType::Context,
+                            old_line_num: Some(1),
+                            new_line_num: Some(1),
+                            content: "\t\tfmt.Println(\"hello\")".into(),
+                            comment: None,
+                        },
+                        DiffLine {
+                            line_type: DiffLineType::Add,
+                            old_line_num: None,
+                            new_line_num: Some(2),
+                            content: "\treturn nil".into(),
+                            comment: None,
+                        },
+                        DiffLine {
+                            line_type: DiffLineType::Context,
+                            old_line_num: Some(3),
+                            new_line_num: Some(3),
+                            content: "no tabs here".into(),
+                            comment: None,
+                        },
+                    ],
+                }],
+            )]),
+        };
+
+        data.expand_tabs(4);
+
+        let lines = &data.file_hunks["test.go"][0].lines;
+        assert_eq!(lines[0].content, "        fmt.Println(\"hello\")");
+        assert_eq!(lines[1].content, "    return nil");
+        assert_eq!(lines[2].content, "no tabs here");
+    }
+
+    #[test]
+    fn test_expand_tabs_custom_width() {
+        let mut data = DiffData {
+            files: vec![],
+            blobs: HashMap::new(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+            file_hunks: HashMap::from([(
+                "test.py".to_string(),
+                vec![DiffHunk {
+                    header: String::new(),
+                    old_start: 1,
+                    old_lines: 1,
+                    new_start: 1,
+                    new_lines: 1,
+                    lines: vec![DiffLine {
+                        line_type: DiffLineType::Context,
+                        old_line_num: Some(1),
+                        new_line_num: Some(1),
+                        content: "\tindented".into(),
+                        comment: None,
+                    }],
+                }],
+            )]),
+        };
+
+        data.expand_tabs(2);
+
+        assert_eq!(data.file_hunks["test.py"][0].lines[0].content, "  indented");
+    }
+
+    #[test]
+    fn test_changed_files_compares_blobs() {
+        let oid = |n: u8| Oid::from_bytes(&[n; 20]).unwrap();
+        let data = |blobs: &[(&str, Option<Oid>, Option<Oid>)]| DiffData {
+            files: vec![],
+            file_hunks: HashMap::new(),
+            blobs: blobs
+                .iter()
+                .map(|(path, old, new)| (path.to_string(), (*old, *new)))
+                .collect(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+        };
+
+        let before = data(&[
+            ("same.rs", Some(oid(1)), Some(oid(2))),
+            ("edited.rs", Some(oid(1)), Some(oid(3))),
+            ("gone.rs", None, Some(oid(4))),
+        ]);
+        let after = data(&[
+            ("same.rs", Some(oid(1)), Some(oid(2))),
+            ("edited.rs", Some(oid(1)), Some(oid(5))),
+            ("new.rs", None, Some(oid(6))),
+        ]);
+
+        let changed = after.changed_files(&before);
+        let expected: HashSet<String> = ["edited.rs", "gone.rs", "new.rs"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(changed, expected);
+    }
+}
@@ -1,7 +1,7 @@
 //! File tree construction from diff data.
 //! Builds hierarchical file trees for the UI.
 
-use super::diff::FileChange;
+use super::diff::{FileChange, FileStatus};
 use std::collections::HashMap;
 
 /// A node in the file tree
@@ -12,6 +12,11 @@ pub struct FileTreeNode {
     pub is_folder: bool,
     pub children: Vec<FileTreeNode>,
     pub status: Option<String>,
+    /// Lines the file adds and removes; for folders, all of their files do
+    pub additions: usize,
+    pub deletions: usize,
+    /// Changed files in a folder, at any depth (1 for files)
+    pub files: usize,
 }
 
 /// Build a hierarchical file tree from a flat list of file changes
@@ -20,21 +25,51 @@ pub fn build_file_tree(files: &[FileChange]) -> Vec<FileTreeNode> {
 
     for file in files {
         let parts: Vec<&str> = file.path.split('/').collect();
-        insert_path(&mut root, &parts, &file.path, file.status.as_str(), "");
+        insert_path(&mut root, &parts, file, "");
     }
 
     // Convert HashMap to sorted Vec
     let mut nodes: Vec<FileTreeNode> = root.into_values().collect();
     compact_tree(&mut nodes);
     sort_tree(&mut nodes);
+    add_up_folders(&mut nodes);
+    nodes
+}
+
+/// Give each folder the totals of the files below it
+fn add_up_folders(nodes: &mut [FileTreeNode]) {
+    for node in nodes.iter_mut().filter(|node| node.is_folder) {
+        add_up_folders(&mut node.children);
+        node.additions = node.children.iter().map(|child| child.additions).sum();
+        node.deletions = node.children.iter().map(|child| child.deletions).sum();
+        node.files = node.children.iter().map(|child| child.files).sum();
+    }
+}
+
+/// The files as a flat list rather than a tree: no folders, each file named
+/// by its whole path, sorted by it.
+pub fn build_file_list(files: &[FileChange]) -> Vec<FileTreeNode> {
+    let mut nodes: Vec<FileTreeNode> = files
+        .iter()
+        .map(|file| FileTreeNode {
+            name: file.path.clone(),
+            path: file.path.clone(),
+            is_folder: false,
+            children: Vec::new(),
+            status: Some(file.status.as_str().to_string()),
+            additions: file.additions,
+            deletions: file.deletions,
+            files: 1,
+        })
+        .collect();
+    nodes.sort_by_key(|node| node.path.to_lowercase());
     nodes
 }
 
 fn insert_path(
     nodes: &mut HashMap<String, FileTreeNode>,
     parts: &[&str],
-    full_path: &str,
-    status: &str,
+    file: &FileChange,
     prefix: &str,
 ) {
     if parts.is_empty() {
@@ -54,18 +89,23 @@ fn insert_path(
     let node = nodes.entry(name.clone()).or_insert_with(|| FileTreeNode {
         name: name.clone(),
         path: if is_file {
-            full_path.to_string()
+            file.path.clone()
         } else {
             folder_path.clone()
         },
         is_folder: !is_file,
         children: Vec::new(),
         status: None,
+        additions: 0,
+        deletions: 0,
+        files: usize::from(is_file),
     });
 
     if is_file {
-        node.status = Some(status.to_string());
-        node.path = full_path.to_string();
+        node.status = Some(file.status.as_str().to_string());
+        node.path = file.path.clone();
+        node.additions = file.additions;
+        node.deletions = file.deletions;
     } else {
         let mut child_map: HashMap<String, FileTreeNode> = node
             .children
@@ -73,7 +113,7 @@ fn insert_path(
             .map(|n| (n.name.clone(), n))
             .collect();
 
-        insert_path(&mut child_map, &parts[1..], full_path, status, &folder_path);
+        insert_path(&mut child_map, &parts[1..], file, &folder_path);
 
         node.children = child_map.into_values().collect();
     }
@@ -81,12 +121,10 @@ fn insert_path(
 
 fn sort_tree(nodes: &mut [FileTreeNode]) {
     // Sort: folders first, then alphabetically
-    nodes.sort_by(|a, b| {
-        match (a.is_folder, b.is_folder) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-        }
+    nodes.sort_by(|a, b| match (a.is_folder, b.is_folder) {
+        (true, false) => std::cmp::Ordering::Less,
+        (false, true) => std::cmp::Ordering::Greater,
+        _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
     });
 
     for node in nodes {
@@ -94,6 +132,101 @@ fn sort_tree(nodes: &mut [FileTreeNode]) {
     }
 }
 
+/// Which files the tree lists, for focusing on e.g. only new files or only
+/// `.rs` files. Empty lists keep all files.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileFilter {
+    pub statuses: Vec<FileStatus>,
+    /// Without the dot, lowercase
+    pub extensions: Vec<String>,
+    /// Leave deleted files out, unless `statuses` asks for them
+    pub hide_deleted: bool,
+}
+
+impl FileFilter {
+    /// Extensions as typed into the filter box: `rs`, `.rs` or several
+    /// separated by commas or spaces (`rs, toml`)
+    pub fn parse_extensions(text: &str) -> Vec<String> {
+        text.split(|c: char| c == ',' || c.is_whitespace())
+            .map(|extension| extension.trim_start_matches('.').to_lowercase())
+            .filter(|extension| !extension.is_empty())
+            .collect()
+    }
+
+    pub fn matches(&self, file: &FileChange) -> bool {
+        let extension = std::path::Path::new(&file.path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let hidden = self.hide_deleted
+            && file.status == FileStatus::Deleted
+            && !self.statuses.contains(&FileStatus::Deleted);
+        !hidden
+            && (self.statuses.is_empty() || self.statuses.contains(&file.status))
+            && (self.extensions.is_empty()
+                || extension.is_some_and(|extension| self.extensions.contains(&extension)))
+    }
+}
+
+/// How the files of each folder are ordered. Folders stay above them, by
+/// name unless sorted by size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeSort {
+    /// By name (as [`build_file_tree`] leaves them)
+    Path,
+    /// Most changed lines first, folders by those of all their files
+    Size,
+    /// Added, then deleted, renamed and modified files
+    Status,
+    /// In the order of the commits that last changed them
+    Commits,
+}
+
+impl TreeSort {
+    /// The order named by the `tree_sort` setting: `path`, `size`, `status`
+    /// or `commits`. By path if unknown.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "size" => TreeSort::Size,
+            "status" => TreeSort::Status,
+            "commits" => TreeSort::Commits,
+            _ => TreeSort::Path,
+        }
+    }
+}
+
+/// Order the files of every folder of `nodes` by `sort`. For
+/// [`TreeSort::Commits`], `last_commit` has the index of the last commit
+/// that changed each file; files it doesn't list go last.
+pub fn sort_file_tree(
+    nodes: &mut [FileTreeNode],
+    sort: TreeSort,
+    last_commit: &HashMap<String, usize>,
+) {
+    let status_rank = |node: &FileTreeNode| match node.status.as_deref() {
+        Some("added") => 0,
+        Some("deleted") => 1,
+        Some("renamed") => 2,
+        _ => 3,
+    };
+    // Stable, so ties stay by name
+    match sort {
+        TreeSort::Path => {}
+        TreeSort::Size => {
+            nodes.sort_by_key(|n| (!n.is_folder, std::cmp::Reverse(n.additions + n.deletions)))
+        }
+        TreeSort::Status => nodes.sort_by_key(|n| (!n.is_folder, status_rank(n))),
+        TreeSort::Commits => nodes.sort_by_key(|n| {
+            (
+                !n.is_folder,
+                last_commit.get(&n.path).copied().unwrap_or(usize::MAX),
+            )
+        }),
+    }
+    for node in nodes {
+        sort_file_tree(&mut node.children, sort, last_commit);
+    }
+}
+
 /// Compact single-child folder chains into "first/.../last" display names.
 /// This reduces visual clutter when deeply nested folders have only one subfolder.
 fn compact_tree(nodes: &mut [FileTreeNode]) {
@@ -166,14 +299,24 @@ pub fn flatten_tree_with_state(
             depth,
             is_folder: node.is_folder,
             is_expanded,
-            status: node.status.clone().unwrap_or_else(|| "modified".to_string()),
+            status: node
+                .status
+                .clone()
+                .unwrap_or_else(|| "modified".to_string()),
+            additions: node.additions,
+            deletions: node.deletions,
+            files: node.files,
             comment_count: 0,
             viewed: false,
         });
 
         // Only recurse into children if the folder is expanded
         if node.is_folder && is_expanded {
-            result.extend(flatten_tree_with_state(&node.children, depth + 1, expanded_state));
+            result.extend(flatten_tree_with_state(
+                &node.children,
+                depth + 1,
+                expanded_state,
+            ));
         }
     }
 
@@ -189,6 +332,9 @@ pub struct FlatFileEntry {
     pub is_folder: bool,
     pub is_expanded: bool,
     pub status: String,
+    pub additions: usize,
+    pub deletions: usize,
+    pub files: usize,
     pub comment_count: i32,
     pub viewed: bool,
 }
@@ -224,6 +370,33 @@ pub fn collect_folder_paths_under(nodes: &[FileTreeNode], target_path: &str) ->
     paths
 }
 
+/// Collect the paths of all files in a file tree, in the order it shows
+/// them with every folder expanded
+pub fn collect_file_paths(nodes: &[FileTreeNode]) -> Vec<String> {
+    let mut paths = Vec::new();
+    for node in nodes {
+        if node.is_folder {
+            paths.extend(collect_file_paths(&node.children));
+        } else {
+            paths.push(node.path.clone());
+        }
+    }
+    paths
+}
+
+/// Collect the paths of the folders a file is in, outermost first (to
+/// expand them and reveal it)
+pub fn collect_folder_paths_above(nodes: &[FileTreeNode], file_path: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    for node in nodes {
+        if node.is_folder && file_path.starts_with(&format!("{}/", node.path)) {
+            paths.push(node.path.clone());
+            paths.extend(collect_folder_paths_above(&node.children, file_path));
+        }
+    }
+    paths
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -237,18 +410,27 @@ mod tests {
                 status: FileStatus::Modified,
                 additions: 10,
                 deletions: 5,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "src/lib.rs".to_string(),
                 status: FileStatus::Added,
                 additions: 20,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "README.md".to_string(),
                 status: FileStatus::Modified,
                 additions: 2,
                 deletions: 1,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
         ];
 
@@ -271,18 +453,27 @@ mod tests {
                 status: FileStatus::Modified,
                 additions: 10,
                 deletions: 5,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "src/lib.rs".to_string(),
                 status: FileStatus::Added,
                 additions: 20,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "README.md".to_string(),
                 status: FileStatus::Modified,
                 additions: 2,
                 deletions: 1,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
         ];
 
@@ -295,6 +486,17 @@ mod tests {
         assert_eq!(flat_expanded[0].name, "src");
         assert!(flat_expanded[0].is_folder);
         assert!(flat_expanded[0].is_expanded);
+        // The totals of src's files
+        assert_eq!(
+            (
+                flat_expanded[0].files,
+                flat_expanded[0].additions,
+                flat_expanded[0].deletions
+            ),
+            (2, 30, 5)
+        );
+        let main = flat_expanded.iter().find(|e| e.name == "main.rs").unwrap();
+        assert_eq!((main.additions, main.deletions), (10, 5));
 
         // When src is collapsed, should have 2 entries: src folder, README.md
         expanded_state.insert("src".to_string(), false);
@@ -305,6 +507,107 @@ mod tests {
         assert_eq!(flat_collapsed[1].name, "README.md");
     }
 
+    #[test]
+    fn test_build_file_list() {
+        let file = |path: &str| FileChange {
+            path: path.to_string(),
+            status: FileStatus::Modified,
+            additions: 1,
+            deletions: 0,
+            rename: None,
+            encoding: None,
+            generated: false,
+        };
+        let list = build_file_list(&[
+            file("src/git/diff.rs"),
+            file("README.md"),
+            file("Cargo.toml"),
+        ]);
+        let names: Vec<&str> = list.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["Cargo.toml", "README.md", "src/git/diff.rs"]);
+        assert!(list.iter().all(|n| !n.is_folder && n.path == n.name));
+        assert_eq!(flatten_tree_with_state(&list, 0, &HashMap::new()).len(), 3);
+    }
+
+    #[test]
+    fn test_file_filter() {
+        let file = |path: &str, status| FileChange {
+            path: path.to_string(),
+            status,
+            additions: 1,
+            deletions: 0,
+            rename: None,
+            encoding: None,
+            generated: false,
+        };
+        let filter = FileFilter {
+            statuses: vec![FileStatus::Added, FileStatus::Renamed],
+            extensions: FileFilter::parse_extensions(".RS, toml"),
+            hide_deleted: false,
+        };
+        assert_eq!(filter.extensions, ["rs", "toml"]);
+        assert!(filter.matches(&file("src/new.rs", FileStatus::Added)));
+        assert!(filter.matches(&file("Cargo.toml", FileStatus::Renamed)));
+        assert!(!filter.matches(&file("src/old.rs", FileStatus::Modified)));
+        assert!(!filter.matches(&file("README.md", FileStatus::Added)));
+        assert!(!filter.matches(&file("Makefile", FileStatus::Added)));
+        assert!(FileFilter::default().matches(&file("Makefile", FileStatus::Deleted)));
+
+        let mut filter = FileFilter {
+            hide_deleted: true,
+            ..FileFilter::default()
+        };
+        assert!(!filter.matches(&file("old.rs", FileStatus::Deleted)));
+        assert!(filter.matches(&file("new.rs", FileStatus::Added)));
+        // Asking for deleted files shows them anyway
+        filter.statuses = vec![FileStatus::Deleted];
+        assert!(filter.matches(&file("old.rs", FileStatus::Deleted)));
+    }
+
+    #[test]
+    fn test_sort_file_tree() {
+        let file = |path: &str, status, additions| FileChange {
+            path: path.to_string(),
+            status,
+            additions,
+            deletions: 0,
+            rename: None,
+            encoding: None,
+            generated: false,
+        };
+        let files = vec![
+            file("src/a.rs", FileStatus::Modified, 2),
+            file("b.rs", FileStatus::Modified, 1),
+            file("c.rs", FileStatus::Deleted, 5),
+            file("d.rs", FileStatus::Added, 3),
+        ];
+        let names = |sort, last_commit: &HashMap<String, usize>| {
+            let mut tree = build_file_tree(&files);
+            sort_file_tree(&mut tree, sort, last_commit);
+            tree.iter().map(|n| n.name.clone()).collect::<Vec<_>>()
+        };
+        let none = HashMap::new();
+        assert_eq!(
+            names(TreeSort::Path, &none),
+            ["src", "b.rs", "c.rs", "d.rs"]
+        );
+        assert_eq!(
+            names(TreeSort::Size, &none),
+            ["src", "c.rs", "d.rs", "b.rs"]
+        );
+        assert_eq!(
+            names(TreeSort::Status, &none),
+            ["src", "d.rs", "c.rs", "b.rs"]
+        );
+        let last_commit = HashMap::from([("c.rs".to_string(), 0), ("b.rs".to_string(), 1)]);
+        assert_eq!(
+            names(TreeSort::Commits, &last_commit),
+            ["src", "c.rs", "b.rs", "d.rs"]
+        );
+        assert_eq!(TreeSort::from_name("size"), TreeSort::Size);
+        assert_eq!(TreeSort::from_name("bogus"), TreeSort::Path);
+    }
+
     #[test]
     fn test_collect_folder_paths() {
         let files = vec![
@@ -313,18 +616,27 @@ mod tests {
                 status: FileStatus::Modified,
                 additions: 10,
                 deletions: 5,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "src/git/diff.rs".to_string(),
                 status: FileStatus::Added,
                 additions: 20,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "tests/test.rs".to_string(),
                 status: FileStatus::Modified,
                 additions: 5,
                 deletions: 2,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
         ];
 
@@ -346,18 +658,27 @@ mod tests {
                 status: FileStatus::Modified,
                 additions: 10,
                 deletions: 5,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "src/git/repo.rs".to_string(),
                 status: FileStatus::Added,
                 additions: 20,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "src/app.rs".to_string(),
                 status: FileStatus::Modified,
                 additions: 5,
                 deletions: 2,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
         ];
 
@@ -375,6 +696,37 @@ mod tests {
         assert!(paths_under_git.contains(&"src/git".to_string()));
     }
 
+    #[test]
+    fn test_collect_file_paths_and_folders_above() {
+        let file = |path: &str| FileChange {
+            path: path.to_string(),
+            status: FileStatus::Modified,
+            additions: 1,
+            deletions: 0,
+            rename: None,
+            encoding: None,
+            generated: false,
+        };
+        let files = vec![
+            file("src/git/diff.rs"),
+            file("src/app.rs"),
+            file("README.md"),
+        ];
+
+        let tree = build_file_tree(&files);
+
+        // Folders come first, as the tree shows them
+        assert_eq!(
+            collect_file_paths(&tree),
+            ["src/git/diff.rs", "src/app.rs", "README.md"]
+        );
+        assert_eq!(
+            collect_folder_paths_above(&tree, "src/git/diff.rs"),
+            ["src", "src/git"]
+        );
+        assert!(collect_folder_paths_above(&tree, "README.md").is_empty());
+    }
+
     #[test]
     fn test_compact_single_child_chain() {
         // A chain of 3+ single-child folders should be compacted into "a/.../c"
@@ -383,6 +735,9 @@ mod tests {
             status: FileStatus::Modified,
             additions: 1,
             deletions: 0,
+            rename: None,
+            encoding: None,
+            generated: false,
         }];
 
         let tree = build_file_tree(&files);
@@ -403,6 +758,9 @@ mod tests {
             status: FileStatus::Added,
             additions: 5,
             deletions: 0,
+            rename: None,
+            encoding: None,
+            generated: false,
         }];
 
         let tree = build_file_tree(&files);
@@ -426,12 +784,18 @@ mod tests {
                 status: FileStatus::Modified,
                 additions: 3,
                 deletions: 1,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "a/c/file2.txt".to_string(),
                 status: FileStatus::Added,
                 additions: 7,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
         ];
 
@@ -455,6 +819,9 @@ mod tests {
             status: FileStatus::Modified,
             additions: 1,
             deletions: 0,
+            rename: None,
+            encoding: None,
+            generated: false,
         }];
 
         let tree = build_file_tree(&files);
@@ -476,12 +843,18 @@ mod tests {
                 status: FileStatus::Modified,
                 additions: 1,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "b/c/c/b/foo/bar/file2.txt".to_string(),
                 status: FileStatus::Added,
                 additions: 1,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
         ];
 
@@ -502,12 +875,18 @@ mod tests {
                 status: FileStatus::Modified,
                 additions: 1,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "x/y/z/w/file2.txt".to_string(),
                 status: FileStatus::Added,
                 additions: 1,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
         ];
 
@@ -528,12 +907,18 @@ mod tests {
                 status: FileStatus::Modified,
                 additions: 1,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
             FileChange {
                 path: "src/x/y/z/file2.txt".to_string(),
                 status: FileStatus::Added,
                 additions: 1,
                 deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
             },
         ];
 
@@ -551,14 +936,15 @@ mod tests {
 
     #[test]
     fn test_compact_tree_with_flatten_and_expand_state() {
-        let files = vec![
-            FileChange {
-                path: "src/main/java/com/example/Service.java".to_string(),
-                status: FileStatus::Modified,
-                additions: 10,
-                deletions: 5,
-            },
-        ];
+        let files = vec![FileChange {
+            path: "src/main/java/com/example/Service.java".to_string(),
+            status: FileStatus::Modified,
+            additions: 10,
+            deletions: 5,
+            rename: None,
+            encoding: None,
+            generated: false,
+        }];
 
         let tree = build_file_tree(&files);
 
@@ -594,6 +980,9 @@ mod tests {
             status: FileStatus::Modified,
             additions: 1,
             deletions: 0,
+            rename: None,
+            encoding: None,
+            generated: false,
         }];
 
         let tree = build_file_tree(&files);
@@ -0,0 +1,127 @@
+//! Pairing up files on disk for `--files` and `--dirs`, which diff paths
+//! outside any repository.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A file to diff, with where it is on each side; `None` where it doesn't
+/// exist.
+#[derive(Debug)]
+pub struct DiskFile {
+    /// Path shown in the file tree, `/`-separated
+    pub path: String,
+    pub old: Option<PathBuf>,
+    pub new: Option<PathBuf>,
+}
+
+/// The two files of `--files`, shown under the new file's path.
+pub fn file_pair(old: &Path, new: &Path) -> Result<Vec<DiskFile>> {
+    for path in [old, new] {
+        if !path.is_file() {
+            return Err(anyhow!("{} is not a file", path.display()));
+        }
+    }
+    let path = new.to_string_lossy();
+    Ok(vec![DiskFile {
+        path: path.strip_prefix("./").unwrap_or(&path).to_string(),
+        old: Some(old.to_path_buf()),
+        new: Some(new.to_path_buf()),
+    }])
+}
+
+/// Every file under either directory of `--dirs`, matched up by relative
+/// path.
+pub fn dir_pairs(old: &Path, new: &Path) -> Result<Vec<DiskFile>> {
+    let old_files = list_files(old)?;
+    let mut new_files = list_files(new)?;
+
+    let mut files: Vec<DiskFile> = old_files
+        .into_iter()
+        .map(|(path, old)| DiskFile {
+            new: new_files.remove(&path),
+            old: Some(old),
+            path,
+        })
+        .collect();
+    files.extend(new_files.into_iter().map(|(path, new)| DiskFile {
+        path,
+        old: None,
+        new: Some(new),
+    }));
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Files under `root` by their `/`-separated path relative to it. `.git`
+/// directories are left out, so two checkouts can be compared.
+fn list_files(root: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    if !root.is_dir() {
+        return Err(anyhow!("{} is not a directory", root.display()));
+    }
+    let mut files = BTreeMap::new();
+    let mut pending = vec![(String::new(), root.to_path_buf())];
+    while let Some((prefix, dir)) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            let relative = format!("{}{}", prefix, name);
+            // Symlinks are followed to files but not into directories
+            if entry.file_type()?.is_dir() {
+                if name != ".git" {
+                    pending.push((format!("{}/", relative), path));
+                }
+            } else if path.is_file() {
+                files.insert(relative, path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn test_dir_pairs() {
+        let dir = TempDir::new("fs-diff");
+        let root = dir.path();
+        let write = |path: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "x").unwrap();
+        };
+        write("old/kept.txt");
+        write("old/src/removed.rs");
+        write("old/.git/HEAD");
+        write("new/kept.txt");
+        write("new/src/added.rs");
+
+        let files = dir_pairs(&root.join("old"), &root.join("new"));
+        let files: Vec<_> = files
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.path, f.old.is_some(), f.new.is_some()))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("kept.txt".to_string(), true, true),
+                ("src/added.rs".to_string(), false, true),
+                ("src/removed.rs".to_string(), true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_pair_rejects_directories() {
+        let dir = TempDir::new("fs-pair");
+        assert!(file_pair(dir.path(), dir.path()).is_err());
+    }
+}
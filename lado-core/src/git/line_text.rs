@@ -0,0 +1,173 @@
+//! Shared storage for diff line text.
+//!
+//! The lines of a file's diff are slices of one buffer rather than a `String`
+//! each, which saves an allocation (and its bookkeeping) per line on
+//! monorepo-sized reviews.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+/// Text of one diff line, borrowed from a buffer shared with its neighbours.
+#[derive(Clone)]
+pub struct LineText {
+    buffer: Arc<str>,
+    start: u32,
+    end: u32,
+}
+
+impl LineText {
+    pub fn as_str(&self) -> &str {
+        &self.buffer[self.start as usize..self.end as usize]
+    }
+}
+
+impl Deref for LineText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for LineText {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Default for LineText {
+    fn default() -> Self {
+        Self::from("")
+    }
+}
+
+impl From<&str> for LineText {
+    fn from(s: &str) -> Self {
+        Self {
+            buffer: s.into(),
+            start: 0,
+            end: s.len() as u32,
+        }
+    }
+}
+
+impl From<String> for LineText {
+    fn from(s: String) -> Self {
+        Self::from(s.as_str())
+    }
+}
+
+impl fmt::Debug for LineText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for LineText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq for LineText {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for LineText {}
+
+impl PartialEq<str> for LineText {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for LineText {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// Hashes like the `str` it holds, so content hashes (see `viewed_state`)
+/// don't depend on how the text is stored.
+impl Hash for LineText {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+/// Collects the text of many lines into shared buffers.
+#[derive(Default)]
+pub struct LineArena {
+    buffer: String,
+    /// Lines in `buffer` not yet turned into `LineText`s
+    pending: Vec<Range<u32>>,
+    lines: Vec<LineText>,
+}
+
+impl LineArena {
+    /// Append a line; its `LineText` is at the same position in `finish()`.
+    pub fn push(&mut self, line: &str) {
+        // Offsets are 32-bit, so start a new buffer before they'd overflow
+        if self.buffer.len() + line.len() > u32::MAX as usize {
+            self.flush();
+        }
+        let start = self.buffer.len() as u32;
+        self.buffer.push_str(line);
+        self.pending.push(start..self.buffer.len() as u32);
+    }
+
+    /// The pushed lines, in order.
+    pub fn finish(mut self) -> Vec<LineText> {
+        self.flush();
+        self.lines
+    }
+
+    fn flush(&mut self) {
+        let buffer: Arc<str> = std::mem::take(&mut self.buffer).into();
+        self.lines
+            .extend(self.pending.drain(..).map(|range| LineText {
+                buffer: Arc::clone(&buffer),
+                start: range.start,
+                end: range.end,
+            }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    #[test]
+    fn test_lines_share_one_buffer() {
+        let mut arena = LineArena::default();
+        arena.push("fn main() {");
+        arena.push("");
+        arena.push("}");
+        let lines = arena.finish();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "fn main() {");
+        assert_eq!(lines[1], "");
+        assert_eq!(lines[2], "}");
+        assert!(Arc::ptr_eq(&lines[0].buffer, &lines[2].buffer));
+    }
+
+    #[test]
+    fn test_hashes_like_str() {
+        let hash = |value: &dyn Fn(&mut DefaultHasher)| {
+            let mut hasher = DefaultHasher::new();
+            value(&mut hasher);
+            hasher.finish()
+        };
+        let text = LineText::from("let x = 1;");
+        assert_eq!(
+            hash(&|h| text.hash(h)),
+            hash(&|h| "let x = 1;".to_string().hash(h))
+        );
+    }
+}
@@ -0,0 +1,19 @@
+mod diff;
+mod file_tree;
+mod fs_diff;
+mod line_text;
+mod patch;
+mod repository;
+mod shallow;
+
+pub use diff::{
+    align_side_by_side, CommentData, DiffData, DiffHunk, DiffLine, DiffLineType, FileChange,
+    FileDiff, FileStatus, LinePair, Rename,
+};
+pub use file_tree::{
+    build_file_list, build_file_tree, collect_file_paths, collect_folder_paths, collect_folder_paths_above,
+    collect_folder_paths_under, flatten_tree_with_state,
+    sort_file_tree, FileFilter, FileTreeNode, FlatFileEntry, TreeSort,
+};
+pub use patch::parse_patch;
+pub use repository::{CommitDetails, FileDelta, Repository, TextConv};
@@ -0,0 +1,336 @@
+//! Parsing unified diffs (`git diff`, `diff -u`) into `DiffData`, for
+//! patches that don't come from a repository.
+
+use super::diff::{
+    DiffData, DiffHunk, DiffLine, DiffLineType, FileChange, FileDiff, FileStatus, Rename,
+};
+use super::line_text::LineArena;
+use crate::generated;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+/// Parse every file of a unified diff.
+pub fn parse_patch(text: &str) -> Result<DiffData> {
+    let mut data = DiffData {
+        files: Vec::new(),
+        file_hunks: HashMap::new(),
+        blobs: HashMap::new(),
+        structural_hunks: HashMap::new(),
+        split_renames: HashMap::new(),
+    };
+    let mut file: Option<PatchFile> = None;
+    // Old and new lines still to come in the current hunk
+    let mut remaining = (0u32, 0u32);
+
+    for line in text.lines() {
+        if remaining != (0, 0) {
+            if let Some(file) = file.as_mut() {
+                file.push_line(line, &mut remaining);
+                continue;
+            }
+        }
+
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            add_file(&mut data, file.take());
+            file = Some(PatchFile::new(git_new_path(paths), true));
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            let git_style = file
+                .as_ref()
+                .is_some_and(|f| f.git_style && f.hunks.is_empty());
+            if !git_style {
+                // Plain `diff -u` output starts each file here
+                add_file(&mut data, file.take());
+                file = Some(PatchFile::new(String::new(), false));
+            }
+            if let Some(file) = file.as_mut() {
+                match file.patch_path(path) {
+                    None => file.status = FileStatus::Added,
+                    Some(path) => file.path = path,
+                }
+            }
+        } else if let (Some(path), Some(file)) = (line.strip_prefix("+++ "), file.as_mut()) {
+            match file.patch_path(path) {
+                None => file.status = FileStatus::Deleted,
+                Some(path) => file.path = path,
+            }
+        } else if let (Some(header), Some(file)) = (line.strip_prefix("@@ "), file.as_mut()) {
+            let hunk = parse_hunk_header(header)
+                .ok_or_else(|| anyhow!("Malformed hunk header: {}", line))?;
+            remaining = (hunk.old_lines, hunk.new_lines);
+            file.next_old = hunk.old_start;
+            file.next_new = hunk.new_start;
+            file.hunks.push(hunk);
+        } else if let Some(file) = file.as_mut() {
+            if line.starts_with("new file mode") {
+                file.status = FileStatus::Added;
+            } else if line.starts_with("deleted file mode") {
+                file.status = FileStatus::Deleted;
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                file.status = FileStatus::Renamed;
+                file.path = path.to_string();
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                file.renamed_from = Some(path.to_string());
+            } else if let Some(percent) = line.strip_prefix("similarity index ") {
+                file.similarity = percent.trim_end_matches('%').parse().ok();
+            }
+            // Anything else (index and mode lines, commit
+            // messages of `git format-patch` output) doesn't show up in the diff
+        }
+    }
+    add_file(&mut data, file);
+
+    if data.files.is_empty() {
+        return Err(anyhow!("No diff found in the patch"));
+    }
+    Ok(data)
+}
+
+/// A file of the patch, while it is being read.
+struct PatchFile {
+    path: String,
+    status: FileStatus,
+    /// Paths carry `a/` and `b/` prefixes (`git diff` output)
+    git_style: bool,
+    hunks: Vec<DiffHunk>,
+    arena: LineArena,
+    /// Hunk, type and line numbers of each line pushed into `arena`
+    lines: Vec<(usize, DiffLineType, Option<u32>, Option<u32>)>,
+    /// Line numbers of the next old and new line of the current hunk
+    next_old: u32,
+    next_new: u32,
+    /// Old path and similarity of a renamed file
+    renamed_from: Option<String>,
+    similarity: Option<u8>,
+}
+
+impl PatchFile {
+    fn new(path: String, git_style: bool) -> Self {
+        Self {
+            path,
+            status: FileStatus::Modified,
+            git_style,
+            hunks: Vec::new(),
+            arena: LineArena::default(),
+            lines: Vec::new(),
+            next_old: 0,
+            next_new: 0,
+            renamed_from: None,
+            similarity: None,
+        }
+    }
+
+    /// Path of a `---`/`+++` line; `None` for /dev/null.
+    fn patch_path(&self, path: &str) -> Option<String> {
+        // `diff -u` follows the path with a tab and a timestamp
+        let path = path.split('\t').next().unwrap_or(path);
+        if path == "/dev/null" {
+            return None;
+        }
+        let path = if self.git_style {
+            path.strip_prefix("a/")
+                .or_else(|| path.strip_prefix("b/"))
+                .unwrap_or(path)
+        } else {
+            path
+        };
+        Some(path.to_string())
+    }
+
+    fn push_line(&mut self, line: &str, remaining: &mut (u32, u32)) {
+        if self.hunks.is_empty() {
+            return;
+        }
+
+        // Editors like to strip the space off empty context lines
+        let (line_type, content) = match line.split_at_checked(1) {
+            Some(("+", content)) => (DiffLineType::Add, content),
+            Some(("-", content)) => (DiffLineType::Remove, content),
+            Some((" ", content)) => (DiffLineType::Context, content),
+            // "\ No newline at end of file"
+            Some(("\\", _)) => return,
+            _ => (DiffLineType::Context, ""),
+        };
+        let old = (line_type != DiffLineType::Add).then_some(self.next_old);
+        let new = (line_type != DiffLineType::Remove).then_some(self.next_new);
+        if old.is_some() {
+            self.next_old += 1;
+            remaining.0 = remaining.0.saturating_sub(1);
+        }
+        if new.is_some() {
+            self.next_new += 1;
+            remaining.1 = remaining.1.saturating_sub(1);
+        }
+        self.arena.push(content);
+        self.lines.push((self.hunks.len() - 1, line_type, old, new));
+    }
+
+    fn finish(mut self) -> FileDiff {
+        let (mut additions, mut deletions) = (0, 0);
+        for ((hunk, line_type, old_line_num, new_line_num), content) in
+            self.lines.into_iter().zip(self.arena.finish())
+        {
+            match line_type {
+                DiffLineType::Add => additions += 1,
+                DiffLineType::Remove => deletions += 1,
+                _ => {}
+            }
+            self.hunks[hunk].lines.push(DiffLine {
+                line_type,
+                old_line_num,
+                new_line_num,
+                content,
+                comment: None,
+            });
+        }
+        let rename = self.renamed_from.map(|from| Rename {
+            from,
+            // git writes one for every rename; without it, take it as a move
+            similarity: self.similarity.unwrap_or(100),
+        });
+        // Patches carry no attributes; only the heuristics tell
+        let generated = generated::is_generated(&self.path, None, &self.hunks);
+        FileDiff {
+            file: FileChange {
+                path: self.path,
+                status: self.status,
+                additions,
+                deletions,
+                rename,
+                encoding: None,
+                generated,
+            },
+            hunks: self.hunks,
+            blobs: (None, None),
+        }
+    }
+}
+
+/// Add the file read so far, if any.
+fn add_file(data: &mut DiffData, file: Option<PatchFile>) {
+    if let Some(file) = file.filter(|f| !f.path.is_empty()) {
+        data.add_file(file.finish());
+    }
+}
+
+/// New-side path of a `diff --git a/x b/x` line, until `+++` says better.
+fn git_new_path(paths: &str) -> String {
+    paths
+        .split_once(" b/")
+        .map_or(paths, |(_, new)| new)
+        .to_string()
+}
+
+/// `-12,7 +12,8 @@ fn main() {` (the part after the leading `@@ `)
+fn parse_hunk_header(header: &str) -> Option<DiffHunk> {
+    let (ranges, _) = header.split_once(" @@")?;
+    let (old, new) = ranges.split_once(' ')?;
+    let range = |r: &str| -> Option<(u32, u32)> {
+        match r.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((r.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_lines) = range(old.strip_prefix('-')?)?;
+    let (new_start, new_lines) = range(new.strip_prefix('+')?)?;
+    Some(DiffHunk {
+        header: format!("@@ {}", header),
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        lines: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIT_PATCH: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@ mod a;
+ fn a() {}
+--- removed line that looks like a header
++++ added line that looks like a header
+
+@@ -10 +10,2 @@
+ x
++y
+diff --git a/new.txt b/new.txt
+new file mode 100644
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+\\ No newline at end of file
+diff --git a/old.txt b/renamed.txt
+similarity index 100%
+rename from old.txt
+rename to renamed.txt
+diff --git a/logo.png b/logo.png
+Binary files a/logo.png and b/logo.png differ
+";
+
+    #[test]
+    fn test_parse_git_patch() {
+        let data = parse_patch(GIT_PATCH).unwrap();
+        let files: Vec<_> = data
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.status, f.additions, f.deletions))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("src/lib.rs", FileStatus::Modified, 2, 1),
+                ("new.txt", FileStatus::Added, 1, 0),
+                ("renamed.txt", FileStatus::Renamed, 0, 0),
+                ("logo.png", FileStatus::Modified, 0, 0),
+            ]
+        );
+
+        let rename = data.files[2].rename.as_ref().unwrap();
+        assert_eq!((rename.from.as_str(), rename.similarity), ("old.txt", 100));
+
+        let hunks = &data.file_hunks["src/lib.rs"];
+        assert_eq!(hunks.len(), 2);
+        let lines = &hunks[0].lines;
+        assert_eq!(lines[1].line_type, DiffLineType::Remove);
+        assert_eq!(lines[1].content, "-- removed line that looks like a header");
+        assert_eq!(lines[1].old_line_num, Some(2));
+        assert_eq!(lines[2].new_line_num, Some(2));
+        // Empty context line with its space stripped
+        assert_eq!(lines[3].line_type, DiffLineType::Context);
+        assert_eq!(
+            (lines[3].old_line_num, lines[3].new_line_num),
+            (Some(3), Some(3))
+        );
+        assert_eq!(hunks[1].lines[1].new_line_num, Some(11));
+        assert_eq!(data.file_hunks["new.txt"][0].lines.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_plain_unified_diff() {
+        let patch = "\
+--- old/notes.txt\t2024-01-15 10:30:00.000000000 +0100
++++ new/notes.txt\t2024-01-16 09:00:00.000000000 +0100
+@@ -1,2 +1,2 @@
+-first
++First
+ second
+";
+        let data = parse_patch(patch).unwrap();
+        assert_eq!(data.files.len(), 1);
+        assert_eq!(data.files[0].path, "new/notes.txt");
+        assert_eq!(data.file_hunks["new/notes.txt"][0].lines.len(), 3);
+    }
+
+    #[test]
+    fn test_empty_patch_is_an_error() {
+        assert!(parse_patch("").is_err());
+        assert!(parse_patch("just some text\n").is_err());
+    }
+}
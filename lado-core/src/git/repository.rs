@@ -0,0 +1,1528 @@
+use super::diff::{
+    DiffData, DiffHunk, DiffLine, DiffLineType, FileChange, FileDiff, FileStatus, Rename,
+};
+use super::fs_diff;
+use super::line_text::LineArena;
+use crate::encoding;
+use crate::funcname;
+use crate::generated;
+use crate::github::PrCommit;
+use crate::mbox;
+use anyhow::{anyhow, Context, Result};
+use async_channel::Receiver;
+use git2::{DiffOptions, Oid, Repository as Git2Repo};
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Turns the contents of a file (by path) into the text that is diffed, like
+/// git's `textconv`; `None` diffs them as they are.
+pub type TextConv = Arc<dyn Fn(&str, &[u8]) -> Option<Vec<u8>> + Send + Sync>;
+
+pub struct Repository {
+    repo: Git2Repo,
+    textconv: Option<TextConv>,
+    /// Compare CRLF and LF line endings as the same
+    normalize_eol: bool,
+}
+
+impl Repository {
+    /// Open the repository at the current directory
+    pub fn open_current_dir() -> Result<Self> {
+        Self::discover(Path::new("."))
+    }
+
+    /// Open the repository `dir` is in
+    pub fn discover(dir: &Path) -> Result<Self> {
+        let repo = Git2Repo::discover(dir).context("Not a git repository")?;
+        Ok(Self {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        })
+    }
+
+    /// A repository without a working directory or refs, keeping its objects
+    /// in memory. Stands in for one when the diff doesn't come from git.
+    pub fn in_memory() -> Result<Self> {
+        let odb = git2::Odb::new()?;
+        odb.add_new_mempack_backend(1)?;
+        let repo = Git2Repo::from_odb(odb)?;
+        Ok(Self {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        })
+    }
+
+    /// Open a repository at the given path
+    #[allow(dead_code)]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let repo = Git2Repo::open(path).context("Failed to open repository")?;
+        Ok(Self {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        })
+    }
+
+    /// Diff file contents as `textconv` renders them.
+    pub fn set_textconv(&mut self, textconv: TextConv) {
+        self.textconv = Some(textconv);
+    }
+
+    /// Diff CRLF line endings as LF, so a file checked in from Windows doesn't
+    /// show as rewritten. Also on if the repository's `core.autocrlf` is;
+    /// files `.gitattributes` marks `-text` keep theirs either way.
+    pub fn set_normalize_line_endings(&mut self, normalize: bool) {
+        let autocrlf = self
+            .repo
+            .config()
+            .and_then(|config| config.get_string("core.autocrlf"))
+            .is_ok_and(|value| value == "input" || value == "true");
+        self.normalize_eol = normalize || autocrlf;
+    }
+
+    /// The working directory; `None` for bare and in-memory repositories
+    pub fn workdir(&self) -> Option<&Path> {
+        self.repo.workdir()
+    }
+
+    /// The `.git` directory, where HEAD and the refs are
+    pub fn git_dir(&self) -> &Path {
+        self.repo.path()
+    }
+
+    /// Whether this is a shallow clone, whose history stops early
+    pub fn is_shallow(&self) -> bool {
+        self.repo.is_shallow()
+    }
+
+    /// Write the objects lado makes from now on (e.g. the commits of a
+    /// patch series) to memory, leaving the repository as it was
+    pub fn keep_new_objects_in_memory(&self) -> Result<()> {
+        // Objects are written to the backend of the highest priority
+        self.repo.odb()?.add_new_mempack_backend(1000)?;
+        Ok(())
+    }
+
+    /// URL of a remote, if it exists and has one
+    pub fn remote_url(&self, name: &str) -> Option<String> {
+        let remote = self.repo.find_remote(name).ok()?;
+        remote.url().map(str::to_string)
+    }
+
+    /// Find the default branch (main or master)
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub fn find_default_branch(&self) -> Result<String> {
+        // Try common default branch names
+        for branch in &["main", "master"] {
+            if self
+                .repo
+                .find_branch(branch, git2::BranchType::Local)
+                .is_ok()
+            {
+                return Ok(branch.to_string());
+            }
+        }
+
+        // Try to get from remote HEAD
+        if let Ok(remote) = self.repo.find_remote("origin") {
+            if let Some(_url) = remote.url() {
+                // Check for origin/main or origin/master
+                for branch in &["origin/main", "origin/master"] {
+                    if self.repo.revparse_single(branch).is_ok() {
+                        return Ok(branch.strip_prefix("origin/").unwrap().to_string());
+                    }
+                }
+            }
+        }
+
+        Err(anyhow!("Could not find default branch (main or master)"))
+    }
+
+    /// Resolve a ref name to an OID
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub fn resolve_ref(&self, ref_name: &str) -> Result<Oid> {
+        // First try as a direct ref
+        if let Ok(reference) = self.repo.find_reference(ref_name) {
+            if let Some(oid) = reference.target() {
+                return Ok(oid);
+            }
+        }
+
+        // Try as a branch name
+        if let Ok(branch) = self.repo.find_branch(ref_name, git2::BranchType::Local) {
+            if let Some(reference) = branch.get().target() {
+                return Ok(reference);
+            }
+        }
+
+        // Try as a remote branch
+        let remote_ref = format!("origin/{}", ref_name);
+        if let Ok(branch) = self.repo.find_branch(&remote_ref, git2::BranchType::Remote) {
+            if let Some(reference) = branch.get().target() {
+                return Ok(reference);
+            }
+        }
+
+        // Try revparse as last resort
+        let obj = self
+            .repo
+            .revparse_single(ref_name)
+            .context(format!("Could not resolve ref: {}", ref_name))?;
+
+        Ok(obj.id())
+    }
+
+    /// The best common ancestor of `a` and `b`: where one forked off the
+    /// other, as `git merge-base` finds it
+    pub fn merge_base(&self, a: Oid, b: Oid) -> Result<Oid> {
+        Ok(self.repo.merge_base(a, b)?)
+    }
+
+    /// Names of the local branches, then the remote branches, then the tags,
+    /// each sorted, to pick a ref to diff against from.
+    pub fn ref_names(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for kind in [git2::BranchType::Local, git2::BranchType::Remote] {
+            let mut branches: Vec<String> = self
+                .repo
+                .branches(Some(kind))?
+                .filter_map(|branch| Some(branch.ok()?.0.name().ok()??.to_string()))
+                // origin/HEAD is only an alias of the remote's default branch
+                .filter(|name| !name.ends_with("/HEAD"))
+                .collect();
+            branches.sort();
+            names.extend(branches);
+        }
+        let mut tags: Vec<String> = self
+            .repo
+            .tag_names(None)?
+            .iter()
+            .flatten()
+            .map(str::to_string)
+            .collect();
+        tags.sort();
+        names.extend(tags);
+        Ok(names)
+    }
+
+    /// Commit the patches of `series` one after the other on top of `base`,
+    /// dated as their mails are; oldest first.
+    pub fn apply_series(&self, base: Oid, series: &mbox::Series) -> Result<Vec<Oid>> {
+        let mut parent = self.repo.find_commit(base)?;
+        let mut commits = Vec::new();
+        for (index, mail) in series.patches.iter().enumerate() {
+            let number = format!("{}/{}", index + 1, series.patches.len());
+            let diff = git2::Diff::from_buffer(mail.patch.as_bytes())
+                .with_context(|| format!("Patch {} is not a valid diff", number))?;
+            let mut applied = self
+                .repo
+                .apply_to_tree(&parent.tree()?, &diff, None)
+                .with_context(|| format!("Patch {} doesn't apply", number))?;
+            let tree = self.repo.find_tree(applied.write_tree_to(&self.repo)?)?;
+            let time = chrono::DateTime::parse_from_rfc2822(&mail.date).map_or(
+                parent.time(),
+                |date| git2::Time::new(date.timestamp(), date.offset().local_minus_utc() / 60),
+            );
+            let (name, email) = mail.author();
+            let signature = git2::Signature::new(name, email, &time)
+                .with_context(|| format!("Patch {} has no valid author", number))?;
+            let commit = self.repo.commit(
+                None,
+                &signature,
+                &signature,
+                &mail.commit_message(),
+                &tree,
+                &[&parent],
+            )?;
+            parent = self.repo.find_commit(commit)?;
+            commits.push(commit);
+        }
+        Ok(commits)
+    }
+
+    /// Commits reachable from `head` but not from `base`, oldest first, in
+    /// the same shape as a PR's commits.
+    pub fn commits_between(&self, base: Oid, head: Oid) -> Result<Vec<PrCommit>> {
+        let mut walk = self.repo.revwalk()?;
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+        walk.push(head)?;
+        walk.hide(base)?;
+        walk.map(|oid| {
+            let commit = self.repo.find_commit(oid?)?;
+            let sha = commit.id().to_string();
+            let author = commit.author();
+            Ok(PrCommit {
+                short_sha: sha.chars().take(7).collect(),
+                parent_sha: commit.parent_ids().next().map(|p| p.to_string()),
+                message: commit.message().unwrap_or_default().to_string(),
+                author: author.name().unwrap_or_default().to_string(),
+                authored_at: chrono::DateTime::from_timestamp(author.when().seconds(), 0)
+                    .map_or_else(String::new, |time| time.to_rfc3339()),
+                sha,
+            })
+        })
+        .collect()
+    }
+
+    /// For each file the commits between `base` and `head` change, the
+    /// index (in [`Self::commits_between`]) of the last one that changed it.
+    /// Only trees are compared.
+    #[tracing::instrument(skip_all)]
+    pub fn last_changed(&self, base: Oid, head: Oid) -> Result<HashMap<String, usize>> {
+        let mut walk = self.repo.revwalk()?;
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+        walk.push(head)?;
+        walk.hide(base)?;
+        let mut last = HashMap::new();
+        for (index, oid) in walk.enumerate() {
+            let commit = self.repo.find_commit(oid?)?;
+            let parent_tree = commit.parent(0).ok().map(|p| p.tree()).transpose()?;
+            let diff =
+                self.repo
+                    .diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+            for delta in diff.deltas() {
+                if let Some(path) = delta.new_file().path().or_else(|| delta.old_file().path()) {
+                    last.insert(path.to_string_lossy().to_string(), index);
+                }
+            }
+        }
+        Ok(last)
+    }
+
+    /// For each of `hunks` of `path`, the commits between `base` and `head`
+    /// that made it, oldest first: the last to add a line it adds, and the
+    /// first to remove a line it removes. Lines are matched by their text
+    /// without whitespace, which tab expansion and line endings don't
+    /// change; blank lines aren't matched.
+    #[tracing::instrument(skip_all)]
+    pub fn hunk_commits(
+        &self,
+        base: Oid,
+        head: Oid,
+        path: &str,
+        hunks: &[DiffHunk],
+    ) -> Result<Vec<Vec<Oid>>> {
+        let key = |text: &str| -> String { text.split_whitespace().collect() };
+        let mut walk = self.repo.revwalk()?;
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+        walk.push(head)?;
+        walk.hide(base)?;
+        // Lines each commit that changed the file added and removed
+        let mut changes: Vec<(Oid, HashSet<String>, HashSet<String>)> = Vec::new();
+        for oid in walk {
+            let commit = self.repo.find_commit(oid?)?;
+            let parent_tree = commit.parent(0).ok().map(|p| p.tree()).transpose()?;
+            let mut opts = DiffOptions::new();
+            opts.pathspec(path).disable_pathspec_match(true);
+            let diff = self.repo.diff_tree_to_tree(
+                parent_tree.as_ref(),
+                Some(&commit.tree()?),
+                Some(&mut opts),
+            )?;
+            let (mut added, mut removed) = (HashSet::new(), HashSet::new());
+            diff.print(git2::DiffFormat::Patch, |_, _, line| {
+                let text = key(&String::from_utf8_lossy(line.content()));
+                match line.origin() {
+                    '+' => added.insert(text),
+                    '-' => removed.insert(text),
+                    _ => false,
+                };
+                true
+            })?;
+            if !added.is_empty() || !removed.is_empty() {
+                changes.push((commit.id(), added, removed));
+            }
+        }
+
+        Ok(hunks
+            .iter()
+            .map(|hunk| {
+                let made_by: BTreeSet<usize> = hunk
+                    .lines
+                    .iter()
+                    .filter_map(|line| {
+                        let text = key(&line.content);
+                        if text.is_empty() {
+                            return None;
+                        }
+                        match line.line_type {
+                            DiffLineType::Add => changes
+                                .iter()
+                                .rposition(|(_, added, _)| added.contains(&text)),
+                            DiffLineType::Remove => changes
+                                .iter()
+                                .position(|(_, _, removed)| removed.contains(&text)),
+                            _ => None,
+                        }
+                    })
+                    .collect();
+                made_by.into_iter().map(|index| changes[index].0).collect()
+            })
+            .collect())
+    }
+
+    /// The whole message, people, date and size of the commit `sha`.
+    pub fn commit_details(&self, sha: &str) -> Result<CommitDetails> {
+        let commit = Oid::from_str(sha)
+            .and_then(|oid| self.repo.find_commit(oid))
+            .with_context(|| format!("Commit {} is not in this repository", sha))?;
+        let parent_tree = commit.parent(0).ok().map(|p| p.tree()).transpose()?;
+        let diff =
+            self.repo
+                .diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+        let signature = |s: &git2::Signature| {
+            format!(
+                "{} <{}>",
+                s.name().unwrap_or_default(),
+                s.email().unwrap_or_default()
+            )
+        };
+        let (author, committer) = (commit.author(), commit.committer());
+        Ok(CommitDetails {
+            message: commit.message().unwrap_or_default().trim_end().to_string(),
+            time: author.when().seconds(),
+            author: signature(&author),
+            committer: signature(&committer),
+            files_changed: diff.deltas().len(),
+        })
+    }
+
+    /// Get the HEAD commit OID
+    pub fn head_commit(&self) -> Result<Oid> {
+        let head = self.repo.head().context("Failed to get HEAD")?;
+        head.target().ok_or_else(|| anyhow!("HEAD has no target"))
+    }
+
+    /// Compute diff between two commits
+    pub fn diff_commits(&self, base_oid: Oid, head_oid: Oid) -> Result<DiffData> {
+        let changes = self.changed_files(base_oid, head_oid)?;
+        self.diff_files(changes, None)
+    }
+
+    /// Compute the diff between two commits again, reusing the hunks of every
+    /// file whose blobs are the same as in `previous`.
+    pub fn rediff_commits(
+        &self,
+        base_oid: Oid,
+        head_oid: Oid,
+        previous: &DiffData,
+    ) -> Result<DiffData> {
+        let changes = self.changed_files(base_oid, head_oid)?;
+        self.diff_files(changes, Some(previous))
+    }
+
+    /// List the files changed between two commits. Only trees are compared,
+    /// so this is quick even for huge diffs; contents are diffed separately
+    /// by `diff_files` or `stream_file_diffs`.
+    #[tracing::instrument(skip_all)]
+    pub fn changed_files(&self, base_oid: Oid, head_oid: Oid) -> Result<Vec<FileDelta>> {
+        tree_changes(&self.repo, base_oid, head_oid)
+    }
+
+    /// `changed_files` on a worker thread, as detecting renames among many
+    /// files reads their blobs. The receiver gets the one result.
+    pub fn changed_files_in_background(
+        &self,
+        base_oid: Oid,
+        head_oid: Oid,
+    ) -> Receiver<Result<Vec<FileDelta>>> {
+        let (tx, rx) = async_channel::bounded(1);
+        let repo_path = self.repo.path().to_path_buf();
+        std::thread::spawn(move || {
+            let result = Git2Repo::open(&repo_path)
+                .map_err(|e| anyhow!("Failed to open repository: {}", e))
+                .and_then(|repo| tree_changes(&repo, base_oid, head_oid));
+            // A closed channel means the diff is no longer wanted
+            let _ = tx.send_blocking(result);
+        });
+        rx
+    }
+
+    /// Diff what is staged against HEAD (`git diff --staged`).
+    #[tracing::instrument(skip_all)]
+    pub fn diff_index(&self) -> Result<DiffData> {
+        let diff = self
+            .repo
+            .diff_tree_to_index(self.head_tree()?.as_ref(), None, None)
+            .context("Failed to compute diff")?;
+        // Staged contents are blobs already
+        self.diff_files(file_deltas(diff)?, None)
+    }
+
+    /// Diff the working tree against HEAD: everything not committed yet,
+    /// staged or not, with untracked files. Their contents are added to the
+    /// object database as blobs, so `keep_new_objects_in_memory` should be
+    /// called first to leave the repository as it was.
+    #[tracing::instrument(skip_all)]
+    pub fn diff_workdir(&self) -> Result<DiffData> {
+        let workdir = self
+            .repo
+            .workdir()
+            .ok_or_else(|| anyhow!("The repository has no working tree"))?;
+        let mut options = git2::DiffOptions::new();
+        options.include_untracked(true).recurse_untracked_dirs(true);
+        let diff = self
+            .repo
+            .diff_tree_to_workdir_with_index(self.head_tree()?.as_ref(), Some(&mut options))
+            .context("Failed to compute diff")?;
+
+        let mut data = DiffData {
+            files: Vec::new(),
+            file_hunks: HashMap::new(),
+            blobs: HashMap::new(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+        };
+        for mut delta in file_deltas(diff)? {
+            // Files in the working tree have no blob yet; submodules are
+            // directories, left without one
+            let path = workdir.join(&delta.path);
+            let is_file = path.symlink_metadata().is_ok_and(|meta| !meta.is_dir());
+            if delta.status != FileStatus::Deleted && is_file {
+                // With git's filters applied, as `git add` would
+                let blob = self
+                    .repo
+                    .blob_path(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                delta.new = Some(blob);
+            }
+            // Blobs kept in memory can't be read by worker threads, so these
+            // are diffed one after another
+            let textconv = self.textconv.as_ref();
+            data.add_file(diff_file(&self.repo, delta, textconv, self.normalize_eol)?);
+        }
+        Ok(data)
+    }
+
+    /// Tree of the HEAD commit; `None` before the first commit
+    fn head_tree(&self) -> Result<Option<git2::Tree<'_>>> {
+        match self.repo.head() {
+            Ok(head) => Ok(Some(head.peel_to_tree().context("Failed to get HEAD tree")?)),
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => Ok(None),
+            Err(e) => Err(e).context("Failed to get HEAD"),
+        }
+    }
+
+    /// Diff the contents of `changes` across worker threads, reusing the
+    /// hunks of files whose blobs are the same as in `previous`.
+    #[tracing::instrument(skip_all, fields(files = changes.len()))]
+    pub fn diff_files(
+        &self,
+        changes: Vec<FileDelta>,
+        previous: Option<&DiffData>,
+    ) -> Result<DiffData> {
+        // Files whose blobs haven't changed since `previous` diff the same
+        let previous_files: HashMap<&str, &FileChange> = previous
+            .map(|p| p.files.iter().map(|f| (f.path.as_str(), f)).collect())
+            .unwrap_or_default();
+        let reusable = |delta: &FileDelta| {
+            let previous = previous?;
+            let file = previous_files.get(delta.path.as_str())?;
+            let blobs = (delta.old, delta.new);
+            let unchanged =
+                file.status == delta.status && previous.blobs.get(&delta.path) == Some(&blobs);
+            unchanged.then(|| FileDiff {
+                file: (*file).clone(),
+                hunks: previous
+                    .file_hunks
+                    .get(&delta.path)
+                    .cloned()
+                    .unwrap_or_default(),
+                blobs,
+            })
+        };
+
+        // git2 repositories can't be shared between threads, so each worker
+        // opens its own handle.
+        let repo_path = self.repo.path().to_path_buf();
+        let textconv = self.textconv.as_ref();
+        let normalize_eol = self.normalize_eol;
+        let per_file: Vec<FileDiff> = changes
+            .into_par_iter()
+            .map_init(
+                || Git2Repo::open(&repo_path),
+                |repo, delta| {
+                    if let Some(reused) = reusable(&delta) {
+                        return Ok(reused);
+                    }
+                    let repo = repo
+                        .as_ref()
+                        .map_err(|e| anyhow!("Failed to open repository: {}", e))?;
+                    diff_file(repo, delta, textconv, normalize_eol)
+                },
+            )
+            .collect::<Result<_>>()?;
+
+        let mut data = DiffData {
+            files: Vec::with_capacity(per_file.len()),
+            file_hunks: HashMap::new(),
+            blobs: HashMap::new(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+        };
+        for file_diff in per_file {
+            data.add_file(file_diff);
+        }
+        Ok(data)
+    }
+
+    /// Diff the contents of `changes` in the background, sending each file as
+    /// soon as it is done. The receiver closes once every file has been sent.
+    pub fn stream_file_diffs(&self, changes: Vec<FileDelta>) -> Receiver<Result<FileDiff>> {
+        let (tx, rx) = async_channel::unbounded();
+        let repo_path = self.repo.path().to_path_buf();
+        let textconv = self.textconv.clone();
+        let normalize_eol = self.normalize_eol;
+        std::thread::spawn(move || {
+            changes.into_par_iter().for_each_init(
+                || (Git2Repo::open(&repo_path), tx.clone()),
+                |(repo, tx), delta| {
+                    let result = repo
+                        .as_ref()
+                        .map_err(|e| anyhow!("Failed to open repository: {}", e))
+                        .and_then(|repo| diff_file(repo, delta, textconv.as_ref(), normalize_eol));
+                    // A closed channel means the diff is no longer shown
+                    let _ = tx.send_blocking(result);
+                },
+            );
+        });
+        rx
+    }
+
+    /// Contents of a blob, e.g. one of `DiffData::blobs`.
+    pub fn blob_content(&self, id: Oid) -> Result<Vec<u8>> {
+        Ok(self.repo.find_blob(id)?.content().to_vec())
+    }
+
+    /// Blob of `path` in the tree of `commit` (or a tag of it); `None` if
+    /// it isn't a file there.
+    pub fn blob_at(&self, commit: Oid, path: &str) -> Result<Option<Oid>> {
+        let tree = self
+            .repo
+            .find_object(commit, None)
+            .and_then(|object| object.peel_to_commit())
+            .with_context(|| format!("Failed to find commit {}", commit))?
+            .tree()
+            .context("Failed to get commit tree")?;
+        let entry = tree.get_path(Path::new(path)).ok();
+        Ok(entry
+            .filter(|entry| entry.kind() == Some(git2::ObjectType::Blob))
+            .map(|entry| entry.id()))
+    }
+
+    /// Diff one file between two commits (or tags), whatever else changed
+    /// between them: `path` as it was at `base` against it at `head`. Empty
+    /// if it is the same in both.
+    pub fn diff_path(&self, path: &str, base: Oid, head: Oid) -> Result<DiffData> {
+        let (old, new) = (self.blob_at(base, path)?, self.blob_at(head, path)?);
+        let status = match (old, new) {
+            (None, None) => return Err(anyhow!("{} is in neither version", path)),
+            (None, _) => FileStatus::Added,
+            (_, None) => FileStatus::Deleted,
+            _ => FileStatus::Modified,
+        };
+
+        let mut data = DiffData {
+            files: Vec::new(),
+            file_hunks: HashMap::new(),
+            blobs: HashMap::new(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+        };
+        if old != new {
+            let delta = FileDelta {
+                path: path.to_string(),
+                status,
+                old,
+                new,
+                renamed_from: None,
+            };
+            let textconv = self.textconv.as_ref();
+            data.add_file(diff_file(&self.repo, delta, textconv, self.normalize_eol)?);
+        }
+        Ok(data)
+    }
+
+    /// Diff two files on disk (`--files`).
+    pub fn diff_file_paths(&self, old: &Path, new: &Path) -> Result<DiffData> {
+        self.diff_disk_files(fs_diff::file_pair(old, new)?)
+    }
+
+    /// Diff two directories on disk file by file (`--dirs`).
+    pub fn diff_dirs(&self, old: &Path, new: &Path) -> Result<DiffData> {
+        self.diff_disk_files(fs_diff::dir_pairs(old, new)?)
+    }
+
+    /// Diff files read from disk rather than from commits. Their contents are
+    /// added to the object database as blobs, so the repository is normally
+    /// `in_memory()`; files whose contents match are left out.
+    #[tracing::instrument(skip_all, fields(files = files.len()))]
+    fn diff_disk_files(&self, files: Vec<fs_diff::DiskFile>) -> Result<DiffData> {
+        let blob = |path: Option<&PathBuf>| -> Result<Option<Oid>> {
+            path.map(|path| {
+                let content = std::fs::read(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                Ok(self.repo.blob(&content)?)
+            })
+            .transpose()
+        };
+
+        let mut data = DiffData {
+            files: Vec::new(),
+            file_hunks: HashMap::new(),
+            blobs: HashMap::new(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+        };
+        for file in files {
+            let (old, new) = (blob(file.old.as_ref())?, blob(file.new.as_ref())?);
+            if old == new {
+                continue;
+            }
+            let status = match (old, new) {
+                (None, _) => FileStatus::Added,
+                (_, None) => FileStatus::Deleted,
+                _ => FileStatus::Modified,
+            };
+            // An in-memory repository can't be opened again by worker
+            // threads, so these are diffed one after another
+            let delta = FileDelta {
+                path: file.path,
+                status,
+                old,
+                new,
+                renamed_from: None,
+            };
+            let textconv = self.textconv.as_ref();
+            data.add_file(diff_file(&self.repo, delta, textconv, self.normalize_eol)?);
+        }
+        Ok(data)
+    }
+}
+
+/// A commit beyond its summary, as `commit_details` reads it
+#[derive(Debug, Clone)]
+pub struct CommitDetails {
+    /// The whole message
+    pub message: String,
+    /// `Name <email>`
+    pub author: String,
+    pub committer: String,
+    /// When it was authored, in seconds since the epoch
+    pub time: i64,
+    /// Files changed from its first parent (from nothing for a root commit)
+    pub files_changed: usize,
+}
+
+/// A changed file as listed by the tree diff, before its content is diffed.
+pub struct FileDelta {
+    pub path: String,
+    pub status: FileStatus,
+    /// Blob on each side; `None` if the file doesn't exist there (or is a submodule)
+    pub old: Option<Oid>,
+    pub new: Option<Oid>,
+    /// Old path of a renamed file
+    pub renamed_from: Option<String>,
+}
+
+/// The files changed between the trees of two commits of `repo`.
+fn tree_changes(repo: &Git2Repo, base_oid: Oid, head_oid: Oid) -> Result<Vec<FileDelta>> {
+    let base_commit = repo
+        .find_commit(base_oid)
+        .context("Failed to find base commit")?;
+    let head_commit = repo
+        .find_commit(head_oid)
+        .context("Failed to find head commit")?;
+
+    let base_tree = base_commit
+        .tree()
+        .context("Failed to get base commit tree")?;
+    let head_tree = head_commit
+        .tree()
+        .context("Failed to get head commit tree")?;
+
+    let diff = repo
+        .diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None)
+        .context("Failed to compute diff")?;
+    file_deltas(diff)
+}
+
+/// The files changed by `diff`, with renames detected
+fn file_deltas(mut diff: git2::Diff) -> Result<Vec<FileDelta>> {
+    // Untracked files can be where a file was moved to, too
+    let mut find = git2::DiffFindOptions::new();
+    find.renames(true).for_untracked(true);
+    diff.find_similar(Some(&mut find))
+        .context("Failed to detect renames")?;
+
+    let changes = diff
+        .deltas()
+        .map(|delta| FileDelta {
+            path: delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            status: match delta.status() {
+                git2::Delta::Added | git2::Delta::Untracked => FileStatus::Added,
+                git2::Delta::Deleted => FileStatus::Deleted,
+                git2::Delta::Modified => FileStatus::Modified,
+                git2::Delta::Renamed => FileStatus::Renamed,
+                _ => FileStatus::Modified,
+            },
+            old: blob_id(&delta.old_file()),
+            new: blob_id(&delta.new_file()),
+            renamed_from: (delta.status() == git2::Delta::Renamed)
+                .then(|| delta.old_file().path())
+                .flatten()
+                .map(|p| p.to_string_lossy().to_string()),
+        })
+        .collect();
+    Ok(changes)
+}
+
+fn blob_id(file: &git2::DiffFile) -> Option<Oid> {
+    if file.id().is_zero() || file.mode() == git2::FileMode::Commit {
+        None
+    } else {
+        Some(file.id())
+    }
+}
+
+/// The contents of `blob` (empty if there is none), converted by `textconv`.
+fn converted<'a>(
+    blob: Option<&'a git2::Blob>,
+    path: &str,
+    textconv: Option<&TextConv>,
+) -> Cow<'a, [u8]> {
+    let raw = blob.map(|b| b.content()).unwrap_or_default();
+    match textconv.and_then(|convert| convert(path, raw)) {
+        Some(text) => Cow::Owned(text),
+        None => Cow::Borrowed(raw),
+    }
+}
+
+/// Whether git converts the line endings of `path`: anything but files
+/// `.gitattributes` marks `-text` (or `binary`).
+fn converts_eol(repo: &Git2Repo, path: &Path) -> bool {
+    let flags = git2::AttrCheckFlags::FILE_THEN_INDEX;
+    let text = repo
+        .get_attr(path, "text", flags)
+        .map(git2::AttrValue::from_string);
+    !matches!(text, Ok(git2::AttrValue::False))
+}
+
+/// The `linguist-generated` attribute of `path`, if `.gitattributes` sets
+/// or unsets it.
+fn linguist_generated(repo: &Git2Repo, path: &Path) -> Option<bool> {
+    let flags = git2::AttrCheckFlags::FILE_THEN_INDEX;
+    let value = repo.get_attr(path, "linguist-generated", flags).ok()?;
+    match git2::AttrValue::from_string(value) {
+        git2::AttrValue::True => Some(true),
+        git2::AttrValue::False => Some(false),
+        git2::AttrValue::String(value) => Some(value == "true"),
+        _ => None,
+    }
+}
+
+/// Diff the contents of a single file, converted by `textconv` if given and
+/// with CRLF line endings made LF if `normalize_eol`.
+#[tracing::instrument(skip_all, fields(path = %delta.path))]
+fn diff_file(
+    repo: &Git2Repo,
+    delta: FileDelta,
+    textconv: Option<&TextConv>,
+    normalize_eol: bool,
+) -> Result<FileDiff> {
+    let old_blob = delta.old.map(|id| repo.find_blob(id)).transpose()?;
+    let new_blob = delta.new.map(|id| repo.find_blob(id)).transpose()?;
+    let path = Path::new(&delta.path);
+    let mut old_content = converted(old_blob.as_ref(), &delta.path, textconv);
+    let mut new_content = converted(new_blob.as_ref(), &delta.path, textconv);
+    // Both sides are diffed as UTF-8, so that libgit2 compares characters
+    // rather than bytes and the lines can be shown as they are
+    let encoding = encoding::detect(&[&old_content, &new_content]);
+    if encoding.is_some() {
+        for content in [&mut old_content, &mut new_content] {
+            let text = encoding::decode(content, encoding).into_owned();
+            *content = Cow::Owned(text.into_bytes());
+        }
+    }
+    if normalize_eol && converts_eol(repo, path) {
+        for content in [&mut old_content, &mut new_content] {
+            if content.windows(2).any(|pair| pair == b"\r\n") {
+                *content = Cow::Owned(lf_line_endings(content));
+            }
+        }
+    }
+
+    let mut opts = DiffOptions::new();
+    opts.context_lines(3);
+
+    let patch = git2::Patch::from_buffers(
+        &old_content,
+        Some(path),
+        &new_content,
+        Some(path),
+        Some(&mut opts),
+    )
+    .with_context(|| format!("Failed to diff {}", delta.path))?;
+
+    // All line text of the file goes into one buffer; the lines get their
+    // content once it's complete
+    let mut arena = LineArena::default();
+    let mut hunks = Vec::with_capacity(patch.num_hunks());
+    let mut lines = Vec::new();
+    for hunk_idx in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(hunk_idx)?;
+        for line_idx in 0..line_count {
+            let line = patch.line_in_hunk(hunk_idx, line_idx)?;
+            arena.push(String::from_utf8_lossy(line.content()).trim_end_matches('\n'));
+            let line_type = match line.origin() {
+                '+' => DiffLineType::Add,
+                '-' => DiffLineType::Remove,
+                ' ' => DiffLineType::Context,
+                _ => DiffLineType::Context,
+            };
+            lines.push((hunk_idx, line_type, line.old_lineno(), line.new_lineno()));
+        }
+
+        hunks.push(DiffHunk {
+            header: String::from_utf8_lossy(hunk.header()).to_string(),
+            old_start: hunk.old_start(),
+            old_lines: hunk.old_lines(),
+            new_start: hunk.new_start(),
+            new_lines: hunk.new_lines(),
+            lines: Vec::with_capacity(line_count),
+        });
+    }
+    for ((hunk_idx, line_type, old_line_num, new_line_num), content) in
+        lines.into_iter().zip(arena.finish())
+    {
+        hunks[hunk_idx].lines.push(DiffLine {
+            line_type,
+            old_line_num,
+            new_line_num,
+            content,
+            comment: None,
+        });
+    }
+
+    // Name the section around each hunk where a grammar can find it; the
+    // new version unless the file was deleted
+    let (text, new_side) = if new_content.is_empty() {
+        (&old_content, false)
+    } else {
+        (&new_content, true)
+    };
+    let source = String::from_utf8_lossy(text);
+    if let Some(sections) = funcname::Sections::parse(&delta.path, &source) {
+        for hunk in &mut hunks {
+            let start = if new_side {
+                hunk.new_start
+            } else {
+                hunk.old_start
+            };
+            if let Some(section) = sections.around(start) {
+                hunk.header = funcname::with_context(&hunk.header, section);
+            }
+        }
+    }
+
+    // Counted by libgit2 while building the patch
+    let (_, additions, deletions) = patch.line_stats()?;
+    let rename = delta.renamed_from.map(|from| {
+        let lines = |content: &[u8]| {
+            let unterminated = !content.is_empty() && !content.ends_with(b"\n");
+            content.iter().filter(|&&b| b == b'\n').count() + usize::from(unterminated)
+        };
+        Rename::new(from, lines(&old_content), lines(&new_content), deletions)
+    });
+    let generated = generated::is_generated(&delta.path, linguist_generated(repo, path), &hunks);
+    let file = FileChange {
+        path: delta.path,
+        status: delta.status,
+        additions,
+        deletions,
+        rename,
+        encoding,
+        generated,
+    };
+    Ok(FileDiff {
+        file,
+        hunks,
+        blobs: (delta.old, delta.new),
+    })
+}
+
+/// `content` with each CRLF replaced by LF; lone CRs are kept.
+fn lf_line_endings(content: &[u8]) -> Vec<u8> {
+    let mut lf = Vec::with_capacity(content.len());
+    for (i, &byte) in content.iter().enumerate() {
+        if byte != b'\r' || content.get(i + 1) != Some(&b'\n') {
+            lf.push(byte);
+        }
+    }
+    lf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    /// A new repository in a temporary directory, which goes with the guard
+    fn temp_repo(name: &str) -> (TempDir, Git2Repo) {
+        let dir = TempDir::new(name);
+        let repo = Git2Repo::init(dir.path()).unwrap();
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_open_current_dir() {
+        // This test should pass when run from within a git repo
+        let result = Repository::open_current_dir();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_diff_file_counts_changed_lines() {
+        // In-memory object database, so the test doesn't write to a real repo
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let old = repo.blob(b"a\nb\nc\n").unwrap();
+        let new = repo.blob(b"a\nB\nc\nd\n").unwrap();
+        let delta = FileDelta {
+            path: "letters.txt".to_string(),
+            status: FileStatus::Modified,
+            old: Some(old),
+            new: Some(new),
+            renamed_from: None,
+        };
+
+        let diff = diff_file(&repo, delta, None, false).unwrap();
+        assert_eq!((diff.file.additions, diff.file.deletions), (2, 1));
+        assert_eq!(diff.hunks.len(), 1);
+    }
+
+    #[test]
+    fn test_diff_file_rename_similarity() {
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let old = repo.blob(b"a\nb\nc\nd\n").unwrap();
+        let new = repo.blob(b"a\nb\nc\nD\n").unwrap();
+        let delta = FileDelta {
+            path: "new.txt".to_string(),
+            status: FileStatus::Renamed,
+            old: Some(old),
+            new: Some(new),
+            renamed_from: Some("old.txt".to_string()),
+        };
+
+        let diff = diff_file(&repo, delta, None, false).unwrap();
+        let rename = diff.file.rename.unwrap();
+        assert_eq!(rename.from, "old.txt");
+        assert_eq!(rename.similarity, 75);
+    }
+
+    #[test]
+    fn test_diff_file_decodes_latin1() {
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let old = repo.blob(b"name = caf\xe9\n").unwrap();
+        let new = repo.blob(b"name = cr\xe8me br\xfbl\xe9e\n").unwrap();
+        let delta = FileDelta {
+            path: "menu.ini".to_string(),
+            status: FileStatus::Modified,
+            old: Some(old),
+            new: Some(new),
+            renamed_from: None,
+        };
+
+        let diff = diff_file(&repo, delta, None, false).unwrap();
+        assert_eq!(diff.file.encoding, Some("windows-1252"));
+        let lines: Vec<&str> = diff.hunks[0].lines.iter().map(|l| &*l.content).collect();
+        assert_eq!(lines, ["name = café", "name = crème brûlée"]);
+    }
+
+    #[test]
+    fn test_diff_file_normalizes_line_endings() {
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let old = repo.blob(b"a\r\nb\r\n").unwrap();
+        let new = repo.blob(b"a\nb\nc\n").unwrap();
+        let delta = || FileDelta {
+            path: "windows.txt".to_string(),
+            status: FileStatus::Modified,
+            old: Some(old),
+            new: Some(new),
+            renamed_from: None,
+        };
+
+        let diff = diff_file(&repo, delta(), None, false).unwrap();
+        assert_eq!((diff.file.additions, diff.file.deletions), (3, 2));
+        let diff = diff_file(&repo, delta(), None, true).unwrap();
+        assert_eq!((diff.file.additions, diff.file.deletions), (1, 0));
+        assert_eq!(lf_line_endings(b"a\r\nb\rc\r\n"), b"a\nb\rc\n");
+    }
+
+    #[test]
+    fn test_diff_file_applies_textconv() {
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let old = repo.blob(b"\x01\x02").unwrap();
+        let new = repo.blob(b"\x01\x03").unwrap();
+        let delta = FileDelta {
+            path: "data.bin".to_string(),
+            status: FileStatus::Modified,
+            old: Some(old),
+            new: Some(new),
+            renamed_from: None,
+        };
+        let hex: TextConv = Arc::new(|path, content| {
+            path.ends_with(".bin").then(|| {
+                content
+                    .iter()
+                    .map(|b| format!("{:02x}\n", b))
+                    .collect::<String>()
+                    .into_bytes()
+            })
+        });
+
+        let diff = diff_file(&repo, delta, Some(&hex), false).unwrap();
+        let lines: Vec<&str> = diff.hunks[0]
+            .lines
+            .iter()
+            .map(|l| l.content.as_str())
+            .collect();
+        assert_eq!(lines, ["01", "02", "03"]);
+        assert_eq!((diff.file.additions, diff.file.deletions), (1, 1));
+    }
+
+    #[test]
+    fn test_commits_between_lists_oldest_first() {
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let time = git2::Time::new(1_700_000_000, 0);
+        let sig = git2::Signature::new("Ada", "ada@example.com", &time).unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        // base <- first <- second
+        let mut oids: Vec<Oid> = Vec::new();
+        for message in ["base", "first", "second"] {
+            let parent = oids.last().map(|&oid| repo.find_commit(oid).unwrap());
+            let parents: Vec<_> = parent.iter().collect();
+            oids.push(
+                repo.commit(None, &sig, &sig, message, &tree, &parents)
+                    .unwrap(),
+            );
+        }
+        let (base, head) = (oids[0], oids[2]);
+        drop(tree);
+
+        let repo = Repository {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        };
+        let commits = repo.commits_between(base, head).unwrap();
+        let messages: Vec<_> = commits.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(messages, ["first", "second"]);
+        assert_eq!(commits[0].parent_sha, Some(base.to_string()));
+        assert_eq!(commits[1].author, "Ada");
+        assert_eq!(commits[1].authored_at, "2023-11-14T22:13:20+00:00");
+    }
+
+    #[test]
+    fn test_last_changed() {
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let sig = git2::Signature::now("Ada", "ada@example.com").unwrap();
+        // base, then a.txt and b.txt added, then a.txt changed
+        let mut oids: Vec<Oid> = Vec::new();
+        for files in [
+            &[][..],
+            &[("a.txt", "1"), ("b.txt", "1")],
+            &[("a.txt", "2"), ("b.txt", "1")],
+        ] {
+            let mut builder = repo.treebuilder(None).unwrap();
+            for (name, content) in files {
+                let blob = repo.blob(content.as_bytes()).unwrap();
+                builder.insert(name, blob, 0o100644).unwrap();
+            }
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let parent = oids.last().map(|&oid| repo.find_commit(oid).unwrap());
+            let parents: Vec<_> = parent.iter().collect();
+            oids.push(
+                repo.commit(None, &sig, &sig, "commit", &tree, &parents)
+                    .unwrap(),
+            );
+        }
+
+        let repo = Repository {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        };
+        let last = repo.last_changed(oids[0], oids[2]).unwrap();
+        assert_eq!(last.len(), 2);
+        assert_eq!(last["a.txt"], 1);
+        assert_eq!(last["b.txt"], 0);
+
+        // a.txt alone, from before it existed and from when it was changed
+        let added = repo.diff_path("a.txt", oids[0], oids[2]).unwrap();
+        assert_eq!(added.files[0].status, FileStatus::Added);
+        let changed = &repo.diff_path("a.txt", oids[1], oids[2]).unwrap().files[0];
+        assert_eq!((changed.additions, changed.deletions), (1, 1));
+        let same = repo.diff_path("b.txt", oids[1], oids[2]).unwrap();
+        assert!(same.files.is_empty());
+        assert!(repo.diff_path("c.txt", oids[0], oids[2]).is_err());
+    }
+
+    #[test]
+    fn test_apply_series() {
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let sig = git2::Signature::now("Ada", "ada@example.com").unwrap();
+        let empty = repo.treebuilder(None).unwrap().write().unwrap();
+        let empty = repo.find_tree(empty).unwrap();
+        let base = repo.commit(None, &sig, &sig, "Start", &empty, &[]).unwrap();
+        drop(empty);
+        let repo = Repository {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        };
+        let series = mbox::parse(
+            "From 1 Mon Sep 17 00:00:00 2001
+From: Ada <ada@example.com>
+Date: Mon, 15 Jan 2024 10:01:00 +0100
+Subject: [PATCH 1/2] Add a greeting
+
+---
+diff --git a/hello.txt b/hello.txt
+new file mode 100644
+--- /dev/null
++++ b/hello.txt
+@@ -0,0 +1 @@
++hello
+From 2 Mon Sep 17 00:00:00 2001
+From: Ada <ada@example.com>
+Date: Mon, 15 Jan 2024 10:02:00 +0100
+Subject: [PATCH 2/2] Be polite
+
+---
+diff --git a/hello.txt b/hello.txt
+--- a/hello.txt
++++ b/hello.txt
+@@ -1 +1 @@
+-hello
++hello, please
+",
+        );
+
+        let commits = repo.apply_series(base, &series).unwrap();
+        assert_eq!(commits.len(), 2);
+        let head = repo.repo.find_commit(commits[1]).unwrap();
+        assert_eq!(head.parent_id(0).unwrap(), commits[0]);
+        assert_eq!(head.summary(), Some("Be polite"));
+        let blob = head.tree().unwrap().get_name("hello.txt").unwrap().id();
+        assert_eq!(repo.repo.find_blob(blob).unwrap().content(), b"hello, please\n");
+    }
+
+    #[test]
+    fn test_hunk_commits() {
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let sig = git2::Signature::now("Ada", "ada@example.com").unwrap();
+        // base, then a line added at the end, then the first line changed
+        // and the last one removed
+        let mut oids: Vec<Oid> = Vec::new();
+        for content in [
+            "fn a() {}\n\nfn b() {}\n",
+            "fn a() {}\n\nfn b() {}\nfn c() {}\n",
+            "fn a(x: u8) {}\n\nfn b() {}\n",
+        ] {
+            let mut builder = repo.treebuilder(None).unwrap();
+            let blob = repo.blob(content.as_bytes()).unwrap();
+            builder.insert("lib.rs", blob, 0o100644).unwrap();
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let parent = oids.last().map(|&oid| repo.find_commit(oid).unwrap());
+            let parents: Vec<_> = parent.iter().collect();
+            oids.push(
+                repo.commit(None, &sig, &sig, "commit", &tree, &parents)
+                    .unwrap(),
+            );
+        }
+
+        let repo = Repository {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        };
+        let line = |line_type, content: &str| DiffLine {
+            line_type,
+            old_line_num: None,
+            new_line_num: None,
+            content: content.into(),
+            comment: None,
+        };
+        let hunk = |lines| DiffHunk {
+            header: String::new(),
+            old_start: 1,
+            old_lines: 1,
+            new_start: 1,
+            new_lines: 1,
+            lines,
+        };
+        let hunks = [
+            // Tabs in place of the spaces still match
+            hunk(vec![
+                line(DiffLineType::Remove, "fn a() {}"),
+                line(DiffLineType::Add, "fn a(x:\tu8) {}"),
+            ]),
+            hunk(vec![
+                line(DiffLineType::Context, "fn b() {}"),
+                line(DiffLineType::Remove, "fn c() {}"),
+            ]),
+            hunk(vec![line(DiffLineType::Add, "")]),
+        ];
+        let made_by = repo.hunk_commits(oids[0], oids[2], "lib.rs", &hunks).unwrap();
+        assert_eq!(made_by, [vec![oids[2]], vec![oids[2]], vec![]]);
+        // Before the last commit, the line added at the end came from the first
+        let added = [hunk(vec![line(DiffLineType::Add, "fn c() {}")])];
+        let made_by = repo.hunk_commits(oids[0], oids[2], "lib.rs", &added).unwrap();
+        assert_eq!(made_by, [vec![oids[1]]]);
+    }
+
+    #[test]
+    fn test_commit_details() {
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let author = git2::Signature::new(
+            "Ada",
+            "ada@example.com",
+            &git2::Time::new(1_700_000_000, 60),
+        )
+        .unwrap();
+        let committer = git2::Signature::now("Bob", "bob@example.com").unwrap();
+        let mut builder = repo.treebuilder(None).unwrap();
+        for name in ["a.txt", "b.txt"] {
+            let blob = repo.blob(name.as_bytes()).unwrap();
+            builder.insert(name, blob, 0o100644).unwrap();
+        }
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let message = "Add files\n\nBoth of them.\n";
+        let oid = repo
+            .commit(None, &author, &committer, message, &tree, &[])
+            .unwrap();
+        drop(tree);
+        drop(builder);
+
+        let repo = Repository {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        };
+        let details = repo.commit_details(&oid.to_string()).unwrap();
+        assert_eq!(details.message, "Add files\n\nBoth of them.");
+        assert_eq!(details.author, "Ada <ada@example.com>");
+        assert_eq!(details.committer, "Bob <bob@example.com>");
+        assert_eq!(details.time, 1_700_000_000);
+        assert_eq!(details.files_changed, 2);
+        assert!(repo.commit_details(&"0".repeat(40)).is_err());
+    }
+
+    #[test]
+    fn test_diff_workdir_and_index() {
+        let (dir, repo) = temp_repo("workdir");
+        let root = dir.path();
+        let sig = git2::Signature::now("Ada", "ada@example.com").unwrap();
+        std::fs::write(root.join("a.txt"), "one\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "commit", &tree, &[])
+            .unwrap();
+        drop(tree);
+
+        // a.txt changed, b.txt staged and c.txt untracked
+        std::fs::write(root.join("a.txt"), "two\n").unwrap();
+        std::fs::write(root.join("b.txt"), "new\n").unwrap();
+        index.add_path(Path::new("b.txt")).unwrap();
+        index.write().unwrap();
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(root.join("src/c.txt"), "untracked\n").unwrap();
+
+        let repo = Repository {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        };
+        let staged = repo.diff_index().unwrap();
+        let workdir = repo.keep_new_objects_in_memory().and_then(|_| repo.diff_workdir());
+
+        let paths = |data: &DiffData| -> Vec<(String, FileStatus)> {
+            let mut paths: Vec<_> = data.files.iter().map(|f| (f.path.clone(), f.status)).collect();
+            paths.sort_by(|a, b| a.0.cmp(&b.0));
+            paths
+        };
+        assert_eq!(paths(&staged), [("b.txt".to_string(), FileStatus::Added)]);
+        let workdir = workdir.unwrap();
+        assert_eq!(
+            paths(&workdir),
+            [
+                ("a.txt".to_string(), FileStatus::Modified),
+                ("b.txt".to_string(), FileStatus::Added),
+                ("src/c.txt".to_string(), FileStatus::Added),
+            ]
+        );
+        let hunks = &workdir.file_hunks["a.txt"];
+        let contents: Vec<&str> = hunks[0].lines.iter().map(|l| l.content.as_str()).collect();
+        assert_eq!(contents, ["one", "two"]);
+    }
+
+    #[test]
+    fn test_changed_files_in_background() {
+        let (_dir, repo) = temp_repo("background");
+        let sig = git2::Signature::now("Ada", "ada@example.com").unwrap();
+        let mut oids = Vec::new();
+        for (file, content) in [("a.txt", "one\n"), ("b.txt", "two\n")] {
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder
+                .insert(file, repo.blob(content.as_bytes()).unwrap(), 0o100644)
+                .unwrap();
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let parent = oids.last().map(|&oid| repo.find_commit(oid).unwrap());
+            let parents: Vec<_> = parent.iter().collect();
+            oids.push(repo.commit(None, &sig, &sig, file, &tree, &parents).unwrap());
+        }
+
+        let repo = Repository {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        };
+        let changes = repo
+            .changed_files_in_background(oids[0], oids[1])
+            .recv_blocking()
+            .unwrap()
+            .unwrap();
+
+        let mut paths: Vec<_> = changes.iter().map(|c| (c.path.as_str(), c.status)).collect();
+        paths.sort_by_key(|&(path, _)| path);
+        assert_eq!(
+            paths,
+            [("a.txt", FileStatus::Deleted), ("b.txt", FileStatus::Added)]
+        );
+    }
+
+    #[test]
+    fn test_listed_files_are_filled_in_as_streamed() {
+        let (_dir, repo) = temp_repo("streamed");
+        let sig = git2::Signature::now("Ada", "ada@example.com").unwrap();
+        let mut oids = Vec::new();
+        for files in [&[("a.txt", "one\n"), ("b.txt", "two\n")][..], &[("a.txt", "one\nuno\n")]] {
+            let mut builder = repo.treebuilder(None).unwrap();
+            for (file, content) in files {
+                builder
+                    .insert(file, repo.blob(content.as_bytes()).unwrap(), 0o100644)
+                    .unwrap();
+            }
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let parent = oids.last().map(|&oid| repo.find_commit(oid).unwrap());
+            let parents: Vec<_> = parent.iter().collect();
+            oids.push(repo.commit(None, &sig, &sig, "change", &tree, &parents).unwrap());
+        }
+        let repo = Repository {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        };
+
+        // Listed up front: paths and statuses, nothing of the contents yet
+        let changes = repo.changed_files(oids[0], oids[1]).unwrap();
+        let mut data = DiffData::listed(&changes);
+        let listed: Vec<_> = data
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.status, f.additions, f.deletions))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("a.txt", FileStatus::Modified, 0, 0),
+                ("b.txt", FileStatus::Deleted, 0, 0)
+            ]
+        );
+        assert!(data.file_hunks.is_empty() && data.blobs.is_empty());
+
+        // Streamed in later, in whatever order the workers finish
+        let positions: HashMap<String, usize> = changes
+            .iter()
+            .enumerate()
+            .map(|(i, change)| (change.path.clone(), i))
+            .collect();
+        let results = repo.stream_file_diffs(changes);
+        while let Ok(result) = results.recv_blocking() {
+            let file_diff = result.unwrap();
+            data.set_file(positions[&file_diff.file.path], file_diff);
+        }
+
+        let whole = repo.diff_commits(oids[0], oids[1]).unwrap();
+        let counts = |data: &DiffData| -> Vec<_> {
+            data.files
+                .iter()
+                .map(|f| (f.path.clone(), f.status, f.additions, f.deletions))
+                .collect()
+        };
+        assert_eq!(counts(&data), counts(&whole));
+        assert_eq!(counts(&data)[0], ("a.txt".to_string(), FileStatus::Modified, 1, 0));
+        assert_eq!(data.blobs, whole.blobs);
+        assert_eq!(data.file_hunks["a.txt"].len(), whole.file_hunks["a.txt"].len());
+    }
+
+    #[test]
+    fn test_merge_base() {
+        let (_dir, repo) = temp_repo("merge-base");
+        let sig = git2::Signature::now("Ada", "ada@example.com").unwrap();
+        let (fork, main, feature) = {
+            let tree = repo
+                .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+                .unwrap();
+            let commit = |message, parents: &[&git2::Commit]| {
+                let oid = repo.commit(None, &sig, &sig, message, &tree, parents).unwrap();
+                repo.find_commit(oid).unwrap()
+            };
+            let fork = commit("fork", &[]);
+            let main = commit("main", &[&fork]);
+            let feature = commit("feature", &[&fork]);
+            (fork.id(), main.id(), feature.id())
+        };
+
+        let repo = Repository {
+            repo,
+            textconv: None,
+            normalize_eol: false,
+        };
+        assert_eq!(repo.merge_base(main, feature).unwrap(), fork);
+        assert_eq!(repo.merge_base(fork, feature).unwrap(), fork);
+    }
+}
@@ -0,0 +1,85 @@
+//! Shallow clones (e.g. CI checkouts or `git clone --depth 1`) stop their
+//! history early, so the base of a diff may not be there. Fetching the rest
+//! of the history from `origin` brings it in.
+
+use super::Repository;
+use anyhow::{anyhow, Context, Result};
+use async_channel::Receiver;
+use std::process::Command;
+
+impl Repository {
+    /// Fetch the history missing from this shallow clone in the background,
+    /// along with `reference` if it is a branch not fetched yet. Sends what
+    /// went wrong if git failed.
+    pub fn deepen(&self, reference: Option<&str>) -> Receiver<Result<()>> {
+        let (tx, rx) = async_channel::bounded(1);
+        let git_dir = self.git_dir().to_path_buf();
+        let args = fetch_args(reference);
+        std::thread::spawn(move || {
+            let result = Command::new("git")
+                .arg("--git-dir")
+                .arg(&git_dir)
+                .args(&args)
+                .output()
+                .context("Failed to run git. Is it installed?")
+                .and_then(|output| {
+                    if output.status.success() {
+                        Ok(())
+                    } else {
+                        let stderr = String::from_utf8_lossy(&output.stderr);
+                        Err(anyhow!("git {} failed: {}", args.join(" "), stderr.trim()))
+                    }
+                });
+            let _ = tx.send_blocking(result);
+        });
+        rx
+    }
+}
+
+/// Arguments to `git` fetching all of `origin`'s history, and `reference`
+/// as a remote-tracking branch if it names one. Revisions like `main~2` or
+/// commit ids are left to the history fetched. `--` keeps a reference
+/// starting with `-` from being taken for an option.
+fn fetch_args(reference: Option<&str>) -> Vec<String> {
+    let mut args = vec![
+        "fetch".to_string(),
+        "--unshallow".to_string(),
+        "--".to_string(),
+        "origin".to_string(),
+    ];
+    let branch = reference
+        .map(|reference| reference.strip_prefix("origin/").unwrap_or(reference))
+        .filter(|branch| {
+            let is_commit_id = branch.len() >= 7 && branch.chars().all(|c| c.is_ascii_hexdigit());
+            !branch.is_empty() && !branch.contains(['^', '~', ':', '@']) && !is_commit_id
+        });
+    if let Some(branch) = branch {
+        args.push(format!("{}:refs/remotes/origin/{}", branch, branch));
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fetch_args() {
+        let base = ["fetch", "--unshallow", "--", "origin"];
+        assert_eq!(fetch_args(None), base);
+        assert_eq!(
+            fetch_args(Some("main")),
+            [&base[..], &["main:refs/remotes/origin/main"]].concat()
+        );
+        assert_eq!(
+            fetch_args(Some("origin/feature/x")),
+            [&base[..], &["feature/x:refs/remotes/origin/feature/x"]].concat()
+        );
+        assert_eq!(fetch_args(Some("main~2")), base);
+        assert_eq!(fetch_args(Some("3f2a9c1")), base);
+        assert_eq!(
+            fetch_args(Some("--upload-pack=x")),
+            [&base[..], &["--upload-pack=x:refs/remotes/origin/--upload-pack=x"]].concat()
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::process::{Command, Output};
+use std::time::Instant;
+
+/// Represents PR branch information
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct PrInfo {
+    pub base_ref: String,
+    pub head_ref: String,
+    pub title: String,
+}
+
+/// Which side of the diff a comment is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommentSide {
+    Left,  // Old/original code (deletions)
+    Right, // New/modified code (additions)
+}
+
+/// A single PR review comment
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct PrComment {
+    pub id: u64,
+    pub in_reply_to_id: Option<u64>,
+    pub path: String,
+    pub line: Option<u32>,
+    pub side: CommentSide,
+    pub body: String,
+    pub author: String,
+    pub created_at: String,
+    pub commit_id: String,
+    pub original_commit_id: String,
+    /// The thread it is in has been marked resolved
+    pub resolved: bool,
+}
+
+/// A single commit in a PR
+#[derive(Debug, Clone)]
+pub struct PrCommit {
+    pub sha: String,
+    pub short_sha: String,
+    pub parent_sha: Option<String>,
+    pub message: String,
+    pub author: String,
+    /// When the commit was authored, in ISO 8601 as GitHub gives it
+    pub authored_at: String,
+}
+
+/// An open PR, as listed by `lado pr list`
+#[derive(Debug, Clone)]
+pub struct PrSummary {
+    pub number: u32,
+    pub title: String,
+    pub author: String,
+    pub updated_at: String,
+}
+
+/// Comments grouped by file path, then by line number
+pub type FileComments = HashMap<String, Vec<PrComment>>;
+
+/// A file's comments keyed by the diff side and line they are attached to
+pub type LineComments<'a> = HashMap<(CommentSide, u32), Vec<&'a PrComment>>;
+
+/// Run gh with `args`, logging the invocation and how long it took.
+fn gh(args: &[&str]) -> Result<Output> {
+    run_gh(Command::new("gh").args(args), args)
+}
+
+/// Run gh with `args` in `dir`, for the repository (and its GitHub remote)
+/// there rather than the one of the process's working directory
+fn gh_in(dir: &Path, args: &[&str]) -> Result<Output> {
+    run_gh(Command::new("gh").args(args).current_dir(dir), args)
+}
+
+fn run_gh(command: &mut Command, args: &[&str]) -> Result<Output> {
+    let started = Instant::now();
+    let output = command
+        .output()
+        .context("Failed to execute gh CLI. Is it installed?")?;
+    tracing::debug!(
+        command = %format!("gh {}", args.join(" ")),
+        status = %output.status,
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "ran gh"
+    );
+    Ok(output)
+}
+
+/// Whether the gh CLI is installed and runs
+pub fn is_installed() -> bool {
+    gh(&["--version"]).is_ok_and(|output| output.status.success())
+}
+
+/// Fetch PR information using the gh CLI
+pub fn get_pr_info(dir: &Path, pr_number: u32) -> Result<PrInfo> {
+    let output = gh_in(dir, &[
+        "pr",
+        "view",
+        &pr_number.to_string(),
+        "--json",
+        "baseRefName,headRefName,title",
+    ])?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("gh pr view failed: {}", stderr));
+    }
+
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("Failed to parse gh output")?;
+
+    let base_ref = json["baseRefName"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Missing baseRefName"))?
+        .to_string();
+
+    let head_ref = json["headRefName"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Missing headRefName"))?
+        .to_string();
+
+    let title = json["title"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Missing title"))?
+        .to_string();
+
+    Ok(PrInfo {
+        base_ref,
+        head_ref,
+        title,
+    })
+}
+
+/// List the repository's open PRs, most recently updated first
+#[tracing::instrument]
+pub fn list_open_prs(dir: &Path) -> Result<Vec<PrSummary>> {
+    let output = gh_in(dir, &[
+        "pr",
+        "list",
+        "--state",
+        "open",
+        "--limit",
+        "100",
+        "--json",
+        "number,title,author,updatedAt",
+    ])?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("gh pr list failed: {}", stderr));
+    }
+
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("Failed to parse gh output")?;
+
+    let prs_array = json.as_array().ok_or_else(|| anyhow!("Expected array"))?;
+
+    let mut prs: Vec<PrSummary> = prs_array
+        .iter()
+        .filter_map(|pr| {
+            Some(PrSummary {
+                number: pr["number"].as_u64()? as u32,
+                title: pr["title"].as_str().unwrap_or("").to_string(),
+                author: pr["author"]["login"].as_str().unwrap_or("").to_string(),
+                updated_at: pr["updatedAt"].as_str().unwrap_or("").to_string(),
+            })
+        })
+        .collect();
+    // ISO 8601 timestamps sort chronologically as strings
+    prs.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+
+    Ok(prs)
+}
+
+/// Get PR info including base/head refs and title
+#[tracing::instrument]
+pub fn get_pr_refs(dir: &Path, pr_number: u32) -> Result<PrInfo> {
+    get_pr_info(dir, pr_number)
+}
+
+/// Fetch PR review comments using the gh CLI
+#[tracing::instrument]
+pub fn get_pr_comments(dir: &Path, pr_number: u32) -> Result<Vec<PrComment>> {
+    let output = gh_in(dir, &[
+        "api",
+        &format!("repos/{{owner}}/{{repo}}/pulls/{}/comments", pr_number),
+        "--paginate",
+    ])?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("gh api failed: {}", stderr));
+    }
+
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("Failed to parse gh output")?;
+
+    let comments_array = json.as_array().ok_or_else(|| anyhow!("Expected array"))?;
+
+    // Only the GraphQL API knows which threads are resolved
+    let resolved = resolved_comment_ids(dir, pr_number).unwrap_or_else(|e| {
+        eprintln!("Warning: Could not fetch resolved threads: {:#}", e);
+        HashSet::new()
+    });
+
+    let comments = comments_array
+        .iter()
+        .map(|comment| {
+            let mut comment = parse_comment(comment);
+            comment.resolved = resolved.contains(&comment.id);
+            comment
+        })
+        .collect();
+
+    Ok(comments)
+}
+
+/// A review comment as the REST API returns it
+fn parse_comment(comment: &serde_json::Value) -> PrComment {
+    let text = |value: &serde_json::Value| value.as_str().unwrap_or("").to_string();
+    PrComment {
+        id: comment["id"].as_u64().unwrap_or(0),
+        in_reply_to_id: comment["in_reply_to_id"].as_u64(),
+        path: text(&comment["path"]),
+        line: comment["line"].as_u64().map(|n| n as u32),
+        side: match comment["side"].as_str() {
+            Some("LEFT") => CommentSide::Left,
+            _ => CommentSide::Right,
+        },
+        body: text(&comment["body"]),
+        author: text(&comment["user"]["login"]),
+        created_at: text(&comment["created_at"]),
+        commit_id: text(&comment["commit_id"]),
+        original_commit_id: text(&comment["original_commit_id"]),
+        resolved: false,
+    }
+}
+
+/// Post a review comment on `line` of `path` on `side` of the diff, as of
+/// the PR's head commit `commit_id`, and return it as GitHub stored it.
+#[tracing::instrument(skip(body))]
+pub fn post_review_comment(
+    dir: &Path,
+    pr_number: u32,
+    commit_id: &str,
+    path: &str,
+    line: u32,
+    side: CommentSide,
+    body: &str,
+) -> Result<PrComment> {
+    let side = match side {
+        CommentSide::Left => "LEFT",
+        CommentSide::Right => "RIGHT",
+    };
+    let output = gh_in(dir, &[
+        "api",
+        "-X",
+        "POST",
+        &format!("repos/{{owner}}/{{repo}}/pulls/{}/comments", pr_number),
+        "-f",
+        &format!("body={}", body),
+        "-f",
+        &format!("commit_id={}", commit_id),
+        "-f",
+        &format!("path={}", path),
+        "-F",
+        &format!("line={}", line),
+        "-f",
+        &format!("side={}", side),
+    ])?;
+    posted_comment(&output)
+}
+
+/// Reply to the review thread started by comment `comment_id`, and return
+/// the reply as GitHub stored it.
+#[tracing::instrument(skip(body))]
+pub fn post_reply(dir: &Path, pr_number: u32, comment_id: u64, body: &str) -> Result<PrComment> {
+    let output = gh_in(dir, &[
+        "api",
+        "-X",
+        "POST",
+        &format!(
+            "repos/{{owner}}/{{repo}}/pulls/{}/comments/{}/replies",
+            pr_number, comment_id
+        ),
+        "-f",
+        &format!("body={}", body),
+    ])?;
+    posted_comment(&output)
+}
+
+/// The comment a `gh api -X POST` created
+fn posted_comment(output: &Output) -> Result<PrComment> {
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("gh api failed: {}", stderr));
+    }
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("Failed to parse gh output")?;
+    Ok(parse_comment(&json))
+}
+
+/// Ids of the review comments in the PR's resolved threads
+fn resolved_comment_ids(dir: &Path, pr_number: u32) -> Result<HashSet<u64>> {
+    const QUERY: &str = "\
+query($owner: String!, $repo: String!, $number: Int!, $endCursor: String) {
+  repository(owner: $owner, name: $repo) {
+    pullRequest(number: $number) {
+      reviewThreads(first: 100, after: $endCursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes { isResolved comments(first: 100) { nodes { databaseId } } }
+      }
+    }
+  }
+}";
+    let output = gh_in(dir, &[
+        "api",
+        "graphql",
+        "--paginate",
+        "-F",
+        "owner={owner}",
+        "-F",
+        "repo={repo}",
+        "-F",
+        &format!("number={}", pr_number),
+        "-f",
+        &format!("query={}", QUERY),
+        "--jq",
+        ".data.repository.pullRequest.reviewThreads.nodes[] \
+         | select(.isResolved) | .comments.nodes[].databaseId",
+    ])?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("gh api graphql failed: {}", stderr));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect())
+}
+
+/// Login of the user gh is logged in as
+pub fn current_user() -> Result<String> {
+    let output = gh(&["api", "user", "--jq", ".login"])?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("gh api failed: {}", stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Fetch commits for a PR using the gh CLI
+#[tracing::instrument]
+pub fn get_pr_commits(dir: &Path, pr_number: u32) -> Result<Vec<PrCommit>> {
+    let output = gh_in(dir, &[
+        "api",
+        &format!("repos/{{owner}}/{{repo}}/pulls/{}/commits", pr_number),
+        "--paginate",
+    ])?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("gh api failed: {}", stderr));
+    }
+
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("Failed to parse gh output")?;
+
+    let commits_array = json.as_array().ok_or_else(|| anyhow!("Expected array"))?;
+
+    let mut commits = Vec::new();
+    for commit in commits_array {
+        let sha = commit["sha"].as_str().unwrap_or("").to_string();
+        let short_sha = sha.chars().take(7).collect();
+        let message = commit["commit"]["message"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        let author = commit["commit"]["author"]["name"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        let authored_at = commit["commit"]["author"]["date"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        let parent_sha = commit["parents"]
+            .as_array()
+            .and_then(|parents| parents.first())
+            .and_then(|p| p["sha"].as_str())
+            .map(|s| s.to_string());
+
+        commits.push(PrCommit {
+            sha,
+            short_sha,
+            parent_sha,
+            message,
+            author,
+            authored_at,
+        });
+    }
+
+    Ok(commits)
+}
+
+/// Group comments by file path
+pub fn group_comments_by_file(comments: Vec<PrComment>) -> FileComments {
+    let mut grouped: FileComments = HashMap::new();
+    for comment in comments {
+        grouped.entry(comment.path.clone()).or_default().push(comment);
+    }
+    // Sort comments within each file by line number, then by creation time for threads
+    for comments in grouped.values_mut() {
+        comments.sort_by(|a, b| {
+            let line_cmp = a.line.cmp(&b.line);
+            if line_cmp == std::cmp::Ordering::Equal {
+                // Sort by id to maintain thread order (reply ids are higher)
+                a.id.cmp(&b.id)
+            } else {
+                line_cmp
+            }
+        });
+    }
+    grouped
+}
+
+/// Index one file's comments by (side, line) so each diff line can look up
+/// its comments directly. Comments without a line are left out; the order
+/// within each entry follows `comments`.
+pub fn index_comments_by_line(comments: &[PrComment]) -> LineComments<'_> {
+    let mut index: LineComments = HashMap::new();
+    for comment in comments {
+        if let Some(line) = comment.line {
+            index.entry((comment.side, line)).or_default().push(comment);
+        }
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_comment() {
+        let json = serde_json::json!({
+            "id": 7,
+            "in_reply_to_id": 3,
+            "path": "src/main.rs",
+            "line": 12,
+            "side": "LEFT",
+            "body": "Why?",
+            "user": { "login": "ada" },
+            "created_at": "2024-01-15T10:30:00Z",
+            "commit_id": "abc",
+            "original_commit_id": "def",
+        });
+        let comment = parse_comment(&json);
+        assert_eq!(comment.id, 7);
+        assert_eq!(comment.in_reply_to_id, Some(3));
+        assert_eq!(comment.line, Some(12));
+        assert_eq!(comment.side, CommentSide::Left);
+        assert_eq!(comment.author, "ada");
+        assert_eq!(comment.original_commit_id, "def");
+        assert!(!comment.resolved);
+    }
+}
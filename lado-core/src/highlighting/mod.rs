@@ -0,0 +1,276 @@
+mod syntax;
+pub mod theme;
+mod tree_sitter_hl;
+
+use lru::LruCache;
+use rayon::prelude::*;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use syntax::SyntaxHighlighter;
+use theme::HighlightTheme;
+use tree_sitter_hl::TreeSitterHighlighter;
+
+/// A highlighted line consisting of styled spans.
+#[derive(Debug, Clone)]
+pub struct HighlightedLine {
+    pub spans: Vec<HighlightedSpan>,
+}
+
+/// A span of text with color.
+#[derive(Debug, Clone)]
+pub struct HighlightedSpan {
+    pub text: String,
+    pub color: String,
+}
+
+/// Unified highlighter: tree-sitter for supported languages, syntect as fallback.
+///
+/// Cloning is cheap (grammars and syntax sets are shared), so a snapshot can
+/// be handed to worker threads.
+#[derive(Clone)]
+pub struct Highlighter {
+    tree_sitter: Arc<TreeSitterHighlighter>,
+    syntect: SyntaxHighlighter,
+    current_theme: HighlightTheme,
+    /// UI theme name the colors were derived from
+    theme_name: String,
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        Self {
+            tree_sitter: Arc::new(TreeSitterHighlighter::new()),
+            syntect: SyntaxHighlighter::new(),
+            current_theme: theme::dark(),
+            theme_name: "dark".to_string(),
+        }
+    }
+
+    /// Update the color theme. `ui_theme` is the UI theme name
+    /// (e.g. "dark", "light", "solarized-dark", "solarized-light").
+    pub fn set_theme(&mut self, ui_theme: &str) {
+        self.current_theme = theme::theme_for_ui(ui_theme);
+        self.theme_name = ui_theme.to_string();
+
+        // Also update syntect theme for fallback
+        let syntect_theme = match ui_theme {
+            "light" => "InspiredGitHub",
+            "solarized-light" => "Doom Solarized Light",
+            "solarized-dark" => "Solarized (dark)",
+            _ => "base16-ocean.dark",
+        };
+        self.syntect.set_theme(syntect_theme);
+    }
+
+    /// Highlight `code` for the file at `file_path`.
+    /// Uses tree-sitter when available, syntect otherwise.
+    #[tracing::instrument(skip_all, fields(path = file_path))]
+    pub fn highlight(&self, code: &str, file_path: &str) -> Vec<HighlightedLine> {
+        let ext = file_path.rsplit('.').next().unwrap_or("");
+        if self.tree_sitter.can_highlight(ext) {
+            self.tree_sitter.highlight(code, ext, &self.current_theme)
+        } else {
+            self.syntect.highlight(code, file_path)
+        }
+    }
+
+    /// Highlight many files at once, spread across worker threads.
+    /// Takes `(path, code)` pairs and returns entries for a [`HighlightCache`].
+    #[tracing::instrument(skip_all)]
+    pub fn highlight_batch(
+        &self,
+        files: Vec<(String, String)>,
+    ) -> Vec<(HighlightKey, Vec<HighlightedLine>)> {
+        files
+            .into_par_iter()
+            .map(|(path, code)| {
+                let lines = self.highlight(&code, &path);
+                (self.cache_key(&code, &path), lines)
+            })
+            .collect()
+    }
+
+    /// Highlight `code` through `cache`: reuse an earlier result for the same
+    /// file content and theme, or highlight and remember it.
+    pub fn highlight_with_cache(
+        &self,
+        code: &str,
+        file_path: &str,
+        cache: &mut HighlightCache,
+    ) -> Vec<HighlightedLine> {
+        let key = self.cache_key(code, file_path);
+        if let Some(lines) = cache.entries.get(&key) {
+            return lines.clone();
+        }
+        let lines = self.highlight(code, file_path);
+        cache.entries.put(key, lines.clone());
+        lines
+    }
+
+    /// Earlier highlighting of `code` from `cache`, if there is one.
+    pub fn cached(
+        &self,
+        code: &str,
+        file_path: &str,
+        cache: &mut HighlightCache,
+    ) -> Option<Vec<HighlightedLine>> {
+        cache.entries.get(&self.cache_key(code, file_path)).cloned()
+    }
+
+    /// Cache key for highlighting `code` as `file_path` with the current theme.
+    pub fn cache_key(&self, code: &str, file_path: &str) -> HighlightKey {
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+        HighlightKey {
+            path: file_path.to_string(),
+            content: hasher.finish(),
+            theme: self.theme_name.clone(),
+        }
+    }
+}
+
+/// Identifies one highlighting result.
+///
+/// The content hash stands in for the commit: the same file at the same
+/// commits always produces the same diff text, and a changed diff never
+/// reuses a stale result.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HighlightKey {
+    path: String,
+    content: u64,
+    theme: String,
+}
+
+/// Number of highlighted files kept around.
+const CACHE_CAPACITY: usize = 512;
+
+/// Least-recently-used cache of highlighted files, so switching between files,
+/// commits or themes during a review doesn't highlight them again.
+pub struct HighlightCache {
+    entries: LruCache<HighlightKey, Vec<HighlightedLine>>,
+}
+
+impl HighlightCache {
+    pub fn contains(&self, key: &HighlightKey) -> bool {
+        self.entries.contains(key)
+    }
+
+    pub fn extend(&mut self, entries: Vec<(HighlightKey, Vec<HighlightedLine>)>) {
+        for (key, lines) in entries {
+            self.entries.put(key, lines);
+        }
+    }
+}
+
+impl Default for HighlightCache {
+    fn default() -> Self {
+        Self {
+            entries: LruCache::new(NonZeroUsize::new(CACHE_CAPACITY).unwrap()),
+        }
+    }
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_sitter_used_for_rust() {
+        let hl = Highlighter::new();
+        let code = "fn main() {\n    let x: i32 = 42;\n}\n";
+        let result = hl.highlight(code, "src/main.rs");
+
+        assert_eq!(result.len(), 3);
+        // Tree-sitter produces richer spans than syntect
+        assert!(result[0].spans.len() >= 2);
+    }
+
+    #[test]
+    fn test_syntect_fallback_for_unknown_ext() {
+        let hl = Highlighter::new();
+        let code = "some text\n";
+        let result = hl.highlight(code, "file.rkt");
+
+        assert!(!result.is_empty());
+    }
+
+    #[test]
+    fn test_batch_matches_single_file_highlighting() {
+        let hl = Highlighter::new();
+        let files = vec![
+            ("a.rs".to_string(), "fn a() {}\n".to_string()),
+            ("b.py".to_string(), "def b():\n    pass\n".to_string()),
+        ];
+        let mut cache = HighlightCache::default();
+        cache.extend(hl.highlight_batch(files.clone()));
+
+        for (path, code) in &files {
+            let cached = hl
+                .cached(code, path, &mut cache)
+                .expect("file was batch highlighted");
+            assert_eq!(cached.len(), hl.highlight(code, path).len());
+        }
+    }
+
+    #[test]
+    fn test_cache_is_keyed_by_content_and_theme() {
+        let mut hl = Highlighter::new();
+        let mut cache = HighlightCache::default();
+        hl.highlight_with_cache("fn a() {}\n", "a.rs", &mut cache);
+        assert!(hl.cached("fn a() {}\n", "a.rs", &mut cache).is_some());
+
+        // Different content for the same path
+        assert!(hl.cached("let x = 1;\n", "a.rs", &mut cache).is_none());
+
+        // Same content under another theme, and back again
+        hl.set_theme("light");
+        assert!(hl.cached("fn a() {}\n", "a.rs", &mut cache).is_none());
+        hl.highlight_with_cache("fn a() {}\n", "a.rs", &mut cache);
+        hl.set_theme("dark");
+        assert!(hl.cached("fn a() {}\n", "a.rs", &mut cache).is_some());
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let hl = Highlighter::new();
+        let mut cache = HighlightCache::default();
+        let code = |i: usize| format!("let x = {};\n", i);
+        let entries = (0..CACHE_CAPACITY)
+            .map(|i| (hl.cache_key(&code(i), "a.rs"), Vec::new()))
+            .collect();
+        cache.extend(entries);
+
+        // Using the oldest entry keeps it, so the next oldest goes instead
+        assert!(hl.cached(&code(0), "a.rs", &mut cache).is_some());
+        cache.extend(vec![(hl.cache_key(&code(CACHE_CAPACITY), "a.rs"), Vec::new())]);
+        assert!(cache.contains(&hl.cache_key(&code(0), "a.rs")));
+        assert!(!cache.contains(&hl.cache_key(&code(1), "a.rs")));
+        assert!(cache.contains(&hl.cache_key(&code(2), "a.rs")));
+        assert!(cache.contains(&hl.cache_key(&code(CACHE_CAPACITY), "a.rs")));
+    }
+
+    #[test]
+    fn test_theme_switching() {
+        let mut hl = Highlighter::new();
+        let code = "fn test() {}\n";
+
+        let dark_result = hl.highlight(code, "test.rs");
+        hl.set_theme("light");
+        let light_result = hl.highlight(code, "test.rs");
+
+        // Both should produce output, but colors should differ
+        assert!(!dark_result.is_empty());
+        assert!(!light_result.is_empty());
+        // At least one span color should differ between themes
+        let dark_colors: Vec<&str> = dark_result[0].spans.iter().map(|s| s.color.as_str()).collect();
+        let light_colors: Vec<&str> = light_result[0].spans.iter().map(|s| s.color.as_str()).collect();
+        assert_ne!(dark_colors, light_colors, "dark and light themes should produce different colors");
+    }
+}
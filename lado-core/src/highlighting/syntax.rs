@@ -1,8 +1,8 @@
 //! Syntect-based syntax highlighting (fallback for languages without tree-sitter).
 
-use std::io::Cursor;
+use std::sync::OnceLock;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::{Style, Theme, ThemeSet};
+use syntect::highlighting::{Style, ThemeSet};
 use syntect::parsing::SyntaxSet;
 use syntect::util::LinesWithEndings;
 
@@ -11,66 +11,65 @@ use super::{HighlightedLine, HighlightedSpan};
 /// Maximum number of spans per line to prevent UI slowdown
 const MAX_SPANS_PER_LINE: usize = 50;
 
-/// Embedded custom themes (compiled into binary)
-const DOOM_SOLARIZED_LIGHT: &str = include_str!("../../themes/doom-solarized-light.tmTheme");
+/// syntect's default themes plus our custom ones, dumped by build.rs
+const THEME_DUMP: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/themes.themedump"));
 
-/// Syntax highlighter using syntect
+/// Syntax definitions, loaded on first use. Most files are highlighted by
+/// tree-sitter, so many sessions never need them.
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// Color themes, loaded on first use.
+fn theme_set() -> &'static ThemeSet {
+    static THEME_SET: OnceLock<ThemeSet> = OnceLock::new();
+    THEME_SET.get_or_init(|| {
+        syntect::dumps::from_uncompressed_data(THEME_DUMP)
+            .expect("theme dump from build.rs is valid")
+    })
+}
+
+/// Syntax highlighter using syntect. Syntaxes and themes are shared and
+/// loaded lazily on the first highlight.
+#[derive(Clone)]
 pub struct SyntaxHighlighter {
-    syntax_set: SyntaxSet,
-    theme_set: ThemeSet,
     current_theme: String,
 }
 
 impl SyntaxHighlighter {
     pub fn new() -> Self {
-        let mut theme_set = ThemeSet::load_defaults();
-
-        // Load custom embedded themes
-        if let Ok(theme) = Self::parse_theme(DOOM_SOLARIZED_LIGHT) {
-            theme_set.themes.insert("Doom Solarized Light".to_string(), theme);
-        }
-
         Self {
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set,
             current_theme: "base16-ocean.dark".to_string(),
         }
     }
 
-    /// Parse a .tmTheme from string
-    fn parse_theme(theme_str: &str) -> Result<Theme, syntect::LoadingError> {
-        let mut cursor = Cursor::new(theme_str.as_bytes());
-        ThemeSet::load_from_reader(&mut cursor)
-    }
-
-    /// Set the current theme by name
+    /// Set the current theme by name. Unknown names fall back to the default
+    /// theme when highlighting.
     pub fn set_theme(&mut self, theme_name: &str) {
-        if self.theme_set.themes.contains_key(theme_name) {
-            self.current_theme = theme_name.to_string();
-        }
+        self.current_theme = theme_name.to_string();
     }
 
     /// Highlight a code snippet and return styled spans
     pub fn highlight(&self, code: &str, file_path: &str) -> Vec<HighlightedLine> {
         let extension = file_path.rsplit('.').next().unwrap_or("");
 
-        let syntax = self
-            .syntax_set
+        let syntax_set = syntax_set();
+        let syntax = syntax_set
             .find_syntax_by_extension(extension)
-            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+            .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
 
-        let theme = self
-            .theme_set
-            .themes
+        let themes = &theme_set().themes;
+        let theme = themes
             .get(&self.current_theme)
-            .unwrap_or_else(|| &self.theme_set.themes["base16-ocean.dark"]);
+            .unwrap_or_else(|| &themes["base16-ocean.dark"]);
 
         let mut highlighter = HighlightLines::new(syntax, theme);
         let mut result = Vec::new();
 
         for line in LinesWithEndings::from(code) {
             let ranges: Vec<(Style, &str)> = highlighter
-                .highlight_line(line, &self.syntax_set)
+                .highlight_line(line, syntax_set)
                 .unwrap_or_default();
 
             // Cap spans to prevent UI slowdown on very long lines
@@ -121,4 +120,11 @@ mod tests {
 
         assert_eq!(result.len(), 1);
     }
+
+    #[test]
+    fn test_bundled_themes_include_custom() {
+        let themes = &theme_set().themes;
+        assert!(themes.contains_key("base16-ocean.dark"));
+        assert!(themes.contains_key("Doom Solarized Light"));
+    }
 }
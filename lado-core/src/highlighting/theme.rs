@@ -1,6 +1,30 @@
-//! Color mappings from tree-sitter highlight names to Slint colors per UI theme.
+//! Color mappings from tree-sitter highlight names to colors per UI theme.
 
-use slint::Color;
+/// An opaque RGB color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+impl Color {
+    pub const fn from_rgb_u8(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    pub fn red(&self) -> u8 {
+        self.r
+    }
+
+    pub fn green(&self) -> u8 {
+        self.g
+    }
+
+    pub fn blue(&self) -> u8 {
+        self.b
+    }
+}
 
 /// Standard highlight capture names used across all tree-sitter grammars.
 /// The order here determines the index used in `HighlightTheme::colors`.
@@ -36,6 +60,7 @@ pub const HIGHLIGHT_NAMES: &[&str] = &[
 ];
 
 /// Colors indexed parallel to `HIGHLIGHT_NAMES`.
+#[derive(Clone)]
 pub struct HighlightTheme {
     pub colors: Vec<Color>,
     /// Default foreground when no highlight applies
@@ -0,0 +1,45 @@
+//! The GUI-independent part of lado: reading diffs from git (or Mercurial,
+//! patches and plain directories), talking to GitHub through the `gh` CLI
+//! and to Sourcehut's mailing lists, and syntax highlighting. Nothing in
+//! here knows about Slint, so other front ends and tools can reuse it.
+//!
+//! ```no_run
+//! use lado_core::git::Repository;
+//! use lado_core::highlighting::Highlighter;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let repo = Repository::open_current_dir()?;
+//! let base = repo.resolve_ref(&repo.find_default_branch()?)?;
+//! let diff = repo.diff_commits(base, repo.head_commit()?)?;
+//!
+//! let highlighter = Highlighter::new();
+//! for file in &diff.files {
+//!     println!("{} +{} -{}", file.path, file.additions, file.deletions);
+//!     for hunk in diff.file_hunks.get(&file.path).into_iter().flatten() {
+//!         let source: String = hunk.lines.iter().map(|l| format!("{}\n", l.content)).collect();
+//!         let _highlighted = highlighter.highlight(&source, &file.path);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod difftastic;
+pub mod encoding;
+pub mod exclude;
+pub mod folding;
+pub mod funcname;
+pub mod generated;
+pub mod git;
+pub mod github;
+pub mod highlighting;
+pub mod lsp;
+pub mod mbox;
+pub mod scm;
+pub mod search;
+pub mod semantic;
+pub mod sourcehut;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod todos;
+pub mod usages;
@@ -0,0 +1,339 @@
+//! A minimal client for language servers (rust-analyzer, gopls, clangd,
+//! ...), enough to ask where a symbol is defined.
+//!
+//! Speaks JSON-RPC over the server's stdin and stdout. Requests block until
+//! their answer arrives; requests the server sends in the meantime get an
+//! empty answer and notifications are ignored.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// Where something is defined
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    /// 0-based, as in the protocol
+    pub line: u32,
+}
+
+/// A running language server, shut down when dropped.
+pub struct LanguageServer {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+    /// Version of each document sent to the server, by URI
+    documents: HashMap<String, i32>,
+}
+
+impl LanguageServer {
+    /// Start `command` (split at whitespace) for the project in `root`.
+    pub fn start(command: &str, root: &Path) -> Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| anyhow!("The language server command is empty"))?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start {}. Is it installed?", program))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            bail!("{} has no stdin or stdout", program);
+        };
+        let mut server = Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            next_id: 0,
+            documents: HashMap::new(),
+        };
+
+        let root_uri = file_uri(root);
+        server.request(
+            "initialize",
+            json!({
+                "processId": std::process::id(),
+                "rootUri": root_uri,
+                "workspaceFolders": [{"uri": root_uri, "name": "lado"}],
+                "capabilities": {
+                    "textDocument": {"definition": {"linkSupport": true}},
+                },
+            }),
+        )?;
+        server.notify("initialized", json!({}))?;
+        tracing::debug!(command, "started language server");
+        Ok(server)
+    }
+
+    /// Where the symbol at `line` and `character` of `path` is defined, both
+    /// 0-based and `character` in UTF-16 code units. `text` is the content
+    /// of `path` the position refers to.
+    #[tracing::instrument(skip(self, text))]
+    pub fn definition(
+        &mut self,
+        path: &Path,
+        text: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Vec<Location>> {
+        let uri = file_uri(path);
+        self.sync(&uri, path, text)?;
+        let result = self.request(
+            "textDocument/definition",
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": line, "character": character},
+            }),
+        )?;
+        Ok(locations(&result))
+    }
+
+    /// Let the server know `text` is the content of the document at `uri`.
+    fn sync(&mut self, uri: &str, path: &Path, text: &str) -> Result<()> {
+        match self.documents.get_mut(uri) {
+            Some(version) => {
+                *version += 1;
+                let version = *version;
+                self.notify(
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": {"uri": uri, "version": version},
+                        "contentChanges": [{"text": text}],
+                    }),
+                )
+            }
+            None => {
+                self.documents.insert(uri.to_string(), 1);
+                self.notify(
+                    "textDocument/didOpen",
+                    json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": language_id(path),
+                            "version": 1,
+                            "text": text,
+                        },
+                    }),
+                )
+            }
+        }
+    }
+
+    fn send(&mut self, message: Value) -> Result<()> {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> Result<()> {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}))
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))?;
+        loop {
+            let message = read_message(&mut self.stdout)?;
+            match (message.get("id"), message.get("method")) {
+                (Some(answer_id), None) if *answer_id == id => {
+                    if let Some(error) = message.get("error") {
+                        bail!(
+                            "{} failed: {}",
+                            method,
+                            error["message"].as_str().unwrap_or("unknown error")
+                        );
+                    }
+                    return Ok(message["result"].clone());
+                }
+                // The server asks something (e.g. for configuration)
+                (Some(request_id), Some(_)) => {
+                    let request_id = request_id.clone();
+                    self.send(json!({"jsonrpc": "2.0", "id": request_id, "result": null}))?;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for LanguageServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Read one message: headers, an empty line, then `Content-Length` bytes of
+/// JSON.
+fn read_message(reader: &mut impl BufRead) -> Result<Value> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            bail!("The language server quit");
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let length = length.ok_or_else(|| anyhow!("The language server sent no Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).context("The language server sent invalid JSON")
+}
+
+/// The locations of a definition result: nothing, a `Location`, or a list
+/// of `Location`s or `LocationLink`s.
+fn locations(result: &Value) -> Vec<Location> {
+    let items = match result {
+        Value::Array(items) => items.as_slice(),
+        Value::Null => &[],
+        single => std::slice::from_ref(single),
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            let (uri, range) = match item.get("targetUri") {
+                Some(uri) => (uri, &item["targetSelectionRange"]),
+                None => (&item["uri"], &item["range"]),
+            };
+            Some(Location {
+                path: uri_path(uri.as_str()?)?,
+                line: range["start"]["line"].as_u64()? as u32,
+            })
+        })
+        .collect()
+}
+
+/// `languageId` of a document, by its extension
+fn language_id(path: &Path) -> &str {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    match extension {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "h" => "c",
+        "cc" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "kt" | "kts" => "kotlin",
+        "sh" | "bash" => "shellscript",
+        "md" => "markdown",
+        "yml" => "yaml",
+        other => other,
+    }
+}
+
+/// `file://` URI of an absolute path
+fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        // C:/src -> file:///C:/src
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// The path of a `file://` URI
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match (byte, tail) {
+            (b'%', [high, low, tail @ ..]) => {
+                let hex = std::str::from_utf8(&[*high, *low]).ok()?.to_string();
+                bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+                rest = tail;
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // file:///C:/src -> C:/src
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] => path[1..].to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_message() {
+        let body = r#"{"jsonrpc":"2.0","id":0,"result":null}"#;
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let message = read_message(&mut input.as_bytes()).unwrap();
+        assert_eq!(message["id"], 0);
+        assert!(read_message(&mut "".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_locations() {
+        let location = json!({
+            "uri": "file:///src/my%20lado/src/app.rs",
+            "range": {"start": {"line": 41, "character": 4}, "end": {"line": 41, "character": 9}},
+        });
+        let expected = Location {
+            path: PathBuf::from("/src/my lado/src/app.rs"),
+            line: 41,
+        };
+        assert_eq!(locations(&location), std::slice::from_ref(&expected));
+        assert_eq!(locations(&json!([location])), std::slice::from_ref(&expected));
+        assert_eq!(locations(&Value::Null), []);
+
+        let link = json!([{
+            "targetUri": "file:///src/my%20lado/src/app.rs",
+            "targetRange": {"start": {"line": 40, "character": 0}, "end": {"line": 50, "character": 1}},
+            "targetSelectionRange": {"start": {"line": 41, "character": 4}, "end": {"line": 41, "character": 9}},
+        }]);
+        assert_eq!(locations(&link), [expected]);
+    }
+
+    #[test]
+    fn test_file_uri() {
+        let path = Path::new("/src/my lado/ünï.rs");
+        assert_eq!(file_uri(path), "file:///src/my%20lado/%C3%BCn%C3%AF.rs");
+        assert_eq!(uri_path(&file_uri(path)).unwrap(), path);
+        assert_eq!(
+            uri_path("file:///C:/src/lado").unwrap(),
+            PathBuf::from("C:/src/lado")
+        );
+    }
+}
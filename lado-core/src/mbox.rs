@@ -0,0 +1,318 @@
+//! Patch series as `git format-patch` mails them for review on a mailing
+//! list: an mbox with a mail per patch, after a cover letter (`[PATCH 0/N]`)
+//! if there is one. Committed on top of their base
+//! ([`crate::git::Repository::apply_series`]), the patches can be stepped
+//! through like the commits of a PR.
+
+use chrono::DateTime;
+
+/// A mail of the series
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mail {
+    /// Subject without the `[PATCH v2 1/3]` tag
+    pub subject: String,
+    /// `Name <email>`
+    pub from: String,
+    /// As the `Date` header gives it (RFC 2822)
+    pub date: String,
+    /// Text up to the patch: the rest of the commit message, or all of a
+    /// cover letter
+    pub message: String,
+    /// The patch itself, from the first `diff --git` on; empty in a cover
+    /// letter
+    pub patch: String,
+    /// `Message-ID`, with its angle brackets
+    pub message_id: String,
+    /// `Message-ID` of the mail it answers, if it does
+    pub in_reply_to: Option<String>,
+}
+
+/// The mails of an mbox
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Series {
+    pub cover_letter: Option<Mail>,
+    pub patches: Vec<Mail>,
+    /// Commit the series applies to, if `format-patch --base` named it
+    pub base_commit: Option<String>,
+}
+
+impl Mail {
+    /// The commit message of a patch: its subject, then the rest
+    pub fn commit_message(&self) -> String {
+        if self.message.is_empty() {
+            self.subject.clone()
+        } else {
+            format!("{}\n\n{}", self.subject, self.message)
+        }
+    }
+
+    /// Author name and email of `from`
+    pub fn author(&self) -> (&str, &str) {
+        match self.from.rsplit_once('<') {
+            Some((name, email)) => (
+                name.trim().trim_matches('"'),
+                email.trim_end().trim_end_matches('>'),
+            ),
+            None => (self.from.trim(), self.from.trim()),
+        }
+    }
+
+    /// `date` in ISO 8601, as GitHub dates commits; empty if it doesn't parse
+    pub fn date_iso(&self) -> String {
+        DateTime::parse_from_rfc2822(&self.date)
+            .map(|date| date.to_rfc3339())
+            .unwrap_or_default()
+    }
+}
+
+/// Split an mbox into its mails: the patches in order and the cover letter,
+/// the mail without a patch.
+pub fn parse(text: &str) -> Series {
+    let mut series = Series::default();
+    for lines in split(text) {
+        let Some(mail) = parse_mail(&lines) else {
+            continue;
+        };
+        let base_commit = lines
+            .iter()
+            .find_map(|line| line.strip_prefix("base-commit: "));
+        if let Some(base) = base_commit {
+            series.base_commit = Some(base.trim().to_string());
+        }
+        if mail.patch.is_empty() {
+            series.cover_letter.get_or_insert(mail);
+        } else {
+            series.patches.push(mail);
+        }
+    }
+    series
+}
+
+/// All mails of an mbox in order, e.g. those of a thread
+pub fn mails(text: &str) -> Vec<Mail> {
+    split(text)
+        .iter()
+        .filter_map(|lines| parse_mail(lines))
+        .collect()
+}
+
+/// The lines of each mail
+fn split(text: &str) -> Vec<Vec<&str>> {
+    let mut mails: Vec<Vec<&str>> = Vec::new();
+    for line in text.lines() {
+        // Each mail starts with a `From <sha> <date>` line
+        if line.starts_with("From ") || mails.is_empty() {
+            mails.push(Vec::new());
+            if line.starts_with("From ") {
+                continue;
+            }
+        }
+        if let Some(mail) = mails.last_mut() {
+            mail.push(line);
+        }
+    }
+    mails
+}
+
+fn parse_mail(lines: &[&str]) -> Option<Mail> {
+    let blank = lines.iter().position(|line| line.is_empty())?;
+    // Headers, with folded lines joined
+    let mut headers: Vec<String> = Vec::new();
+    for line in &lines[..blank] {
+        match headers.last_mut() {
+            Some(header) if line.starts_with([' ', '\t']) => {
+                header.push(' ');
+                header.push_str(line.trim());
+            }
+            _ => headers.push(line.to_string()),
+        }
+    }
+    let header = |name: &str| {
+        headers.iter().find_map(|header| {
+            let (key, value) = header.split_once(':')?;
+            key.eq_ignore_ascii_case(name)
+                .then(|| decode_words(value.trim()))
+        })
+    };
+    let subject = header("Subject")?;
+
+    let body = &lines[blank + 1..];
+    let diff_start = body.iter().position(|line| line.starts_with("diff --git "));
+    // The message ends at the `---` before the diffstat, or at the `-- `
+    // that starts the signature
+    let text_end = diff_start.unwrap_or(body.len());
+    let message_end = body[..text_end]
+        .iter()
+        .position(|line| *line == "---" || *line == "-- ")
+        .unwrap_or(text_end);
+    let patch = match diff_start {
+        Some(start) => {
+            // Up to the `-- ` that starts the signature
+            let end = body[start..]
+                .iter()
+                .position(|line| *line == "-- ")
+                .map_or(body.len(), |end| start + end);
+            body[start..end].join("\n") + "\n"
+        }
+        None => String::new(),
+    };
+
+    Some(Mail {
+        subject: strip_tag(&subject).to_string(),
+        from: header("From").unwrap_or_default(),
+        date: header("Date").unwrap_or_default(),
+        message: body[..message_end].join("\n").trim().to_string(),
+        patch,
+        message_id: header("Message-ID").unwrap_or_default(),
+        in_reply_to: header("In-Reply-To"),
+    })
+}
+
+/// `Fix it` of `[PATCH v2 1/3] Fix it`
+fn strip_tag(subject: &str) -> &str {
+    match subject.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((_, rest)) => rest.trim_start(),
+        None => subject,
+    }
+}
+
+/// Decode the `=?UTF-8?q?...?=` words of a header, as non-ASCII names come.
+/// Base64 words are left as they are.
+fn decode_words(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let (before, word) = rest.split_at(start);
+        let q_word = word[2..].split_once('?').and_then(|(_charset, word)| {
+            let (encoding, word) = word.split_once('?')?;
+            let (text, after) = word.split_once("?=")?;
+            encoding.eq_ignore_ascii_case("q").then_some((text, after))
+        });
+        let Some((text, after)) = q_word else {
+            decoded.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            after_word = false;
+            continue;
+        };
+        // Whitespace between encoded words doesn't count
+        if !(after_word && before.trim().is_empty()) {
+            decoded.push_str(before);
+        }
+        decoded.push_str(&decode_q(text));
+        rest = after;
+        after_word = true;
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The text of a `q`-encoded word: `_` for spaces and `=XX` for bytes
+fn decode_q(text: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut chars = text.bytes();
+    while let Some(byte) = chars.next() {
+        match byte {
+            b'_' => bytes.push(b' '),
+            b'=' => {
+                let hex: Vec<u8> = chars.by_ref().take(2).collect();
+                let value = std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match value {
+                    Some(value) => bytes.push(value),
+                    None => bytes.extend(b"=".iter().chain(&hex)),
+                }
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERIES: &str = "\
+From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001
+From: Ada Lovelace <ada@example.com>
+Date: Mon, 15 Jan 2024 10:00:00 +0100
+Subject: [PATCH v2 0/2] Greet
+ politely
+Message-ID: <cover@example.com>
+
+Two patches.
+
+base-commit: 1234567890abcdef
+--\x20
+2.43.0
+
+From 1111111111111111111111111111111111111111 Mon Sep 17 00:00:00 2001
+From: =?UTF-8?q?Ren=C3=A9e_Dupont?= <renee@example.com>
+Date: Mon, 15 Jan 2024 10:01:00 +0100
+Subject: [PATCH v2 1/2] Add a greeting
+Message-ID: <1@example.com>
+In-Reply-To: <cover@example.com>
+
+It was missing.
+---
+ hello.txt | 1 +
+ 1 file changed, 1 insertion(+)
+
+diff --git a/hello.txt b/hello.txt
+new file mode 100644
+index 0000000..ce01362
+--- /dev/null
++++ b/hello.txt
+@@ -0,0 +1 @@
++hello
+--\x20
+2.43.0
+
+From 2222222222222222222222222222222222222222 Mon Sep 17 00:00:00 2001
+From: Ada Lovelace <ada@example.com>
+Date: Mon, 15 Jan 2024 10:02:00 +0100
+Subject: [PATCH v2 2/2] Be polite
+
+---
+diff --git a/hello.txt b/hello.txt
+--- a/hello.txt
++++ b/hello.txt
+@@ -1 +1 @@
+-hello
++hello, please
+--\x20
+2.43.0
+";
+
+    #[test]
+    fn test_parse() {
+        let series = parse(SERIES);
+        let cover = series.cover_letter.as_ref().unwrap();
+        assert_eq!(cover.subject, "Greet politely");
+        assert!(cover.message.starts_with("Two patches."));
+        assert_eq!(series.base_commit.as_deref(), Some("1234567890abcdef"));
+
+        let subjects: Vec<&str> = series.patches.iter().map(|p| &*p.subject).collect();
+        assert_eq!(subjects, ["Add a greeting", "Be polite"]);
+        let first = &series.patches[0];
+        assert_eq!(first.from, "Renée Dupont <renee@example.com>");
+        assert_eq!(first.author(), ("Renée Dupont", "renee@example.com"));
+        assert_eq!(first.commit_message(), "Add a greeting\n\nIt was missing.");
+        assert!(first.patch.starts_with("diff --git a/hello.txt"));
+        assert!(first.patch.ends_with("+hello\n"));
+        assert_eq!(series.patches[1].commit_message(), "Be polite");
+        assert_eq!(first.date_iso(), "2024-01-15T10:01:00+01:00");
+        assert_eq!(first.message_id, "<1@example.com>");
+        assert_eq!(first.in_reply_to.as_deref(), Some("<cover@example.com>"));
+        assert_eq!(mails(SERIES).len(), 3);
+    }
+
+    #[test]
+    fn test_decode_words() {
+        assert_eq!(decode_words("=?UTF-8?q?J=C3=B6rg?= <j@x.org>"), "Jörg <j@x.org>");
+        assert_eq!(decode_words("=?utf-8?q?a?= =?utf-8?q?b?="), "ab");
+        assert_eq!(decode_words("plain"), "plain");
+    }
+}
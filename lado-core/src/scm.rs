@@ -0,0 +1,108 @@
+//! The version control a working copy is under. Git repositories are read
+//! through libgit2 ([`crate::git::Repository`]); Mercurial ones are diffed
+//! with the `hg` command, whose git-style patches are parsed like a patch
+//! on stdin.
+
+use crate::git::{parse_patch, DiffData};
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Version control system of a working copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scm {
+    Git,
+    Mercurial,
+}
+
+impl Scm {
+    /// The version control of `dir` and the root of its working copy: the
+    /// nearest directory above it with a `.git` or `.hg`.
+    pub fn detect(dir: &Path) -> Option<(Scm, PathBuf)> {
+        dir.ancestors().find_map(|dir| {
+            // `.git` is a file in worktrees and submodules
+            if dir.join(".git").exists() {
+                Some((Scm::Git, dir.to_path_buf()))
+            } else if dir.join(".hg").is_dir() {
+                Some((Scm::Mercurial, dir.to_path_buf()))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// What to diff in a Mercurial working copy, by revisions (`hg help revsets`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HgChanges {
+    /// What a revision changed, against its first parent
+    Change(String),
+    /// From one revision to another
+    Between(String, String),
+    /// The working directory against its parent
+    WorkingDir,
+}
+
+/// Arguments to `hg` for the git-style diff of `changes`
+fn diff_args(changes: &HgChanges) -> Vec<String> {
+    let mut args = vec!["diff".to_string(), "--git".to_string()];
+    match changes {
+        HgChanges::Change(rev) => args.extend(["-c".to_string(), rev.clone()]),
+        HgChanges::Between(base, head) => {
+            args.extend(["-r".to_string(), base.clone(), "-r".to_string(), head.clone()])
+        }
+        HgChanges::WorkingDir => {}
+    }
+    args
+}
+
+/// The diff of `changes` in the Mercurial working copy at `root`.
+#[tracing::instrument]
+pub fn hg_diff(root: &Path, changes: &HgChanges) -> Result<DiffData> {
+    let output = Command::new("hg")
+        .args(diff_args(changes))
+        .current_dir(root)
+        // Plain output, whatever aliases and extensions the user set up
+        .env("HGPLAIN", "1")
+        .output()
+        .context("Failed to execute hg. Is Mercurial installed?")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("hg diff failed: {}", stderr.trim()));
+    }
+    parse_patch(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_detect() {
+        let dir = TempDir::new("scm");
+        let root = dir.path();
+        fs::create_dir_all(root.join("hg/.hg")).unwrap();
+        fs::create_dir_all(root.join("hg/src/nested/.git")).unwrap();
+        fs::create_dir_all(root.join("hg/src/nested/lib")).unwrap();
+
+        let hg = Scm::detect(&root.join("hg/src"));
+        let git = Scm::detect(&root.join("hg/src/nested/lib"));
+        assert_eq!(hg, Some((Scm::Mercurial, root.join("hg"))));
+        assert_eq!(git, Some((Scm::Git, root.join("hg/src/nested"))));
+    }
+
+    #[test]
+    fn test_diff_args() {
+        assert_eq!(
+            diff_args(&HgChanges::Change("tip".to_string())),
+            ["diff", "--git", "-c", "tip"]
+        );
+        assert_eq!(
+            diff_args(&HgChanges::Between("default".to_string(), "feature".to_string())),
+            ["diff", "--git", "-r", "default", "-r", "feature"]
+        );
+        assert_eq!(diff_args(&HgChanges::WorkingDir), ["diff", "--git"]);
+    }
+}
@@ -0,0 +1,189 @@
+//! Full-text search across the lines of a diff: every file's added, removed
+//! and unchanged lines. The lines are indexed once per diff, lowercased, so
+//! searching again as the query is typed stays quick on large diffs.
+
+use crate::git::{DiffData, DiffLineType};
+
+/// A match of the query on a line of the diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    pub path: String,
+    /// Number of the line in the old and the new version of the file;
+    /// added lines have no old number and removed ones no new number
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+    /// Character columns of the match in the line, end exclusive
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A line of the diff as it is searched
+struct IndexedLine {
+    /// Index into `SearchIndex::paths`
+    file: usize,
+    old_line: Option<u32>,
+    new_line: Option<u32>,
+    content: String,
+    /// `content` lowercased a character at a time, so byte offsets of
+    /// matches convert to the same columns in both
+    folded: String,
+}
+
+/// The lines of a diff's files, in the order of its files
+pub struct SearchIndex {
+    paths: Vec<String>,
+    lines: Vec<IndexedLine>,
+}
+
+impl SearchIndex {
+    /// Index the lines of `data` as they are shown: a file's structural diff
+    /// or split rename instead of its hunks.
+    pub fn new(data: &DiffData) -> Self {
+        let mut lines = Vec::new();
+        for (file, change) in data.files.iter().enumerate() {
+            let code = data
+                .shown_hunks(&change.path)
+                .iter()
+                .flat_map(|hunk| &hunk.lines)
+                .filter(|line| {
+                    matches!(
+                        line.line_type,
+                        DiffLineType::Add | DiffLineType::Remove | DiffLineType::Context
+                    )
+                });
+            for line in code {
+                lines.push(IndexedLine {
+                    file,
+                    old_line: line.old_line_num,
+                    new_line: line.new_line_num,
+                    content: line.content.to_string(),
+                    folded: fold_case(&line.content),
+                });
+            }
+        }
+        Self {
+            paths: data.files.iter().map(|f| f.path.clone()).collect(),
+            lines,
+        }
+    }
+
+    /// Every match of `query`, in the order of the files and their lines.
+    /// Matching ignores case unless the query has capitals in it.
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let exact = query.chars().any(char::is_uppercase);
+        let needle = if exact { query.to_string() } else { fold_case(query) };
+        let mut hits = Vec::new();
+        for line in &self.lines {
+            let haystack = if exact { &line.content } else { &line.folded };
+            for (at, _) in haystack.match_indices(needle.as_str()) {
+                let start = haystack[..at].chars().count();
+                hits.push(SearchHit {
+                    path: self.paths[line.file].clone(),
+                    old_line: line.old_line,
+                    new_line: line.new_line,
+                    start,
+                    end: start + needle.chars().count(),
+                });
+            }
+        }
+        hits
+    }
+}
+
+/// `text` lowercased character by character. Characters whose lowercase is
+/// longer than one character are kept as they are.
+fn fold_case(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            let mut lower = c.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(l), None) => l,
+                _ => c,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{DiffHunk, DiffLine, FileChange, FileStatus};
+    use std::collections::HashMap;
+
+    fn line(line_type: DiffLineType, old: Option<u32>, new: Option<u32>, text: &str) -> DiffLine {
+        DiffLine {
+            line_type,
+            old_line_num: old,
+            new_line_num: new,
+            content: text.into(),
+            comment: None,
+        }
+    }
+
+    fn data(files: Vec<(&str, Vec<DiffLine>)>) -> DiffData {
+        let mut data = DiffData {
+            files: vec![],
+            file_hunks: HashMap::new(),
+            blobs: HashMap::new(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+        };
+        for (path, lines) in files {
+            data.files.push(FileChange {
+                path: path.to_string(),
+                status: FileStatus::Modified,
+                additions: 0,
+                deletions: 0,
+                rename: None,
+                encoding: None,
+                generated: false,
+            });
+            let hunk = DiffHunk {
+                header: "@@ -1 +1 @@".to_string(),
+                old_start: 1,
+                old_lines: 1,
+                new_start: 1,
+                new_lines: 1,
+                lines,
+            };
+            data.file_hunks.insert(path.to_string(), vec![hunk]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_search_across_files() {
+        let data = data(vec![
+            (
+                "a.rs",
+                vec![
+                    line(DiffLineType::Remove, Some(1), None, "let Count = 1;"),
+                    line(DiffLineType::Add, None, Some(1), "let count = count + 1;"),
+                ],
+            ),
+            (
+                "b.rs",
+                vec![line(DiffLineType::Context, Some(4), Some(5), "// über count")],
+            ),
+        ]);
+        let index = SearchIndex::new(&data);
+
+        let hits = index.search("count");
+        let found: Vec<_> = hits.iter().map(|h| (h.path.as_str(), h.start)).collect();
+        assert_eq!(
+            found,
+            vec![("a.rs", 4), ("a.rs", 4), ("a.rs", 12), ("b.rs", 8)]
+        );
+        assert_eq!((hits[0].old_line, hits[0].new_line), (Some(1), None));
+        assert_eq!((hits[3].old_line, hits[3].new_line), (Some(4), Some(5)));
+        assert_eq!(hits[3].end, 13);
+
+        // Capitals match exactly
+        assert_eq!(index.search("Count").len(), 1);
+        assert!(index.search("").is_empty());
+        assert!(index.search("missing").is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+//! Item-level diff of Rust files: which functions were added, removed or got
+//! a new signature. Meant as an overview above the line diff of a large
+//! refactor, where moved and re-indented code buries the actual API changes.
+//!
+//! Functions are found with tree-sitter, also inside `impl`, `trait` and
+//! inline `mod` blocks, and named by their path there (`Repository::open`,
+//! `<Highlighter as Default>::default`, `tests::test_parse`).
+
+use std::fmt;
+use tree_sitter::{Node, Parser};
+
+/// How an item changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemChangeKind {
+    Added,
+    Removed,
+    SignatureChanged,
+}
+
+/// A function that differs between the two versions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemChange {
+    pub kind: ItemChangeKind,
+    /// Path of the function within the file
+    pub name: String,
+    /// Signature in the new version, or the old one for removed functions
+    pub signature: String,
+}
+
+impl fmt::Display for ItemChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (sign, note) = match self.kind {
+            ItemChangeKind::Added => ('+', ""),
+            ItemChangeKind::Removed => ('-', ""),
+            ItemChangeKind::SignatureChanged => ('~', " (signature changed)"),
+        };
+        write!(f, "{} fn {}{}", sign, self.name, note)
+    }
+}
+
+/// The functions of `new` that aren't in `old`, those of `old` that aren't in
+/// `new` and those whose signature changed, in that order and each in file
+/// order. `None` if tree-sitter couldn't parse them.
+pub fn diff_rust_items(old: &str, new: &str) -> Option<Vec<ItemChange>> {
+    let old_items = rust_functions(old)?;
+    let new_items = rust_functions(new)?;
+    let find = |items: &[Function], name: &str| items.iter().position(|i| i.name == name);
+
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for item in &new_items {
+        match find(&old_items, &item.name) {
+            None => added.push(item.change(ItemChangeKind::Added)),
+            Some(i) if old_items[i].signature != item.signature => {
+                changed.push(item.change(ItemChangeKind::SignatureChanged))
+            }
+            Some(_) => {}
+        }
+    }
+    let removed = old_items
+        .iter()
+        .filter(|item| find(&new_items, &item.name).is_none())
+        .map(|item| item.change(ItemChangeKind::Removed));
+
+    Some(added.into_iter().chain(removed).chain(changed).collect())
+}
+
+struct Function {
+    name: String,
+    /// Everything before the body, with whitespace collapsed
+    signature: String,
+}
+
+impl Function {
+    fn change(&self, kind: ItemChangeKind) -> ItemChange {
+        ItemChange {
+            kind,
+            name: self.name.clone(),
+            signature: self.signature.clone(),
+        }
+    }
+}
+
+fn rust_functions(source: &str) -> Option<Vec<Function>> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_rust::LANGUAGE.into())
+        .ok()?;
+    let tree = parser.parse(source, None)?;
+    let mut functions = Vec::new();
+    collect(tree.root_node(), source, "", &mut functions);
+    Some(functions)
+}
+
+/// Add the functions among the children of `node` to `functions`, their
+/// names prefixed with `scope`.
+fn collect(node: Node, source: &str, scope: &str, functions: &mut Vec<Function>) {
+    let text = |node: Node| node.utf8_text(source.as_bytes()).unwrap_or_default();
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "function_item" | "function_signature_item" => {
+                let Some(name) = child.child_by_field_name("name") else {
+                    continue;
+                };
+                let end = child
+                    .child_by_field_name("body")
+                    .map_or(child.end_byte(), |body| body.start_byte());
+                let signature = source[child.start_byte()..end]
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                functions.push(Function {
+                    name: format!("{}{}", scope, text(name)),
+                    signature: signature.trim_end_matches(';').to_string(),
+                });
+            }
+            "impl_item" | "trait_item" | "mod_item" => {
+                let Some(body) = child.child_by_field_name("body") else {
+                    continue;
+                };
+                let name = match (child.kind(), child.child_by_field_name("trait")) {
+                    ("impl_item", Some(trait_name)) => format!(
+                        "<{} as {}>",
+                        child.child_by_field_name("type").map_or("", text),
+                        text(trait_name)
+                    ),
+                    ("impl_item", None) => child
+                        .child_by_field_name("type")
+                        .map_or("", text)
+                        .to_string(),
+                    _ => child
+                        .child_by_field_name("name")
+                        .map_or("", text)
+                        .to_string(),
+                };
+                collect(body, source, &format!("{}{}::", scope, name), functions);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "
+fn main() {}
+
+fn helper(a: u32) -> u32 {
+    a
+}
+
+struct Parser;
+
+impl Parser {
+    fn parse(&self, input: &str) {}
+}
+
+impl Default for Parser {
+    fn default() -> Self { Parser }
+}
+";
+
+    const NEW: &str = "
+fn main() {
+    // Only the body changed
+    run();
+}
+
+struct Parser;
+
+impl Parser {
+    fn parse(&self, input: &str,
+             strict: bool) {}
+
+    fn reset(&mut self) {}
+}
+
+impl Default for Parser {
+    fn default()
+        -> Self { Parser }
+}
+";
+
+    #[test]
+    fn test_diff_rust_items() {
+        let changes: Vec<String> = diff_rust_items(OLD, NEW)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "+ fn Parser::reset",
+                "- fn helper",
+                "~ fn Parser::parse (signature changed)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_rust_items_unchanged() {
+        assert_eq!(diff_rust_items(OLD, OLD), Some(Vec::new()));
+        assert_eq!(
+            diff_rust_items("", "trait Run { fn run(&self); }").unwrap()[0].name,
+            "Run::run"
+        );
+    }
+}
@@ -0,0 +1,368 @@
+//! Patchsets on Sourcehut's mailing lists (lists.sr.ht). A patchset is
+//! fetched as an mbox with curl (public lists need no token), and so is
+//! the thread it was mailed in, whose review replies comment on a patch by
+//! writing below the lines of it they quote.
+
+use crate::github::{CommentSide, PrComment};
+use crate::mbox::{self, Mail, Series};
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::process::Command;
+
+/// A patchset on a list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchsetId {
+    /// Host of the lists, `lists.sr.ht` unless self-hosted
+    pub host: String,
+    /// `~owner/list`
+    pub list: String,
+    pub id: u64,
+}
+
+impl PatchsetId {
+    /// Parse the URL of a patchset, `https://lists.sr.ht/~owner/list/patches/123`,
+    /// or the `~owner/list/patches/123` (or `~owner/list/123`) of it.
+    pub fn parse(text: &str) -> Option<PatchsetId> {
+        let text = text.trim().trim_end_matches('/');
+        let (host, path) = match text.split_once("://") {
+            Some((_scheme, rest)) => rest.split_once('/')?,
+            None => ("lists.sr.ht", text),
+        };
+        let parts: Vec<&str> = path.split('/').collect();
+        let (owner, list, id) = match parts[..] {
+            [owner, list, "patches", id] | [owner, list, "patches", id, "mbox"] | [owner, list, id] => {
+                (owner, list, id)
+            }
+            _ => return None,
+        };
+        if !owner.starts_with('~') || list.is_empty() {
+            return None;
+        }
+        Some(PatchsetId {
+            host: host.to_string(),
+            list: format!("{}/{}", owner, list),
+            id: id.parse().ok()?,
+        })
+    }
+
+    /// Its page on the lists
+    pub fn url(&self) -> String {
+        format!("https://{}/{}/patches/{}", self.host, self.list, self.id)
+    }
+}
+
+impl fmt::Display for PatchsetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/patches/{}", self.list, self.id)
+    }
+}
+
+/// Fetch `url`, logging how it went
+fn curl(url: &str) -> Result<String> {
+    let output = Command::new("curl")
+        .args(["-fsSL", "--max-time", "30", url])
+        .output()
+        .context("Failed to execute curl. Is it installed?")?;
+    tracing::debug!(%url, status = %output.status, "fetched from sourcehut");
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Could not fetch {}: {}", url, stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The patches of `id`, with its cover letter if it was mailed with one.
+pub fn get_series(id: &PatchsetId) -> Result<Series> {
+    let series = mbox::parse(&curl(&format!("{}/mbox", id.url()))?);
+    if series.patches.is_empty() {
+        return Err(anyhow!("Patchset {} has no patches", id));
+    }
+    Ok(series)
+}
+
+/// The mails of the thread `series` was mailed in that aren't its own,
+/// oldest first. Fills in the cover letter if the patchset's mbox lacked it.
+pub fn get_replies(id: &PatchsetId, series: &mut Series) -> Result<Vec<Mail>> {
+    let first = series
+        .patches
+        .first()
+        .ok_or_else(|| anyhow!("Patchset {} has no patches", id))?;
+    // Patches mailed with a cover letter answer it
+    let root = first
+        .in_reply_to
+        .clone()
+        .unwrap_or_else(|| first.message_id.clone());
+    let thread = mbox::mails(&curl(&format!(
+        "https://{}/{}/{}/mbox",
+        id.host,
+        id.list,
+        encode(&root)
+    ))?);
+
+    if series.cover_letter.is_none() {
+        series.cover_letter = thread
+            .iter()
+            .find(|mail| mail.message_id == root && mail.patch.is_empty())
+            .cloned();
+    }
+    let own: HashSet<&str> = series
+        .patches
+        .iter()
+        .map(|mail| mail.message_id.as_str())
+        .chain([root.as_str()])
+        .collect();
+    Ok(thread
+        .iter()
+        .filter(|mail| !own.contains(mail.message_id.as_str()))
+        .cloned()
+        .collect())
+}
+
+/// A message id as it goes in a URL path
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || b"-._~@".contains(&byte) {
+                (byte as char).to_string()
+            } else {
+                format!("%{:02X}", byte)
+            }
+        })
+        .collect()
+}
+
+/// The inline comments of `replies` on the patches of `series`, which were
+/// committed as `commits` (in the same order). A comment is what a reviewer
+/// wrote below quoted lines of a patch, on the last of those lines.
+pub fn review_comments(series: &Series, replies: &[Mail], commits: &[String]) -> Vec<PrComment> {
+    let patches: HashMap<&str, usize> = series
+        .patches
+        .iter()
+        .enumerate()
+        .map(|(index, mail)| (mail.message_id.as_str(), index))
+        .collect();
+    let by_id: HashMap<&str, &Mail> = replies
+        .iter()
+        .map(|mail| (mail.message_id.as_str(), mail))
+        .collect();
+
+    let mut comments = Vec::new();
+    for reply in replies {
+        let commit = answered_patch(reply, &patches, &by_id).and_then(|index| commits.get(index));
+        let Some(commit) = commit else {
+            continue;
+        };
+        for (path, side, line, body) in inline_comments(&reply.message) {
+            comments.push(PrComment {
+                id: comments.len() as u64 + 1,
+                in_reply_to_id: None,
+                path,
+                line: Some(line),
+                side,
+                body,
+                author: reply.author().0.to_string(),
+                created_at: reply.date_iso(),
+                commit_id: commit.clone(),
+                original_commit_id: commit.clone(),
+                resolved: false,
+            });
+        }
+    }
+    comments
+}
+
+/// Index of the patch `reply` answers, directly or through other replies
+fn answered_patch(
+    reply: &Mail,
+    patches: &HashMap<&str, usize>,
+    by_id: &HashMap<&str, &Mail>,
+) -> Option<usize> {
+    let mut parent = reply.in_reply_to.as_deref();
+    // Bounded, should replies answer each other in a loop
+    for _ in 0..=by_id.len() {
+        let id = parent?;
+        if let Some(index) = patches.get(id) {
+            return Some(*index);
+        }
+        parent = by_id.get(id)?.in_reply_to.as_deref();
+    }
+    None
+}
+
+/// The comments written below quoted diff lines in `text`: the path, side
+/// and line of the last quoted line, and the comment
+fn inline_comments(text: &str) -> Vec<(String, CommentSide, u32, String)> {
+    let mut comments = Vec::new();
+    let mut path: Option<String> = None;
+    // Old and new line numbers of the next quoted line of a hunk
+    let mut next: Option<(u32, u32)> = None;
+    // The line a comment written now is on
+    let mut anchor: Option<(CommentSide, u32)> = None;
+    let mut body: Vec<&str> = Vec::new();
+
+    let mut finish = |anchor: Option<(CommentSide, u32)>, path: &Option<String>, body: &mut Vec<&str>| {
+        let text = body.join("\n").trim().to_string();
+        body.clear();
+        if let (Some((side, line)), Some(path), false) = (anchor, path, text.is_empty()) {
+            comments.push((path.clone(), side, line, text));
+        }
+    };
+
+    for line in text.lines() {
+        let Some(quoted) = unquote(line) else {
+            if anchor.is_some() {
+                body.push(line);
+            }
+            continue;
+        };
+        finish(anchor, &path, &mut body);
+        if let Some(paths) = quoted.strip_prefix("diff --git ") {
+            path = paths.split_once(" b/").map(|(_, new)| new.to_string());
+            next = None;
+            anchor = None;
+        } else if quoted.starts_with("@@ ") {
+            next = hunk_start(quoted);
+            anchor = None;
+        } else if let Some((old, new)) = next.as_mut() {
+            match quoted.chars().next() {
+                Some('+') => {
+                    anchor = Some((CommentSide::Right, *new));
+                    *new += 1;
+                }
+                Some('-') => {
+                    anchor = Some((CommentSide::Left, *old));
+                    *old += 1;
+                }
+                // Context, whose space mail clients may have trimmed
+                Some(' ') | None => {
+                    anchor = Some((CommentSide::Right, *new));
+                    *old += 1;
+                    *new += 1;
+                }
+                // Quoted prose, e.g. of an earlier reply
+                _ => {}
+            }
+        }
+    }
+    finish(anchor, &path, &mut body);
+    comments
+}
+
+/// The text of a quoted line, at any depth (`> +line`, `>> +line`)
+fn unquote(line: &str) -> Option<&str> {
+    let mut rest = line.strip_prefix('>')?;
+    loop {
+        rest = rest.strip_prefix(' ').unwrap_or(rest);
+        match rest.strip_prefix('>') {
+            Some(deeper) => rest = deeper,
+            None => return Some(rest),
+        }
+    }
+}
+
+/// First old and new line numbers of the hunk `header` starts
+fn hunk_start(header: &str) -> Option<(u32, u32)> {
+    let mut ranges = header.strip_prefix("@@ ")?.split(' ');
+    let start = |range: &str| -> Option<u32> { range.split(',').next()?.parse().ok() };
+    let old = start(ranges.next()?.strip_prefix('-')?)?;
+    let new = start(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_id() {
+        let id = PatchsetId {
+            host: "lists.sr.ht".to_string(),
+            list: "~ada/lado-devel".to_string(),
+            id: 4711,
+        };
+        assert_eq!(
+            PatchsetId::parse("https://lists.sr.ht/~ada/lado-devel/patches/4711/"),
+            Some(id.clone())
+        );
+        assert_eq!(PatchsetId::parse("~ada/lado-devel/patches/4711"), Some(id.clone()));
+        assert_eq!(PatchsetId::parse("~ada/lado-devel/4711"), Some(id.clone()));
+        assert_eq!(id.url(), "https://lists.sr.ht/~ada/lado-devel/patches/4711");
+        assert_eq!(
+            PatchsetId::parse("https://lists.example.org/~ada/x/patches/1").map(|id| id.host),
+            Some("lists.example.org".to_string())
+        );
+        assert_eq!(PatchsetId::parse("ada/lado-devel/4711"), None);
+        assert_eq!(PatchsetId::parse("~ada/lado-devel/patches/x"), None);
+        assert_eq!(encode("<a+b@example.com>"), "%3Ca%2Bb@example.com%3E");
+    }
+
+    fn mail(message_id: &str, in_reply_to: Option<&str>, message: &str) -> Mail {
+        Mail {
+            subject: "Re: Fix it".to_string(),
+            from: "Bo <bo@example.com>".to_string(),
+            date: "Mon, 15 Jan 2024 11:00:00 +0100".to_string(),
+            message: message.to_string(),
+            patch: String::new(),
+            message_id: message_id.to_string(),
+            in_reply_to: in_reply_to.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_review_comments() {
+        let series = Series {
+            patches: vec![
+                Mail {
+                    patch: "diff --git a/a.txt b/a.txt\n".to_string(),
+                    ..mail("<1@x>", None, "")
+                },
+                Mail {
+                    patch: "diff --git a/b.txt b/b.txt\n".to_string(),
+                    ..mail("<2@x>", Some("<1@x>"), "")
+                },
+            ],
+            ..Series::default()
+        };
+        let review = "\
+On Mon, Ada wrote:
+> ---
+> diff --git a/src/lib.rs b/src/lib.rs
+> @@ -10,4 +10,4 @@ fn main() {
+>  fn one() {}
+> -fn two() {}
+> +fn three() {}
+Why three?
+
+>  fn four() {}
+>
+> +fn five() {}
+Nice.
+
+Thanks";
+        let replies = vec![
+            mail("<r1@x>", Some("<2@x>"), review),
+            // Answers the review, quoting it two deep
+            mail("<r2@x>", Some("<r1@x>"), ">> -fn two() {}\n> Why three?\nBecause."),
+            mail("<r3@x>", Some("<unknown@x>"), review),
+        ];
+        let commits = ["c1".to_string(), "c2".to_string()];
+
+        let comments = review_comments(&series, &replies, &commits);
+        let found: Vec<_> = comments
+            .iter()
+            .map(|c| (c.path.as_str(), c.side, c.line, c.body.as_str(), c.commit_id.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("src/lib.rs", CommentSide::Right, Some(11), "Why three?", "c2"),
+                ("src/lib.rs", CommentSide::Right, Some(14), "Nice.\n\nThanks", "c2"),
+            ]
+        );
+        assert_eq!(comments[0].author, "Bo");
+
+        // Without the diff header, the quoted hunk has no file to go on
+        assert!(inline_comments(replies[1].message.as_str()).is_empty());
+    }
+}
@@ -0,0 +1,28 @@
+//! Helpers shared by the unit tests, here and in lado's (with the
+//! `test-support` feature).
+
+use std::path::{Path, PathBuf};
+
+/// A fresh directory under the system's temporary directory, removed again
+/// when dropped, so a failing test doesn't leave it behind.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create `lado-<name>-<pid>`, emptying what an earlier run left there.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("lado-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
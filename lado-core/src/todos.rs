@@ -0,0 +1,104 @@
+//! `TODO`, `FIXME` and `HACK` markers in the lines a diff adds: easy to miss
+//! when reading through a review, and easy to merge and forget.
+
+use crate::git::{DiffHunk, DiffLineType};
+
+/// Markers looked for, as whole words in upper case
+pub const MARKERS: [&str; 3] = ["TODO", "FIXME", "HACK"];
+
+/// A marker in an added line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Todo {
+    pub marker: &'static str,
+    /// Line number in the new version of the file
+    pub line: u32,
+    /// The line from the marker on, e.g. `TODO: handle errors`
+    pub text: String,
+}
+
+/// The markers in the lines `hunks` add, in order. A line counts once, for
+/// its first marker.
+pub fn find(hunks: &[DiffHunk]) -> Vec<Todo> {
+    hunks
+        .iter()
+        .flat_map(|hunk| &hunk.lines)
+        .filter(|line| line.line_type == DiffLineType::Add)
+        .filter_map(|line| {
+            let (at, marker) = first_marker(&line.content)?;
+            Some(Todo {
+                marker,
+                line: line.new_line_num?,
+                text: line.content[at..].trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Byte offset and kind of the first marker in `text`
+fn first_marker(text: &str) -> Option<(usize, &'static str)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    MARKERS
+        .iter()
+        .flat_map(|&marker| {
+            text.match_indices(marker)
+                .filter(move |&(at, _)| {
+                    // `TODO`, `TODO:` and `TODO(name)`, not `TODOS` or `MY_TODO`
+                    !text[..at].chars().next_back().is_some_and(is_word)
+                        && !text[at + marker.len()..]
+                            .chars()
+                            .next()
+                            .is_some_and(is_word)
+                })
+                .map(move |(at, _)| (at, marker))
+        })
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffLine;
+
+    fn line(line_type: DiffLineType, num: u32, content: &str) -> DiffLine {
+        DiffLine {
+            line_type,
+            old_line_num: (line_type != DiffLineType::Add).then_some(num),
+            new_line_num: (line_type != DiffLineType::Remove).then_some(num),
+            content: content.into(),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        let hunk = DiffHunk {
+            header: "@@ -1,3 +1,5 @@".to_string(),
+            old_start: 1,
+            old_lines: 3,
+            new_start: 1,
+            new_lines: 5,
+            lines: vec![
+                line(DiffLineType::Context, 1, "// TODO: already there"),
+                line(DiffLineType::Remove, 2, "// FIXME: removed"),
+                line(DiffLineType::Add, 2, "    // FIXME(ana): HACK around #12  "),
+                line(DiffLineType::Add, 3, "let todos = MY_TODO + TODOS;"),
+                line(DiffLineType::Add, 4, "/* HACK */"),
+            ],
+        };
+        assert_eq!(
+            find(&[hunk]),
+            [
+                Todo {
+                    marker: "FIXME",
+                    line: 2,
+                    text: "FIXME(ana): HACK around #12".to_string(),
+                },
+                Todo {
+                    marker: "HACK",
+                    line: 4,
+                    text: "HACK */".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Where a symbol is used in the files of a commit, e.g. the callers of a
+//! function a diff changes: every line naming it as a whole word. Plain text
+//! matching, so it also finds the word in comments and strings.
+
+use crate::git::Repository;
+use anyhow::{anyhow, Result};
+use async_channel::Receiver;
+use git2::{ObjectType, Oid, Repository as Git2Repo, TreeWalkMode, TreeWalkResult};
+
+/// Usages listed at most; a word this common isn't worth going through
+pub const MAX_USAGES: usize = 500;
+
+/// A line naming the symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub path: String,
+    pub line: u32,
+    /// The line, trimmed
+    pub text: String,
+}
+
+/// Whether `word` can be looked for: an identifier, not just punctuation
+pub fn is_symbol(word: &str) -> bool {
+    word.chars().any(|c| c.is_alphabetic() || c == '_') && word.chars().all(is_word_char)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Line numbers and lines of `text` naming `word` as a whole word, in order
+pub fn find(text: &str, word: &str) -> Vec<(u32, String)> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            line.match_indices(word).any(|(at, _)| {
+                // `run` in `run(` and `self.run`, not in `rerun` or `run_all`
+                !line[..at].chars().next_back().is_some_and(is_word_char)
+                    && !line[at + word.len()..].chars().next().is_some_and(is_word_char)
+            })
+        })
+        .map(|(index, line)| (index as u32 + 1, line.trim().to_string()))
+        .collect()
+}
+
+impl Repository {
+    /// Look for `word` in every text file of `commit`'s tree, in the
+    /// background, and send its usages in tree order (at most
+    /// [`MAX_USAGES`]).
+    pub fn find_usages(&self, commit: Oid, word: &str) -> Receiver<Result<Vec<Usage>>> {
+        let (tx, rx) = async_channel::bounded(1);
+        let repo_path = self.git_dir().to_path_buf();
+        let word = word.to_string();
+        std::thread::spawn(move || {
+            let result = Git2Repo::open(&repo_path)
+                .map_err(|e| anyhow!("Failed to open repository: {}", e))
+                .and_then(|repo| usages_in_commit(&repo, commit, &word));
+            let _ = tx.send_blocking(result);
+        });
+        rx
+    }
+}
+
+fn usages_in_commit(repo: &Git2Repo, commit: Oid, word: &str) -> Result<Vec<Usage>> {
+    let tree = repo.find_commit(commit)?.tree()?;
+    let mut usages = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        if usages.len() >= MAX_USAGES {
+            return TreeWalkResult::Abort;
+        }
+        if entry.kind() != Some(ObjectType::Blob) {
+            return TreeWalkResult::Ok;
+        }
+        let Ok(blob) = repo.find_blob(entry.id()) else {
+            return TreeWalkResult::Ok;
+        };
+        if blob.is_binary() {
+            return TreeWalkResult::Ok;
+        }
+        let path = format!("{}{}", dir, entry.name().unwrap_or_default());
+        let text = String::from_utf8_lossy(blob.content());
+        usages.extend(find(&text, word).into_iter().map(|(line, text)| Usage {
+            path: path.clone(),
+            line,
+            text,
+        }));
+        TreeWalkResult::Ok
+    })?;
+    usages.truncate(MAX_USAGES);
+    Ok(usages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let text = "fn run() {}\n\nfn rerun() { run(); self.run_all(); }\n    x.run // run\n";
+        assert_eq!(
+            find(text, "run"),
+            [
+                (1, "fn run() {}".to_string()),
+                (3, "fn rerun() { run(); self.run_all(); }".to_string()),
+                (4, "x.run // run".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_symbol() {
+        assert!(is_symbol("file_count"));
+        assert!(is_symbol("_"));
+        assert!(!is_symbol("42"));
+        assert!(!is_symbol("->"));
+        assert!(!is_symbol("a b"));
+    }
+
+    #[test]
+    fn test_usages_in_commit() {
+        let odb = git2::Odb::new().unwrap();
+        odb.add_new_mempack_backend(1).unwrap();
+        let repo = Git2Repo::from_odb(odb).unwrap();
+        let blob = |content: &[u8]| repo.blob(content).unwrap();
+        let mut src = repo.treebuilder(None).unwrap();
+        src.insert("lib.rs", blob(b"pub fn parse() {}\n"), 0o100644)
+            .unwrap();
+        src.insert("logo.png", blob(b"\x89PNG\0parse"), 0o100644)
+            .unwrap();
+        let src = src.write().unwrap();
+        let mut root = repo.treebuilder(None).unwrap();
+        root.insert("main.rs", blob(b"fn main() {\n    parse();\n}\n"), 0o100644)
+            .unwrap();
+        root.insert("src", src, 0o040000).unwrap();
+        let tree = repo.find_tree(root.write().unwrap()).unwrap();
+        let signature = git2::Signature::now("ada", "ada@example.com").unwrap();
+        let commit = repo
+            .commit(None, &signature, &signature, "Parse", &tree, &[])
+            .unwrap();
+
+        let usages = usages_in_commit(&repo, commit, "parse").unwrap();
+        let found: Vec<(&str, u32)> = usages.iter().map(|u| (&*u.path, u.line)).collect();
+        assert_eq!(found, [("main.rs", 2), ("src/lib.rs", 1)]);
+    }
+}
@@ -1,47 +1,195 @@
-use crate::cli::{Args, DiffTarget};
+use crate::attachments;
+use crate::cli::{Args, DiffTarget, SettingOverrides};
+use crate::composer::{self, ComposeTarget};
+use crate::difftastic;
+use crate::encoding;
+use crate::exclude::ExcludeFilter;
+use crate::export;
+use crate::folding;
+use crate::funcname;
 use crate::git::{
-    build_file_tree, collect_folder_paths, collect_folder_paths_under, flatten_tree_with_state,
-    DiffData, FileTreeNode, Repository,
+    align_side_by_side, build_file_list, build_file_tree, collect_file_paths, collect_folder_paths, collect_folder_paths_above,
+    collect_folder_paths_under, flatten_tree_with_state,
+    sort_file_tree, CommentData, DiffData, DiffHunk, DiffLine as GitDiffLine, DiffLineType,
+    FileChange, FileDelta, FileDiff, FileFilter, FileStatus, FileTreeNode, Repository, TreeSort,
 };
-use crate::github::{self, FileComments, PrCommit};
-use crate::highlighting::Highlighter;
-use crate::models::{DiffLineModel, FileEntryModel, PrCommitModel, TextSpanModel};
+use crate::github::{self, CommentSide, FileComments, PrCommit};
+use crate::goto_definition;
+use crate::highlighting::{HighlightCache, HighlightedLine, Highlighter};
+use crate::large_file::{self, LargeFileGuard};
+use crate::lazy_highlight::{self, CodeRows, LazyHighlight, RowHeights, RowOffsets};
+use crate::lsp;
+use crate::pdf;
+use crate::models::{
+    matches_filter, parse_hex_color, wrap_diff_line, DiffLineModel, FileEntryModel, PrCommitModel,
+    TextSpanModel, CONVENTIONAL_KINDS,
+};
+use crate::plugins;
+use crate::mbox::{Mail, Series};
+use crate::pr_loader::{self, PatchsetEvent, PrEvent};
+use crate::recent;
+use crate::scripting::{self, Scripts};
+use crate::scroll_markers;
+use crate::search::{SearchHit, SearchIndex};
+use crate::selection::{Selection, SelectionState};
+use crate::session::{self, Session};
+use crate::semantic;
+use crate::sourcehut::{self, PatchsetId};
+use crate::summary;
+use crate::target;
+use crate::timestamps;
+use crate::todos;
+use crate::usages;
 use crate::viewed_state::{self, ViewedState};
-use crate::{DiffLine, FileEntry, MainWindow, PrCommitEntry};
-use anyhow::{Context, Result};
+use crate::watcher;
+use crate::{
+    CoverLetter, DiffLine, DiffStats, FileEntry, MainWindow, PrCommitEntry, SessionFile,
+    SessionStats, TodoEntry, UsageEntry,
+};
+use anyhow::{anyhow, Context, Result};
 use slint::{ComponentHandle, Model, ModelRc, VecModel};
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::Instant;
+
+/// Diffs touching more files than this show their file tree before the
+/// file contents have been diffed.
+const PROGRESSIVE_FILES: usize = 500;
 
 pub struct App {
     window: MainWindow,
     repo: Rc<Repository>,
-    target: DiffTarget,
+    /// Where the window was opened: relative paths, gh and hg go by it
+    /// rather than the process's working directory, which a `--serve`
+    /// instance shares among its windows
+    dir: Rc<PathBuf>,
+    /// What is diffed; the base can be switched from the toolbar
+    target: Rc<RefCell<DiffTarget>>,
     diff_data: Rc<RefCell<Option<DiffData>>>,
+    /// Bumped whenever a different diff is shown, so background loads for
+    /// the previous one know to stop
+    diff_generation: Rc<Cell<u64>>,
     pr_comments: Rc<RefCell<Option<FileComments>>>,
     pr_commits: Rc<RefCell<Vec<PrCommit>>>,
     all_pr_comments: Rc<RefCell<Vec<github::PrComment>>>,
+    /// Notes of annotation plugins on the shown diff, worked out on a worker
+    /// once per diff and shown next to `all_pr_comments`
+    annotations: Rc<RefCell<Vec<github::PrComment>>>,
     pr_base_ref: Rc<RefCell<Option<String>>>,
     pr_head_ref: Rc<RefCell<Option<String>>>,
     highlighter: Rc<RefCell<Highlighter>>,
+    /// Highlighting of the diff's files, precomputed on worker threads
+    highlight_cache: Rc<RefCell<HighlightCache>>,
+    /// Highlighting still pending for the displayed file's off-screen rows
+    lazy_highlight: Rc<RefCell<Option<LazyHighlight>>>,
+    /// Size limits above which a file is only rendered on request
+    large_files: Rc<RefCell<LargeFileGuard>>,
     /// Cached file tree for re-flattening when folders are toggled
     file_tree: Rc<RefCell<Vec<FileTreeNode>>>,
-    /// Expanded state for folders (path -> is_expanded)
+    /// Index of the last commit that changed each file, for the tree's
+    /// commit order; worked out when first needed for a diff
+    last_commits: Rc<RefCell<Option<HashMap<String, usize>>>>,
+    /// Expanded state for folders (path -> is_expanded), kept per target
+    /// with the viewed state
     expanded_state: Rc<RefCell<HashMap<String, bool>>>,
     /// Persisted per-file viewed state
     viewed_state: Rc<RefCell<ViewedState>>,
     /// Key derived from diff target for viewed state persistence
-    target_key: String,
+    target_key: Rc<RefCell<String>>,
+    /// File to select when the diff is first shown (`--file`)
+    open_file: Rc<RefCell<Option<String>>>,
+    /// File and line to scroll to once that file's rows are shown (`--line`)
+    open_line: Rc<RefCell<Option<(String, u32)>>>,
+    /// Settings given on the command line, which aren't saved
+    overrides: Rc<SettingOverrides>,
+    /// Files left out of the tree unless the toolbar reveals them
+    exclude: Rc<RefCell<ExcludeFilter>>,
+    /// Rhai scripts from the config directory
+    scripts: Rc<Scripts>,
+    /// Show the Rust item summary above the diff (`semantic_diff`)
+    semantic_diff: bool,
+    /// Refreshes the diff when its files or refs change, while it lives
+    watcher: Option<Rc<notify::RecommendedWatcher>>,
+    /// Language servers for go to definition (`language_servers`)
+    definitions: Option<Rc<goto_definition::Servers>>,
+    /// Clicks on the diff's code, for selecting words, lines and ranges
+    selection: Rc<RefCell<SelectionState>>,
+    /// Time spent and files viewed since the window opened
+    session: Rc<RefCell<Session>>,
+    /// What the review comment being written goes on
+    compose_target: Rc<RefCell<Option<ComposeTarget>>>,
+    /// Column of the diff last clicked: comments there go on its side
+    pressed_side: Rc<Cell<CommentSide>>,
+    /// Where the rows of the file shown are, kept until they change
+    row_offsets: Rc<RefCell<RowOffsets>>,
+    /// The search across the diff's files
+    search: Rc<RefCell<DiffSearch>>,
+}
+
+/// The search across the diff: the index of its lines, built when first
+/// searched, and the matches of the query with the one shown
+#[derive(Default)]
+struct DiffSearch {
+    /// Index of the diff of a generation, with that many of its files diffed
+    index: Option<(u64, usize, SearchIndex)>,
+    hits: Vec<SearchHit>,
+    current: Option<usize>,
+}
+
+impl Clone for App {
+    /// Cheap handle to the same window and shared state, for use in async tasks.
+    fn clone(&self) -> Self {
+        Self {
+            window: self.window.clone_strong(),
+            repo: Rc::clone(&self.repo),
+            dir: Rc::clone(&self.dir),
+            target: Rc::clone(&self.target),
+            diff_data: Rc::clone(&self.diff_data),
+            diff_generation: Rc::clone(&self.diff_generation),
+            pr_comments: Rc::clone(&self.pr_comments),
+            pr_commits: Rc::clone(&self.pr_commits),
+            all_pr_comments: Rc::clone(&self.all_pr_comments),
+            annotations: Rc::clone(&self.annotations),
+            pr_base_ref: Rc::clone(&self.pr_base_ref),
+            pr_head_ref: Rc::clone(&self.pr_head_ref),
+            highlighter: Rc::clone(&self.highlighter),
+            highlight_cache: Rc::clone(&self.highlight_cache),
+            lazy_highlight: Rc::clone(&self.lazy_highlight),
+            large_files: Rc::clone(&self.large_files),
+            file_tree: Rc::clone(&self.file_tree),
+            last_commits: Rc::clone(&self.last_commits),
+            expanded_state: Rc::clone(&self.expanded_state),
+            viewed_state: Rc::clone(&self.viewed_state),
+            target_key: Rc::clone(&self.target_key),
+            open_file: Rc::clone(&self.open_file),
+            open_line: Rc::clone(&self.open_line),
+            overrides: Rc::clone(&self.overrides),
+            exclude: Rc::clone(&self.exclude),
+            scripts: Rc::clone(&self.scripts),
+            semantic_diff: self.semantic_diff,
+            watcher: self.watcher.clone(),
+            definitions: self.definitions.clone(),
+            selection: Rc::clone(&self.selection),
+            session: Rc::clone(&self.session),
+            compose_target: Rc::clone(&self.compose_target),
+            pressed_side: Rc::clone(&self.pressed_side),
+            row_offsets: Rc::clone(&self.row_offsets),
+            search: Rc::clone(&self.search),
+        }
+    }
 }
 
-/// Count comments that actually match a diff line for a given file.
-/// Only counts comments whose line number matches a line in the diff,
-/// so stale/resolved comments pointing at lines no longer in the diff are excluded.
+/// Count comments that actually match a diff line for a given file, and
+/// how many of them are in threads not resolved yet. Only counts comments
+/// whose line number matches a line in the diff, so stale comments pointing
+/// at lines no longer in the diff are excluded.
 fn count_matching_comments(
     hunks: &[crate::git::DiffHunk],
     comments: &[github::PrComment],
-) -> i32 {
+) -> (i32, i32) {
     use std::collections::HashSet;
 
     // Collect all (side, line) pairs present in the diff
@@ -58,7 +206,7 @@ fn count_matching_comments(
         }
     }
 
-    comments
+    let matching: Vec<_> = comments
         .iter()
         .filter(|c| match c.line {
             Some(line) => match c.side {
@@ -67,30 +215,43 @@ fn count_matching_comments(
             },
             None => false,
         })
-        .count() as i32
+        .collect();
+    let unresolved = matching.iter().filter(|c| !c.resolved).count();
+    (matching.len() as i32, unresolved as i32)
 }
 
 /// Convert flat file entries to Slint FileEntry models, enriching with comment counts.
 /// Only counts comments that match actual diff lines (excludes stale comments).
+#[tracing::instrument(skip_all)]
 fn build_file_entries(
     flat_entries: &[crate::git::FlatFileEntry],
     pr_comments: Option<&FileComments>,
     diff_data: Option<&DiffData>,
     viewed_state: Option<(&ViewedState, &str)>,
 ) -> Vec<FileEntry> {
+    let generated: HashSet<&str> = diff_data
+        .iter()
+        .flat_map(|data| &data.files)
+        .filter(|file| file.generated)
+        .map(|file| file.path.as_str())
+        .collect();
     flat_entries
         .iter()
         .map(|f| {
             let mut model = FileEntryModel::from(f);
+            model.generated = !f.is_folder && generated.contains(f.path.as_str());
             if let (Some(comments), Some(data)) = (pr_comments, diff_data) {
                 if let Some(file_comments) = comments.get(&f.path) {
                     let hunks = data.file_hunks.get(&f.path);
-                    model.comment_count = match hunks {
+                    (model.comment_count, model.unresolved_count) = match hunks {
                         Some(h) => count_matching_comments(h, file_comments),
-                        None => 0,
+                        None => (0, 0),
                     };
                 }
             }
+            if let Some(hunks) = diff_data.and_then(|data| data.file_hunks.get(&f.path)) {
+                model.todo_count = todos::find(hunks).len() as i32;
+            }
             // Apply viewed state from persistence
             if let Some((vs, tk)) = viewed_state {
                 if !f.is_folder {
@@ -109,17 +270,46 @@ fn build_file_entries(
         .collect()
 }
 
-/// Pick the initial focus row: first unviewed non-folder, else first non-folder, else -1.
+/// The TODO/FIXME/HACK markers the diff adds, in the order of its files.
+fn todo_entries(data: &DiffData) -> ModelRc<TodoEntry> {
+    let entries: Vec<TodoEntry> = data
+        .files
+        .iter()
+        .flat_map(|file| {
+            let hunks = data.file_hunks.get(&file.path);
+            todos::find(hunks.map_or(&[], Vec::as_slice))
+                .into_iter()
+                .map(|todo| TodoEntry {
+                    path: file.path.as_str().into(),
+                    line: todo.line as i32,
+                    text: todo.text.into(),
+                })
+        })
+        .collect();
+    ModelRc::from(Rc::new(VecModel::from(entries)))
+}
+
+/// Pick the initial focus row: first unviewed non-folder that isn't generated, else first
+/// non-folder, else -1.
 /// Matches J/K navigation semantics (which skips folders and viewed files).
 fn find_initial_focus_index(entries: &[FileEntry]) -> i32 {
     entries
         .iter()
-        .position(|e| !e.is_folder && !e.viewed)
+        .position(|e| !e.is_folder && !e.viewed && !e.generated)
         .or_else(|| entries.iter().position(|e| !e.is_folder))
         .map(|i| i as i32)
         .unwrap_or(-1)
 }
 
+/// Index of the row showing `path`, for `--file`.
+fn find_file_index(entries: &[FileEntry], path: &str) -> Result<i32> {
+    entries
+        .iter()
+        .position(|e| !e.is_folder && e.path == path)
+        .map(|i| i as i32)
+        .ok_or_else(|| anyhow!("{} is not part of this diff", path))
+}
+
 /// Look up the viewed status of a file by path, independent of the file tree
 /// model. Used to keep the diff header's checkbox correct even when the file
 /// is hidden by a collapsed ancestor.
@@ -138,19 +328,81 @@ fn is_path_viewed(
     viewed.is_viewed(target_key, path, hash)
 }
 
+/// Show in the toolbar how many of the diff's files are marked viewed.
+fn show_review_progress(
+    window: &MainWindow,
+    viewed: &ViewedState,
+    diff_data: Option<&DiffData>,
+    target_key: &str,
+) {
+    let files = diff_data.map_or(&[][..], |data| &data.files[..]);
+    let done = files
+        .iter()
+        .filter(|file| is_path_viewed(&file.path, viewed, diff_data, target_key))
+        .count();
+    window.set_files_viewed(done as i32);
+    window.set_files_total(files.len() as i32);
+}
+
+/// Folders holding nothing but generated files, at any depth
+fn generated_folders(files: &[FileChange]) -> Vec<String> {
+    let mut all_generated: HashMap<&str, bool> = HashMap::new();
+    for file in files {
+        for (end, _) in file.path.match_indices('/') {
+            *all_generated.entry(&file.path[..end]).or_insert(true) &= file.generated;
+        }
+    }
+    all_generated
+        .into_iter()
+        .filter(|(_, generated)| *generated)
+        .map(|(folder, _)| folder.to_string())
+        .collect()
+}
+
+/// Lines `path` adds and removes in the diff
+fn changed_lines(diff_data: Option<&DiffData>, path: &str) -> usize {
+    diff_data
+        .and_then(|data| data.files.iter().find(|f| f.path == path))
+        .map_or(0, |f| f.additions + f.deletions)
+}
+
 impl App {
-    pub fn new(args: Args) -> Result<Self> {
+    /// Open the window for `args` as run in `dir`. The backend has been
+    /// chosen by then ([`crate::backend::select`]).
+    pub fn new(args: Args, dir: PathBuf) -> Result<Self> {
+        // Load persisted settings, with those given on the command line
+        let mut config = crate::config::load();
+        args.overrides.apply(&mut config);
+        for problem in config.validate_keybindings() {
+            eprintln!("Warning: {}", problem);
+        }
+
         let window = MainWindow::new().context("Failed to create window")?;
-        let repo = Rc::new(Repository::open_current_dir()?);
-        let target = DiffTarget::parse(args.target.as_deref());
+        // Translations are chosen by the system's locale unless configured
+        if let Some(language) = &config.language {
+            if let Err(e) = slint::select_bundled_translation(language) {
+                eprintln!("Warning: Could not switch to language {}: {}", language, e);
+            }
+        }
+        let target = DiffTarget::from_args(&args);
+        let repo = Rc::new(target::open_repository(&dir, &target)?);
+        let (open_file, open_line) = match args.file_location() {
+            Some((file, line)) => {
+                let file = file.strip_prefix("./").unwrap_or(&file).to_string();
+                (Some(file.clone()), line.map(|line| (file, line)))
+            }
+            None => (None, None),
+        };
 
-        // Load persisted settings
-        let config = crate::config::load();
         window.set_app_settings(crate::AppSettings {
             ui_theme: config.ui_theme.clone().into(),
             font_size: config.font_size,
             tab_width: config.tab_width,
             line_wrap_column: config.line_wrap_column,
+            relative_timestamps: config.relative_timestamps,
+            tree_sort: config.tree_sort.clone().into(),
+            flat_list: config.flat_file_list,
+            hide_deleted: config.hide_deleted_files,
             key_unified: config.key_unified.clone().into(),
             key_side_by_side: config.key_side_by_side.clone().into(),
             key_scroll_down: config.key_scroll_down.clone().into(),
@@ -162,46 +414,166 @@ impl App {
         });
         // Apply theme from config (theme is derived from theme-name in Slint)
         window.set_theme_name(config.ui_theme.clone().into());
-        // Restore persisted panel width
+        window.set_keybinding_conflicts(conflicts_model(&config));
+        // A patch from stdin has no file contents to diff structurally
+        window.set_structural_available(
+            !matches!(target, DiffTarget::Stdin) && difftastic::is_available(),
+        );
+        // Only a diff of commits has other versions of its files to compare with
+        window.set_compare_available(!matches!(
+            target,
+            DiffTarget::Stdin
+                | DiffTarget::Files { .. }
+                | DiffTarget::Dirs { .. }
+                | DiffTarget::WorkingTree
+                | DiffTarget::Staged
+        ));
+        let profile_names: Vec<slint::SharedString> =
+            config.profiles.keys().map(Into::into).collect();
+        window.set_profile_names(ModelRc::from(Rc::new(VecModel::from(profile_names))));
+        if let Some(report) = crate::crash::take_unreported() {
+            window.set_crash_report(report.display().to_string().into());
+        }
+        if config.check_for_updates && !args.offline {
+            let window_weak = window.as_weak();
+            std::thread::spawn(move || match crate::update::newer_release() {
+                Ok(Some(release)) => {
+                    let _ = window_weak.upgrade_in_event_loop(move |window| {
+                        window.set_update_url(release.url.into());
+                        window.set_update_notes(release.body.trim().into());
+                        window.set_update_version(release.tag.into());
+                    });
+                }
+                Ok(None) => {}
+                // Not worth a warning on every launch without network
+                Err(e) => tracing::debug!("{:#}", e),
+            });
+        }
+        // First launch: a short setup, which ends by saving the config
+        if crate::config::is_first_run() {
+            window.set_onboarding_visible(true);
+            let window_weak = window.as_weak();
+            let offline = args.offline;
+            std::thread::spawn(move || {
+                let status = github_status(offline);
+                let _ = window_weak.upgrade_in_event_loop(move |window| {
+                    window.set_onboarding_github_status(status.into());
+                });
+            });
+        }
+        // Restore persisted panel widths and window geometry
         window.set_left_panel_width(config.panel_width);
+        window.set_side_by_side_split(config.side_by_side_split.clamp(0.2, 0.8));
+        restore_window_geometry(&window, &config);
 
         // Set the diff title based on target
-        let diff_title = match &target {
-            DiffTarget::DefaultBranch => {
-                let default_branch = repo.find_default_branch()?;
-                format!("HEAD vs {}", default_branch)
+        let diff_title = target::title(&dir, &repo, &target)?;
+        window.set_diff_title(diff_title.clone().into());
+
+        // Remember the target, and offer the others opened in this repository
+        if let Some(workdir) = repo.workdir().map(Path::to_path_buf) {
+            let recent = recent::record(&workdir, &target, &diff_title);
+            let titles: Vec<slint::SharedString> =
+                recent.iter().map(|t| t.title.as_str().into()).collect();
+            window.set_recent_targets(ModelRc::from(Rc::new(VecModel::from(titles))));
+            window.on_open_recent(move |index| {
+                if let Some(target) = recent.get(index as usize) {
+                    if let Err(e) = recent::open(&workdir, target) {
+                        eprintln!("Warning: {}", e);
+                    }
+                }
+            });
+        }
+
+        // Bases the toolbar can switch the diff to
+        if target::with_base(&target, "").is_some() {
+            match repo.ref_names() {
+                Ok(names) => {
+                    let names: Vec<slint::SharedString> =
+                        names.into_iter().map(Into::into).collect();
+                    window.set_base_refs(ModelRc::from(Rc::new(VecModel::from(names))));
+                }
+                Err(e) => eprintln!("Warning: Could not list the branches and tags: {}", e),
             }
-            DiffTarget::Ref(r) => format!("HEAD vs {}", r),
-            DiffTarget::PullRequest(pr) => format!("PR #{}", pr),
-        };
-        window.set_diff_title(diff_title.into());
+        }
 
         // Initialize syntax highlighter with theme matching UI theme
         let mut highlighter = Highlighter::new();
-        highlighter.set_theme(config.ui_theme.as_str());
+        highlighter.set_theme(config.syntax_theme.as_ref().unwrap_or(&config.ui_theme));
 
         let viewed_state = Rc::new(RefCell::new(ViewedState::load()));
-        let target_key = viewed_state::target_key(&target);
+        let target_key = viewed_state::migrated_target_key(
+            &mut viewed_state.borrow_mut(),
+            repo.workdir(),
+            &target,
+        );
+        let expanded_state = viewed_state.borrow().folder_state(&target_key);
+
+        let repo_workdir = repo.workdir().map(Path::to_path_buf);
+        let window_weak = window.as_weak();
+        let watcher = match args.no_watch {
+            true => None,
+            false => watcher::watch(&target, repo.git_dir(), repo.workdir(), move || {
+                let _ = window_weak.upgrade_in_event_loop(|window| window.invoke_refresh_diff());
+            })
+            .unwrap_or_else(|e| {
+                eprintln!("Warning: Could not watch for changes to refresh the diff: {}", e);
+                None
+            }),
+        };
 
         let app = Self {
             window,
             repo,
-            target,
+            dir: Rc::new(dir.clone()),
+            target: Rc::new(RefCell::new(target)),
             diff_data: Rc::new(RefCell::new(None)),
+            diff_generation: Rc::new(Cell::new(0)),
             pr_comments: Rc::new(RefCell::new(None)),
             pr_commits: Rc::new(RefCell::new(Vec::new())),
             all_pr_comments: Rc::new(RefCell::new(Vec::new())),
+            annotations: Rc::new(RefCell::new(Vec::new())),
             pr_base_ref: Rc::new(RefCell::new(None)),
             pr_head_ref: Rc::new(RefCell::new(None)),
             highlighter: Rc::new(RefCell::new(highlighter)),
+            highlight_cache: Rc::new(RefCell::new(HighlightCache::default())),
+            lazy_highlight: Rc::new(RefCell::new(None)),
+            large_files: Rc::new(RefCell::new(LargeFileGuard::new(
+                config.large_file_lines,
+                config.large_file_bytes,
+            ))),
             file_tree: Rc::new(RefCell::new(Vec::new())),
-            expanded_state: Rc::new(RefCell::new(HashMap::new())),
+            last_commits: Rc::new(RefCell::new(None)),
+            expanded_state: Rc::new(RefCell::new(expanded_state)),
             viewed_state,
-            target_key,
+            target_key: Rc::new(RefCell::new(target_key)),
+            open_file: Rc::new(RefCell::new(open_file)),
+            open_line: Rc::new(RefCell::new(open_line)),
+            overrides: Rc::new(args.overrides),
+            exclude: Rc::new(RefCell::new(ExcludeFilter::new(&config.exclude))),
+            scripts: Rc::new(Scripts::load(&dir)),
+            semantic_diff: config.semantic_diff,
+            watcher: watcher.map(Rc::new),
+            definitions: repo_workdir
+                .and_then(|workdir| {
+                    goto_definition::Servers::start(&config.language_servers, &workdir)
+                })
+                .map(Rc::new),
+            selection: Rc::new(RefCell::new(SelectionState::default())),
+            session: Rc::new(RefCell::new(Session::new(Instant::now(), chrono::Utc::now()))),
+            compose_target: Rc::new(RefCell::new(None)),
+            pressed_side: Rc::new(Cell::new(CommentSide::Right)),
+            row_offsets: Rc::new(RefCell::new(RowOffsets::default())),
+            search: Rc::new(RefCell::new(DiffSearch::default())),
         };
 
         app.setup_callbacks()?;
-        app.load_diff()?;
+        if let Err(e) = app.load_diff() {
+            // A shallow clone can fetch what it lacks; other failures end lado
+            if !app.offer_deepen(&e) {
+                return Err(e);
+            }
+        }
 
         Ok(app)
     }
@@ -211,31 +583,147 @@ impl App {
         let diff_data = Rc::clone(&self.diff_data);
         let pr_comments = Rc::clone(&self.pr_comments);
         let highlighter = Rc::clone(&self.highlighter);
+        let highlight_cache = Rc::clone(&self.highlight_cache);
+        let lazy_highlight = Rc::clone(&self.lazy_highlight);
+        let large_files = Rc::clone(&self.large_files);
         let viewed_state_for_select = Rc::clone(&self.viewed_state);
-        let target_key_for_select = self.target_key.clone();
+        let target_key_for_select = Rc::clone(&self.target_key);
+        let repo = Rc::clone(&self.repo);
+        let semantic_diff = self.semantic_diff;
+        let session = Rc::clone(&self.session);
 
         // File selection callback
         self.window.on_file_selected(move |path| {
             let window = window_weak.unwrap();
             let path_str = path.to_string();
+            session.borrow_mut().show_file(&path_str, Instant::now());
 
             let data_borrow = diff_data.borrow();
             if let Some(ref data) = *data_borrow {
                 let comments = pr_comments.borrow();
                 let hl = highlighter.borrow();
+                let mut cache = highlight_cache.borrow_mut();
                 let wrap = window.get_app_settings().line_wrap_column.max(0) as usize;
-                let lines = get_lines_for_file(data, &path_str, comments.as_ref(), &hl, wrap);
+                let (lines, lazy) = get_lines_for_file(
+                    data,
+                    &path_str,
+                    comments.as_ref(),
+                    Some((&viewed_state_for_select.borrow(), &target_key_for_select.borrow())),
+                    &hl,
+                    &mut cache,
+                    &large_files.borrow(),
+                    wrap,
+                    window.get_side_by_side_mode(),
+                );
                 window.set_lines(lines);
+                *lazy_highlight.borrow_mut() = lazy;
             }
 
             let viewed = is_path_viewed(
                 &path_str,
                 &viewed_state_for_select.borrow(),
                 data_borrow.as_ref(),
-                &target_key_for_select,
+                &target_key_for_select.borrow(),
             );
+            let structural = data_borrow
+                .as_ref()
+                .is_some_and(|d| d.structural_hunks.contains_key(&path_str));
+            let rename = data_borrow
+                .as_ref()
+                .and_then(|d| d.files.iter().find(|f| f.path == path_str))
+                .and_then(|f| f.rename.clone());
+            let encoding = data_borrow.as_ref().and_then(|d| file_encoding(d, &path_str));
+            let split_rename = data_borrow
+                .as_ref()
+                .is_some_and(|d| d.split_renames.contains_key(&path_str));
+            // Patches have no contents to show whole
+            let splittable = data_borrow
+                .as_ref()
+                .and_then(|d| d.blobs.get(&path_str))
+                .is_some_and(|&(old, new)| old.is_some() && new.is_some());
+            let summary = data_borrow
+                .as_ref()
+                .map(|d| item_summary(semantic_diff, &repo, d, &path_str))
+                .unwrap_or_default();
             window.set_selected_file(path);
             window.set_selected_file_viewed(viewed);
+            window.set_selected_file_structural(structural);
+            window.set_selected_file_summary(summary.into());
+            window.set_selected_file_renamed_from(
+                rename.as_ref().map_or("", |r| r.from.as_str()).into(),
+            );
+            window.set_selected_file_similarity(rename.map_or(0, |r| r.similarity as i32));
+            window.set_selected_file_split_rename(split_rename);
+            window.set_selected_file_rename_splittable(splittable);
+            window.set_selected_file_encoding(encoding.unwrap_or_default().into());
+        });
+
+        // Renamed file shown as a deletion and an addition, or by its changes
+        let window_weak = self.window.as_weak();
+        let diff_data = Rc::clone(&self.diff_data);
+        let repo = Rc::clone(&self.repo);
+        self.window.on_toggle_split_rename(move || {
+            let window = window_weak.unwrap();
+            let path = window.get_selected_file().to_string();
+            let mut data_borrow = diff_data.borrow_mut();
+            let Some(data) = data_borrow.as_mut() else {
+                return;
+            };
+            if data.split_renames.remove(&path).is_none() {
+                let (old, new) = data.blobs.get(&path).copied().unwrap_or_default();
+                let encoding = file_encoding(data, &path);
+                let content = |blob: Option<git2::Oid>| -> Result<String> {
+                    let bytes = blob.map_or(Ok(Vec::new()), |id| repo.blob_content(id))?;
+                    Ok(encoding::decode(&bytes, encoding).into_owned())
+                };
+                match content(old).and_then(|old| Ok((old, content(new)?))) {
+                    Ok((old, new)) => {
+                        let tab_width = window.get_app_settings().tab_width as usize;
+                        data.set_split_rename(path.clone(), &old, &new, tab_width);
+                    }
+                    Err(e) => {
+                        eprintln!("Warning: Could not read {}: {:#}", path, e);
+                        return;
+                    }
+                }
+            }
+            drop(data_borrow);
+            // Shows the file again, with the other hunks
+            window.invoke_file_selected(path.into());
+        });
+
+        // Structural diff (difftastic) of the selected file switched on or off
+        let window_weak = self.window.as_weak();
+        let diff_data = Rc::clone(&self.diff_data);
+        let repo = Rc::clone(&self.repo);
+        self.window.on_toggle_structural(move || {
+            let window = window_weak.unwrap();
+            let path = window.get_selected_file().to_string();
+            let mut data_borrow = diff_data.borrow_mut();
+            let Some(data) = data_borrow.as_mut() else {
+                return;
+            };
+            if data.structural_hunks.remove(&path).is_none() {
+                let (old, new) = data.blobs.get(&path).copied().unwrap_or_default();
+                let content = |blob: Option<git2::Oid>| -> Result<Vec<u8>> {
+                    blob.map_or(Ok(Vec::new()), |id| repo.blob_content(id))
+                };
+                let hunks =
+                    content(old).and_then(|old| difftastic::diff(&path, &old, &content(new)?));
+                match hunks {
+                    Ok(hunks) => {
+                        let tab_width = window.get_app_settings().tab_width as usize;
+                        data.set_structural_hunks(path.clone(), hunks, tab_width);
+                    }
+                    Err(e) => {
+                        eprintln!("Warning: Could not diff {} with difftastic: {:#}", path, e);
+                        return;
+                    }
+                }
+            }
+            drop(data_borrow);
+            // Shows the file again, with the other hunks
+            window.invoke_file_selected(path.into());
         });
 
         // Folder toggle callback for collapsing/expanding directories
@@ -245,7 +733,7 @@ impl App {
         let pr_comments = Rc::clone(&self.pr_comments);
         let diff_data = Rc::clone(&self.diff_data);
         let viewed_state = Rc::clone(&self.viewed_state);
-        let target_key = self.target_key.clone();
+        let target_key = Rc::clone(&self.target_key);
         self.window.on_folder_toggled(move |path| {
             let window = window_weak.unwrap();
             let path_str = path.to_string();
@@ -256,6 +744,7 @@ impl App {
                 let is_expanded = state.get(&path_str).copied().unwrap_or(true);
                 state.insert(path_str.clone(), !is_expanded);
             }
+            save_folder_state(&viewed_state, &target_key.borrow(), &expanded_state.borrow());
 
             // Re-flatten the tree with updated expanded state
             let tree = file_tree.borrow();
@@ -266,7 +755,7 @@ impl App {
                 &flat_entries,
                 pr_comments.borrow().as_ref(),
                 diff_data.borrow().as_ref(),
-                Some((&viewed_state.borrow(), &target_key)),
+                Some((&viewed_state.borrow(), &target_key.borrow())),
             );
 
             // The re-flatten invalidated focused-index (rows shifted). Re-anchor it
@@ -286,11 +775,10 @@ impl App {
             window.set_files(ModelRc::from(files_model));
         });
 
-        let window_weak = self.window.as_weak();
-        self.window.on_toggle_view_mode(move || {
-            let _window = window_weak.unwrap();
-            println!("Toggle view mode");
-        });
+        // Side by side, removed and added lines share rows, so the rows are
+        // built anew
+        let app = self.clone();
+        self.window.on_toggle_view_mode(move || app.render_selected_file());
 
         let window_weak = self.window.as_weak();
         self.window.on_toggle_fullscreen(move || {
@@ -299,26 +787,265 @@ impl App {
             window.window().set_fullscreen(!is_fullscreen);
         });
 
-        let window_weak = self.window.as_weak();
+        // The window lives as long as the process, so the reference cycle
+        // through this handle is harmless
+        let app = self.clone();
         self.window.on_refresh_diff(move || {
-            let _window = window_weak.unwrap();
-            println!("Refresh diff");
+            if let Err(e) = app.refresh_diff() {
+                eprintln!("Warning: Could not refresh diff: {}", e);
+            }
         });
 
-        // Commit selection callback for PR commit navigation
+        let app = self.clone();
+        self.window.on_export_report(move |format| {
+            if format == "markdown" {
+                app.export_summary();
+            } else {
+                app.report_exported(app.export_report(&format));
+            }
+        });
+
+        let app = self.clone();
+        self.window.on_symbol_clicked(move |row, column| {
+            if let Err(e) = app.go_to_definition(row, column) {
+                eprintln!("Warning: Could not go to the definition: {:#}", e);
+            }
+        });
+
+        let app = self.clone();
+        self.window.on_unfold(move |row| {
+            if let Err(e) = app.unfold(row) {
+                eprintln!("Warning: Could not show the unchanged lines: {:#}", e);
+            }
+        });
+
+        let app = self.clone();
+        self.window.on_toggle_hunk_reviewed(move |row| app.toggle_hunk_reviewed(row));
+
+        let app = self.clone();
+        self.window.on_search(move |query| app.search(&query));
+
+        let app = self.clone();
+        self.window.on_search_step(move |delta| app.step_search(delta));
+
+        let search = Rc::clone(&self.search);
+        self.window.on_search_hits(move |path, is_folder, _| {
+            let folder = format!("{}/", path);
+            let hits = &search.borrow().hits;
+            let count = match is_folder {
+                true => hits.iter().filter(|hit| hit.path.starts_with(&folder)).count(),
+                false => hits.iter().filter(|hit| hit.path == path.as_str()).count(),
+            };
+            count as i32
+        });
+
+        let app = self.clone();
+        self.window.on_compose_comment(move |row| app.compose_comment(row));
+
+        let app = self.clone();
+        self.window.on_post_comment(move |body| app.post_comment(body.to_string()));
+
+        let app = self.clone();
+        self.window.on_hunk_hovered(move |row| app.hunk_hovered(row));
+
+        let app = self.clone();
+        self.window.on_deepen_history(move || app.deepen_history());
+
+        let app = self.clone();
+        self.window.on_hunk_at(move |offset| app.hunk_at(offset));
+
+        let app = self.clone();
+        self.window
+            .on_next_unreviewed_hunk(move |offset| app.next_unreviewed_hunk(offset));
+
+        let app = self.clone();
+        self.window.on_next_unviewed_file(move || app.next_unviewed_file());
+
+        let app = self.clone();
+        self.window.on_session_opened(move || app.show_session_stats());
+
+        let window_weak = self.window.as_weak();
+        self.window.on_scroll_overview(move |lines, font_size| {
+            let heights = RowHeights::of(&window_weak.unwrap(), font_size);
+            scroll_markers::overview(&lines, heights)
+        });
+
+        let app = self.clone();
+        self.window.on_row_pressed(move |row, column, shift, old_side| {
+            let side = if old_side { CommentSide::Left } else { CommentSide::Right };
+            app.pressed_side.set(side);
+            app.press_row(row, column, shift);
+        });
+
+        let window_weak = self.window.as_weak();
+        self.window.on_selection_text(move || {
+            let window = window_weak.unwrap();
+            Selection::from_shown(&window.get_diff_selection())
+                .map(|selection| selection.text(&window.get_lines()).into())
+                .unwrap_or_default()
+        });
+
+        let app = self.clone();
+        self.window.on_base_selected(move |base| {
+            if let Err(e) = app.switch_base(&base) {
+                eprintln!("Warning: Could not diff against {}: {:#}", base, e);
+                let status = format!("Could not diff against {}", base);
+                app.flash_status(&status, std::time::Duration::from_secs(3));
+            }
+        });
+
+        let app = self.clone();
+        self.window.on_compare_with(move |path, reference| {
+            if let Err(e) = app.compare_with(&path, &reference) {
+                app.window.set_compare_lines(ModelRc::default());
+                app.window.set_compare_message(format!("{:#}", e).into());
+            }
+        });
+
+        let app = self.clone();
+        self.window.on_todo_selected(move |path, line| {
+            if let Err(e) = app.goto(&path, u32::try_from(line).ok()) {
+                eprintln!("Warning: {}", e);
+            }
+        });
+
+        let app = self.clone();
+        self.window.on_find_usages(move || app.find_usages());
+        let app = self.clone();
+        self.window.on_usage_selected(move |path, line| {
+            if let Err(e) = app.show_usage(&path, line.max(1) as u32) {
+                eprintln!("Warning: Could not show {}: {:#}", path, e);
+            }
+        });
+
+        // Fill in highlighting for rows scrolled into view
+        let app = self.clone();
+        self.window.on_diff_viewport_changed(move |top, height| {
+            if let Some(lazy) = app.lazy_highlight.borrow_mut().as_mut() {
+                let offsets = app.row_offsets();
+                lazy.show_viewport(top, height, &offsets, &app.highlighter.borrow());
+            }
+        });
+
+        // Render a file held back by the large-file guard, a batch of lines
+        // per event loop turn
         let window_weak = self.window.as_weak();
+        let diff_data = Rc::clone(&self.diff_data);
+        let pr_comments = Rc::clone(&self.pr_comments);
+        let highlighter = Rc::clone(&self.highlighter);
+        let lazy_highlight = Rc::clone(&self.lazy_highlight);
+        let large_files = Rc::clone(&self.large_files);
+        let viewed_state = Rc::clone(&self.viewed_state);
+        let target_key = Rc::clone(&self.target_key);
+        self.window.on_load_large_file(move || {
+            let window = window_weak.unwrap();
+            let path = window.get_selected_file().to_string();
+            large_files.borrow_mut().allow(&path);
+
+            let model = Rc::new(VecModel::<DiffLine>::default());
+            window.set_lines(ModelRc::from(Rc::clone(&model)));
+            *lazy_highlight.borrow_mut() = None;
+
+            let window_weak = window.as_weak();
+            let diff_data = Rc::clone(&diff_data);
+            let pr_comments = Rc::clone(&pr_comments);
+            let highlighter = Rc::clone(&highlighter);
+            let lazy_highlight = Rc::clone(&lazy_highlight);
+            let viewed_state = Rc::clone(&viewed_state);
+            let target_key = Rc::clone(&target_key);
+            let file = path.clone();
+            let spawned = slint::spawn_local(async move {
+                // Next hunk and line within it (0 = the hunk header)
+                let (mut hunk_idx, mut line_idx) = (0, 0);
+                let mut lazy_rows = Vec::new();
+                loop {
+                    let Some(window) = window_weak.upgrade() else {
+                        return;
+                    };
+                    // Stop if another file (or diff) has been shown meanwhile
+                    if window.get_lines() != ModelRc::from(Rc::clone(&model)) {
+                        return;
+                    }
+                    let wrap = window.get_app_settings().line_wrap_column.max(0) as usize;
+
+                    let done = {
+                        let data = diff_data.borrow();
+                        let Some(hunks) = data.as_ref().and_then(|d| d.file_hunks.get(&file))
+                        else {
+                            return;
+                        };
+                        let comments = pr_comments.borrow();
+                        let mut rows = FileRows::new(
+                            &file,
+                            comments.as_ref(),
+                            None,
+                            wrap,
+                            window.get_side_by_side_mode(),
+                            model.row_count(),
+                        );
+                        let mut budget = large_file::STREAM_LINES;
+                        while budget > 0 && hunk_idx < hunks.len() {
+                            let hunk = &hunks[hunk_idx];
+                            match line_idx {
+                                0 => {
+                                    rows.push_header(
+                                        hunk,
+                                        viewed_state.borrow().is_hunk_reviewed(
+                                            &target_key.borrow(),
+                                            &file,
+                                            viewed_state::hash_hunk(hunk),
+                                        ),
+                                    );
+                                    line_idx += 1;
+                                    budget -= 1;
+                                }
+                                i => {
+                                    // A batch of lines at once, so that side by
+                                    // side they are paired up within it
+                                    let end = (i - 1 + budget).min(hunk.lines.len());
+                                    rows.push_lines(&hunk.lines[i - 1..end]);
+                                    budget -= end - (i - 1);
+                                    line_idx = end + 1;
+                                }
+                            }
+                            if line_idx > hunk.lines.len() {
+                                hunk_idx += 1;
+                                line_idx = 0;
+                            }
+                        }
+                        model.extend(rows.rows);
+                        lazy_rows.extend(rows.lazy_rows);
+                        hunk_idx == hunks.len()
+                    };
+
+                    if done {
+                        let mut lazy =
+                            LazyHighlight::new(&file, Rc::clone(&model), lazy_rows, wrap);
+                        lazy.ensure_rows(0, 0, &highlighter.borrow());
+                        *lazy_highlight.borrow_mut() = Some(lazy);
+                        return;
+                    }
+                    next_event_loop_turn().await;
+                }
+            });
+            if let Err(e) = spawned {
+                eprintln!("Warning: Could not start loading {}: {}", path, e);
+            }
+        });
+
+        // Commit selection callback for PR commit navigation
         let repo = Rc::clone(&self.repo);
         let pr_commits = Rc::clone(&self.pr_commits);
         let pr_base_ref = Rc::clone(&self.pr_base_ref);
         let pr_head_ref = Rc::clone(&self.pr_head_ref);
         let all_pr_comments = Rc::clone(&self.all_pr_comments);
-        let highlighter = Rc::clone(&self.highlighter);
-        self.window.on_commit_selected(move |idx| {
-            let window = window_weak.unwrap();
+        let app = self.clone();
+        self.window.on_commit_selected(move |first, last| {
             let commits = pr_commits.borrow();
             let comments = all_pr_comments.borrow();
 
-            let diff_result: Option<(Result<DiffData>, Option<FileComments>)> = if idx < 0 {
+            let range = usize::try_from(first).ok().zip(usize::try_from(last).ok());
+            let selected: Option<(git2::Oid, git2::Oid, FileComments)> = if first < 0 {
                 // "All changes" - diff base to head
                 let base_ref = pr_base_ref.borrow();
                 let head_ref = pr_head_ref.borrow();
@@ -327,128 +1054,106 @@ impl App {
                     let head_oid = repo.resolve_ref(head).ok();
                     if let (Some(b), Some(h)) = (base_oid, head_oid) {
                         // Show all comments for full diff
-                        let grouped = github::group_comments_by_file(comments.clone());
-                        Some((repo.diff_commits(b, h), Some(grouped)))
+                        Some((b, h, github::group_comments_by_file(comments.clone())))
                     } else {
                         None
                     }
                 } else {
                     None
                 }
-            } else if let Some(commit) = commits.get(idx as usize) {
-                // Single commit - diff parent to this commit
-                if let Some(ref parent_sha) = commit.parent_sha {
-                    let parent_oid = repo.resolve_ref(parent_sha).ok();
-                    let commit_oid = repo.resolve_ref(&commit.sha).ok();
-                    if let (Some(p), Some(c)) = (parent_oid, commit_oid) {
-                        // Filter comments to only show those on this commit
-                        let filtered: Vec<_> = comments
-                            .iter()
-                            .filter(|c| c.original_commit_id == commit.sha)
-                            .cloned()
-                            .collect();
-                        let grouped = github::group_comments_by_file(filtered);
-                        Some((repo.diff_commits(p, c), Some(grouped)))
-                    } else {
-                        None
-                    }
+            } else if let Some(range) = range.and_then(|(first, last)| commits.get(first..=last)) {
+                // Commits first to last (one commit, or a shift+clicked range)
+                // - diff the first one's parent to the last one, or the base
+                // to it if the first one has no parent
+                let base_ref = pr_base_ref.borrow();
+                let from = range[0].parent_sha.as_ref().or(base_ref.as_ref());
+                let from_oid = from.and_then(|from| repo.resolve_ref(from).ok());
+                let to_oid = repo.resolve_ref(&range[range.len() - 1].sha).ok();
+                if let (Some(f), Some(t)) = (from_oid, to_oid) {
+                    // Filter comments to only show those on these commits
+                    let filtered: Vec<_> = comments
+                        .iter()
+                        .filter(|c| range.iter().any(|commit| commit.sha == c.original_commit_id))
+                        .cloned()
+                        .collect();
+                    Some((f, t, github::group_comments_by_file(filtered)))
                 } else {
-                    // First commit in PR - no parent, show empty diff or handle differently
-                    // For now, just show the commit itself compared to base
-                    let base_ref = pr_base_ref.borrow();
-                    if let Some(base) = base_ref.as_ref() {
-                        let base_oid = repo.resolve_ref(base).ok();
-                        let commit_oid = repo.resolve_ref(&commit.sha).ok();
-                        if let (Some(b), Some(c)) = (base_oid, commit_oid) {
-                            let filtered: Vec<_> = comments
-                                .iter()
-                                .filter(|c| c.original_commit_id == commit.sha)
-                                .cloned()
-                                .collect();
-                            let grouped = github::group_comments_by_file(filtered);
-                            Some((repo.diff_commits(b, c), Some(grouped)))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
+                    None
                 }
             } else {
                 None
             };
 
-            if let Some((Ok(mut diff_data), grouped_comments)) = diff_result {
-                diff_data.expand_tabs(window.get_app_settings().tab_width as usize);
-                // Build hierarchical file tree and flatten for UI
-                // Use empty expanded state for commit-specific views (fresh view each time)
-                let tree = build_file_tree(&diff_data.files);
-                let flat_entries = flatten_tree_with_state(&tree, 0, &HashMap::new());
-
-                let file_entries =
-                    build_file_entries(&flat_entries, grouped_comments.as_ref(), Some(&diff_data), None);
-
-                let initial_focus = find_initial_focus_index(&file_entries);
-                let initial_viewed = if initial_focus >= 0 {
-                    file_entries
-                        .get(initial_focus as usize)
-                        .map(|e| e.viewed)
-                        .unwrap_or(false)
-                } else {
-                    false
-                };
+            if let Some((from, to, grouped_comments)) = selected {
+                app.show_commit_diff(from, to, grouped_comments);
+            }
+        });
 
-                let files_model = Rc::new(VecModel::from(file_entries));
-                window.set_files(ModelRc::from(files_model));
-
-                if initial_focus >= 0 {
-                    if let Some(initial) = flat_entries.get(initial_focus as usize) {
-                        window.set_focused_index(initial_focus);
-                        window.set_selected_file(initial.path.clone().into());
-                        window.set_selected_file_viewed(initial_viewed);
-                        let hl = highlighter.borrow();
-                        let wrap = window.get_app_settings().line_wrap_column.max(0) as usize;
-                        let lines = get_lines_for_file(
-                            &diff_data,
-                            &initial.path,
-                            grouped_comments.as_ref(),
-                            &hl,
-                            wrap,
-                        );
-                        window.set_lines(lines);
-                    }
+        // Details of a commit of the commit list, for its popover
+        let window_weak = self.window.as_weak();
+        let repo = Rc::clone(&self.repo);
+        let pr_commits = Rc::clone(&self.pr_commits);
+        self.window.on_commit_details_requested(move |idx| {
+            let window = window_weak.unwrap();
+            let commits = pr_commits.borrow();
+            let Some(commit) = usize::try_from(idx).ok().and_then(|i| commits.get(i)) else {
+                return;
+            };
+            window.set_commit_details(commit_details(&repo, commit));
+        });
+
+        // Filter box of the commit list
+        let window_weak = self.window.as_weak();
+        let pr_commits = Rc::clone(&self.pr_commits);
+        self.window.on_commit_filter_edited(move |filter| {
+            let window = window_weak.unwrap();
+            let entries = window.get_commits();
+            for (row, commit) in pr_commits.borrow().iter().enumerate() {
+                let Some(mut entry) = entries.row_data(row) else {
+                    break;
+                };
+                let filtered_out = !matches_filter(commit, &filter);
+                if entry.filtered_out != filtered_out {
+                    entry.filtered_out = filtered_out;
+                    entries.set_row_data(row, entry);
                 }
             }
         });
 
         // Settings changed callback
         let highlighter = Rc::clone(&self.highlighter);
+        let highlight_cache = Rc::clone(&self.highlight_cache);
+        let lazy_highlight = Rc::clone(&self.lazy_highlight);
         let window_weak = self.window.as_weak();
         let diff_data = Rc::clone(&self.diff_data);
         let pr_comments = Rc::clone(&self.pr_comments);
+        let large_files = Rc::clone(&self.large_files);
+        let overrides = Rc::clone(&self.overrides);
+        let viewed_state = Rc::clone(&self.viewed_state);
+        let target_key = Rc::clone(&self.target_key);
         self.window.on_settings_changed(move |settings| {
             // Persist settings to config file
             let window = window_weak.unwrap();
-            let config = crate::config::Config {
-                ui_theme: settings.ui_theme.to_string(),
-                font_size: settings.font_size,
-                tab_width: settings.tab_width,
-                line_wrap_column: settings.line_wrap_column,
+            let saved = crate::config::load();
+            let mut config = crate::config::Config {
                 panel_width: window.get_left_panel_width(),
-                key_unified: settings.key_unified.to_string(),
-                key_side_by_side: settings.key_side_by_side.to_string(),
-                key_scroll_down: settings.key_scroll_down.to_string(),
-                key_scroll_up: settings.key_scroll_up.to_string(),
-                key_file_next: settings.key_file_next.to_string(),
-                key_file_prev: settings.key_file_prev.to_string(),
-                key_prev_commit: settings.key_prev_commit.to_string(),
-                key_next_commit: settings.key_next_commit.to_string(),
+                side_by_side_split: window.get_side_by_side_split(),
+                // Keep fields that aren't part of AppSettings (window geometry, backend)
+                ..settings_config(&settings, saved.clone())
             };
+            overrides.restore(&saved, &mut config);
             if let Err(e) = crate::config::save(&config) {
                 eprintln!("Warning: Could not save settings: {}", e);
             }
+            window.set_keybinding_conflicts(conflicts_model(&config));
 
-            highlighter.borrow_mut().set_theme(settings.ui_theme.as_str());
+            let syntax_theme = overrides.syntax_theme.as_ref().or(config.syntax_theme.as_ref());
+            highlighter
+                .borrow_mut()
+                .set_theme(syntax_theme.map_or(settings.ui_theme.as_str(), String::as_str));
+            if let Some(ref data) = *diff_data.borrow() {
+                highlight_in_background(data, &highlighter.borrow(), &highlight_cache);
+            }
 
             // Re-highlight currently selected file
             let selected_file = window.get_selected_file().to_string();
@@ -456,14 +1161,81 @@ impl App {
                 if let Some(ref data) = *diff_data.borrow() {
                     let comments = pr_comments.borrow();
                     let hl = highlighter.borrow();
+                    let mut cache = highlight_cache.borrow_mut();
                     let wrap = settings.line_wrap_column.max(0) as usize;
-                    let lines = get_lines_for_file(data, &selected_file, comments.as_ref(), &hl, wrap);
+                    let (lines, lazy) = get_lines_for_file(
+                        data,
+                        &selected_file,
+                        comments.as_ref(),
+                        Some((&viewed_state.borrow(), &target_key.borrow())),
+                        &hl,
+                        &mut cache,
+                        &large_files.borrow(),
+                        wrap,
+                        window.get_side_by_side_mode(),
+                    );
                     window.set_lines(lines);
+                    *lazy_highlight.borrow_mut() = lazy;
+                }
+            }
+        });
+
+        // Profile chosen in the settings panel: switch to its settings, which
+        // settings-changed then saves like any others
+        self.window.on_apply_profile(|mut settings, name| {
+            let mut config = crate::config::load();
+            if let Some(profile) = config.profiles.get(name.as_str()).cloned() {
+                // Not part of AppSettings, so saved here for settings-changed
+                // to keep
+                if profile.syntax_theme.is_some() {
+                    config.syntax_theme = profile.syntax_theme.clone();
+                    if let Err(e) = crate::config::save(&config) {
+                        eprintln!("Warning: Could not save settings: {}", e);
+                    }
+                }
+                if let Some(theme) = &profile.ui_theme {
+                    settings.ui_theme = theme.into();
+                }
+                if let Some(size) = profile.font_size {
+                    settings.font_size = size;
+                }
+                if let Some(column) = profile.line_wrap_column {
+                    settings.line_wrap_column = column;
                 }
             }
+            settings
+        });
+
+        // A key pressed for an action in the settings panel: the action
+        // that already has it, to warn before taking it over
+        let window_weak = self.window.as_weak();
+        self.window.on_key_conflict(move |key, action| {
+            let window = window_weak.unwrap();
+            settings_config(&window.get_app_settings(), Default::default())
+                .key_taken_by(&key, &action)
+                .unwrap_or_default()
+                .into()
         });
 
         // Find next file callback (skips directories)
+        // Excluded files revealed or hidden again (show-excluded is already
+        // flipped), or the tree's order or layout changed: rebuild it with
+        // the same folders expanded
+        let app = self.clone();
+        self.window.on_toggle_excluded(move || app.rebuild_tree());
+        let app = self.clone();
+        self.window.on_tree_view_changed(move || app.rebuild_tree());
+
+        // Keys bound by scripts
+        let app = self.clone();
+        self.window.on_script_key(move |key| {
+            let Some(actions) = app.scripts.key(&key, app.script_state()) else {
+                return false;
+            };
+            app.apply_script_actions_when_done(actions, None);
+            true
+        });
+
         let window_weak = self.window.as_weak();
         self.window.on_find_next_file(move |current_idx, direction| {
             let window = window_weak.unwrap();
@@ -498,7 +1270,7 @@ impl App {
         let pr_comments = Rc::clone(&self.pr_comments);
         let diff_data = Rc::clone(&self.diff_data);
         let viewed_state = Rc::clone(&self.viewed_state);
-        let target_key = self.target_key.clone();
+        let target_key = Rc::clone(&self.target_key);
         self.window.on_expand_all_directories(move || {
             let window = window_weak.unwrap();
             let tree = file_tree.borrow();
@@ -511,6 +1283,7 @@ impl App {
                     state.insert(path, true);
                 }
             }
+            save_folder_state(&viewed_state, &target_key.borrow(), &expanded_state.borrow());
 
             // Re-flatten the tree
             let state = expanded_state.borrow();
@@ -520,7 +1293,7 @@ impl App {
                 &flat_entries,
                 pr_comments.borrow().as_ref(),
                 diff_data.borrow().as_ref(),
-                Some((&viewed_state.borrow(), &target_key)),
+                Some((&viewed_state.borrow(), &target_key.borrow())),
             );
 
             let files_model = Rc::new(VecModel::from(file_entries));
@@ -534,7 +1307,7 @@ impl App {
         let pr_comments = Rc::clone(&self.pr_comments);
         let diff_data = Rc::clone(&self.diff_data);
         let viewed_state = Rc::clone(&self.viewed_state);
-        let target_key = self.target_key.clone();
+        let target_key = Rc::clone(&self.target_key);
         self.window.on_collapse_all_directories(move || {
             let window = window_weak.unwrap();
             let tree = file_tree.borrow();
@@ -547,6 +1320,7 @@ impl App {
                     state.insert(path, false);
                 }
             }
+            save_folder_state(&viewed_state, &target_key.borrow(), &expanded_state.borrow());
 
             // Re-flatten the tree
             let state = expanded_state.borrow();
@@ -556,7 +1330,7 @@ impl App {
                 &flat_entries,
                 pr_comments.borrow().as_ref(),
                 diff_data.borrow().as_ref(),
-                Some((&viewed_state.borrow(), &target_key)),
+                Some((&viewed_state.borrow(), &target_key.borrow())),
             );
 
             let files_model = Rc::new(VecModel::from(file_entries));
@@ -570,7 +1344,7 @@ impl App {
         let pr_comments = Rc::clone(&self.pr_comments);
         let diff_data = Rc::clone(&self.diff_data);
         let viewed_state = Rc::clone(&self.viewed_state);
-        let target_key = self.target_key.clone();
+        let target_key = Rc::clone(&self.target_key);
         self.window.on_toggle_focused_directory(move || {
             let window = window_weak.unwrap();
             let files = window.get_files();
@@ -587,6 +1361,11 @@ impl App {
                         let is_expanded = state.get(&path).copied().unwrap_or(true);
                         state.insert(path, !is_expanded);
                     }
+                    save_folder_state(
+                        &viewed_state,
+                        &target_key.borrow(),
+                        &expanded_state.borrow(),
+                    );
 
                     // Re-flatten the tree
                     let tree = file_tree.borrow();
@@ -597,7 +1376,7 @@ impl App {
                         &flat_entries,
                         pr_comments.borrow().as_ref(),
                         diff_data.borrow().as_ref(),
-                        Some((&viewed_state.borrow(), &target_key)),
+                        Some((&viewed_state.borrow(), &target_key.borrow())),
                     );
 
                     let files_model = Rc::new(VecModel::from(file_entries));
@@ -613,7 +1392,7 @@ impl App {
         let pr_comments = Rc::clone(&self.pr_comments);
         let diff_data = Rc::clone(&self.diff_data);
         let viewed_state = Rc::clone(&self.viewed_state);
-        let target_key = self.target_key.clone();
+        let target_key = Rc::clone(&self.target_key);
         self.window.on_expand_focused_recursive(move || {
             let window = window_weak.unwrap();
             let files = window.get_files();
@@ -635,6 +1414,11 @@ impl App {
                             state.insert(p, true);
                         }
                     }
+                    save_folder_state(
+                        &viewed_state,
+                        &target_key.borrow(),
+                        &expanded_state.borrow(),
+                    );
 
                     // Re-flatten the tree
                     let state = expanded_state.borrow();
@@ -644,7 +1428,7 @@ impl App {
                         &flat_entries,
                         pr_comments.borrow().as_ref(),
                         diff_data.borrow().as_ref(),
-                        Some((&viewed_state.borrow(), &target_key)),
+                        Some((&viewed_state.borrow(), &target_key.borrow())),
                     );
 
                     let files_model = Rc::new(VecModel::from(file_entries));
@@ -656,8 +1440,9 @@ impl App {
         // Toggle viewed state callback
         let window_weak = self.window.as_weak();
         let viewed_state = Rc::clone(&self.viewed_state);
-        let target_key = self.target_key.clone();
+        let target_key = Rc::clone(&self.target_key);
         let diff_data = Rc::clone(&self.diff_data);
+        let session = Rc::clone(&self.session);
         self.window.on_toggle_viewed(move |idx| {
             let window = window_weak.unwrap();
             let files = window.get_files();
@@ -669,10 +1454,11 @@ impl App {
 
                 let path = entry.path.to_string();
                 let mut vs = viewed_state.borrow_mut();
-                let tk = &target_key;
+                let tk = target_key.borrow();
 
                 if entry.viewed {
-                    vs.set_unviewed(tk, &path);
+                    vs.set_unviewed(&tk, &path);
+                    session.borrow_mut().set_viewed(&path, None);
                 } else {
                     let data = diff_data.borrow();
                     let hash = data
@@ -680,7 +1466,9 @@ impl App {
                         .and_then(|d| d.file_hunks.get(&path))
                         .map(|h| viewed_state::hash_diff_content(h))
                         .unwrap_or(0);
-                    vs.set_viewed(tk, &path, hash);
+                    vs.set_viewed(&tk, &path, hash);
+                    let lines = changed_lines(data.as_ref(), &path);
+                    session.borrow_mut().set_viewed(&path, Some(lines));
                 }
 
                 if let Err(e) = vs.save() {
@@ -699,9 +1487,15 @@ impl App {
 
                 // If the toggled file is the one currently displayed, keep the
                 // diff header's checkbox in sync.
-                if window.get_selected_file().to_string() == path {
+                if window.get_selected_file() == path.as_str() {
                     window.set_selected_file_viewed(!entry.viewed);
                 }
+                show_review_progress(
+                    &window,
+                    &viewed_state.borrow(),
+                    diff_data.borrow().as_ref(),
+                    &tk,
+                );
             }
         });
 
@@ -709,8 +1503,9 @@ impl App {
         // the file is hidden from the tree by a collapsed ancestor).
         let window_weak = self.window.as_weak();
         let viewed_state = Rc::clone(&self.viewed_state);
-        let target_key = self.target_key.clone();
+        let target_key = Rc::clone(&self.target_key);
         let diff_data = Rc::clone(&self.diff_data);
+        let session = Rc::clone(&self.session);
         self.window.on_toggle_selected_viewed(move || {
             let window = window_weak.unwrap();
             let path = window.get_selected_file().to_string();
@@ -720,17 +1515,20 @@ impl App {
 
             let mut vs = viewed_state.borrow_mut();
             let data_borrow = diff_data.borrow();
-            let was_viewed = is_path_viewed(&path, &vs, data_borrow.as_ref(), &target_key);
+            let was_viewed = is_path_viewed(&path, &vs, data_borrow.as_ref(), &target_key.borrow());
 
             if was_viewed {
-                vs.set_unviewed(&target_key, &path);
+                vs.set_unviewed(&target_key.borrow(), &path);
+                session.borrow_mut().set_viewed(&path, None);
             } else {
                 let hash = data_borrow
                     .as_ref()
                     .and_then(|d| d.file_hunks.get(&path))
                     .map(|h| viewed_state::hash_diff_content(h))
                     .unwrap_or(0);
-                vs.set_viewed(&target_key, &path, hash);
+                vs.set_viewed(&target_key.borrow(), &path, hash);
+                let lines = changed_lines(data_borrow.as_ref(), &path);
+                session.borrow_mut().set_viewed(&path, Some(lines));
             }
 
             if let Err(e) = vs.save() {
@@ -740,6 +1538,12 @@ impl App {
             drop(data_borrow);
 
             window.set_selected_file_viewed(!was_viewed);
+            show_review_progress(
+                &window,
+                &viewed_state.borrow(),
+                diff_data.borrow().as_ref(),
+                &target_key.borrow(),
+            );
 
             // If the toggled file is currently visible in the tree, also update
             // the per-row entry so its checkbox reflects the new state.
@@ -747,7 +1551,7 @@ impl App {
             if let Some(model) = files.as_any().downcast_ref::<VecModel<FileEntry>>() {
                 for i in 0..model.row_count() {
                     if let Some(entry) = model.row_data(i) {
-                        if entry.path.to_string() == path {
+                        if entry.path == path.as_str() {
                             let mut updated = entry.clone();
                             updated.viewed = !was_viewed;
                             model.set_row_data(i, updated);
@@ -762,204 +1566,2434 @@ impl App {
     }
 
     fn load_diff(&self) -> Result<()> {
-        // Resolve the target to actual commits
-        let (base_oid, head_oid) = match &self.target {
-            DiffTarget::DefaultBranch => {
-                let default_branch = self.repo.find_default_branch()?;
-                let base = self.repo.resolve_ref(&default_branch)?;
-                let head = self.repo.head_commit()?;
-                (base, head)
-            }
-            DiffTarget::Ref(ref_name) => {
-                let base = self.repo.resolve_ref(ref_name)?;
-                let head = self.repo.head_commit()?;
-                (base, head)
-            }
-            DiffTarget::PullRequest(pr_num) => {
-                let pr_info = github::get_pr_refs(*pr_num)?;
-                let base = self.repo.resolve_ref(&pr_info.base_ref)?;
-                let head = self.repo.resolve_ref(&pr_info.head_ref)?;
+        if let DiffTarget::PullRequest(pr_num) = &*self.target.borrow() {
+            // PR data comes from gh; fetch it in the background
+            self.load_pr(*pr_num);
+            return Ok(());
+        }
+        if let DiffTarget::Mbox(path) = &*self.target.borrow() {
+            return self.load_series(path);
+        }
+        if let DiffTarget::Patchset(id) = &*self.target.borrow() {
+            // Mails come from the list; fetch them in the background
+            self.load_patchset(id.clone());
+            return Ok(());
+        }
+        let diff_data = target::diff_without_commits(&self.dir, &self.repo, &self.target.borrow())?;
+        if let Some(diff_data) = diff_data {
+            self.diff_generation.set(self.diff_generation.get() + 1);
+            return self.show_diff_data(diff_data);
+        }
 
-                // Update toolbar with PR title
-                self.window
-                    .set_diff_title(format!("PR #{}: {}", pr_num, pr_info.title).into());
+        let Some((base_oid, head_oid)) = self.target_commits()? else {
+            return Ok(());
+        };
+        self.show_diff(base_oid, head_oid)?;
+        if let DiffTarget::Log(_) = &*self.target.borrow() {
+            self.show_local_commits(base_oid, head_oid)?;
+        }
+        Ok(())
+    }
 
-                // Store refs for later commit navigation
-                *self.pr_base_ref.borrow_mut() = Some(pr_info.base_ref);
-                *self.pr_head_ref.borrow_mut() = Some(pr_info.head_ref);
+    /// Explain `error` and offer to fetch the missing history if the diff
+    /// failed in a shallow clone, whose history likely lacks the base.
+    /// Whether it did.
+    fn offer_deepen(&self, error: &anyhow::Error) -> bool {
+        if !self.repo.is_shallow() {
+            return false;
+        }
+        eprintln!("Warning: {:#}", error);
+        self.window.set_shallow_error(format!("{:#}", error).into());
+        true
+    }
 
-                // Fetch PR commits
-                match github::get_pr_commits(*pr_num) {
-                    Ok(commits) => {
-                        // Convert to UI model
-                        let commit_entries: Vec<PrCommitEntry> = commits
-                            .iter()
-                            .map(|c| PrCommitModel::from(c).into())
-                            .collect();
-                        let commits_model = Rc::new(VecModel::from(commit_entries));
-                        self.window.set_commits(ModelRc::from(commits_model));
-                        *self.pr_commits.borrow_mut() = commits;
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Could not fetch PR commits: {}", e);
-                    }
-                }
+    /// Show how many of the diff's files are marked viewed in the toolbar.
+    fn show_review_progress(&self) {
+        show_review_progress(
+            &self.window,
+            &self.viewed_state.borrow(),
+            self.diff_data.borrow().as_ref(),
+            &self.target_key.borrow(),
+        );
+    }
 
-                // Fetch PR comments
-                match github::get_pr_comments(*pr_num) {
-                    Ok(comments) => {
-                        let grouped = github::group_comments_by_file(comments.clone());
-                        *self.pr_comments.borrow_mut() = Some(grouped);
-                        *self.all_pr_comments.borrow_mut() = comments;
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Could not fetch PR comments: {}", e);
-                    }
-                }
+    /// Fetch the history the shallow clone lacks, with the target's base if
+    /// it isn't there at all, then load the diff again.
+    fn deepen_history(&self) {
+        let base = match &*self.target.borrow() {
+            DiffTarget::PullRequest(_) => self.pr_base_ref.borrow().clone(),
+            DiffTarget::Ref(base) | DiffTarget::Log(Some(base)) => Some(base.clone()),
+            DiffTarget::Refs { base, .. } | DiffTarget::MergeBase { base, .. } => {
+                Some(base.clone())
+            }
+            _ => None,
+        };
+        let missing = base.filter(|base| self.repo.resolve_ref(base).is_err());
+
+        self.window.set_deepening(true);
+        let answer = self.repo.deepen(missing.as_deref());
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            let fetched = answer.recv().await;
+            app.window.set_deepening(false);
+            match fetched {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    app.window.set_shallow_error(format!("{:#}", e).into());
+                    return;
+                }
+                Err(_) => return,
+            }
+            app.window.set_shallow_error("".into());
+            if let Err(e) = app.load_diff() {
+                if !app.offer_deepen(&e) {
+                    eprintln!("Error: Could not load the diff: {:#}", e);
+                }
+            }
+        });
+        if let Err(e) = spawned {
+            eprintln!("Warning: Could not start fetching the history: {}", e);
+            self.window.set_deepening(false);
+        }
+    }
+
+    /// Diff against `base` instead of the target's base, as if lado had been
+    /// started that way. Viewed marks are those kept for the new target.
+    fn switch_base(&self, base: &str) -> Result<()> {
+        let Some(target) = target::with_base(&self.target.borrow(), base) else {
+            return Ok(());
+        };
+        self.repo.resolve_ref(base)?;
+        self.window.set_diff_title(target::title(&self.dir, &self.repo, &target)?.into());
+        *self.target_key.borrow_mut() = viewed_state::migrated_target_key(
+            &mut self.viewed_state.borrow_mut(),
+            self.repo.workdir(),
+            &target,
+        );
+        *self.expanded_state.borrow_mut() =
+            self.viewed_state.borrow().folder_state(&self.target_key.borrow());
+        *self.target.borrow_mut() = target;
+        self.load_diff()
+    }
+
+    /// List the commits between `base` and `head` for stepping through, as
+    /// is done with a PR's commits.
+    fn show_local_commits(&self, base_oid: git2::Oid, head_oid: git2::Oid) -> Result<()> {
+        let commits = self.repo.commits_between(base_oid, head_oid)?;
+        // "All changes" diffs these again
+        *self.pr_base_ref.borrow_mut() = Some(base_oid.to_string());
+        *self.pr_head_ref.borrow_mut() = Some(head_oid.to_string());
+        self.set_commits(commits);
+        Ok(())
+    }
+
+    /// Commit the patch series in the mbox at `path` and show it.
+    fn load_series(&self, path: &Path) -> Result<()> {
+        self.show_series(&target::read_series(&self.dir.join(path))?)?;
+        Ok(())
+    }
+
+    /// Commit `series` and show it like a PR: all its changes, its patches
+    /// to step through and its cover letter. The commits of the patches.
+    fn show_series(&self, series: &Series) -> Result<Vec<git2::Oid>> {
+        let (base_oid, commits) = target::apply_series(&self.repo, series)?;
+        let head_oid = commits.last().copied().unwrap_or(base_oid);
+        self.show_diff(base_oid, head_oid)?;
+        self.show_local_commits(base_oid, head_oid)?;
+        self.set_cover_letter(series.cover_letter.as_ref());
+        Ok(commits)
+    }
+
+    fn set_cover_letter(&self, mail: Option<&Mail>) {
+        let cover_letter = mail
+            .map(|mail| CoverLetter {
+                subject: mail.subject.clone().into(),
+                from: mail.from.clone().into(),
+                text: mail.message.clone().into(),
+            })
+            .unwrap_or_default();
+        self.window.set_cover_letter(cover_letter);
+    }
+
+    /// List `commits` in the commit list, with the conventional commit types
+    /// among them to filter by. The filter box keeps filtering them.
+    fn set_commits(&self, commits: Vec<PrCommit>) {
+        let filter = self.window.get_commit_filter();
+        let commit_entries: Vec<PrCommitEntry> = commits
+            .iter()
+            .map(|c| PrCommitEntry {
+                filtered_out: !matches_filter(c, &filter),
+                ..PrCommitModel::from(c).into()
+            })
+            .collect();
+        let kinds: Vec<slint::SharedString> = CONVENTIONAL_KINDS
+            .iter()
+            .filter(|kind| commit_entries.iter().any(|c| c.kind == **kind))
+            .map(|kind| (*kind).into())
+            .collect();
+        self.window
+            .set_commits(ModelRc::from(Rc::new(VecModel::from(commit_entries))));
+        self.window
+            .set_commit_kinds(ModelRc::from(Rc::new(VecModel::from(kinds))));
+        *self.pr_commits.borrow_mut() = commits;
+    }
+
+    /// Resolve the target to the (base, head) commits to diff. `None` for a
+    /// PR whose refs haven't loaded yet, or a diff not made from commits. A
+    /// patch series (or patchset) is diffed between the commits it was
+    /// applied as.
+    fn target_commits(&self) -> Result<Option<(git2::Oid, git2::Oid)>> {
+        if !matches!(
+            *self.target.borrow(),
+            DiffTarget::PullRequest(_) | DiffTarget::Mbox(_) | DiffTarget::Patchset(_)
+        ) {
+            return target::commits(&self.dir, &self.repo, &self.target.borrow());
+        }
+        let base_ref = self.pr_base_ref.borrow();
+        let head_ref = self.pr_head_ref.borrow();
+        let (Some(base_ref), Some(head_ref)) = (base_ref.as_ref(), head_ref.as_ref()) else {
+            return Ok(None);
+        };
+        Ok(Some((
+            self.repo.resolve_ref(base_ref)?,
+            self.repo.resolve_ref(head_ref)?,
+        )))
+    }
+
+    /// The tree of those of `files` the exclude globs and the toolbar leave,
+    /// or their flat list, in the order of the tree-sort setting.
+    fn sorted_tree(&self, files: &[FileChange]) -> Vec<FileTreeNode> {
+        let settings = self.window.get_app_settings();
+        let files = tree_files(files, &self.exclude.borrow(), &self.window);
+        let mut tree = if settings.flat_list {
+            build_file_list(&files)
+        } else {
+            build_file_tree(&files)
+        };
+        let sort = TreeSort::from_name(&settings.tree_sort);
+        if sort == TreeSort::Commits && self.last_commits.borrow().is_none() {
+            let last_commits = match self.target_commits() {
+                Ok(Some((base, head))) => self.repo.last_changed(base, head),
+                Ok(None) => Ok(HashMap::new()),
+                Err(e) => Err(e),
+            };
+            *self.last_commits.borrow_mut() = Some(last_commits.unwrap_or_else(|e| {
+                tracing::debug!("{:#}", e);
+                HashMap::new()
+            }));
+        }
+        let last_commits = self.last_commits.borrow();
+        sort_file_tree(&mut tree, sort, last_commits.as_ref().unwrap_or(&HashMap::new()));
+        tree
+    }
+
+    /// Build the tree of the shown diff again, with the same folders
+    /// expanded.
+    fn rebuild_tree(&self) {
+        let data = self.diff_data.borrow();
+        let Some(data) = data.as_ref() else {
+            return;
+        };
+        let tree = self.sorted_tree(&data.files);
+        let flat_entries = flatten_tree_with_state(&tree, 0, &self.expanded_state.borrow());
+        let file_entries = build_file_entries(
+            &flat_entries,
+            self.pr_comments.borrow().as_ref(),
+            Some(data),
+            Some((&self.viewed_state.borrow(), &self.target_key.borrow())),
+        );
+        let selected = self.window.get_selected_file().to_string();
+        let focus = flat_entries
+            .iter()
+            .position(|e| e.path == selected)
+            .map_or(-1, |i| i as i32);
+        self.window.set_focused_index(focus);
+        self.window.set_files(ModelRc::from(Rc::new(VecModel::from(file_entries))));
+        *self.file_tree.borrow_mut() = tree;
+    }
+
+    /// Recompute the diff and update only what changed: files whose blobs
+    /// are unchanged keep their hunks, and the selected file keeps its place
+    /// (and its rows, so the scroll position) unless its content changed.
+    #[tracing::instrument(skip_all)]
+    fn refresh_diff(&self) -> Result<()> {
+        // A single PR commit never changes
+        if self.window.get_selected_commit_index() >= 0 {
+            return Ok(());
+        }
+        let commits = self.target_commits()?;
+        let on_disk = matches!(
+            *self.target.borrow(),
+            DiffTarget::Files { .. }
+                | DiffTarget::Dirs { .. }
+                | DiffTarget::WorkingTree
+                | DiffTarget::Staged
+        );
+        if commits.is_none() && !on_disk {
+            return Ok(());
+        }
+
+        let refreshed = self.diff_data.borrow().as_ref().map(|previous| {
+            let data = match commits {
+                Some((base_oid, head_oid)) => {
+                    self.repo.rediff_commits(base_oid, head_oid, previous)?
+                }
+                // Files on disk and uncommitted changes are simply diffed again
+                None => target::load_diff(&self.dir, &self.repo, &self.target.borrow())?,
+            };
+            let changed = data.changed_files(previous);
+            anyhow::Ok((data, changed))
+        });
+        let Some(refreshed) = refreshed else {
+            // Nothing shown yet
+            return match commits {
+                Some((base_oid, head_oid)) => self.show_diff(base_oid, head_oid),
+                None => Ok(()),
+            };
+        };
+        let (mut diff_data, changed) = refreshed?;
+        if changed.is_empty() {
+            return Ok(());
+        }
+        self.diff_generation.set(self.diff_generation.get() + 1);
+        diff_data.expand_tabs(self.window.get_app_settings().tab_width as usize);
+
+        // Rebuild the tree with the same folders expanded
+        *self.last_commits.borrow_mut() = None;
+        let tree = self.sorted_tree(&diff_data.files);
+        let flat_entries = flatten_tree_with_state(&tree, 0, &self.expanded_state.borrow());
+        let file_entries = build_file_entries(
+            &flat_entries,
+            self.pr_comments.borrow().as_ref(),
+            Some(&diff_data),
+            Some((&self.viewed_state.borrow(), &self.target_key.borrow())),
+        );
+
+        // Keep the selected file if it's still part of the diff
+        let previous_selection = self.window.get_selected_file().to_string();
+        let (focus, selected) = if diff_data.blobs.contains_key(&previous_selection) {
+            let row = flat_entries
+                .iter()
+                .position(|e| e.path == previous_selection)
+                .map_or(-1, |i| i as i32);
+            (row, previous_selection.clone())
+        } else {
+            let row = find_initial_focus_index(&file_entries);
+            let path = usize::try_from(row)
+                .ok()
+                .and_then(|i| flat_entries.get(i))
+                .map(|e| e.path.clone())
+                .unwrap_or_default();
+            (row, path)
+        };
+
+        self.window.set_files(ModelRc::from(Rc::new(VecModel::from(file_entries))));
+        self.window.set_todos(todo_entries(&diff_data));
+        self.window.set_focused_index(focus);
+        self.window.set_selected_file(selected.clone().into());
+        let viewed = is_path_viewed(
+            &selected,
+            &self.viewed_state.borrow(),
+            Some(&diff_data),
+            &self.target_key.borrow(),
+        );
+        self.window.set_selected_file_viewed(viewed);
+        self.window.set_selected_file_structural(false);
+        self.window.set_selected_file_summary(
+            item_summary(self.semantic_diff, &self.repo, &diff_data, &selected).into(),
+        );
+
+        if selected != previous_selection || changed.contains(&selected) {
+            let comments = self.pr_comments.borrow();
+            let hl = self.highlighter.borrow();
+            let mut cache = self.highlight_cache.borrow_mut();
+            let wrap = self.window.get_app_settings().line_wrap_column.max(0) as usize;
+            let (lines, lazy) = get_lines_for_file(
+                &diff_data,
+                &selected,
+                comments.as_ref(),
+                Some((&self.viewed_state.borrow(), &self.target_key.borrow())),
+                &hl,
+                &mut cache,
+                &self.large_files.borrow(),
+                wrap,
+                self.window.get_side_by_side_mode(),
+            );
+            self.window.set_lines(lines);
+            *self.lazy_highlight.borrow_mut() = lazy;
+        }
+
+        highlight_in_background(
+            &diff_data,
+            &self.highlighter.borrow(),
+            &self.highlight_cache,
+        );
+
+        *self.file_tree.borrow_mut() = tree;
+        *self.diff_data.borrow_mut() = Some(diff_data);
+        self.show_review_progress();
+        Ok(())
+    }
+
+    /// Fetch PR info, commits and comments off the UI thread and apply each
+    /// result as it arrives. The diff is shown as soon as the refs are known.
+    fn load_pr(&self, pr_num: u32) {
+        let events = pr_loader::spawn(&self.dir, pr_num);
+        let mut pending = pr_loader::ALL_LABELS.to_vec();
+        self.window
+            .set_loading_status(format!("Loading {}…", pending.join(", ")).into());
+
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            while let Ok(event) = events.recv().await {
+                pending.retain(|l| *l != event.label());
+                let status = if pending.is_empty() {
+                    String::new()
+                } else {
+                    format!("Loading {}…", pending.join(", "))
+                };
+                app.window.set_loading_status(status.into());
+                app.apply_pr_event(pr_num, event);
+            }
+            app.window.set_loading_status("".into());
+        });
+        if let Err(e) = spawned {
+            eprintln!("Warning: Could not start loading PR #{}: {}", pr_num, e);
+        }
+    }
+
+    fn apply_pr_event(&self, pr_num: u32, event: PrEvent) {
+        match event {
+            PrEvent::Info(Ok(pr_info)) => {
+                // Update toolbar with PR title
+                self.window
+                    .set_diff_title(format!("PR #{}: {}", pr_num, pr_info.title).into());
+
+                let oids = self
+                    .repo
+                    .resolve_ref(&pr_info.base_ref)
+                    .and_then(|base| Ok((base, self.repo.resolve_ref(&pr_info.head_ref)?)));
+
+                // Store refs for later commit navigation
+                *self.pr_base_ref.borrow_mut() = Some(pr_info.base_ref);
+                *self.pr_head_ref.borrow_mut() = Some(pr_info.head_ref);
+
+                if let Err(e) = oids.and_then(|(base, head)| self.show_diff(base, head)) {
+                    self.show_load_error(pr_num, &e);
+                    self.offer_deepen(&e);
+                }
+            }
+            PrEvent::Info(Err(e)) => self.show_load_error(pr_num, &e),
+            PrEvent::Commits(Ok(commits)) => self.set_commits(commits),
+            PrEvent::Commits(Err(e)) => {
+                eprintln!("Warning: Could not fetch PR commits: {}", e);
+            }
+            PrEvent::Comments(Ok(comments)) => self.show_comments(comments),
+            PrEvent::Comments(Err(e)) => {
+                eprintln!("Warning: Could not fetch PR comments: {}", e);
+            }
+        }
+    }
+
+    /// Show the review comments that arrived, those of a PR or of the
+    /// replies to a patchset.
+    fn show_comments(&self, comments: Vec<github::PrComment>) {
+        *self.all_pr_comments.borrow_mut() = plugins::loaded().filter_comments(comments);
+        self.group_comments();
+        // Single-commit views filter comments themselves; only the full
+        // PR diff needs to pick up the new comment counts and threads.
+        if self.window.get_selected_commit_index() < 0 {
+            self.refresh_comments();
+        }
+    }
+
+    /// Group the review comments and the plugins' annotations by file for
+    /// the tree and the diff.
+    fn group_comments(&self) {
+        let mut shown = self.all_pr_comments.borrow().clone();
+        shown.extend(self.annotations.borrow().iter().cloned());
+        *self.pr_comments.borrow_mut() = Some(github::group_comments_by_file(shown));
+    }
+
+    /// Have the annotation plugins annotate `data` on a worker, then show
+    /// their notes with the comments unless another diff is shown by then.
+    fn annotate_in_background(&self, data: &DiffData) {
+        // The previous diff's notes don't apply to this one
+        if !std::mem::take(&mut *self.annotations.borrow_mut()).is_empty() {
+            self.group_comments();
+        }
+        if !plugins::loaded().annotates() {
+            return;
+        }
+        let files = plugins::Plugins::annotated_files(data);
+        let (tx, rx) = async_channel::bounded(1);
+        std::thread::spawn(move || {
+            let _ = tx.send_blocking(plugins::loaded().annotate(&files));
+        });
+
+        let generation = self.diff_generation.get();
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            let Ok(annotations) = rx.recv().await else {
+                return;
+            };
+            if annotations.is_empty() || app.diff_generation.get() != generation {
+                return;
+            }
+            *app.annotations.borrow_mut() = annotations;
+            app.group_comments();
+            // Single-commit views group their comments themselves
+            if app.window.get_selected_commit_index() < 0 {
+                app.refresh_comments();
+            }
+        });
+        if let Err(e) = spawned {
+            eprintln!("Warning: Could not start the annotation plugins: {}", e);
+        }
+    }
+
+    /// The shown diff as scripts see it.
+    fn script_state(&self) -> scripting::ScriptState {
+        script_state(&self.window, self.diff_data.borrow().as_ref(), &self.target.borrow())
+    }
+
+    /// Carry out what scripts ask for once they are done on their thread;
+    /// for the diff of `generation`, only while it is still shown.
+    fn apply_script_actions_when_done(
+        &self,
+        actions: async_channel::Receiver<Vec<scripting::Action>>,
+        generation: Option<u64>,
+    ) {
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            let Ok(actions) = actions.recv().await else {
+                return;
+            };
+            if generation.is_none_or(|generation| app.diff_generation.get() == generation) {
+                apply_script_actions(&app.window, &app.exclude, actions);
+            }
+        });
+        if let Err(e) = spawned {
+            eprintln!("Warning: Could not run the scripts: {}", e);
+        }
+    }
+
+    /// Fetch the Sourcehut patchset `id` in the background and show it as a
+    /// patch series, then the inline comments of the replies reviewing it.
+    fn load_patchset(&self, id: PatchsetId) {
+        let events = pr_loader::spawn_patchset(id.clone());
+        self.window.set_loading_status("Loading patchset…".into());
+        let label = id.to_string();
+
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            // The series and the commits it was applied as, for the replies
+            let mut applied = None;
+            while let Ok(event) = events.recv().await {
+                match event {
+                    PatchsetEvent::Series(Ok(series)) => {
+                        let subject = series
+                            .cover_letter
+                            .as_ref()
+                            .or(series.patches.first())
+                            .map(|mail| mail.subject.as_str())
+                            .unwrap_or_default();
+                        app.window
+                            .set_diff_title(format!("Patchset {}: {}", id, subject).into());
+                        app.window.set_loading_status("Loading replies…".into());
+                        match app.show_series(&series) {
+                            Ok(commits) => applied = Some((series, commits)),
+                            Err(e) => app.show_patchset_error(&id, &e),
+                        }
+                    }
+                    PatchsetEvent::Series(Err(e)) => app.show_patchset_error(&id, &e),
+                    PatchsetEvent::Replies(Ok((replies, cover_letter))) => {
+                        let Some((series, commits)) = &applied else {
+                            continue;
+                        };
+                        app.set_cover_letter(cover_letter.as_ref());
+                        let shas: Vec<String> = commits.iter().map(|c| c.to_string()).collect();
+                        app.show_comments(sourcehut::review_comments(series, &replies, &shas));
+                    }
+                    PatchsetEvent::Replies(Err(e)) => {
+                        eprintln!("Warning: Could not fetch the replies to patchset {}: {:#}", id, e);
+                    }
+                }
+            }
+            app.window.set_loading_status("".into());
+        });
+        if let Err(e) = spawned {
+            eprintln!("Warning: Could not start loading patchset {}: {}", label, e);
+        }
+    }
+
+    fn show_patchset_error(&self, id: &PatchsetId, error: &anyhow::Error) {
+        eprintln!("Error: Could not load patchset {}: {:#}", id, error);
+        self.window
+            .set_diff_title(format!("Patchset {}: failed to load", id).into());
+    }
+
+    fn show_load_error(&self, pr_num: u32, error: &anyhow::Error) {
+        eprintln!("Error: Could not load PR #{}: {:#}", pr_num, error);
+        self.window
+            .set_diff_title(format!("PR #{}: failed to load", pr_num).into());
+    }
+
+    /// Re-render comment counts in the file tree and the comment threads of
+    /// the selected file after PR comments arrive.
+    fn refresh_comments(&self) {
+        self.refresh_file_entries();
+        self.render_selected_file();
+    }
+
+    /// Rebuild the file tree rows (comment counts, viewed marks) from the
+    /// current diff.
+    fn refresh_file_entries(&self) {
+        let data_borrow = self.diff_data.borrow();
+        let Some(ref data) = *data_borrow else {
+            // Diff not shown yet; it will pick up the comments when it is
+            return;
+        };
+
+        let tree = self.file_tree.borrow();
+        let flat_entries = flatten_tree_with_state(&tree, 0, &self.expanded_state.borrow());
+        let file_entries = build_file_entries(
+            &flat_entries,
+            self.pr_comments.borrow().as_ref(),
+            Some(data),
+            Some((&self.viewed_state.borrow(), &self.target_key.borrow())),
+        );
+        // Rows are unchanged, so focused-index stays valid
+        let files_model = Rc::new(VecModel::from(file_entries));
+        self.window.set_files(ModelRc::from(files_model));
+    }
+
+    /// Render the selected file's rows from the current diff.
+    fn render_selected_file(&self) {
+        let data_borrow = self.diff_data.borrow();
+        let Some(ref data) = *data_borrow else {
+            return;
+        };
+
+        let selected = self.window.get_selected_file().to_string();
+        if !selected.is_empty() {
+            let comments = self.pr_comments.borrow();
+            let hl = self.highlighter.borrow();
+            let mut cache = self.highlight_cache.borrow_mut();
+            let wrap = self.window.get_app_settings().line_wrap_column.max(0) as usize;
+            let (lines, lazy) = get_lines_for_file(
+                data,
+                &selected,
+                comments.as_ref(),
+                Some((&self.viewed_state.borrow(), &self.target_key.borrow())),
+                &hl,
+                &mut cache,
+                &self.large_files.borrow(),
+                wrap,
+                self.window.get_side_by_side_mode(),
+            );
+            self.window.set_lines(lines);
+            *self.lazy_highlight.borrow_mut() = lazy;
+        }
+    }
+
+    /// Diff the commits selected in the commit list, `from` to `to`, on
+    /// worker threads as `show_diff` does, then show it with
+    /// `grouped_comments`. Selecting again meanwhile drops this diff.
+    fn show_commit_diff(&self, from: git2::Oid, to: git2::Oid, grouped_comments: FileComments) {
+        // Stop filling in a diff that's still loading
+        self.diff_generation.set(self.diff_generation.get() + 1);
+        let generation = self.diff_generation.get();
+
+        let changes = self.repo.changed_files_in_background(from, to);
+        self.window.set_loading_status("Listing changed files…".into());
+        self.window.set_loading_progress(-1.0);
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            let Ok(changes) = changes.recv().await else {
+                return;
+            };
+            if app.diff_generation.get() != generation {
+                return;
+            }
+            let diff = match changes {
+                Ok(changes) => app.collect_file_diffs(changes).await,
+                Err(e) => Err(e),
+            };
+            if app.diff_generation.get() != generation {
+                return;
+            }
+            app.finish_loading();
+            match diff {
+                Ok(Some(data)) => app.show_selected_commits(data, Some(grouped_comments)),
+                Ok(None) => {}
+                Err(e) => eprintln!("Error: Could not diff the selected commits: {:#}", e),
+            }
+        });
+        if let Err(e) = spawned {
+            self.finish_loading();
+            eprintln!("Error: Could not start diffing the selected commits: {}", e);
+        }
+    }
+
+    /// Populate the file tree and the initial diff view with the diff of
+    /// commits selected in the commit list.
+    fn show_selected_commits(
+        &self,
+        mut diff_data: DiffData,
+        grouped_comments: Option<FileComments>,
+    ) {
+        let window = &self.window;
+        diff_data.expand_tabs(window.get_app_settings().tab_width as usize);
+        // Build hierarchical file tree and flatten for UI
+        // Use empty expanded state for commit-specific views (fresh view each time)
+        let tree = self.sorted_tree(&diff_data.files);
+        let flat_entries = flatten_tree_with_state(&tree, 0, &HashMap::new());
+
+        let file_entries =
+            build_file_entries(&flat_entries, grouped_comments.as_ref(), Some(&diff_data), None);
+
+        let initial_focus = find_initial_focus_index(&file_entries);
+        let initial_viewed = if initial_focus >= 0 {
+            file_entries
+                .get(initial_focus as usize)
+                .map(|e| e.viewed)
+                .unwrap_or(false)
+        } else {
+            false
+        };
+
+        let files_model = Rc::new(VecModel::from(file_entries));
+        window.set_files(ModelRc::from(files_model));
+        window.set_todos(todo_entries(&diff_data));
+
+        if initial_focus >= 0 {
+            if let Some(initial) = flat_entries.get(initial_focus as usize) {
+                window.set_focused_index(initial_focus);
+                window.set_selected_file(initial.path.clone().into());
+                window.set_selected_file_viewed(initial_viewed);
+                window.set_selected_file_structural(false);
+                window.set_selected_file_summary(
+                    item_summary(self.semantic_diff, &self.repo, &diff_data, &initial.path).into(),
+                );
+                let hl = self.highlighter.borrow();
+                let mut cache = self.highlight_cache.borrow_mut();
+                let wrap = window.get_app_settings().line_wrap_column.max(0) as usize;
+                let (lines, lazy) = get_lines_for_file(
+                    &diff_data,
+                    &initial.path,
+                    grouped_comments.as_ref(),
+                    Some((&self.viewed_state.borrow(), &self.target_key.borrow())),
+                    &hl,
+                    &mut cache,
+                    &self.large_files.borrow(),
+                    wrap,
+                    window.get_side_by_side_mode(),
+                );
+                window.set_lines(lines);
+                *self.lazy_highlight.borrow_mut() = lazy;
+            }
+        }
+    }
+
+    /// Compute the diff between two commits and populate the file tree and
+    /// the initial diff view. Git runs on worker threads, so the window
+    /// stays responsive while the diff loads.
+    #[tracing::instrument(skip_all)]
+    fn show_diff(&self, base_oid: git2::Oid, head_oid: git2::Oid) -> Result<()> {
+        // Anything still loading for the previous diff is stale now
+        self.diff_generation.set(self.diff_generation.get() + 1);
+        let generation = self.diff_generation.get();
+
+        let changes = self.repo.changed_files_in_background(base_oid, head_oid);
+        self.window.set_loading_status("Listing changed files…".into());
+        self.window.set_loading_progress(-1.0);
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            let Ok(changes) = changes.recv().await else {
+                return;
+            };
+            if app.diff_generation.get() != generation {
+                return;
+            }
+            let shown = match changes {
+                Ok(changes) if changes.len() > PROGRESSIVE_FILES => {
+                    app.show_diff_progressively(changes)
+                }
+                Ok(changes) => app.diff_in_background(changes).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = shown {
+                app.finish_loading();
+                if !app.offer_deepen(&e) {
+                    eprintln!("Error: Could not load the diff: {:#}", e);
+                }
+            }
+        });
+        if let Err(e) = spawned {
+            self.finish_loading();
+            return Err(anyhow!("Could not start loading the diff: {}", e));
+        }
+        Ok(())
+    }
+
+    /// Diff the contents of `changes` on worker threads, then show them all
+    /// at once.
+    async fn diff_in_background(&self, changes: Vec<FileDelta>) -> Result<()> {
+        let Some(diff_data) = self.collect_file_diffs(changes).await? else {
+            return Ok(());
+        };
+        self.finish_loading();
+        self.show_diff_data(diff_data)
+    }
+
+    /// Diff the contents of `changes` on worker threads, showing progress.
+    /// `None` if another diff was asked for meanwhile.
+    async fn collect_file_diffs(&self, changes: Vec<FileDelta>) -> Result<Option<DiffData>> {
+        let generation = self.diff_generation.get();
+        let total = changes.len();
+        let mut diffs: Vec<Option<FileDiff>> = (0..total).map(|_| None).collect();
+        let positions: HashMap<String, usize> = changes
+            .iter()
+            .enumerate()
+            .map(|(i, change)| (change.path.clone(), i))
+            .collect();
+
+        let results = self.repo.stream_file_diffs(changes);
+        let mut done = 0;
+        while let Ok(result) = results.recv().await {
+            if self.diff_generation.get() != generation {
+                return Ok(None);
+            }
+            let file_diff = result?;
+            if let Some(&index) = positions.get(&file_diff.file.path) {
+                diffs[index] = Some(file_diff);
+            }
+            done += 1;
+            self.show_progress("Diffing files…", done, total);
+        }
+
+        let mut diff_data = DiffData {
+            files: Vec::with_capacity(total),
+            file_hunks: HashMap::new(),
+            blobs: HashMap::new(),
+            structural_hunks: HashMap::new(),
+            split_renames: HashMap::new(),
+        };
+        for file_diff in diffs.into_iter().flatten() {
+            diff_data.add_file(file_diff);
+        }
+        Ok(Some(diff_data))
+    }
+
+    /// Show how far loading `what` has got: `done` of `total` items.
+    fn show_progress(&self, what: &str, done: usize, total: usize) {
+        self.window
+            .set_loading_status(format!("{} {}/{}", what, done, total).into());
+        self.window
+            .set_loading_progress(done as f32 / total.max(1) as f32);
+    }
+
+    /// Clear the loading status and progress bar.
+    fn finish_loading(&self) {
+        self.window.set_loading_status("".into());
+        self.window.set_loading_progress(-1.0);
+    }
+
+    /// Populate the file tree and the initial diff view.
+    fn show_diff_data(&self, mut diff_data: DiffData) -> Result<()> {
+        diff_data.expand_tabs(self.window.get_app_settings().tab_width as usize);
+
+        // Annotation plugins add their notes next to the PR's comments
+        self.annotate_in_background(&diff_data);
+
+        // Build hierarchical file tree and flatten for UI
+        *self.last_commits.borrow_mut() = None;
+        let tree = self.sorted_tree(&diff_data.files);
+        // Folders of nothing but generated files start collapsed
+        for folder in generated_folders(&diff_data.files) {
+            self.expanded_state.borrow_mut().entry(folder).or_insert(false);
+        }
+        let expanded_state = self.expanded_state.borrow();
+        let flat_entries = flatten_tree_with_state(&tree, 0, &expanded_state);
+        drop(expanded_state);
+
+        let file_entries = build_file_entries(
+            &flat_entries,
+            self.pr_comments.borrow().as_ref(),
+            Some(&diff_data),
+            Some((&self.viewed_state.borrow(), &self.target_key.borrow())),
+        );
+
+        // Pick the initial focus row before moving file_entries into the model.
+        let initial_focus = self.initial_focus_index(&file_entries)?;
+
+        let files_model = Rc::new(VecModel::from(file_entries));
+        self.window.set_files(ModelRc::from(files_model));
+        self.window.set_todos(todo_entries(&diff_data));
+
+        // Load the diff for the initial focus row and keep focused-index in sync
+        // with selected-file so the header "viewed" state is driven by the same row.
+        if initial_focus >= 0 {
+            if let Some(initial) = flat_entries.get(initial_focus as usize) {
+                self.window.set_focused_index(initial_focus);
+                self.window.set_selected_file(initial.path.clone().into());
+                let viewed = is_path_viewed(
+                    &initial.path,
+                    &self.viewed_state.borrow(),
+                    Some(&diff_data),
+                    &self.target_key.borrow(),
+                );
+                self.window.set_selected_file_viewed(viewed);
+                self.window.set_selected_file_structural(false);
+                self.window.set_selected_file_summary(
+                    item_summary(self.semantic_diff, &self.repo, &diff_data, &initial.path).into(),
+                );
+                let comments = self.pr_comments.borrow();
+                let hl = self.highlighter.borrow();
+                let mut cache = self.highlight_cache.borrow_mut();
+                let wrap = self.window.get_app_settings().line_wrap_column.max(0) as usize;
+                let (lines, lazy) = get_lines_for_file(
+                    &diff_data,
+                    &initial.path,
+                    comments.as_ref(),
+                    Some((&self.viewed_state.borrow(), &self.target_key.borrow())),
+                    &hl,
+                    &mut cache,
+                    &self.large_files.borrow(),
+                    wrap,
+                    self.window.get_side_by_side_mode(),
+                );
+                self.window.set_lines(lines);
+                *self.lazy_highlight.borrow_mut() = lazy;
+                self.scroll_to_open_line();
+            }
+        }
+
+        // Highlight the remaining files ahead of time so switching is instant
+        highlight_in_background(
+            &diff_data,
+            &self.highlighter.borrow(),
+            &self.highlight_cache,
+        );
+
+        // Store for later use in callbacks
+        *self.file_tree.borrow_mut() = tree;
+        *self.diff_data.borrow_mut() = Some(diff_data);
+        self.show_review_progress();
+
+        if let Some(actions) = self.scripts.on_open(self.script_state()) {
+            self.apply_script_actions_when_done(actions, Some(self.diff_generation.get()));
+        }
+
+        Ok(())
+    }
+
+    /// Show the file tree of a huge diff right away and fill in file contents
+    /// (line counts, viewed marks, the selected file) as workers diff them.
+    fn show_diff_progressively(&self, changes: Vec<FileDelta>) -> Result<()> {
+        let tab_width = self.window.get_app_settings().tab_width as usize;
+        let positions: HashMap<String, usize> = changes
+            .iter()
+            .enumerate()
+            .map(|(i, change)| (change.path.clone(), i))
+            .collect();
+        let diff_data = DiffData::listed(&changes);
+
+        *self.last_commits.borrow_mut() = None;
+        let tree = self.sorted_tree(&diff_data.files);
+        let flat_entries = flatten_tree_with_state(&tree, 0, &self.expanded_state.borrow());
+        let file_entries = build_file_entries(
+            &flat_entries,
+            self.pr_comments.borrow().as_ref(),
+            Some(&diff_data),
+            Some((&self.viewed_state.borrow(), &self.target_key.borrow())),
+        );
+        // Viewed marks need file contents, so this is simply the first file
+        // (unless `--file` asks for another)
+        let initial_focus = self.initial_focus_index(&file_entries)?;
+        let initial = usize::try_from(initial_focus)
+            .ok()
+            .and_then(|i| flat_entries.get(i))
+            .map(|e| e.path.clone())
+            .unwrap_or_default();
+
+        self.window.set_files(ModelRc::from(Rc::new(VecModel::from(file_entries))));
+        self.window.set_todos(ModelRc::default());
+        self.window.set_focused_index(initial_focus);
+        self.window.set_selected_file(initial.into());
+        self.window.set_selected_file_viewed(false);
+        self.window.set_selected_file_structural(false);
+        self.window.set_selected_file_summary("".into());
+        self.window.set_lines(ModelRc::default());
+        *self.lazy_highlight.borrow_mut() = None;
+        *self.file_tree.borrow_mut() = tree;
+        *self.diff_data.borrow_mut() = Some(diff_data);
+
+        let total = changes.len();
+        let results = self.repo.stream_file_diffs(changes);
+        let generation = self.diff_generation.get();
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            let mut done = 0;
+            while let Ok(first) = results.recv().await {
+                if app.diff_generation.get() != generation {
+                    return;
+                }
+
+                // Take in everything that's ready, then update the view once
+                let selected = app.window.get_selected_file();
+                let mut selected_arrived = false;
+                if let Some(data) = app.diff_data.borrow_mut().as_mut() {
+                    let ready = std::iter::from_fn(|| results.try_recv().ok());
+                    for result in std::iter::once(first).chain(ready) {
+                        done += 1;
+                        match result {
+                            Ok(mut file_diff) => {
+                                file_diff.expand_tabs(tab_width);
+                                selected_arrived |= file_diff.file.path == selected.as_str();
+                                if let Some(&index) = positions.get(&file_diff.file.path) {
+                                    data.set_file(index, file_diff);
+                                }
+                            }
+                            Err(e) => eprintln!("Warning: {}", e),
+                        }
+                    }
+                }
+
+                app.show_progress("Diffing files…", done, total);
+                app.refresh_file_entries();
+                if selected_arrived {
+                    app.render_selected_file();
+                    app.scroll_to_open_line();
+                    let data = app.diff_data.borrow();
+                    let viewed = is_path_viewed(
+                        &selected,
+                        &app.viewed_state.borrow(),
+                        data.as_ref(),
+                        &app.target_key.borrow(),
+                    );
+                    app.window.set_selected_file_viewed(viewed);
+                    if let Some(data) = data.as_ref() {
+                        let summary = item_summary(app.semantic_diff, &app.repo, data, &selected);
+                        app.window.set_selected_file_summary(summary.into());
+                    }
+                }
+            }
+
+            if app.diff_generation.get() == generation {
+                app.finish_loading();
+                app.show_review_progress();
+                if let Some(data) = app.diff_data.borrow().as_ref() {
+                    app.window.set_todos(todo_entries(data));
+                    highlight_in_background(data, &app.highlighter.borrow(), &app.highlight_cache);
+                }
+            }
+        });
+        if let Err(e) = spawned {
+            eprintln!("Warning: Could not start diffing files: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Scroll to the `--line` from the command line, once its file is shown.
+    fn scroll_to_open_line(&self) {
+        let selected = self.window.get_selected_file();
+        let mut open_line = self.open_line.borrow_mut();
+        let Some(line) = open_line
+            .take_if(|(file, _)| selected == file.as_str())
+            .map(|(_, line)| line)
+        else {
+            return;
+        };
+
+        // The line itself, or the next one shown if it's outside the hunks
+        let lines = self.window.get_lines();
+        let Some(row) = lines
+            .iter()
+            .position(|l| l.new_line_num.parse::<u32>().is_ok_and(|n| n >= line))
+        else {
+            return;
+        };
+        // Keep a few lines of context above it
+        let top = self.row_offsets().offset(row.saturating_sub(3));
+        self.window.set_diff_scroll_to_file(selected);
+        self.window.set_diff_scroll_to(top);
+    }
+
+    /// Row to focus when a diff is shown: the `--file` from the command line
+    /// the first time, the first unviewed file otherwise.
+    fn initial_focus_index(&self, entries: &[FileEntry]) -> Result<i32> {
+        match self.open_file.borrow_mut().take() {
+            Some(path) => find_file_index(entries, &path),
+            None => Ok(find_initial_focus_index(entries)),
+        }
+    }
+
+    /// Show `path` and scroll to `line` of its new side, as `goto` of the
+    /// editor API does.
+    pub fn goto(&self, path: &str, line: Option<u32>) -> Result<()> {
+        let in_diff = self
+            .diff_data
+            .borrow()
+            .as_ref()
+            .is_some_and(|data| data.files.iter().any(|f| f.path == path));
+        if !in_diff {
+            return Err(anyhow!("{} is not part of this diff", path));
+        }
+        // Folders collapsed above the file keep the focus where it was
+        let entries: Vec<FileEntry> = self.window.get_files().iter().collect();
+        if let Ok(row) = find_file_index(&entries, path) {
+            self.window.set_focused_index(row);
+        }
+        *self.open_line.borrow_mut() = line.map(|line| (path.to_string(), line));
+        self.window.set_selected_file(path.into());
+        self.window.invoke_file_selected(path.into());
+        self.scroll_to_open_line();
+        Ok(())
+    }
+
+    /// Show the unchanged lines folded into `row` of the file shown.
+    fn unfold(&self, row: i32) -> Result<()> {
+        let lines = self.window.get_lines();
+        let Ok(row) = usize::try_from(row) else {
+            return Ok(());
+        };
+        // Fold rows are in the order of the folds
+        let index = (0..row)
+            .filter(|&r| lines.row_data(r).is_some_and(|l| l.line_type == "fold"))
+            .count();
+        let path = self.window.get_selected_file().to_string();
+        {
+            let mut data_borrow = self.diff_data.borrow_mut();
+            let Some(data) = data_borrow.as_mut() else {
+                return Ok(());
+            };
+            let Some(fold) = file_folds(data, &path).get(index).copied() else {
+                return Ok(());
+            };
+            let Some(&(_, Some(new))) = data.blobs.get(&path) else {
+                return Ok(());
+            };
+            let tab = " ".repeat(self.window.get_app_settings().tab_width as usize);
+            let bytes = self.repo.blob_content(new)?;
+            let text = encoding::decode(&bytes, file_encoding(data, &path)).replace('\t', &tab);
+            if let Some(hunks) = data.file_hunks.get_mut(&path) {
+                folding::unfold(hunks, &fold, &text);
+            }
+        }
+        self.render_selected_file();
+        Ok(())
+    }
+
+    /// Search the lines of all files of the diff for `query`, and show the
+    /// first match. An empty query ends the search.
+    fn search(&self, query: &str) {
+        let mut search = self.search.borrow_mut();
+        search.current = None;
+        search.hits = if query.is_empty() {
+            Vec::new()
+        } else {
+            let data = self.diff_data.borrow();
+            let Some(data) = data.as_ref() else {
+                return;
+            };
+            // Indexed again once the diff changed or more of it was diffed
+            let generation = self.diff_generation.get();
+            let diffed = data.file_hunks.len();
+            let index = match search.index.take() {
+                Some((g, d, index)) if (g, d) == (generation, diffed) => index,
+                _ => SearchIndex::new(data),
+            };
+            let hits = index.search(query);
+            search.index = Some((generation, diffed, index));
+            hits
+        };
+        let found = !search.hits.is_empty();
+        self.window.set_search_count(search.hits.len() as i32);
+        self.window.set_search_current(0);
+        self.window.set_search_generation(self.window.get_search_generation() + 1);
+        drop(search);
+        if found {
+            self.step_search(1);
+        }
+    }
+
+    /// Show the next match of the search (`delta` 1), or the previous one
+    /// (-1), going round at the ends.
+    fn step_search(&self, delta: i32) {
+        let mut search = self.search.borrow_mut();
+        let count = search.hits.len();
+        if count == 0 {
+            return;
+        }
+        let current = match search.current {
+            Some(current) => (current + count).wrapping_add_signed(delta as isize) % count,
+            None if delta < 0 => count - 1,
+            None => 0,
+        };
+        search.current = Some(current);
+        let hit = search.hits[current].clone();
+        drop(search);
+
+        self.window.set_search_current(current as i32 + 1);
+        if self.window.get_selected_file() != hit.path.as_str() {
+            if let Err(e) = self.goto(&hit.path, None) {
+                eprintln!("Warning: {:#}", e);
+                return;
+            }
+        }
+        // A new file's rows drop the selection once they are shown, so the
+        // match is selected after that
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            next_event_loop_turn().await;
+            app.select_search_hit(&hit);
+        });
+        if let Err(e) = spawned {
+            eprintln!("Warning: Could not show the match: {}", e);
+        }
+    }
+
+    /// Select the match `hit` in the rows shown and scroll to it.
+    fn select_search_hit(&self, hit: &SearchHit) {
+        if self.window.get_selected_file() != hit.path.as_str() {
+            return;
+        }
+        let lines = self.window.get_lines();
+        let Some(selection) = Selection::of_match(&lines, hit) else {
+            return;
+        };
+        self.window.set_diff_selection(Selection::shown(Some(selection)));
+        // Keep a few lines of context above it
+        let top = self.row_offsets().offset(selection.first_row().saturating_sub(3));
+        self.window.set_diff_scroll_to_file(hit.path.as_str().into());
+        self.window.set_diff_scroll_to(top);
+    }
+
+    /// Open the composer for a review comment on the line of `row` of the
+    /// file shown, or for a reply to the comment there. Comments go on the
+    /// lines of a PR's whole diff, so only that can be commented on.
+    fn compose_comment(&self, row: i32) {
+        let flash = |text| self.flash_status(text, std::time::Duration::from_secs(3));
+        if !matches!(*self.target.borrow(), DiffTarget::PullRequest(_)) {
+            flash("Only pull requests can be commented on");
+            return;
+        }
+        if self.window.get_selected_commit_index() >= 0 {
+            flash("Show all changes to comment on them");
+            return;
+        }
+        let path = self.window.get_selected_file().to_string();
+        let target = usize::try_from(row)
+            .ok()
+            .and_then(|row| {
+                let side = self.pressed_side.get();
+                ComposeTarget::at(&self.window.get_lines(), row, &path, side)
+            });
+        let Some(target) = target else {
+            flash("Click a line or a comment to comment on it");
+            return;
+        };
+        self.window.set_composer_title(target.title().into());
+        self.window.set_composer_error("".into());
+        *self.compose_target.borrow_mut() = Some(target);
+    }
+
+    /// Post the comment written in the composer, then show it under its
+    /// line and close the composer; on failure it stays open with the error.
+    fn post_comment(&self, body: String) {
+        let DiffTarget::PullRequest(pr) = *self.target.borrow() else {
+            return;
+        };
+        let Some(target) = self.compose_target.borrow().clone() else {
+            return;
+        };
+        let head = self
+            .pr_head_ref
+            .borrow()
+            .as_deref()
+            .map(|head| self.repo.resolve_ref(head));
+        let head = match head {
+            Some(Ok(oid)) => oid.to_string(),
+            Some(Err(e)) => {
+                self.window.set_composer_error(format!("{:#}", e).into());
+                return;
+            }
+            None => {
+                let error = "The pull request hasn't loaded yet";
+                self.window.set_composer_error(error.into());
+                return;
+            }
+        };
+
+        self.window.set_composer_posting(true);
+        self.window.set_composer_error("".into());
+        let posted = composer::post(self.dir.to_path_buf(), pr, head, target, body);
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            let posted = posted.recv().await.unwrap_or_else(|e| Err(e.into()));
+            app.window.set_composer_posting(false);
+            match posted {
+                Ok(comment) => {
+                    let mut comments = app.all_pr_comments.borrow().clone();
+                    comments.push(comment);
+                    app.show_comments(comments);
+                    *app.compose_target.borrow_mut() = None;
+                    app.window.set_composer_title("".into());
+                }
+                Err(e) => app.window.set_composer_error(format!("{:#}", e).into()),
+            }
+        });
+        if let Err(e) = spawned {
+            self.window.set_composer_posting(false);
+            eprintln!("Warning: Could not post the comment: {}", e);
+        }
+    }
+
+    /// Mark the hunk whose header is `row` of the file shown as reviewed, or
+    /// not reviewed any more, and dim or undim its rows.
+    fn toggle_hunk_reviewed(&self, row: i32) {
+        let lines = self.window.get_lines();
+        let Ok(row) = usize::try_from(row) else {
+            return;
+        };
+        let Some(header) = lines.row_data(row).filter(|l| l.line_type == "hunk") else {
+            return;
+        };
+        // Hunk headers are in the order of the hunks
+        let index = (0..row)
+            .filter(|&r| lines.row_data(r).is_some_and(|l| l.line_type == "hunk"))
+            .count();
+        let path = self.window.get_selected_file().to_string();
+        let Some(hash) = self.diff_data.borrow().as_ref().and_then(|data| {
+            data.shown_hunks(&path).get(index).map(viewed_state::hash_hunk)
+        }) else {
+            return;
+        };
+
+        let reviewed = !header.reviewed;
+        let mut vs = self.viewed_state.borrow_mut();
+        vs.set_hunk_reviewed(&self.target_key.borrow(), &path, hash, reviewed);
+        if let Err(e) = vs.save() {
+            eprintln!("Warning: Could not save viewed state: {}", e);
+        }
+
+        // The hunk's rows run up to the next hunk or fold
+        for r in row..lines.row_count() {
+            let Some(mut line) = lines.row_data(r) else {
+                break;
+            };
+            if r > row && matches!(line.line_type.as_str(), "hunk" | "fold") {
+                break;
+            }
+            if line.line_type != "comment" {
+                line.reviewed = reviewed;
+                lines.set_row_data(r, line);
+            }
+        }
+    }
+
+    /// Label the hunk headers of the file's diff with the commits each hunk
+    /// comes from, when the pointer first goes over one of them. Only the
+    /// diff of all changes has more than one commit to tell apart.
+    fn hunk_hovered(&self, row: i32) {
+        if self.window.get_selected_commit_index() >= 0 {
+            return;
+        }
+        let lines = self.window.get_lines();
+        let hovered = usize::try_from(row).ok().and_then(|row| lines.row_data(row));
+        if !hovered.is_some_and(|line| line.line_type == "hunk" && line.hunk_commits.is_empty()) {
+            return;
+        }
+        let Ok(Some((base, head))) = self.target_commits() else {
+            return;
+        };
+        let path = self.window.get_selected_file().to_string();
+        let found = match self.diff_data.borrow().as_ref() {
+            Some(data) => self
+                .repo
+                .hunk_commits(base, head, &path, data.shown_hunks(&path)),
+            None => return,
+        };
+        let hunk_commits = match found {
+            Ok(hunk_commits) => hunk_commits,
+            Err(e) => {
+                tracing::debug!("Could not find the commits of {}'s hunks: {:#}", path, e);
+                return;
+            }
+        };
+
+        let pr_commits = self.pr_commits.borrow();
+        let label = |oid: &git2::Oid| {
+            let sha = oid.to_string();
+            match pr_commits.iter().find(|commit| commit.sha == sha) {
+                Some(commit) => format!(
+                    "{} {}",
+                    commit.short_sha,
+                    commit.message.lines().next().unwrap_or_default()
+                ),
+                None => sha[..7].to_string(),
+            }
+        };
+        // Hunk headers are in the order of the hunks
+        let headers = (0..lines.row_count())
+            .filter(|&r| lines.row_data(r).is_some_and(|l| l.line_type == "hunk"));
+        for (r, commits) in headers.zip(hunk_commits) {
+            if let Some(mut line) = lines.row_data(r) {
+                let labels: Vec<String> = commits.iter().map(label).collect();
+                line.hunk_commits = labels.join("; ").into();
+                lines.set_row_data(r, line);
+            }
+        }
+    }
+
+    /// Header row of the hunk shown at vertical offset `offset` of the
+    /// file's diff; -1 if there is none.
+    fn hunk_at(&self, offset: f32) -> i32 {
+        let lines = self.window.get_lines();
+        let top = self.row_offsets().row_at(offset);
+        let is_header = |r: &usize| lines.row_data(*r).is_some_and(|l| l.line_type == "hunk");
+        // The hunk the top row belongs to, or the first below it
+        (0..=top)
+            .rev()
+            .find(is_header)
+            .or_else(|| (top..lines.row_count()).find(is_header))
+            .map_or(-1, |row| row as i32)
+    }
+
+    /// Offset of the next hunk not marked reviewed below `offset` in the
+    /// file's diff; -1 if all of them are.
+    fn next_unreviewed_hunk(&self, offset: f32) -> f32 {
+        let lines = self.window.get_lines();
+        let offsets = self.row_offsets();
+        // Rounding of the row heights shouldn't find the same hunk again
+        let below = offsets.row_at(offset + 1.0);
+        (below..lines.row_count())
+            .map(|row| (row, offsets.offset(row)))
+            .find(|&(row, top)| {
+                top > offset + 1.0
+                    && lines.row_data(row).is_some_and(|l| l.line_type == "hunk" && !l.reviewed)
+            })
+            .map_or(-1.0, |(_, top)| top)
+    }
+
+    /// Where the rows of the file shown are, worked out again only once its
+    /// rows or their heights change
+    fn row_offsets(&self) -> Ref<'_, RowOffsets> {
+        let lines = self.window.get_lines();
+        let font_size = self.window.get_app_settings().font_size as f32;
+        let heights = RowHeights::of(&self.window, font_size);
+        if !self.row_offsets.borrow().are_of(&lines, heights) {
+            *self.row_offsets.borrow_mut() = RowOffsets::new(lines, heights);
+        }
+        self.row_offsets.borrow()
+    }
+
+    /// Show the next file after the selected one that isn't marked viewed,
+    /// from the top again past the last, expanding the folders it is in.
+    /// Files the tree filters out are skipped, and so are generated ones.
+    fn next_unviewed_file(&self) {
+        let paths = collect_file_paths(&self.file_tree.borrow());
+        let selected = self.window.get_selected_file();
+        let start = paths.iter().position(|p| selected == *p).map_or(0, |i| i + 1);
+        let next = {
+            let data = self.diff_data.borrow();
+            let viewed = self.viewed_state.borrow();
+            let target_key = self.target_key.borrow();
+            let generated = |path: &str| {
+                data.as_ref()
+                    .is_some_and(|data| data.files.iter().any(|f| f.path == path && f.generated))
+            };
+            (0..paths.len())
+                .map(|i| &paths[(start + i) % paths.len()])
+                .find(|p| !is_path_viewed(p, &viewed, data.as_ref(), &target_key) && !generated(p))
+                .cloned()
+        };
+        let Some(path) = next else {
+            self.flash_status("All files viewed", std::time::Duration::from_secs(2));
+            return;
+        };
+        let folders = collect_folder_paths_above(&self.file_tree.borrow(), &path);
+        if !folders.is_empty() {
+            let mut state = self.expanded_state.borrow_mut();
+            for folder in folders {
+                state.insert(folder, true);
+            }
+        }
+        save_folder_state(
+            &self.viewed_state,
+            &self.target_key.borrow(),
+            &self.expanded_state.borrow(),
+        );
+        self.rebuild_tree();
+        if let Err(e) = self.goto(&path, None) {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+
+    /// A click on the code of `row` at `column`: two on the same row select
+    /// the word there, three the line, and with Shift the rows up to it.
+    fn press_row(&self, row: i32, column: i32, shift: bool) {
+        let Ok(row) = usize::try_from(row) else {
+            return;
+        };
+        let content = self.window.get_lines().row_data(row).map(|line| line.content);
+        let selection = self.selection.borrow_mut().press(
+            &self.window.get_selected_file(),
+            row,
+            column.max(0) as usize,
+            content.as_deref().unwrap_or_default(),
+            shift,
+            Instant::now(),
+        );
+        self.window.set_diff_selection(Selection::shown(selection));
+    }
 
-                (base, head)
+    /// Ask the language server of the file shown where the symbol at `column`
+    /// of `row` is defined, and show it: in the diff if the file is part of
+    /// it, read-only otherwise.
+    fn go_to_definition(&self, row: i32, column: i32) -> Result<()> {
+        let (Some(servers), Some(workdir)) = (&self.definitions, self.repo.workdir()) else {
+            return Ok(());
+        };
+        let lines = self.window.get_lines();
+        let Some(row) = usize::try_from(row).ok().filter(|&row| row < lines.row_count()) else {
+            return Ok(());
+        };
+        // Wrapped lines continue over several rows, numbered on the first.
+        // Side by side, the new line of a paired row is in its new- fields.
+        let new_text = |l: &DiffLine| {
+            if l.paired {
+                l.new_content.clone()
+            } else {
+                l.content.clone()
             }
         };
+        let mut first = row;
+        let mut column = column.max(0) as usize;
+        while first > 0 && lines.row_data(first).is_some_and(|l| l.is_continuation) {
+            first -= 1;
+            column += lines.row_data(first).map_or(0, |l| new_text(&l).chars().count());
+        }
+        let Some(line) = lines.row_data(first) else {
+            return Ok(());
+        };
+        if line.line_type != "add" && line.line_type != "context" && !line.paired {
+            return Ok(());
+        }
+        let Some(line_num) = line.new_line_num.parse::<u32>().ok().and_then(|n| n.checked_sub(1))
+        else {
+            return Ok(());
+        };
 
-        // Compute the diff
-        let mut diff_data = self.repo.diff_commits(base_oid, head_oid)?;
-        diff_data.expand_tabs(self.window.get_app_settings().tab_width as usize);
+        let path = self.window.get_selected_file().to_string();
+        let new_blob = self
+            .diff_data
+            .borrow()
+            .as_ref()
+            .and_then(|data| data.blobs.get(&path).and_then(|&(_, new)| new));
+        let bytes = match new_blob {
+            Some(id) => self.repo.blob_content(id)?,
+            None => std::fs::read(workdir.join(&path))
+                .with_context(|| format!("Failed to read {}", path))?,
+        };
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        let tab_width = self.window.get_app_settings().tab_width as usize;
+        let Some(character) = text
+            .lines()
+            .nth(line_num as usize)
+            .and_then(|line| goto_definition::utf16_column(line, column, tab_width))
+        else {
+            return Ok(());
+        };
 
-        // Build hierarchical file tree and flatten for UI
-        let tree = build_file_tree(&diff_data.files);
-        let expanded_state = self.expanded_state.borrow();
-        let flat_entries = flatten_tree_with_state(&tree, 0, &expanded_state);
-        drop(expanded_state);
+        let query = goto_definition::Query {
+            path: workdir.join(&path),
+            text,
+            line: line_num,
+            character,
+        };
+        let Some(answer) = servers.definition(query) else {
+            return Ok(());
+        };
+        self.window.set_loading_status("Looking up definition…".into());
+        let app = self.clone();
+        let workdir = workdir.to_path_buf();
+        slint::spawn_local(async move {
+            let result = answer.recv().await;
+            app.window.set_loading_status("".into());
+            let location = match result {
+                Ok(Ok(locations)) => locations.into_iter().next(),
+                Ok(Err(e)) => {
+                    eprintln!("Warning: Could not go to the definition: {:#}", e);
+                    return;
+                }
+                Err(_) => return,
+            };
+            let Some(location) = location else {
+                app.flash_status("No definition found", std::time::Duration::from_secs(2));
+                return;
+            };
+            if let Err(e) = app.show_definition(&workdir, &location) {
+                eprintln!("Warning: Could not go to the definition: {:#}", e);
+            }
+        })?;
+        Ok(())
+    }
 
-        let file_entries = build_file_entries(
-            &flat_entries,
-            self.pr_comments.borrow().as_ref(),
-            Some(&diff_data),
-            Some((&self.viewed_state.borrow(), &self.target_key)),
+    /// Show `text` in the toolbar's status for `duration`.
+    fn flash_status(&self, text: &str, duration: std::time::Duration) {
+        self.window.set_loading_status(text.into());
+        let window_weak = self.window.as_weak();
+        slint::Timer::single_shot(duration, move || {
+            if let Some(window) = window_weak.upgrade() {
+                window.set_loading_status("".into());
+            }
+        });
+    }
+
+    /// Save the diff with its comments in the downloads folder, and return
+    /// where: as a standalone HTML page (`html`), or as a PDF of all files
+    /// (`pdf`) or the selected one (`pdf-file`).
+    fn export_report(&self, format: &str) -> Result<PathBuf> {
+        let data = self.diff_data.borrow();
+        let data = data.as_ref().context("The diff hasn't loaded yet")?;
+        let title = self.window.get_diff_title().to_string();
+        let comments = self.pr_comments.borrow();
+        let (name, content) = match format {
+            "html" => (
+                export::report_file_name(&title, "html"),
+                export::to_html(
+                    data,
+                    &title,
+                    &self.highlighter.borrow(),
+                    &self.window.get_app_settings().ui_theme,
+                    comments.as_ref(),
+                )
+                .into_bytes(),
+            ),
+            "pdf" | "pdf-file" => {
+                let selected = self.window.get_selected_file().to_string();
+                let only_file = (format == "pdf-file").then_some(selected.as_str());
+                if only_file == Some("") {
+                    return Err(anyhow!("No file is selected"));
+                }
+                let name = export::report_file_name(
+                    &format!("{} {}", title, only_file.unwrap_or_default()),
+                    "pdf",
+                );
+                // Paper is white
+                let mut highlighter = self.highlighter.borrow().clone();
+                highlighter.set_theme("light");
+                let commits = self.target_commits().unwrap_or_default();
+                let header = pdf::Header::new(&self.repo, title, commits);
+                let content =
+                    pdf::render(data, only_file, &header, &highlighter, comments.as_ref());
+                (name, content)
+            }
+            _ => return Err(anyhow!("Unknown export format {}", format)),
+        };
+        save_report(&name, &content)
+    }
+
+    /// Fill in the statistics of the review session for the toolbar. A PR's
+    /// comments are fetched again, with the gh user, to count those written
+    /// since the session started.
+    fn show_session_stats(&self) {
+        let stats = self.session.borrow().stats(Instant::now());
+        let pr = match *self.target.borrow() {
+            DiffTarget::PullRequest(pr) => Some(pr),
+            _ => None,
+        };
+        let longest: Vec<SessionFile> = stats
+            .longest
+            .iter()
+            .map(|(path, time)| SessionFile {
+                path: path.into(),
+                time: session::format_duration(*time).into(),
+            })
+            .collect();
+        self.window.set_session_stats(SessionStats {
+            elapsed: session::format_duration(stats.elapsed).into(),
+            files_viewed: stats.files_viewed as i32,
+            lines_viewed: stats.lines_viewed as i32,
+            comments: -1,
+            comments_pending: pr.is_some(),
+            longest: ModelRc::from(Rc::new(VecModel::from(longest))),
+        });
+        let Some(pr) = pr else {
+            return;
+        };
+
+        let (reply, answer) = async_channel::bounded(1);
+        let dir = self.dir.to_path_buf();
+        std::thread::spawn(move || {
+            let written = github::current_user()
+                .and_then(|user| Ok((user, github::get_pr_comments(&dir, pr)?)));
+            let _ = reply.send_blocking(written);
+        });
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            let comments = match answer.recv().await {
+                Ok(Ok((user, comments))) => {
+                    app.session.borrow().comments_written(&comments, &user) as i32
+                }
+                Ok(Err(e)) => {
+                    eprintln!("Warning: Could not count the comments written: {:#}", e);
+                    -1
+                }
+                Err(_) => -1,
+            };
+            let mut stats = app.window.get_session_stats();
+            stats.comments = comments;
+            stats.comments_pending = false;
+            app.window.set_session_stats(stats);
+        });
+        if let Err(e) = spawned {
+            eprintln!("Warning: Could not count the comments written: {}", e);
+        }
+    }
+
+    /// Save the Markdown summary of the review in the downloads folder. A
+    /// PR's comments are those of the gh user, who is looked up first.
+    fn export_summary(&self) {
+        let (reply, answer) = async_channel::bounded(1);
+        if matches!(*self.target.borrow(), DiffTarget::PullRequest(_)) {
+            std::thread::spawn(move || {
+                let _ = reply.send_blocking(github::current_user());
+            });
+        }
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            // Other targets have no comments, nor a sender left
+            let user = match answer.recv().await {
+                Ok(Ok(user)) => Some(user),
+                Ok(Err(e)) => {
+                    eprintln!("Warning: Could not look up the gh user: {:#}", e);
+                    None
+                }
+                Err(_) => None,
+            };
+            app.report_exported(app.write_summary(user.as_deref()));
+        });
+        if let Err(e) = spawned {
+            eprintln!("Warning: Could not export the review: {}", e);
+        }
+    }
+
+    /// Write the Markdown summary with the comments of `user` (everyone's
+    /// if `None`), and return where.
+    fn write_summary(&self, user: Option<&str>) -> Result<PathBuf> {
+        let data = self.diff_data.borrow();
+        let data = data.as_ref().context("The diff hasn't loaded yet")?;
+        let title = self.window.get_diff_title().to_string();
+        let comments: Vec<github::PrComment> = self
+            .all_pr_comments
+            .borrow()
+            .iter()
+            .filter(|comment| user.is_none_or(|user| comment.author == user))
+            .cloned()
+            .collect();
+        let viewed = self.viewed_state.borrow();
+        let text = summary::markdown(
+            &title,
+            data,
+            |path| is_path_viewed(path, &viewed, Some(data), &self.target_key.borrow()),
+            &comments,
         );
+        save_report(&export::report_file_name(&title, "md"), text.as_bytes())
+    }
 
-        // Pick the initial focus row before moving file_entries into the model.
-        let initial_focus = find_initial_focus_index(&file_entries);
+    /// Tell how an export went, in the toolbar.
+    fn report_exported(&self, result: Result<PathBuf>) {
+        match result {
+            Ok(path) => self.flash_status(
+                &format!("Exported to {}", path.display()),
+                std::time::Duration::from_secs(5),
+            ),
+            Err(e) => {
+                eprintln!("Warning: Could not export the review: {:#}", e);
+                self.flash_status("Export failed", std::time::Duration::from_secs(5));
+            }
+        }
+    }
 
-        let files_model = Rc::new(VecModel::from(file_entries));
-        self.window.set_files(ModelRc::from(files_model));
+    /// List where the word selected on an added or removed line is used in
+    /// the files at the head of the diff.
+    fn find_usages(&self) {
+        let lines = self.window.get_lines();
+        let selected = Selection::from_shown(&self.window.get_diff_selection())
+            .filter(|selection| selection.word.is_some())
+            .filter(|selection| {
+                lines
+                    .row_data(selection.anchor)
+                    .is_some_and(|line| matches!(line.line_type.as_str(), "add" | "remove"))
+            })
+            .map(|selection| selection.text(&lines))
+            .filter(|word| usages::is_symbol(word));
+        let Some(word) = selected else {
+            let hint = "Double-click a name on a changed line first";
+            self.flash_status(hint, std::time::Duration::from_secs(3));
+            return;
+        };
+        let head = match self.target_commits() {
+            Ok(Some((_, head))) => head,
+            Ok(None) => {
+                let status = "Usages are only found in commits";
+                self.flash_status(status, std::time::Duration::from_secs(3));
+                return;
+            }
+            Err(e) => {
+                eprintln!("Warning: Could not find usages of {}: {:#}", word, e);
+                return;
+            }
+        };
 
-        // Load the diff for the initial focus row and keep focused-index in sync
-        // with selected-file so the header "viewed" state is driven by the same row.
-        if initial_focus >= 0 {
-            if let Some(initial) = flat_entries.get(initial_focus as usize) {
-                self.window.set_focused_index(initial_focus);
-                self.window.set_selected_file(initial.path.clone().into());
-                let viewed = is_path_viewed(
-                    &initial.path,
-                    &self.viewed_state.borrow(),
-                    Some(&diff_data),
-                    &self.target_key,
-                );
-                self.window.set_selected_file_viewed(viewed);
-                let comments = self.pr_comments.borrow();
-                let hl = self.highlighter.borrow();
-                let wrap = self.window.get_app_settings().line_wrap_column.max(0) as usize;
-                let lines =
-                    get_lines_for_file(&diff_data, &initial.path, comments.as_ref(), &hl, wrap);
-                self.window.set_lines(lines);
+        self.window.set_usages_word(word.clone().into());
+        self.window.set_usages(ModelRc::default());
+        self.window.set_usages_searching(true);
+        let answer = self.repo.find_usages(head, &word);
+        let app = self.clone();
+        let spawned = slint::spawn_local(async move {
+            let found = match answer.recv().await {
+                Ok(Ok(found)) => found,
+                Ok(Err(e)) => {
+                    eprintln!("Warning: Could not find usages of {}: {:#}", word, e);
+                    Vec::new()
+                }
+                Err(_) => Vec::new(),
+            };
+            // Another word was looked for meanwhile, or the list closed
+            if app.window.get_usages_word() != word.as_str() {
+                return;
             }
+            let entries: Vec<UsageEntry> = found
+                .into_iter()
+                .map(|usage| UsageEntry {
+                    path: usage.path.into(),
+                    line: usage.line as i32,
+                    text: usage.text.into(),
+                })
+                .collect();
+            app.window
+                .set_usages(ModelRc::from(Rc::new(VecModel::from(entries))));
+            app.window.set_usages_searching(false);
+        });
+        if let Err(e) = spawned {
+            eprintln!("Warning: Could not find usages: {}", e);
         }
+    }
 
-        // Store for later use in callbacks
-        *self.file_tree.borrow_mut() = tree;
-        *self.diff_data.borrow_mut() = Some(diff_data);
+    /// Show `line` of `path` at the head of the diff: in the diff if the
+    /// file is part of it, read-only otherwise.
+    fn show_usage(&self, path: &str, line: u32) -> Result<()> {
+        if self.goto(path, Some(line)).is_ok() {
+            return Ok(());
+        }
+        let (_, head) = self
+            .target_commits()?
+            .ok_or_else(|| anyhow!("The diff's commits haven't loaded yet"))?;
+        let blob = self
+            .repo
+            .blob_at(head, path)?
+            .ok_or_else(|| anyhow!("{} is not at head", path))?;
+        let text = self.repo.blob_content(blob)?;
+        let tab = " ".repeat(self.window.get_app_settings().tab_width as usize);
+        let lines: Vec<slint::SharedString> = String::from_utf8_lossy(&text)
+            .lines()
+            .map(|line| line.replace('\t', &tab).into())
+            .collect();
+        self.window
+            .set_source_view_lines(ModelRc::from(Rc::new(VecModel::from(lines))));
+        self.window.set_source_view_line(line as i32);
+        self.window.set_source_view_path(path.into());
+        Ok(())
+    }
+
+    /// Show `location` in the diff if its file is part of it, read-only
+    /// otherwise.
+    fn show_definition(&self, workdir: &Path, location: &lsp::Location) -> Result<()> {
+        let line = location.line + 1;
+        if let Ok(relative) = location.path.strip_prefix(workdir) {
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            if self.goto(&relative, Some(line)).is_ok() {
+                return Ok(());
+            }
+        }
+        let text = std::fs::read(&location.path)
+            .with_context(|| format!("Failed to read {}", location.path.display()))?;
+        let tab = " ".repeat(self.window.get_app_settings().tab_width as usize);
+        let lines: Vec<slint::SharedString> = String::from_utf8_lossy(&text)
+            .lines()
+            .map(|line| line.replace('\t', &tab).into())
+            .collect();
+        let shown = location
+            .path
+            .strip_prefix(workdir)
+            .unwrap_or(&location.path)
+            .display()
+            .to_string();
+        self.window
+            .set_source_view_lines(ModelRc::from(Rc::new(VecModel::from(lines))));
+        self.window.set_source_view_line(line as i32);
+        self.window.set_source_view_path(shown.into());
+        Ok(())
+    }
+
+    /// Show `path` as it was at `reference` against the version of it the
+    /// diff shows, in the compare view.
+    fn compare_with(&self, path: &str, reference: &str) -> Result<()> {
+        let reference = reference.trim();
+        let (_, head) = self
+            .target_commits()?
+            .ok_or_else(|| anyhow!("The diff's commits haven't loaded yet"))?;
+        let base = self.repo.resolve_ref(reference)?;
+        let mut data = self.repo.diff_path(path, base, head)?;
+        let head = head.to_string()[..7].to_string();
+        self.window.set_compare_head(head.clone().into());
+        if data.files.is_empty() {
+            self.window.set_compare_lines(ModelRc::default());
+            let same = format!("{} is the same at {} and {}", path, reference, head);
+            self.window.set_compare_message(same.into());
+            return Ok(());
+        }
 
+        let settings = self.window.get_app_settings();
+        data.expand_tabs(settings.tab_width as usize);
+        // Asked for, so shown whatever its size; rows past the first screens
+        // of a long file stay plain, as nothing scrolls them into view here
+        let (lines, _) = get_lines_for_file(
+            &data,
+            path,
+            None,
+            None,
+            &self.highlighter.borrow(),
+            &mut self.highlight_cache.borrow_mut(),
+            &LargeFileGuard::new(0, 0),
+            settings.line_wrap_column.max(0) as usize,
+            self.window.get_side_by_side_mode(),
+        );
+        self.window.set_compare_lines(lines);
+        self.window.set_compare_message("".into());
         Ok(())
     }
 
+    /// The review comments of the PR shown; none for other targets.
+    pub fn comments(&self) -> Vec<github::PrComment> {
+        self.all_pr_comments.borrow().clone()
+    }
+
+    /// Whether the window is still open
+    pub fn is_visible(&self) -> bool {
+        self.window.window().is_visible()
+    }
+
     pub fn run(self) -> Result<()> {
+        self.save_layout_on_close();
         self.window.run().context("Failed to run window")?;
 
-        // Persist panel width on exit
-        let mut config = crate::config::load();
-        config.panel_width = self.window.get_left_panel_width();
-        if let Err(e) = crate::config::save(&config) {
-            eprintln!("Warning: Could not save panel width: {}", e);
+        Ok(())
+    }
+
+    /// Show the window on the event loop that is already running, as
+    /// `--serve` does for other invocations.
+    pub fn show(&self) -> Result<()> {
+        self.save_layout_on_close();
+        self.window.show().context("Failed to show window")
+    }
+
+    fn save_layout_on_close(&self) {
+        // Persist panel widths and window geometry on close, while the window
+        // is still mapped and its size/position are meaningful.
+        let window_weak = self.window.as_weak();
+        self.window.window().on_close_requested(move || {
+            if let Some(window) = window_weak.upgrade() {
+                let mut config = crate::config::load();
+                config.panel_width = window.get_left_panel_width();
+                config.side_by_side_split = window.get_side_by_side_split();
+                store_window_geometry(&window, &mut config);
+                if let Err(e) = crate::config::save(&config) {
+                    eprintln!("Warning: Could not save window layout: {}", e);
+                }
+            }
+            slint::CloseRequestResponse::HideWindow
+        });
+    }
+}
+
+/// Apply persisted window size, position and maximized state before the window is shown.
+fn restore_window_geometry(window: &MainWindow, config: &crate::config::Config) {
+    if let (Some(w), Some(h)) = (config.window_width, config.window_height) {
+        window.window().set_size(slint::LogicalSize::new(w, h));
+    }
+    if let (Some(x), Some(y)) = (config.window_x, config.window_y) {
+        window
+            .window()
+            .set_position(slint::LogicalPosition::new(x, y));
+    }
+    if config.window_maximized {
+        window.window().set_maximized(true);
+    }
+}
+
+/// Record the current window geometry into `config`.
+/// While maximized, the previous normal size/position is kept so un-maximizing
+/// on the next launch restores a sensible window.
+fn store_window_geometry(window: &MainWindow, config: &mut crate::config::Config) {
+    let win = window.window();
+    config.window_maximized = win.is_maximized();
+    if config.window_maximized || win.is_fullscreen() {
+        return;
+    }
+    let scale = win.scale_factor();
+    let size = win.size().to_logical(scale);
+    let pos = win.position().to_logical(scale);
+    config.window_width = Some(size.width);
+    config.window_height = Some(size.height);
+    config.window_x = Some(pos.x);
+    config.window_y = Some(pos.y);
+}
+
+/// The files to list in the tree: those not matching the `exclude` globs,
+/// unless the toolbar reveals them, and passing the tree's filter. Updates
+/// the toolbar's count of hidden files and the tree's stats of all files.
+fn tree_files(
+    files: &[FileChange],
+    exclude: &ExcludeFilter,
+    window: &MainWindow,
+) -> Vec<FileChange> {
+    let (kept, excluded) = exclude.filter(files);
+    window.set_excluded_count(excluded as i32);
+    let listed = if window.get_show_excluded() {
+        files.to_vec()
+    } else {
+        kept
+    };
+    let filter = tree_filter(window);
+    let shown: Vec<FileChange> = listed.into_iter().filter(|file| filter.matches(file)).collect();
+    window.set_diff_stats(DiffStats {
+        files: files.len() as i32,
+        shown: shown.len() as i32,
+        additions: files.iter().map(|file| file.additions).sum::<usize>() as i32,
+        deletions: files.iter().map(|file| file.deletions).sum::<usize>() as i32,
+    });
+    shown
+}
+
+/// The filter chosen above the file tree
+fn tree_filter(window: &MainWindow) -> FileFilter {
+    let statuses = [
+        (window.get_filter_added(), FileStatus::Added),
+        (window.get_filter_modified(), FileStatus::Modified),
+        (window.get_filter_deleted(), FileStatus::Deleted),
+        (window.get_filter_renamed(), FileStatus::Renamed),
+    ];
+    FileFilter {
+        statuses: statuses
+            .into_iter()
+            .filter_map(|(checked, status)| checked.then_some(status))
+            .collect(),
+        extensions: FileFilter::parse_extensions(&window.get_filter_extensions()),
+        hide_deleted: window.get_app_settings().hide_deleted,
+    }
+}
+
+/// The keybinding conflicts of `config`, for the settings panel.
+/// Remember the folders open in the file tree for the next time the
+/// target is reviewed.
+fn save_folder_state(
+    viewed_state: &RefCell<ViewedState>,
+    target_key: &str,
+    expanded: &HashMap<String, bool>,
+) {
+    let mut vs = viewed_state.borrow_mut();
+    vs.set_folder_state(target_key, expanded);
+    if let Err(e) = vs.save() {
+        eprintln!("Warning: Could not save viewed state: {}", e);
+    }
+}
+
+/// The diff as scripts see it.
+fn script_state(
+    window: &MainWindow,
+    diff_data: Option<&DiffData>,
+    target: &DiffTarget,
+) -> scripting::ScriptState {
+    scripting::ScriptState {
+        title: window.get_diff_title().to_string(),
+        pr: match target {
+            DiffTarget::PullRequest(pr) => Some(*pr),
+            _ => None,
+        },
+        selected: window.get_selected_file().to_string(),
+        files: diff_data.map(|d| d.files.clone()).unwrap_or_default(),
+    }
+}
+
+/// Carry out what a script asked for.
+fn apply_script_actions(
+    window: &MainWindow,
+    exclude: &RefCell<ExcludeFilter>,
+    actions: Vec<scripting::Action>,
+) {
+    let mut hid = false;
+    for action in actions {
+        match action {
+            scripting::Action::Hide(glob) => {
+                exclude.borrow_mut().add(&glob);
+                hid = true;
+            }
+            scripting::Action::Select(path) => {
+                window.set_selected_file(path.clone().into());
+                window.invoke_file_selected(path.into());
+            }
         }
+    }
+    if hid {
+        // Rebuilds the tree with the new globs
+        window.invoke_toggle_excluded();
+    }
+}
 
-        Ok(())
+/// `base` with the settings of the settings panel
+fn settings_config(
+    settings: &crate::AppSettings,
+    base: crate::config::Config,
+) -> crate::config::Config {
+    crate::config::Config {
+        ui_theme: settings.ui_theme.to_string(),
+        font_size: settings.font_size,
+        tab_width: settings.tab_width,
+        line_wrap_column: settings.line_wrap_column,
+        relative_timestamps: settings.relative_timestamps,
+        tree_sort: settings.tree_sort.to_string(),
+        flat_file_list: settings.flat_list,
+        hide_deleted_files: settings.hide_deleted,
+        key_unified: settings.key_unified.to_string(),
+        key_side_by_side: settings.key_side_by_side.to_string(),
+        key_scroll_down: settings.key_scroll_down.to_string(),
+        key_scroll_up: settings.key_scroll_up.to_string(),
+        key_file_next: settings.key_file_next.to_string(),
+        key_file_prev: settings.key_file_prev.to_string(),
+        key_prev_commit: settings.key_prev_commit.to_string(),
+        key_next_commit: settings.key_next_commit.to_string(),
+        ..base
+    }
+}
+
+fn conflicts_model(config: &crate::config::Config) -> ModelRc<slint::SharedString> {
+    let conflicts: Vec<slint::SharedString> = config
+        .keybinding_conflicts()
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    ModelRc::from(Rc::new(VecModel::from(conflicts)))
+}
+
+/// Source text fed to the highlighter for a file: the content of every
+/// add/remove/context line in diff order, one per line.
+fn highlight_source(hunks: &[crate::git::DiffHunk]) -> String {
+    let mut source = String::new();
+    for line in hunks.iter().flat_map(|h| &h.lines) {
+        source.push_str(&line.content);
+        source.push('\n');
+    }
+    source
+}
+
+/// Highlight every file of `data` not yet in `cache` on worker threads. The
+/// results land in `cache` once done; until then files are highlighted on demand.
+fn highlight_in_background(
+    data: &DiffData,
+    highlighter: &Highlighter,
+    cache: &Rc<RefCell<HighlightCache>>,
+) {
+    // Files seen before (e.g. under this theme earlier) are already cached
+    let files: Vec<(String, String)> = data
+        .file_hunks
+        .iter()
+        .map(|(path, hunks)| (path.clone(), highlight_source(hunks)))
+        .filter(|(path, code)| !cache.borrow().contains(&highlighter.cache_key(code, path)))
+        .collect();
+    if files.is_empty() {
+        return;
+    }
+    let highlighter = highlighter.clone();
+    let (tx, rx) = async_channel::bounded(1);
+    std::thread::spawn(move || {
+        let _ = tx.send_blocking(highlighter.highlight_batch(files));
+    });
+
+    let cache = Rc::clone(cache);
+    let spawned = slint::spawn_local(async move {
+        if let Ok(batch) = rx.recv().await {
+            cache.borrow_mut().extend(batch);
+        }
+    });
+    if let Err(e) = spawned {
+        eprintln!("Warning: Could not start background highlighting: {}", e);
+    }
+}
+
+/// Items shown in the summary before it is cut short
+const ITEM_SUMMARY_LIMIT: usize = 12;
+
+/// The functions the diff of a Rust file adds, removes or changes the
+/// signature of, one per line; empty for other files and when `enabled`
+/// (`semantic_diff`) is off.
+fn item_summary(enabled: bool, repo: &Repository, data: &DiffData, path: &str) -> String {
+    if !enabled || !path.ends_with(".rs") {
+        return String::new();
     }
+    let Some(&(old, new)) = data.blobs.get(path) else {
+        return String::new();
+    };
+    let content = |blob: Option<git2::Oid>| -> Result<String> {
+        let bytes = blob.map_or(Ok(Vec::new()), |id| repo.blob_content(id))?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    };
+    let changes = match content(old).and_then(|old| Ok((old, content(new)?))) {
+        Ok((old, new)) => semantic::diff_rust_items(&old, &new).unwrap_or_default(),
+        Err(e) => {
+            eprintln!("Warning: Could not read {}: {:#}", path, e);
+            return String::new();
+        }
+    };
+    let mut lines: Vec<String> = changes
+        .iter()
+        .take(ITEM_SUMMARY_LIMIT)
+        .map(ToString::to_string)
+        .collect();
+    if changes.len() > ITEM_SUMMARY_LIMIT {
+        lines.push(format!("… and {} more", changes.len() - ITEM_SUMMARY_LIMIT));
+    }
+    lines.join("\n")
 }
 
-/// Convert hunks for a file into Slint-compatible DiffLine model, interleaving comments
+/// Convert hunks for a file into Slint-compatible DiffLine model, interleaving comments.
+/// Also returns the pending highlighting for large files (see `lazy_highlight`).
+/// Removed lines share their rows with the added lines they align with when
+/// `side_by_side`.
+/// Generated files and those over the large-file limits come back as a
+/// single "notice" row.
+#[tracing::instrument(skip_all, fields(path = path))]
+#[allow(clippy::too_many_arguments)]
 fn get_lines_for_file(
     data: &DiffData,
     path: &str,
     comments: Option<&FileComments>,
+    viewed_state: Option<(&ViewedState, &str)>,
     highlighter: &Highlighter,
+    highlight_cache: &mut HighlightCache,
+    large_files: &LargeFileGuard,
     wrap_column: usize,
-) -> ModelRc<DiffLine> {
-    use crate::git::{CommentData, DiffLine as GitDiffLine, DiffLineType};
-    use crate::models::{parse_hex_color, wrap_diff_line};
+    side_by_side: bool,
+) -> (ModelRc<DiffLine>, Option<LazyHighlight>) {
+    // Everything below borrows from `data`; text is only copied into the rows
+    let hunks = data.shown_hunks(path);
 
-    let hunks = data.file_hunks.get(path).cloned().unwrap_or_default();
+    let generated = data.files.iter().any(|f| f.path == path && f.generated);
+    if let Some(notice) = large_files.notice(path, generated, hunks) {
+        let placeholder = DiffLineModel {
+            line_type: "notice".to_string(),
+            content: notice,
+            ..Default::default()
+        };
+        return (ModelRc::new(VecModel::from(vec![placeholder.into()])), None);
+    }
 
-    // Get comments for this file, if any
-    let file_comments = comments.and_then(|c| c.get(path));
+    // Highlight the file's diff content up front when it's cheap (or already
+    // done in the background); otherwise rows start plain and are highlighted
+    // as they scroll into view.
+    let full_content = highlight_source(hunks);
+    let code_line_count = full_content.lines().count();
+    let highlighted_lines = if code_line_count <= lazy_highlight::CHUNK_LINES {
+        Some(highlighter.highlight_with_cache(&full_content, path, highlight_cache))
+    } else {
+        highlighter.cached(&full_content, path, highlight_cache)
+    };
+    let is_lazy = highlighted_lines.is_none();
 
-    // First, collect all diff lines with their line numbers
-    let diff_lines: Vec<GitDiffLine> = hunks
-        .into_iter()
-        .flat_map(|hunk| {
-            // Create hunk header line (trim trailing newline from git2)
-            let header_line = GitDiffLine {
-                line_type: DiffLineType::Hunk,
-                old_line_num: None,
-                new_line_num: None,
-                content: hunk.header.trim_end().to_string(),
-                comment: None,
-            };
-            // Prepend header to hunk lines
-            std::iter::once(header_line).chain(hunk.lines)
-        })
-        .collect();
+    let mut rows =
+        FileRows::new(path, comments, highlighted_lines, wrap_column, side_by_side, 0);
+    let mut folds = file_folds(data, path).into_iter().peekable();
+    for (index, hunk) in hunks.iter().enumerate() {
+        if let Some(fold) = folds.next_if(|fold| fold.hunk == index) {
+            rows.push_fold(&fold);
+        }
+        let reviewed = viewed_state.is_some_and(|(vs, tk)| {
+            vs.is_hunk_reviewed(tk, path, viewed_state::hash_hunk(hunk))
+        });
+        rows.push_header(hunk, reviewed);
+        rows.push_lines(&hunk.lines);
+    }
 
-    // Reconstruct file content from diff lines for syntax highlighting
-    // We need to highlight the content to get spans for each line
-    let content_lines: Vec<(&GitDiffLine, String)> = diff_lines
-        .iter()
-        .filter(|l| {
-            matches!(
-                l.line_type,
-                DiffLineType::Add | DiffLineType::Remove | DiffLineType::Context
-            )
-        })
-        .map(|l| (l, l.content.clone()))
-        .collect();
+    let model = Rc::new(VecModel::from(rows.rows));
+    let lazy = is_lazy.then(|| {
+        let mut lazy = LazyHighlight::new(path, Rc::clone(&model), rows.lazy_rows, wrap_column);
+        // The top of the file is what's shown first
+        lazy.ensure_rows(0, 0, highlighter);
+        lazy
+    });
+    (ModelRc::from(model), lazy)
+}
 
-    // Create a combined content string for highlighting
-    let full_content: String = content_lines
-        .iter()
-        .map(|(_, c)| c.as_str())
-        .collect::<Vec<_>>()
-        .join("\n")
-        + "\n";
+/// The encoding `path` was decoded from when diffed, if it isn't UTF-8.
+fn file_encoding(data: &DiffData, path: &str) -> Option<&'static str> {
+    data.files.iter().find(|f| f.path == path).and_then(|f| f.encoding)
+}
+
+/// The unchanged regions above the hunks of `path` that can be unfolded:
+/// those of line diffs whose new version is at hand.
+fn file_folds(data: &DiffData, path: &str) -> Vec<folding::Fold> {
+    let has_new_blob = data.blobs.get(path).is_some_and(|(_, new)| new.is_some());
+    match data.file_hunks.get(path) {
+        Some(hunks)
+            if has_new_blob
+                && !data.structural_hunks.contains_key(path)
+                && !data.split_renames.contains_key(path) =>
+        {
+            folding::folds(hunks)
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Rows of one file's diff, built a hunk header or diff line at a time.
+struct FileRows<'a> {
+    /// The file's comments by side and line, looked up once per diff line
+    comment_index: github::LineComments<'a>,
+    /// Highlighting of the file's code lines, in order; `None` when it is
+    /// filled in lazily instead
+    highlighted: Option<std::vec::IntoIter<HighlightedLine>>,
+    wrap_column: usize,
+    /// Whether removed and added lines are paired up into side-by-side rows
+    side_by_side: bool,
+    /// Model row at which `rows` will be inserted
+    first_row: usize,
+    rows: Vec<DiffLine>,
+    /// Model rows of each code line, for lazy highlighting
+    lazy_rows: Vec<CodeRows>,
+    /// Whether the hunk being pushed is marked reviewed
+    reviewed: bool,
+}
+
+impl<'a> FileRows<'a> {
+    fn new(
+        path: &str,
+        comments: Option<&'a FileComments>,
+        highlighted: Option<Vec<HighlightedLine>>,
+        wrap_column: usize,
+        side_by_side: bool,
+        first_row: usize,
+    ) -> Self {
+        // Index this file's comments by line once, rather than scanning them
+        // all for every diff line
+        let comment_index = comments
+            .and_then(|c| c.get(path))
+            .map(|c| github::index_comments_by_line(c))
+            .unwrap_or_default();
+        Self {
+            comment_index,
+            highlighted: highlighted.map(Vec::into_iter),
+            wrap_column,
+            side_by_side,
+            first_row,
+            rows: Vec::new(),
+            lazy_rows: Vec::new(),
+            reviewed: false,
+        }
+    }
+
+    fn push_header(&mut self, hunk: &DiffHunk, reviewed: bool) {
+        self.reviewed = reviewed;
+        let (ranges, context) = funcname::split_header(&hunk.header);
+        let header_line = GitDiffLine {
+            line_type: DiffLineType::Hunk,
+            old_line_num: None,
+            new_line_num: None,
+            content: ranges.into(),
+            comment: None,
+        };
+        let mut model = DiffLineModel::from(&header_line);
+        model.reviewed = reviewed;
+        model.hunk_context = context.to_string();
+        self.rows.push(model.into());
+    }
 
-    // Highlight the content
-    let highlighted_lines = highlighter.highlight(&full_content, path);
+    /// A row standing for the unchanged lines of `fold`
+    fn push_fold(&mut self, fold: &folding::Fold) {
+        let model = DiffLineModel {
+            line_type: "fold".to_string(),
+            content: fold.lines.to_string(),
+            ..Default::default()
+        };
+        self.rows.push(model.into());
+    }
+
+    /// Push consecutive lines of a hunk, each removed line sharing its row
+    /// with the added line it is aligned with when shown side by side.
+    fn push_lines(&mut self, lines: &[GitDiffLine]) {
+        if !self.side_by_side {
+            for diff_line in lines {
+                self.push_line(diff_line);
+            }
+            return;
+        }
 
-    // Map highlighted lines back to diff lines
-    let mut highlight_iter = highlighted_lines.into_iter();
+        // Highlighting comes in line order, which the pairs don't keep
+        let mut models: Vec<Option<DiffLineModel>> =
+            lines.iter().map(|l| Some(self.line_model(l))).collect();
+        let mut code_rows: Vec<Option<CodeRows>> = vec![None; lines.len()];
+        for pair in align_side_by_side(lines) {
+            let (old, new) = match (pair.old, pair.new) {
+                (Some(old), Some(new)) => (old, new),
+                (Some(line), None) | (None, Some(line)) => (line, line),
+                (None, None) => continue,
+            };
+            let mut model = models[old].take().unwrap_or_default();
+            if new != old {
+                let added = models[new].take().unwrap_or_default();
+                model.paired = true;
+                model.new_line_num = added.new_line_num;
+                model.new_content = added.content;
+                model.new_spans = added.spans;
+            }
+            let rows = self.push_rows(model);
+            if is_code(&lines[old]) {
+                code_rows[old] = Some(CodeRows::new(rows.clone()));
+            }
+            self.push_comments(&lines[old]);
+            if new != old {
+                code_rows[new] = Some(CodeRows::paired_new(rows));
+                self.push_comments(&lines[new]);
+            }
+        }
+        if self.highlighted.is_none() {
+            self.lazy_rows.extend(code_rows.into_iter().flatten());
+        }
+    }
 
-    // Build the final lines, interleaving comments
-    let mut result: Vec<DiffLine> = Vec::new();
+    fn push_line(&mut self, diff_line: &GitDiffLine) {
+        let model = self.line_model(diff_line);
+        let rows = self.push_rows(model);
+        if is_code(diff_line) && self.highlighted.is_none() {
+            self.lazy_rows.push(CodeRows::new(rows));
+        }
+        self.push_comments(diff_line);
+    }
 
-    for diff_line in &diff_lines {
+    /// `diff_line` as a row, highlighted unless that's done lazily
+    fn line_model(&mut self, diff_line: &GitDiffLine) -> DiffLineModel {
         // Convert to model
         let mut model = DiffLineModel::from(diff_line);
+        let is_code = is_code(diff_line);
+        model.reviewed = is_code && self.reviewed;
 
         // Add syntax highlighting spans for code lines
-        if matches!(
-            diff_line.line_type,
-            DiffLineType::Add | DiffLineType::Remove | DiffLineType::Context
-        ) {
-            if let Some(hl_line) = highlight_iter.next() {
+        if is_code {
+            if let Some(hl_line) = self.highlighted.as_mut().and_then(Iterator::next) {
                 model.spans = hl_line
                     .spans
                     .into_iter()
@@ -967,64 +4001,148 @@ fn get_lines_for_file(
                     .collect();
             }
         }
+        model
+    }
 
+    /// Push the rows of `model`, and return at which model rows they are.
+    fn push_rows(&mut self, model: DiffLineModel) -> Range<usize> {
         // Wrap long lines into multiple visual rows (no-op when wrap_column == 0)
-        for wrapped in wrap_diff_line(model, wrap_column) {
-            result.push(wrapped.into());
-        }
-
-        // Check if there are comments for this line
-        if let Some(comments) = file_comments {
-            // Get the appropriate line number based on comment side
-            let new_line = diff_line.new_line_num;
-            let old_line = diff_line.old_line_num;
-
-            // Find comments that target this line
-            for comment in comments {
-                let is_match = match comment.line {
-                    Some(line) => {
-                        // Match based on which side the comment is on
-                        match comment.side {
-                            github::CommentSide::Right => new_line == Some(line),
-                            github::CommentSide::Left => old_line == Some(line),
-                        }
-                    }
-                    None => false, // Skip comments without line numbers
-                };
+        let first_row = self.first_row + self.rows.len();
+        for wrapped in wrap_diff_line(model, self.wrap_column) {
+            self.rows.push(wrapped.into());
+        }
+        first_row..self.first_row + self.rows.len()
+    }
 
-                if is_match {
-                    // Create a comment line
-                    let comment_line = GitDiffLine {
-                        line_type: DiffLineType::Comment,
-                        old_line_num: None,
-                        new_line_num: None,
-                        content: String::new(),
-                        comment: Some(CommentData {
-                            author: comment.author.clone(),
-                            body: comment.body.clone(),
-                            timestamp: format_timestamp(&comment.created_at),
-                            is_reply: comment.in_reply_to_id.is_some(),
-                        }),
-                    };
-                    result.push(DiffLineModel::from(&comment_line).into());
-                }
+    /// Attach the comments targeting `diff_line` (new side for additions and
+    /// context, old side for deletions and context)
+    fn push_comments(&mut self, diff_line: &GitDiffLine) {
+        let mut line_comments: Vec<&github::PrComment> = [
+            diff_line
+                .new_line_num
+                .map(|n| (github::CommentSide::Right, n)),
+            diff_line
+                .old_line_num
+                .map(|n| (github::CommentSide::Left, n)),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(|key| self.comment_index.get(&key))
+        .flatten()
+        .copied()
+        .collect();
+        // Keep the file's comment order (by line, then thread order)
+        line_comments.sort_by_key(|c| (c.line, c.id));
+
+        for comment in line_comments {
+            let comment_line = GitDiffLine {
+                line_type: DiffLineType::Comment,
+                old_line_num: None,
+                new_line_num: None,
+                content: Default::default(),
+                comment: Some(Box::new(CommentData {
+                    author: comment.author.clone(),
+                    body: attachments::strip_image_markup(&comment.body),
+                    timestamp: timestamps::absolute(&comment.created_at),
+                    relative_time: timestamps::relative(&comment.created_at),
+                    is_reply: comment.in_reply_to_id.is_some(),
+                    thread_id: comment.in_reply_to_id.unwrap_or(comment.id),
+                    images: attachments::extract_image_urls(&comment.body),
+                })),
+            };
+            self.rows.push(DiffLineModel::from(&comment_line).into());
+        }
+    }
+}
+
+/// Whether `line` is code (added, removed or unchanged) rather than a
+/// header or comment
+fn is_code(line: &GitDiffLine) -> bool {
+    matches!(
+        line.line_type,
+        DiffLineType::Add | DiffLineType::Remove | DiffLineType::Context
+    )
+}
+
+/// What the commit list's popover shows of `commit`: all git knows about it,
+/// or what the PR says when it isn't fetched.
+fn commit_details(repo: &Repository, commit: &PrCommit) -> crate::CommitDetails {
+    match repo.commit_details(&commit.sha) {
+        Ok(details) => {
+            let time = timestamps::from_unix(details.time);
+            crate::CommitDetails {
+                sha: commit.sha.as_str().into(),
+                message: details.message.into(),
+                author: details.author.into(),
+                committer: details.committer.into(),
+                date: format!(
+                    "{} ({})",
+                    timestamps::absolute(&time),
+                    timestamps::relative(&time)
+                )
+                .into(),
+                files_changed: details.files_changed as i32,
+            }
+        }
+        Err(e) => {
+            tracing::debug!("{:#}", e);
+            crate::CommitDetails {
+                sha: commit.sha.as_str().into(),
+                message: commit.message.trim_end().into(),
+                author: commit.author.as_str().into(),
+                committer: Default::default(),
+                date: Default::default(),
+                files_changed: -1,
             }
         }
     }
+}
 
-    ModelRc::new(VecModel::from(result))
+/// Write a report named `name` to the downloads folder (the home folder if
+/// there is none), and return where.
+fn save_report(name: &str, content: &[u8]) -> Result<PathBuf> {
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .context("There is no downloads or home folder")?;
+    let path = dir.join(name);
+    std::fs::write(&path, content)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
 }
 
-/// Format a GitHub timestamp to a more readable format
-fn format_timestamp(timestamp: &str) -> String {
-    // GitHub timestamps are in ISO 8601 format: "2024-01-15T10:30:00Z"
-    // Parse and format to something more readable
-    if timestamp.len() >= 16 {
-        // Extract "2024-01-15 10:30"
-        let date = &timestamp[0..10];
-        let time = &timestamp[11..16];
-        format!("{} {}", date, time)
-    } else {
-        timestamp.to_string()
+/// What the first-run setup says about reviewing pull requests: whether gh
+/// is installed and signed in to GitHub. Runs gh, so off the UI thread.
+fn github_status(offline: bool) -> String {
+    if offline {
+        return "lado runs offline (--offline), so it doesn't use the GitHub CLI. \
+                Branches, commits and patches work as usual."
+            .to_string();
+    }
+    if !github::is_installed() {
+        return "The GitHub CLI (gh) isn't installed. Install it from https://cli.github.com \
+                to review pull requests; branches, commits and patches work without it."
+            .to_string();
     }
+    match github::current_user() {
+        Ok(user) => format!(
+            "Signed in to GitHub as {}. Pull requests can be reviewed with `lado <number>`.",
+            user
+        ),
+        Err(e) => {
+            tracing::debug!("{:#}", e);
+            "gh isn't signed in to GitHub. Run `gh auth login` (or set GH_TOKEN) \
+             to review pull requests."
+                .to_string()
+        }
+    }
+}
+
+/// Resolve on a later turn of the event loop, letting input and redraws
+/// through in between.
+async fn next_event_loop_turn() {
+    let (tx, rx) = async_channel::bounded(1);
+    slint::Timer::single_shot(std::time::Duration::ZERO, move || {
+        let _ = tx.try_send(());
+    });
+    let _ = rx.recv().await;
 }
@@ -0,0 +1,349 @@
+//! Inline image attachments for PR review comments.
+//!
+//! Comment bodies often embed screenshots as markdown images or bare links
+//! to GitHub's attachment hosts. Images are downloaded once (capped in size)
+//! into `~/.cache/lado/images/`, decoded and scaled down on the worker that
+//! fetched them, and shown from memory afterwards.
+
+use anyhow::{anyhow, Context, Result};
+use image::imageops::FilterType;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use slint::{Rgba8Pixel, SharedPixelBuffer};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+/// Largest attachment we are willing to download.
+const MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Size larger images are scaled down to fit: twice the 240px that comment
+/// rows show them at, so they stay sharp on HiDPI screens.
+const MAX_IMAGE_WIDTH: u32 = 1600;
+const MAX_IMAGE_HEIGHT: u32 = 480;
+
+/// Hosts whose bare links are treated as image attachments.
+const ATTACHMENT_PREFIXES: &[&str] = &[
+    "https://user-images.githubusercontent.com/",
+    "https://private-user-images.githubusercontent.com/",
+    "https://github.com/user-attachments/assets/",
+];
+
+/// Extract image URLs from a comment body, in order of appearance.
+///
+/// Recognizes markdown images (`![alt](url)`), HTML `<img src="url">` tags,
+/// and bare links to GitHub attachment hosts.
+pub fn extract_image_urls(body: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    for (_, url) in find_image_markup(body) {
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Remove image markup from a comment body so the text doesn't repeat the URL.
+pub fn strip_image_markup(body: &str) -> String {
+    let mut result = String::with_capacity(body.len());
+    let mut last = 0;
+    for (range, _) in find_image_markup(body) {
+        result.push_str(&body[last..range.start]);
+        last = range.end;
+    }
+    result.push_str(&body[last..]);
+
+    // Collapse the runs of blank lines left behind by removed images
+    let mut lines: Vec<&str> = Vec::new();
+    for line in result.lines().map(str::trim_end) {
+        if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Locate image markup in `body`, returning byte ranges and the image URL.
+fn find_image_markup(body: &str) -> Vec<(std::ops::Range<usize>, String)> {
+    let mut found: Vec<(std::ops::Range<usize>, String)> = Vec::new();
+
+    // Markdown images: ![alt](url) or ![alt](url "title")
+    let mut pos = 0;
+    while let Some(offset) = body[pos..].find("![") {
+        let start = pos + offset;
+        let Some(close_alt) = body[start..].find("](").map(|i| start + i) else {
+            break;
+        };
+        let url_start = close_alt + 2;
+        let Some(end) = markdown_image_end(body, url_start) else {
+            break;
+        };
+        let url = body[url_start..end - 1]
+            .split_whitespace()
+            .next()
+            .unwrap_or_default();
+        if url.starts_with("https://") || url.starts_with("http://") {
+            found.push((start..end, url.to_string()));
+        }
+        pos = end;
+    }
+
+    // HTML images: <img ... src="url" ...>
+    let mut pos = 0;
+    while let Some(offset) = body[pos..].find("<img") {
+        let start = pos + offset;
+        let Some(end) = body[start..].find('>').map(|i| start + i + 1) else {
+            break;
+        };
+        let tag = &body[start..end];
+        if let Some(src_idx) = tag.find("src=\"") {
+            let value = &tag[src_idx + 5..];
+            if let Some(quote) = value.find('"') {
+                found.push((start..end, value[..quote].to_string()));
+            }
+        }
+        pos = end;
+    }
+
+    // Bare attachment links not already covered by markup
+    for prefix in ATTACHMENT_PREFIXES {
+        let mut pos = 0;
+        while let Some(offset) = body[pos..].find(prefix) {
+            let start = pos + offset;
+            let end = body[start..]
+                .find(|c: char| c.is_whitespace() || matches!(c, ')' | '"' | '>' | '<'))
+                .map(|i| start + i)
+                .unwrap_or(body.len());
+            if !found.iter().any(|(r, _)| r.contains(&start)) {
+                found.push((start..end, body[start..end].to_string()));
+            }
+            pos = end;
+        }
+    }
+
+    found.sort_by_key(|(r, _)| r.start);
+    found
+}
+
+/// End (past the closing parenthesis) of a markdown image whose URL starts
+/// at `url_start`, skipping over a quoted title that may hold parentheses.
+fn markdown_image_end(body: &str, url_start: usize) -> Option<usize> {
+    let rest = &body[url_start..];
+    let url_len = rest
+        .find(|c: char| c.is_whitespace() || c == ')')
+        .unwrap_or(rest.len());
+    let mut after = url_len;
+    let title = rest[url_len..].trim_start();
+    if let Some(quote) = title.chars().next().filter(|c| matches!(c, '"' | '\'')) {
+        let title_start = rest.len() - title.len();
+        if let Some(close) = title[1..].find(quote) {
+            after = title_start + 1 + close + 1;
+        }
+    }
+    rest[after..].find(')').map(|i| url_start + after + i + 1)
+}
+
+/// Directory holding downloaded attachments: `~/.cache/lado/images`
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|p| p.join("lado").join("images"))
+}
+
+/// Path an attachment is (or would be) cached at, named by the SHA-256 of
+/// its URL so the name stays the same across lado versions.
+fn cache_path(url: &str) -> Option<PathBuf> {
+    let name: String = Sha256::digest(url.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    cache_dir().map(|dir| dir.join(name))
+}
+
+/// Download an attachment into the cache (no-op if already cached).
+pub fn fetch(url: &str) -> Result<PathBuf> {
+    let path = cache_path(url).ok_or_else(|| anyhow!("Could not determine cache directory"))?;
+    if path.is_file() {
+        return Ok(path);
+    }
+    let dir = path.parent().ok_or_else(|| anyhow!("Could not determine cache directory"))?;
+    std::fs::create_dir_all(dir).context("Failed to create image cache directory")?;
+
+    // Download to a temp name so an interrupted transfer never looks cached
+    let partial = path.with_extension("part");
+    let output = Command::new("curl")
+        .args([
+            "-fsSL",
+            "--max-filesize",
+            &MAX_IMAGE_BYTES.to_string(),
+            "-o",
+        ])
+        .arg(&partial)
+        .arg(url)
+        .current_dir(dir)
+        .output()
+        .context("Failed to execute curl. Is it installed?")?;
+    tracing::debug!(url, status = %output.status, "downloaded image");
+
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&partial);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Image download failed: {}", stderr.trim()));
+    }
+
+    std::fs::rename(&partial, &path).context("Failed to store downloaded image")?;
+    Ok(path)
+}
+
+/// Decode an image and scale it down to fit `MAX_IMAGE_WIDTH` by
+/// `MAX_IMAGE_HEIGHT`.
+fn decode(data: &[u8]) -> Result<SharedPixelBuffer<Rgba8Pixel>> {
+    let mut image = image::ImageReader::new(std::io::Cursor::new(data))
+        .with_guessed_format()?
+        .decode()?;
+    if image.width() > MAX_IMAGE_WIDTH || image.height() > MAX_IMAGE_HEIGHT {
+        image = image.resize(MAX_IMAGE_WIDTH, MAX_IMAGE_HEIGHT, FilterType::Triangle);
+    }
+    let rgba = image.to_rgba8();
+    Ok(SharedPixelBuffer::clone_from_slice(
+        rgba.as_raw(),
+        rgba.width(),
+        rgba.height(),
+    ))
+}
+
+/// Images decoded by [`prefetch`], by URL
+fn decoded() -> &'static Mutex<HashMap<String, SharedPixelBuffer<Rgba8Pixel>>> {
+    static DECODED: OnceLock<Mutex<HashMap<String, SharedPixelBuffer<Rgba8Pixel>>>> =
+        OnceLock::new();
+    DECODED.get_or_init(Default::default)
+}
+
+thread_local! {
+    /// Images handed to the UI, by URL, so rows showing the same attachment
+    /// share one texture
+    static IMAGES: RefCell<HashMap<String, slint::Image>> = RefCell::default();
+}
+
+/// The image at `url` if [`prefetch`] has decoded it. Called on the UI
+/// thread, as `slint::Image` stays on the thread that made it.
+pub fn image(url: &str) -> Option<slint::Image> {
+    IMAGES.with_borrow_mut(|images| {
+        if let Some(image) = images.get(url) {
+            return Some(image.clone());
+        }
+        let pixels = decoded().lock().ok()?.get(url)?.clone();
+        let image = slint::Image::from_rgba8(pixels);
+        images.insert(url.to_string(), image.clone());
+        Some(image)
+    })
+}
+
+/// Download all images referenced by the given comments into the cache and
+/// decode them, several at a time. Failures are reported but don't abort;
+/// the comment then shows without the image.
+pub fn prefetch(comments: &[crate::github::PrComment]) {
+    let mut urls: Vec<String> = Vec::new();
+    for comment in comments {
+        for url in extract_image_urls(&comment.body) {
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+    }
+    urls.retain(|url| !decoded().lock().is_ok_and(|images| images.contains_key(url)));
+    urls.par_iter().for_each(|url| {
+        let pixels = fetch(url).and_then(|path| {
+            let data = std::fs::read(&path).context("Failed to read cached image")?;
+            decode(&data)
+        });
+        match pixels {
+            Ok(pixels) => {
+                if let Ok(mut images) = decoded().lock() {
+                    images.insert(url.clone(), pixels);
+                }
+            }
+            Err(e) => eprintln!("Warning: Could not fetch image {}: {}", url, e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_markdown_image() {
+        let body = "Looks off:\n![screenshot](https://user-images.githubusercontent.com/1/abc.png)";
+        assert_eq!(
+            extract_image_urls(body),
+            vec!["https://user-images.githubusercontent.com/1/abc.png"]
+        );
+    }
+
+    #[test]
+    fn test_extract_html_and_bare_links() {
+        let body = "<img width=\"300\" src=\"https://example.com/a.png\">\n\
+                    see https://github.com/user-attachments/assets/1234-5678 too";
+        assert_eq!(
+            extract_image_urls(body),
+            vec![
+                "https://example.com/a.png",
+                "https://github.com/user-attachments/assets/1234-5678",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignores_plain_links() {
+        assert!(extract_image_urls("see https://example.com/docs").is_empty());
+        assert!(extract_image_urls("[link](https://example.com/a.png)").is_empty());
+    }
+
+    #[test]
+    fn test_markdown_image_title() {
+        let body = "![shot](https://example.com/a.png \"Before (left)\") and after";
+        assert_eq!(extract_image_urls(body), vec!["https://example.com/a.png"]);
+        assert_eq!(strip_image_markup(body), " and after");
+    }
+
+    #[test]
+    fn test_cache_path_is_stable() {
+        let path = cache_path("https://example.com/a.png").unwrap();
+        assert_eq!(
+            path.file_name().unwrap(),
+            "494a30704d4f32ac0b81739d18a66d3638d440cbc6f5669f6af66f840edee5ab"
+        );
+    }
+
+    #[test]
+    fn test_decode_scales_large_images_down() {
+        let encode = |width, height| {
+            let mut data = std::io::Cursor::new(Vec::new());
+            image::RgbaImage::new(width, height)
+                .write_to(&mut data, image::ImageFormat::Png)
+                .unwrap();
+            data.into_inner()
+        };
+        let large = decode(&encode(4000, 600)).unwrap();
+        assert_eq!((large.width(), large.height()), (1600, 240));
+        let small = decode(&encode(300, 200)).unwrap();
+        assert_eq!((small.width(), small.height()), (300, 200));
+        assert!(decode(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_strip_image_markup() {
+        let body = "Before\n\n![img](https://user-images.githubusercontent.com/1/a.png)\n\nAfter";
+        assert_eq!(strip_image_markup(body), "Before\n\nAfter");
+    }
+
+    #[test]
+    fn test_strip_keeps_text_without_images() {
+        assert_eq!(strip_image_markup("just text"), "just text");
+    }
+}
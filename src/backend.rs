@@ -0,0 +1,69 @@
+//! Rendering backend selection.
+//!
+//! Lets users pick the Slint renderer and the winit windowing system from the
+//! config file or CLI, as an escape hatch for GPU driver issues. When both are
+//! left on `auto`, Slint's own defaults (and `SLINT_BACKEND`) apply.
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Slint renderer used to draw the window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Renderer {
+    /// Let Slint decide (honors `SLINT_BACKEND`)
+    #[default]
+    Auto,
+    /// Skia (GPU accelerated)
+    Skia,
+    /// FemtoVG (OpenGL)
+    Femtovg,
+    /// CPU-only software renderer, slowest but works everywhere
+    Software,
+}
+
+impl Renderer {
+    fn slint_name(self) -> Option<&'static str> {
+        match self {
+            Renderer::Auto => None,
+            Renderer::Skia => Some("skia"),
+            Renderer::Femtovg => Some("femtovg"),
+            Renderer::Software => Some("software"),
+        }
+    }
+}
+
+/// Windowing system used by the winit backend on Linux/BSD.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Windowing {
+    /// Wayland when available, X11 otherwise
+    #[default]
+    Auto,
+    Wayland,
+    X11,
+}
+
+/// Configure the Slint platform. Must run once, before any window is
+/// created; the windows `--serve` opens later share the choice.
+pub fn select(renderer: Renderer, windowing: Windowing) -> Result<()> {
+    // winit connects to Wayland whenever WAYLAND_DISPLAY is set and falls back
+    // to X11 otherwise, so hiding one of the display variables forces the choice.
+    // This runs before any other thread is spawned.
+    match windowing {
+        Windowing::Auto => {}
+        Windowing::Wayland => std::env::remove_var("DISPLAY"),
+        Windowing::X11 => std::env::remove_var("WAYLAND_DISPLAY"),
+    }
+
+    let Some(name) = renderer.slint_name() else {
+        return Ok(());
+    };
+
+    slint::BackendSelector::new()
+        .backend_name("winit".to_string())
+        .renderer_name(name.to_string())
+        .select()
+        .map_err(|e| anyhow!("Could not initialize the {} renderer: {}", name, e))
+}
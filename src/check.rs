@@ -0,0 +1,68 @@
+//! `--check`: compute the diff without a window and report through the exit
+//! status whether there is one, like `git diff --quiet`.
+
+use crate::cli::{Args, DiffTarget};
+use crate::exclude::ExcludeFilter;
+use crate::git::FileChange;
+use crate::target;
+use anyhow::Result;
+
+/// Whether the target has any changes; prints the stat line (without the
+/// excluded files) with `--stat`.
+pub fn run(args: &Args) -> Result<bool> {
+    let target = DiffTarget::from_args(args);
+    let dir = std::env::current_dir()?;
+    let repo = target::open_repository(&dir, &target)?;
+    let data = target::load_diff(&dir, &repo, &target)?;
+    if args.stat {
+        let exclude = ExcludeFilter::new(&crate::config::load().exclude);
+        println!("{}", stat_summary(&exclude.filter(&data.files).0));
+    }
+    Ok(!data.files.is_empty())
+}
+
+/// `3 files changed, 10 insertions(+), 2 deletions(-)`, as `git diff --stat`
+/// ends.
+fn stat_summary(files: &[FileChange]) -> String {
+    let plural =
+        |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+    let additions: usize = files.iter().map(|f| f.additions).sum();
+    let deletions: usize = files.iter().map(|f| f.deletions).sum();
+    format!(
+        "{}, {}, {}",
+        plural(files.len(), "file changed", "files changed"),
+        plural(additions, "insertion(+)", "insertions(+)"),
+        plural(deletions, "deletion(-)", "deletions(-)")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::parse_patch;
+
+    #[test]
+    fn test_stat_summary() {
+        let data = parse_patch(
+            "\
+--- a.txt
++++ a.txt
+@@ -1,2 +1,2 @@
+-a
++A
++b
+-c
+--- b.txt
++++ b.txt
+@@ -1 +1 @@
+-x
++y
+",
+        )
+        .unwrap();
+        assert_eq!(
+            stat_summary(&data.files),
+            "2 files changed, 3 insertions(+), 3 deletions(-)"
+        );
+    }
+}
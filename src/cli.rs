@@ -1,20 +1,356 @@
-use clap::{CommandFactory, Parser, ValueHint};
+use crate::backend::{Renderer, Windowing};
+use crate::config::Config;
+use crate::export::ExportFormat;
+use crate::sourcehut::PatchsetId;
+use clap::{CommandFactory, Parser, Subcommand, ValueHint};
 use clap_complete::{generate, Shell};
 use std::io;
+use std::path::PathBuf;
 
 /// lado - Git diff viewer with a side-by-side interface
+///
+/// `lado [TARGET] [HEAD]` is short for `lado diff [TARGET] [HEAD]`.
 #[derive(Parser, Debug)]
 #[command(name = "lado", version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Args {
-    /// Target to diff against HEAD.
-    /// Can be: branch name, commit hash, PR number (42 or #42).
-    /// If omitted, diffs against main/master branch.
-    #[arg(value_hint = ValueHint::Other)]
-    pub target: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub diff: DiffArgs,
+
+    /// Read the diff to show from stdin, as a unified diff (same as `lado -`)
+    #[arg(long, conflicts_with = "target")]
+    pub stdin: bool,
+
+    /// Diff two files on disk; no repository needed
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["target", "stdin", "dirs"]
+    )]
+    pub files: Option<Vec<PathBuf>>,
+
+    /// Diff two directories on disk, file by file; no repository needed
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        value_hint = ValueHint::DirPath,
+        conflicts_with_all = ["target", "stdin"]
+    )]
+    pub dirs: Option<Vec<PathBuf>>,
+
+    /// Review a patch series from an mbox of `git format-patch` mails,
+    /// applied on top of its base in the current repository
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["target", "stdin", "files", "dirs"]
+    )]
+    pub mbox: Option<PathBuf>,
+
+    /// Review the changes not committed yet: the working tree against HEAD,
+    /// untracked files included
+    #[arg(long, conflicts_with_all = ["target", "stdin", "files", "dirs", "mbox", "staged"])]
+    pub worktree: bool,
+
+    /// Review the changes staged for the next commit, against HEAD
+    #[arg(long, conflicts_with_all = ["target", "stdin", "files", "dirs", "mbox"])]
+    pub staged: bool,
+
+    /// Don't refresh the diff when the files or refs it is made from change
+    #[arg(long, global = true)]
+    pub no_watch: bool,
+
+    /// File to select when the diff opens, instead of the first unviewed one.
+    /// `path:line` also scrolls to that line.
+    #[arg(long, global = true, value_hint = ValueHint::FilePath)]
+    pub file: Option<String>,
+
+    /// Line (of the new file) to scroll to in the `--file`
+    #[arg(long, global = true, requires = "file")]
+    pub line: Option<u32>,
+
+    /// Open `lado://owner/repo/pull/N` links with lado
+    #[arg(long)]
+    pub register_url_handler: bool,
 
     /// Generate shell completions
     #[arg(long, value_enum)]
     pub completions: Option<Shell>,
+
+    /// Run as if lado was started in this directory: the repository there
+    /// is diffed and gh asked about its PRs, as with `git -C`
+    #[arg(
+        short = 'C',
+        long = "repo",
+        global = true,
+        value_name = "PATH",
+        value_hint = ValueHint::DirPath
+    )]
+    pub directory: Option<PathBuf>,
+
+    /// Don't use GitHub (gh): numbers like 42 or #42 are taken as refs (42), not PRs
+    #[arg(long, global = true, visible_alias = "no-github")]
+    pub offline: bool,
+
+    /// Config file to use instead of the default one (~/.config/lado/config.toml on Linux)
+    #[arg(long, global = true, value_hint = ValueHint::FilePath)]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub overrides: SettingOverrides,
+
+    /// Renderer to draw the window with (overrides the config file)
+    #[arg(long, global = true, value_enum)]
+    pub renderer: Option<Renderer>,
+
+    /// Windowing system to use on Linux (overrides the config file)
+    #[arg(long, global = true, value_enum)]
+    pub windowing: Option<Windowing>,
+
+    /// Write the diff out instead of opening a window
+    #[arg(long, global = true, value_enum, value_name = "FORMAT")]
+    pub export: Option<ExportFormat>,
+
+    /// File to write `--export` to (stdout if omitted)
+    #[arg(short, long, global = true, requires = "export", value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+
+    /// Keep running and open the diffs of later `lado` invocations in new
+    /// windows of this process, instead of starting one each (Unix)
+    #[arg(long, conflicts_with_all = ["export", "print", "check", "tui"])]
+    pub serve: bool,
+
+    /// Show the diff in the terminal instead of a window (the default
+    /// without a display, e.g. over SSH)
+    #[arg(long, global = true, conflicts_with = "export")]
+    pub tui: bool,
+
+    /// Write the diff to stdout with ANSI colors instead of opening a
+    /// window, e.g. to read in `less -R`
+    #[arg(long, global = true, conflicts_with_all = ["export", "tui"])]
+    pub print: bool,
+
+    /// Only compute the diff: exit with 1 if there are changes, 0 if not
+    #[arg(long, global = true, conflicts_with_all = ["export", "print"])]
+    pub check: bool,
+
+    /// Print a one-line summary of the changes with `--check`
+    #[arg(long, global = true, requires = "check")]
+    pub stat: bool,
+
+    /// Log what lado does to stderr: -v for timings, -vv for more detail
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Write the log to this file instead (at -vv detail unless -v is given)
+    #[arg(long, global = true, value_hint = ValueHint::FilePath)]
+    pub log_file: Option<PathBuf>,
+
+    /// Print a timing summary of diffing, gh calls and highlighting on exit
+    #[arg(long, global = true)]
+    pub profile: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Diff HEAD (or <HEAD>) against a branch, commit or PR (the default)
+    Diff(DiffArgs),
+    /// Review a pull request (requires the gh CLI)
+    Pr(PrArgs),
+    /// Show the changes made by a single commit
+    Show {
+        /// Commit to show
+        #[arg(default_value = "HEAD", value_hint = ValueHint::Other)]
+        commit: String,
+    },
+    /// Show the changes saved in a stash entry
+    Stash {
+        /// Stash entry, as in `stash@{N}`
+        #[arg(default_value_t = 0)]
+        index: usize,
+    },
+    /// Diff HEAD against a branch and step through its commits one by one
+    Log {
+        /// Branch or commit HEAD is based on; main/master if omitted
+        #[arg(value_hint = ValueHint::Other)]
+        base: Option<String>,
+    },
+    /// Review a patchset mailed to a Sourcehut list, with the inline
+    /// comments of the replies reviewing it
+    #[command(visible_alias = "sourcehut")]
+    Srht {
+        /// Patchset URL (https://lists.sr.ht/~owner/list/patches/123) or
+        /// `~owner/list/123`
+        #[arg(value_parser = parse_patchset, value_hint = ValueHint::Url)]
+        patchset: PatchsetId,
+    },
+    /// Pick a target opened before in this repository and open it again
+    Recent,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct PrArgs {
+    #[command(subcommand)]
+    pub command: Option<PrCommand>,
+
+    /// PR number (42 or #42)
+    #[arg(required = true, value_parser = parse_pr_number)]
+    pub number: Option<u32>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PrCommand {
+    /// List the open pull requests and pick one to review
+    List,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct DiffArgs {
+    /// Target to diff against HEAD.
+    /// Can be: branch name, commit hash, PR number (42 or #42), a range
+    /// (`main..feature`, or `main...feature` from where feature forked), a
+    /// `lado://owner/repo/pull/N` link, or `-` for a patch on stdin. If
+    /// omitted, diffs against main/master branch.
+    #[arg(value_hint = ValueHint::Other)]
+    pub target: Option<String>,
+
+    /// Ref to compare the target with instead of HEAD.
+    /// With both given, lado diffs `<TARGET>` (base) against `<HEAD>`;
+    /// neither needs to be checked out.
+    #[arg(value_hint = ValueHint::Other, requires = "target")]
+    pub head: Option<String>,
+}
+
+/// Names of the color themes
+const THEMES: [&str; 4] = ["dark", "light", "solarized-dark", "solarized-light"];
+
+/// Settings for this run only, in place of those of the config file (which
+/// keeps its own).
+#[derive(clap::Args, Debug, Default)]
+pub struct SettingOverrides {
+    /// Profile of the config file to take the themes, font size and wrap
+    /// column of, e.g. for presenting
+    #[arg(long, global = true, value_name = "NAME")]
+    pub config_profile: Option<String>,
+
+    /// Color theme
+    #[arg(long, global = true, value_parser = THEMES)]
+    pub theme: Option<String>,
+
+    /// Colors of the syntax highlighting, if not those of the color theme
+    #[arg(long, global = true, value_parser = THEMES)]
+    pub syntax_theme: Option<String>,
+
+    /// Font size of the diff
+    #[arg(long, global = true, value_parser = clap::value_parser!(i32).range(6..=72))]
+    pub font_size: Option<i32>,
+
+    /// Column to wrap long lines at (0 = no wrapping)
+    #[arg(
+        long,
+        global = true,
+        value_name = "COLUMN",
+        value_parser = clap::value_parser!(i32).range(0..)
+    )]
+    pub line_wrap: Option<i32>,
+}
+
+impl SettingOverrides {
+    /// Fill in the settings not given on the command line from
+    /// `--config-profile`, if there is one.
+    pub fn use_profile(&mut self, config: &Config) -> anyhow::Result<()> {
+        let Some(name) = &self.config_profile else {
+            return Ok(());
+        };
+        let profile = config.profiles.get(name).ok_or_else(|| {
+            let known: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+            anyhow::anyhow!(
+                "No profile {:?} in the config file (it has: {})",
+                name,
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            )
+        })?;
+        self.theme = self.theme.take().or_else(|| profile.ui_theme.clone());
+        self.syntax_theme = self
+            .syntax_theme
+            .take()
+            .or_else(|| profile.syntax_theme.clone());
+        self.font_size = self.font_size.or(profile.font_size);
+        self.line_wrap = self.line_wrap.or(profile.line_wrap_column);
+        Ok(())
+    }
+
+    /// Put the overridden settings into `config`.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(theme) = &self.theme {
+            config.ui_theme = theme.clone();
+        }
+        if let Some(theme) = &self.syntax_theme {
+            config.syntax_theme = Some(theme.clone());
+        }
+        if let Some(size) = self.font_size {
+            config.font_size = size;
+        }
+        if let Some(column) = self.line_wrap {
+            config.line_wrap_column = column;
+        }
+    }
+
+    /// Before `config` is saved: put back the settings of `saved` that are
+    /// still overridden, so only those changed in the window are written.
+    pub fn restore(&self, saved: &Config, config: &mut Config) {
+        if self.theme.as_ref() == Some(&config.ui_theme) {
+            config.ui_theme = saved.ui_theme.clone();
+        }
+        if self.syntax_theme.is_some() {
+            config.syntax_theme = saved.syntax_theme.clone();
+        }
+        if self.font_size == Some(config.font_size) {
+            config.font_size = saved.font_size;
+        }
+        if self.line_wrap == Some(config.line_wrap_column) {
+            config.line_wrap_column = saved.line_wrap_column;
+        }
+    }
+}
+
+fn parse_pr_number(s: &str) -> Result<u32, String> {
+    s.strip_prefix('#')
+        .unwrap_or(s)
+        .parse()
+        .map_err(|_| format!("not a PR number: {}", s))
+}
+
+fn parse_patchset(s: &str) -> Result<PatchsetId, String> {
+    PatchsetId::parse(s).ok_or_else(|| format!("not a Sourcehut patchset: {}", s))
+}
+
+impl Args {
+    /// The file to open and the line to scroll to, from either
+    /// `--file src/app.rs --line 120` or `--file src/app.rs:120`.
+    pub fn file_location(&self) -> Option<(String, Option<u32>)> {
+        let file = self.file.as_deref()?;
+        if self.line.is_none() {
+            if let Some((path, line)) = file.rsplit_once(':') {
+                if let Ok(line) = line.parse() {
+                    return Some((path.to_string(), Some(line)));
+                }
+            }
+        }
+        Some((file.to_string(), self.line))
+    }
 }
 
 /// The resolved diff target
@@ -26,14 +362,112 @@ pub enum DiffTarget {
     Ref(String),
     /// Diff for a pull request
     PullRequest(u32),
+    /// Diff between two git refs, neither of which is HEAD
+    Refs { base: String, head: String },
+    /// Diff from where `head` forked off `base` (their merge base) to
+    /// `head`, as GitHub diffs a pull request
+    MergeBase { base: String, head: String },
+    /// Changes made by one commit (or stash entry), against its first parent
+    Commit(String),
+    /// Diff HEAD against a base (the default branch if `None`), with the
+    /// commits in between listed for stepping through
+    Log(Option<String>),
+    /// Unified diff read from stdin
+    Stdin,
+    /// Two files on disk
+    Files { old: PathBuf, new: PathBuf },
+    /// Two directories on disk
+    Dirs { old: PathBuf, new: PathBuf },
+    /// Patch series mailed by `git format-patch`, in an mbox file
+    Mbox(PathBuf),
+    /// Patchset on a Sourcehut mailing list
+    Patchset(PatchsetId),
+    /// The working tree against HEAD
+    WorkingTree,
+    /// The index against HEAD
+    Staged,
 }
 
 impl DiffTarget {
+    /// Resolve the subcommand, or the bare positional arguments. Two of
+    /// those are always taken as refs, even if they look like PR numbers.
+    pub fn from_args(args: &Args) -> Self {
+        if args.stdin {
+            return DiffTarget::Stdin;
+        }
+        // clap makes sure both paths are there
+        if let Some([old, new]) = args.files.as_deref() {
+            return DiffTarget::Files {
+                old: old.clone(),
+                new: new.clone(),
+            };
+        }
+        if let Some([old, new]) = args.dirs.as_deref() {
+            return DiffTarget::Dirs {
+                old: old.clone(),
+                new: new.clone(),
+            };
+        }
+        if let Some(path) = &args.mbox {
+            return DiffTarget::Mbox(path.clone());
+        }
+        if args.worktree {
+            return DiffTarget::WorkingTree;
+        }
+        if args.staged {
+            return DiffTarget::Staged;
+        }
+        let diff = match &args.command {
+            None => &args.diff,
+            Some(Command::Diff(diff)) => diff,
+            // `pr list` has put the picked PR's number here
+            Some(Command::Pr(PrArgs {
+                number: Some(number),
+                ..
+            })) => return DiffTarget::PullRequest(*number),
+            Some(Command::Pr(_)) => &args.diff,
+            Some(Command::Show { commit }) => return DiffTarget::Commit(commit.clone()),
+            Some(Command::Stash { index }) => {
+                return DiffTarget::Commit(format!("stash@{{{}}}", index))
+            }
+            Some(Command::Log { base }) => return DiffTarget::Log(base.clone()),
+            Some(Command::Srht { patchset }) => return DiffTarget::Patchset(patchset.clone()),
+            // Opened by main, never diffed itself
+            Some(Command::Recent) => &args.diff,
+        };
+        match (&diff.target, &diff.head) {
+            (Some(base), Some(head)) => DiffTarget::Refs {
+                base: base.clone(),
+                head: head.clone(),
+            },
+            (target, _) => match DiffTarget::parse(target.as_deref()) {
+                // Without GitHub, a number (`#` or not) can only be a ref
+                DiffTarget::PullRequest(number) if args.offline => {
+                    DiffTarget::Ref(number.to_string())
+                }
+                target => target,
+            },
+        }
+    }
+
     /// Parse the target argument into a DiffTarget
     pub fn parse(target: Option<&str>) -> Self {
         match target {
             None => DiffTarget::DefaultBranch,
+            Some("-") => DiffTarget::Stdin,
             Some(s) => {
+                // A range, `base..head` or `base...head`; a side left out is HEAD
+                if let Some((base, head)) = s.split_once("...") {
+                    let (base, head) = (or_head(base), or_head(head));
+                    return DiffTarget::MergeBase { base, head };
+                }
+                if let Some((base, head)) = s.split_once("..") {
+                    if head.is_empty() {
+                        return DiffTarget::Ref(or_head(base));
+                    }
+                    let (base, head) = (or_head(base), or_head(head));
+                    return DiffTarget::Refs { base, head };
+                }
                 // Check if it's a PR number (e.g., "42" or "#42")
                 let pr_str = s.strip_prefix('#').unwrap_or(s);
                 if let Ok(pr_num) = pr_str.parse::<u32>() {
@@ -50,6 +484,11 @@ impl DiffTarget {
     }
 }
 
+/// A side of a range, HEAD when it is left out
+fn or_head(side: &str) -> String {
+    if side.is_empty() { "HEAD" } else { side }.to_string()
+}
+
 /// Generate shell completions to stdout
 pub fn generate_completions(shell: Shell) {
     let mut cmd = Args::command();
@@ -59,6 +498,7 @@ pub fn generate_completions(shell: Shell) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
 
     #[test]
     fn test_parse_default() {
@@ -89,6 +529,262 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_two_refs() {
+        let args = Args::parse_from(["lado", "main", "feature"]);
+        assert!(matches!(
+            DiffTarget::from_args(&args),
+            DiffTarget::Refs { base, head } if base == "main" && head == "feature"
+        ));
+
+        // A number is a ref, not a PR, when comparing two refs
+        let args = Args::parse_from(["lado", "v1", "1234"]);
+        assert!(matches!(
+            DiffTarget::from_args(&args),
+            DiffTarget::Refs { head, .. } if head == "1234"
+        ));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let parse = |s| DiffTarget::parse(Some(s));
+        assert!(matches!(
+            parse("main..feature"),
+            DiffTarget::Refs { base, head } if base == "main" && head == "feature"
+        ));
+        assert!(matches!(
+            parse("..feature"),
+            DiffTarget::Refs { base, head } if base == "HEAD" && head == "feature"
+        ));
+        assert!(matches!(parse("main.."), DiffTarget::Ref(r) if r == "main"));
+        assert!(matches!(
+            parse("main...feature"),
+            DiffTarget::MergeBase { base, head } if base == "main" && head == "feature"
+        ));
+        assert!(matches!(
+            parse("v1.0..."),
+            DiffTarget::MergeBase { base, head } if base == "v1.0" && head == "HEAD"
+        ));
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let target = |args: &[&str]| DiffTarget::from_args(&Args::parse_from(args));
+        assert!(matches!(
+            target(&["lado", "pr", "#42"]),
+            DiffTarget::PullRequest(42)
+        ));
+        assert!(matches!(
+            target(&["lado", "diff", "main"]),
+            DiffTarget::Ref(r) if r == "main"
+        ));
+        assert!(matches!(
+            target(&["lado", "show"]),
+            DiffTarget::Commit(c) if c == "HEAD"
+        ));
+        assert!(matches!(
+            target(&["lado", "stash", "2"]),
+            DiffTarget::Commit(c) if c == "stash@{2}"
+        ));
+        assert!(matches!(target(&["lado", "log"]), DiffTarget::Log(None)));
+        // Options work after a subcommand too
+        let args = Args::parse_from(["lado", "pr", "7", "--file", "src/app.rs"]);
+        assert_eq!(args.file.as_deref(), Some("src/app.rs"));
+        assert!(Args::try_parse_from(["lado", "pr", "abc"]).is_err());
+        assert!(Args::try_parse_from(["lado", "pr"]).is_err());
+        let args = Args::parse_from(["lado", "pr", "list"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Pr(PrArgs {
+                command: Some(PrCommand::List),
+                number: None
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_file_location() {
+        let location = |args: &[&str]| Args::parse_from(args).file_location();
+        assert_eq!(location(&["lado"]), None);
+        assert_eq!(
+            location(&["lado", "--file", "src/app.rs"]),
+            Some(("src/app.rs".to_string(), None))
+        );
+        assert_eq!(
+            location(&["lado", "--file", "src/app.rs", "--line", "120"]),
+            Some(("src/app.rs".to_string(), Some(120)))
+        );
+        assert_eq!(
+            location(&["lado", "--file", "src/app.rs:120"]),
+            Some(("src/app.rs".to_string(), Some(120)))
+        );
+        // Not a line number, so part of the path
+        assert_eq!(
+            location(&["lado", "--file", "c:notes.txt"]),
+            Some(("c:notes.txt".to_string(), None))
+        );
+    }
+
+    #[test]
+    fn test_stdin() {
+        assert!(matches!(DiffTarget::parse(Some("-")), DiffTarget::Stdin));
+        let args = Args::parse_from(["lado", "--stdin"]);
+        assert!(matches!(DiffTarget::from_args(&args), DiffTarget::Stdin));
+        assert!(Args::try_parse_from(["lado", "--stdin", "main"]).is_err());
+    }
+
+    #[test]
+    fn test_srht() {
+        let args = Args::parse_from(["lado", "srht", "https://lists.sr.ht/~ada/devel/patches/12"]);
+        assert!(matches!(
+            DiffTarget::from_args(&args),
+            DiffTarget::Patchset(id) if id.list == "~ada/devel" && id.id == 12
+        ));
+        assert!(Args::try_parse_from(["lado", "srht", "main"]).is_err());
+    }
+
+    #[test]
+    fn test_mbox() {
+        let args = Args::parse_from(["lado", "--mbox", "series.mbox"]);
+        assert!(matches!(
+            DiffTarget::from_args(&args),
+            DiffTarget::Mbox(path) if path == Path::new("series.mbox")
+        ));
+        assert!(Args::try_parse_from(["lado", "--mbox", "series.mbox", "main"]).is_err());
+    }
+
+    #[test]
+    fn test_uncommitted() {
+        let args = Args::parse_from(["lado", "--worktree"]);
+        assert!(matches!(DiffTarget::from_args(&args), DiffTarget::WorkingTree));
+        let args = Args::parse_from(["lado", "--staged"]);
+        assert!(matches!(DiffTarget::from_args(&args), DiffTarget::Staged));
+        assert!(Args::try_parse_from(["lado", "--staged", "main"]).is_err());
+        assert!(Args::try_parse_from(["lado", "--worktree", "--staged"]).is_err());
+    }
+
+    #[test]
+    fn test_paths_on_disk() {
+        let args = Args::parse_from(["lado", "--files", "a.rs", "b.rs"]);
+        assert!(matches!(
+            DiffTarget::from_args(&args),
+            DiffTarget::Files { old, new } if old == Path::new("a.rs") && new == Path::new("b.rs")
+        ));
+        let args = Args::parse_from(["lado", "--dirs", "old", "new"]);
+        assert!(matches!(
+            DiffTarget::from_args(&args),
+            DiffTarget::Dirs { old, .. } if old == Path::new("old")
+        ));
+        assert!(Args::try_parse_from(["lado", "--files", "a.rs"]).is_err());
+        assert!(Args::try_parse_from(["lado", "--files", "a", "b", "--dirs", "c", "d"]).is_err());
+        assert!(Args::try_parse_from(["lado", "main", "--dirs", "c", "d"]).is_err());
+    }
+
+    #[test]
+    fn test_export() {
+        let args = Args::parse_from(["lado", "main", "--export", "html", "-o", "review.html"]);
+        assert_eq!(args.export, Some(ExportFormat::Html));
+        assert_eq!(args.output.as_deref(), Some(Path::new("review.html")));
+        assert!(Args::try_parse_from(["lado", "-o", "review.html"]).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let args = Args::parse_from(["lado", "--check", "main", "--stat"]);
+        assert!(args.check && args.stat);
+        assert!(Args::try_parse_from(["lado", "--stat"]).is_err());
+        assert!(Args::try_parse_from(["lado", "--check", "--export", "patch"]).is_err());
+        assert!(Args::try_parse_from(["lado", "--check", "--print"]).is_err());
+    }
+
+    #[test]
+    fn test_setting_overrides() {
+        let args = Args::parse_from(["lado", "--theme", "light", "--font-size", "20"]);
+        let saved = Config::default();
+        let mut config = saved.clone();
+        args.overrides.apply(&mut config);
+        assert_eq!((config.ui_theme.as_str(), config.font_size), ("light", 20));
+
+        // The font size was changed in the window, the theme wasn't
+        config.font_size = 16;
+        args.overrides.restore(&saved, &mut config);
+        assert_eq!((config.ui_theme.as_str(), config.font_size), ("dark", 16));
+
+        assert!(Args::try_parse_from(["lado", "--theme", "neon"]).is_err());
+        assert!(Args::try_parse_from(["lado", "--line-wrap", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_config_profile() {
+        let config = Config {
+            profiles: [(
+                "presentation".to_string(),
+                crate::config::Profile {
+                    ui_theme: Some("light".to_string()),
+                    font_size: Some(20),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Config::default()
+        };
+        let mut args = Args::parse_from([
+            "lado",
+            "--config-profile",
+            "presentation",
+            "--font-size",
+            "24",
+        ]);
+        args.overrides.use_profile(&config).unwrap();
+        // Flags win over the profile
+        assert_eq!(args.overrides.theme.as_deref(), Some("light"));
+        assert_eq!(args.overrides.font_size, Some(24));
+
+        let mut args = Args::parse_from(["lado", "--config-profile", "daily"]);
+        assert!(args.overrides.use_profile(&config).is_err());
+    }
+
+    #[test]
+    fn test_offline() {
+        let target = |args: &[&str]| DiffTarget::from_args(&Args::parse_from(args));
+        assert!(matches!(
+            target(&["lado", "--offline", "42"]),
+            DiffTarget::Ref(r) if r == "42"
+        ));
+        assert!(matches!(
+            target(&["lado", "--no-github", "#42"]),
+            DiffTarget::Ref(r) if r == "42"
+        ));
+        assert!(matches!(
+            target(&["lado", "diff", "#42", "--offline"]),
+            DiffTarget::Ref(r) if r == "42"
+        ));
+        // Online, the same is a pull request
+        assert!(matches!(target(&["lado", "#42"]), DiffTarget::PullRequest(42)));
+        assert!(matches!(
+            target(&["lado", "--offline", "main"]),
+            DiffTarget::Ref(r) if r == "main"
+        ));
+    }
+
+    #[test]
+    fn test_no_watch() {
+        assert!(!Args::parse_from(["lado", "--worktree"]).no_watch);
+        assert!(Args::parse_from(["lado", "--worktree", "--no-watch"]).no_watch);
+        // After a subcommand too
+        assert!(Args::parse_from(["lado", "pr", "42", "--no-watch"]).no_watch);
+        assert!(Args::parse_from(["lado", "log", "main", "--no-watch"]).no_watch);
+    }
+
+    #[test]
+    fn test_directory() {
+        let args = Args::parse_from(["lado", "-C", "../project", "feature"]);
+        assert_eq!(args.directory.as_deref(), Some(Path::new("../project")));
+        assert_eq!(args.diff.target.as_deref(), Some("feature"));
+        let args = Args::parse_from(["lado", "pr", "42", "--repo", "/src/project"]);
+        assert_eq!(args.directory.as_deref(), Some(Path::new("/src/project")));
+    }
+
     #[test]
     fn test_parse_pr_hash() {
         assert!(matches!(
@@ -0,0 +1,175 @@
+//! Writing review comments from the diff view: a new thread on a line of a
+//! pull request, or a reply to a thread shown under one. Posting goes
+//! through `gh` on a worker thread.
+
+use crate::github::{self, CommentSide, PrComment};
+use crate::DiffLine;
+use anyhow::Result;
+use async_channel::Receiver;
+use slint::Model;
+use std::path::PathBuf;
+
+/// What a comment being written goes on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComposeTarget {
+    /// A new thread on `line` of `path`, on `side` of the diff
+    Line {
+        path: String,
+        line: u32,
+        side: CommentSide,
+    },
+    /// A reply to the thread started by comment `thread`, under a comment
+    /// by `author`
+    Reply { thread: u64, author: String },
+}
+
+impl ComposeTarget {
+    /// What a comment written at `row` of the rows shown for `path` goes on:
+    /// the thread of a comment row, or the line of a code row (the line a
+    /// wrapped row continues) in the column clicked, on `side`. Removed lines
+    /// alone on their row are on the old side and added ones on the new side
+    /// whichever column was clicked. `None` for hunk headers, folds and the
+    /// like.
+    pub fn at(
+        lines: &impl Model<Data = DiffLine>,
+        row: usize,
+        path: &str,
+        side: CommentSide,
+    ) -> Option<Self> {
+        let line = lines.row_data(row)?;
+        if line.line_type == "comment" {
+            let thread = line.comment_thread.parse().ok()?;
+            return Some(Self::Reply {
+                thread,
+                author: line.comment_author.to_string(),
+            });
+        }
+
+        // Continuation rows carry no line numbers
+        let start = (0..=row)
+            .rev()
+            .find(|&r| lines.row_data(r).is_some_and(|l| !l.is_continuation))?;
+        let line = lines.row_data(start)?;
+        let (number, side) = match (line.line_type.as_str(), side) {
+            ("remove", _) if !line.paired => (&line.old_line_num, CommentSide::Left),
+            ("remove" | "context", CommentSide::Left) => (&line.old_line_num, CommentSide::Left),
+            ("add" | "remove" | "context", _) => (&line.new_line_num, CommentSide::Right),
+            _ => return None,
+        };
+        Some(Self::Line {
+            path: path.to_string(),
+            line: number.trim().parse().ok()?,
+            side,
+        })
+    }
+
+    /// Heading of the composer, e.g. "Comment on src/app.rs:42"
+    pub fn title(&self) -> String {
+        match self {
+            Self::Line { path, line, side } => {
+                let old = if *side == CommentSide::Left { " (old)" } else { "" };
+                format!("Comment on {}:{}{}", path, line, old)
+            }
+            Self::Reply { author, .. } => format!("Reply to {}", author),
+        }
+    }
+}
+
+/// Post `body` on `target` of pull request `pr_number` of the repository
+/// in `dir` on a worker thread; new threads go on the lines as of the PR's
+/// head commit `head`. The receiver gets the comment as GitHub stored it.
+pub fn post(
+    dir: PathBuf,
+    pr_number: u32,
+    head: String,
+    target: ComposeTarget,
+    body: String,
+) -> Receiver<Result<PrComment>> {
+    let (tx, rx) = async_channel::bounded(1);
+    std::thread::spawn(move || {
+        let posted = match &target {
+            ComposeTarget::Line { path, line, side } => {
+                github::post_review_comment(&dir, pr_number, &head, path, *line, *side, &body)
+            }
+            ComposeTarget::Reply { thread, .. } => {
+                github::post_reply(&dir, pr_number, *thread, &body)
+            }
+        };
+        let _ = tx.send_blocking(posted);
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DiffLineModel;
+    use slint::VecModel;
+
+    fn row(line_type: &str, old: &str, new: &str) -> DiffLineModel {
+        DiffLineModel {
+            line_type: line_type.to_string(),
+            old_line_num: old.to_string(),
+            new_line_num: new.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_target_of_rows() {
+        let comment = DiffLineModel {
+            line_type: "comment".to_string(),
+            comment_author: "ada".to_string(),
+            comment_thread: "17".to_string(),
+            ..Default::default()
+        };
+        let paired = DiffLineModel {
+            paired: true,
+            ..row("remove", "4", "5")
+        };
+        let wrapped = DiffLineModel {
+            is_continuation: true,
+            ..row("add", "", "")
+        };
+        let rows: Vec<DiffLine> = [
+            row("hunk", "", ""),
+            row("context", "1", "1"),
+            row("remove", "2", ""),
+            row("add", "", "2"),
+            wrapped,
+            paired,
+            comment,
+        ]
+        .into_iter()
+        .map(Into::into)
+        .collect();
+        let lines = VecModel::from(rows);
+        let line = |line, side| {
+            Some(ComposeTarget::Line {
+                path: "a.rs".to_string(),
+                line,
+                side,
+            })
+        };
+
+        let at = |row, side| ComposeTarget::at(&lines, row, "a.rs", side);
+        let (left, right) = (CommentSide::Left, CommentSide::Right);
+
+        assert_eq!(at(0, right), None);
+        assert_eq!(at(1, right), line(1, right));
+        assert_eq!(at(1, left), line(1, left));
+        assert_eq!(at(2, right), line(2, left));
+        assert_eq!(at(4, left), line(2, right));
+        assert_eq!(at(5, right), line(5, right));
+        // A paired row clicked in the left column: its removed line
+        assert_eq!(at(5, left), line(4, left));
+        assert_eq!(
+            at(6, right),
+            Some(ComposeTarget::Reply {
+                thread: 17,
+                author: "ada".to_string()
+            })
+        );
+        assert_eq!(at(7, right), None);
+    }
+}
@@ -1,20 +1,59 @@
 //! Configuration persistence for lado settings.
 //!
-//! Settings are stored in `~/.config/lado/config.toml`.
+//! Settings are stored in `config.toml` in lado's directory of the platform
+//! ([`config_dir`]), or the file given with `--config`.
 
+use crate::backend::{Renderer, Windowing};
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Config file given with `--config`, in place of the default one
+static CONFIG_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
 
 /// Configuration struct mirroring Slint's AppSettings.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(default)]
 pub struct Config {
     pub ui_theme: String,
+    /// Theme of the syntax highlighting when it differs from `ui_theme`
+    pub syntax_theme: Option<String>,
     pub font_size: i32,
     pub tab_width: i32,
     /// Column at which to wrap long diff lines. 0 = no wrap.
     pub line_wrap_column: i32,
+    /// Show when comments were written as e.g. `3 hours ago` rather than
+    /// the date and time (which hovering shows either way)
+    pub relative_timestamps: bool,
+    /// Order of the files in the tree: `path`, `size` (most changed lines
+    /// first), `status` (added and deleted first) or `commits` (in the order
+    /// of the commits that last changed them)
+    pub tree_sort: String,
+    /// List the files by their whole paths instead of in a tree of folders
+    pub flat_file_list: bool,
+    /// Leave deleted files out of the tree and of moving between files,
+    /// unless the tree's "Deleted" filter asks for them
+    pub hide_deleted_files: bool,
     pub panel_width: f32,
+    /// Share of the side-by-side view's width taken by the old side
+    pub side_by_side_split: f32,
+    // Window geometry (logical pixels). None = let the window system decide.
+    pub window_width: Option<f32>,
+    pub window_height: Option<f32>,
+    pub window_x: Option<f32>,
+    pub window_y: Option<f32>,
+    pub window_maximized: bool,
+    /// Never call gh: PR-number-like targets are taken as refs (also `--offline`)
+    pub offline: bool,
+    // Rendering backend (applied at startup, overridable with --renderer/--windowing)
+    pub renderer: Renderer,
+    pub windowing: Windowing,
+    /// Files with more changed lines than this are only shown on request. 0 = no limit.
+    pub large_file_lines: i32,
+    /// Same for the size of a file's diff text in bytes. 0 = no limit.
+    pub large_file_bytes: i32,
     // Keybindings
     pub key_unified: String,
     pub key_side_by_side: String,
@@ -24,16 +63,74 @@ pub struct Config {
     pub key_file_prev: String,
     pub key_prev_commit: String,
     pub key_next_commit: String,
+    /// Local clones by `owner/repo`, for opening `lado://` links
+    pub repos: HashMap<String, PathBuf>,
+    /// Globs of files to leave out of the tree and `--stat`, e.g. `vendor/**`
+    pub exclude: Vec<String>,
+    /// Experimental: list the functions a Rust file's diff adds, removes or
+    /// changes the signature of above the diff
+    pub semantic_diff: bool,
+    /// Compare CRLF and LF line endings as the same, so files from Windows
+    /// don't show as rewritten. Always on if the repository's
+    /// `core.autocrlf` is; files `.gitattributes` marks `-text` are left as
+    /// they are.
+    pub normalize_line_endings: bool,
+    /// Language of the interface, e.g. `de` (one of those in `lang/`).
+    /// None = the system's.
+    pub language: Option<String>,
+    /// Look for a newer release of lado once a day (asks GitHub)
+    pub check_for_updates: bool,
+    /// Commands of the language servers for go to definition, by file
+    /// extension, e.g. `rs = "rust-analyzer"`
+    pub language_servers: HashMap<String, String>,
+    /// Proxy to reach GitHub through (with gh and curl), e.g.
+    /// `http://proxy.example.com:3128`. `HTTPS_PROXY` wins over it.
+    pub https_proxy: Option<String>,
+    /// Hosts to reach without the proxy, as in `NO_PROXY`, which wins over it
+    pub no_proxy: Option<String>,
+    /// File of the CA certificates to trust instead of the system's, e.g.
+    /// with the one of a proxy that inspects TLS
+    pub ca_bundle: Option<PathBuf>,
+    /// Named sets of themes and sizes, e.g. `[profiles.presentation]`
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Themes, font size and wrap column to switch to at once (in the settings
+/// panel or with `--config-profile`); those left out, like every other
+/// setting, stay as they are.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Profile {
+    pub ui_theme: Option<String>,
+    pub syntax_theme: Option<String>,
+    pub font_size: Option<i32>,
+    pub line_wrap_column: Option<i32>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             ui_theme: "dark".to_string(),
+            syntax_theme: None,
             font_size: 14,
             tab_width: 4,
             line_wrap_column: 100,
+            relative_timestamps: true,
+            tree_sort: "path".to_string(),
+            flat_file_list: false,
+            hide_deleted_files: false,
             panel_width: 280.0,
+            side_by_side_split: 0.5,
+            window_width: None,
+            window_height: None,
+            window_x: None,
+            window_y: None,
+            window_maximized: false,
+            offline: false,
+            renderer: Renderer::Auto,
+            windowing: Windowing::Auto,
+            large_file_lines: 20_000,
+            large_file_bytes: 5_000_000,
             key_unified: "u".to_string(),
             key_side_by_side: "s".to_string(),
             key_scroll_down: "j".to_string(),
@@ -42,13 +139,301 @@ impl Default for Config {
             key_file_prev: "K".to_string(),
             key_prev_commit: "[".to_string(),
             key_next_commit: "]".to_string(),
+            repos: HashMap::new(),
+            exclude: Vec::new(),
+            semantic_diff: false,
+            normalize_line_endings: false,
+            language: None,
+            check_for_updates: false,
+            language_servers: HashMap::new(),
+            https_proxy: None,
+            no_proxy: None,
+            ca_bundle: None,
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+/// Keys with a fixed meaning that configurable bindings can't take over
+const FIXED_KEYS: [(&str, &str); 11] = [
+    ("?", "Help"),
+    ("e", "Toggle Folder"),
+    ("E", "Expand All Folders"),
+    ("c", "Collapse All Folders"),
+    ("C", "Expand Folder Recursively"),
+    ("v", "Toggle Viewed"),
+    ("n", "Next Unviewed File"),
+    ("f", "Find Usages"),
+    ("r", "Toggle Hunk Reviewed"),
+    ("h", "Next Unreviewed Hunk"),
+    ("Ctrl+c", "Copy Selection"),
+];
+
+/// Keys without a character, by the names keybindings give them (as
+/// `ui/keys.slint` does)
+const NAMED_KEYS: [&str; 14] = [
+    "Up",
+    "Down",
+    "Left",
+    "Right",
+    "PageUp",
+    "PageDown",
+    "Home",
+    "End",
+    "Enter",
+    "Tab",
+    "Backspace",
+    "Delete",
+    "Insert",
+    "Space",
+];
+
+/// The keybinding `key` as the settings panel records it: the modifiers
+/// `Ctrl+`, `Alt+`, `Meta+` in that order, then the character the key
+/// types or its name in [`NAMED_KEYS`], with `Shift+` only before a name,
+/// e.g. `ctrl+shift+j` is `Ctrl+J` and `alt+shift+down` is `Alt+Shift+Down`.
+/// None if no key press gives it.
+pub fn normalize_key(key: &str) -> Option<String> {
+    // The key can be `+` itself
+    let (modifiers, base) = match key.strip_suffix('+') {
+        Some(rest) if rest.is_empty() || rest.ends_with('+') => (rest, "+"),
+        _ => key.rsplit_once('+').unwrap_or(("", key)),
+    };
+    let (mut ctrl, mut alt, mut meta, mut shift) = (false, false, false, false);
+    for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+        match modifier.trim().to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => ctrl = true,
+            "alt" | "option" => alt = true,
+            "meta" | "cmd" | "command" | "super" | "win" => meta = true,
+            "shift" => shift = true,
+            _ => return None,
+        }
+    }
+
+    let mut chars = base.chars();
+    let base = match (chars.next(), chars.next()) {
+        (Some(' '), None) => "Space".to_string(),
+        // Arrow keys as the window's key events give them, which earlier
+        // versions saved as they were
+        (Some('\u{F700}'), None) => "Up".to_string(),
+        (Some('\u{F701}'), None) => "Down".to_string(),
+        (Some('\u{F702}'), None) => "Left".to_string(),
+        (Some('\u{F703}'), None) => "Right".to_string(),
+        (Some(c), None) if c.is_whitespace() || c.is_control() => return None,
+        // Shift only picks the character, which for others depends on the
+        // keyboard layout
+        (Some(c), None) if shift && !c.is_alphabetic() => return None,
+        (Some(c), None) if shift => {
+            shift = false;
+            c.to_uppercase().collect()
+        }
+        (Some(c), None) => c.to_string(),
+        _ => {
+            let name = base.trim();
+            let name = name
+                .strip_suffix("Arrow")
+                .or_else(|| name.strip_suffix("arrow"))
+                .unwrap_or(name);
+            let name = if name.eq_ignore_ascii_case("return") {
+                "Enter"
+            } else {
+                NAMED_KEYS
+                    .into_iter()
+                    .find(|named| named.eq_ignore_ascii_case(name))?
+            };
+            name.to_string()
+        }
+    };
+    let mut normalized = String::new();
+    for (held, prefix) in [
+        (ctrl, "Ctrl+"),
+        (alt, "Alt+"),
+        (meta, "Meta+"),
+        (shift, "Shift+"),
+    ] {
+        if held {
+            normalized.push_str(prefix);
+        }
+    }
+    normalized.push_str(&base);
+    Some(normalized)
+}
+
+/// Actions with configurable keys, as the settings panel names them, in the
+/// order of [`Config::keys`]
+const KEY_ACTIONS: [&str; 8] = [
+    "Unified View",
+    "Side-by-Side View",
+    "Scroll Down",
+    "Scroll Up",
+    "Next File",
+    "Previous File",
+    "Previous Commit",
+    "Next Commit",
+];
+
+impl Config {
+    fn keys(&self) -> [&String; 8] {
+        [
+            &self.key_unified,
+            &self.key_side_by_side,
+            &self.key_scroll_down,
+            &self.key_scroll_up,
+            &self.key_file_next,
+            &self.key_file_prev,
+            &self.key_prev_commit,
+            &self.key_next_commit,
+        ]
+    }
+
+    fn keys_mut(&mut self) -> [&mut String; 8] {
+        [
+            &mut self.key_unified,
+            &mut self.key_side_by_side,
+            &mut self.key_scroll_down,
+            &mut self.key_scroll_up,
+            &mut self.key_file_next,
+            &mut self.key_file_prev,
+            &mut self.key_prev_commit,
+            &mut self.key_next_commit,
+        ]
+    }
+
+    /// Write keybindings as the settings panel does ([`normalize_key`]),
+    /// and reset those no key press can produce to their defaults. Returns
+    /// what was reset and the remaining conflicts, for warnings.
+    pub fn validate_keybindings(&mut self) -> Vec<String> {
+        let defaults = Config::default();
+        let mut problems = Vec::new();
+        let keys = self.keys_mut().into_iter().zip(defaults.keys());
+        for ((key, default), action) in keys.zip(KEY_ACTIONS) {
+            match normalize_key(key) {
+                Some(normalized) => *key = normalized,
+                None => {
+                    problems.push(format!(
+                        "Invalid key {:?} for {}, using {:?}",
+                        key, action, default
+                    ));
+                    *key = default.clone();
+                }
+            }
+        }
+        problems.extend(self.keybinding_conflicts());
+        problems
+    }
+
+    /// The action other than `action` that `key` is bound to, fixed or
+    /// configured, to warn before binding it again.
+    pub fn key_taken_by(&self, key: &str, action: &str) -> Option<&'static str> {
+        self.keys()
+            .into_iter()
+            .map(String::as_str)
+            .zip(KEY_ACTIONS)
+            .chain(FIXED_KEYS)
+            .find(|&(bound, other)| bound == key && other != action)
+            .map(|(_, other)| other)
+    }
+
+    /// Keys bound to more than one action, of which only one would ever run.
+    pub fn keybinding_conflicts(&self) -> Vec<String> {
+        let bindings = self
+            .keys()
+            .into_iter()
+            .map(String::as_str)
+            .zip(KEY_ACTIONS)
+            .chain(FIXED_KEYS);
+        let mut by_key: Vec<(&str, Vec<&str>)> = Vec::new();
+        for (key, action) in bindings {
+            match by_key.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, actions)) => actions.push(action),
+                None => by_key.push((key, vec![action])),
+            }
         }
+        by_key
+            .into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|(key, actions)| format!("\"{}\" is bound to {}", key, actions.join(" and ")))
+            .collect()
     }
 }
 
-/// Returns the path to the config file: `~/.config/lado/config.toml`
+/// lado's directory for settings: `~/.config/lado` on Linux,
+/// `~/Library/Application Support/lado` on macOS and `%APPDATA%\lado` on
+/// Windows
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("lado"))
+}
+
+/// Returns the path to the config file: `config.toml` in [`config_dir`],
+/// unless another one is in use
 pub fn config_path() -> Option<PathBuf> {
-    dirs::config_dir().map(|p| p.join("lado").join("config.toml"))
+    if let Some(path) = CONFIG_OVERRIDE.get() {
+        return Some(path.clone());
+    }
+    config_dir().map(|p| p.join("config.toml"))
+}
+
+/// Whether lado runs for the first time: no config file was saved yet, nor
+/// one given with `--config`
+pub fn is_first_run() -> bool {
+    CONFIG_OVERRIDE.get().is_none() && config_path().is_some_and(|path| !path.exists())
+}
+
+/// On macOS and Windows, move the files of `~/.config/lado` (where the docs
+/// used to send everyone) to [`config_dir`], unless that already exists.
+pub fn migrate_legacy_dir() {
+    let (Some(home), Some(dir)) = (dirs::home_dir(), config_dir()) else {
+        return;
+    };
+    let legacy = home.join(".config").join("lado");
+    if legacy == dir {
+        return;
+    }
+    match move_dir_contents(&legacy, &dir) {
+        Ok(true) => eprintln!(
+            "Moved lado's settings from {} to {}",
+            legacy.display(),
+            dir.display()
+        ),
+        Ok(false) => {}
+        Err(e) => eprintln!(
+            "Warning: Could not move settings from {}: {}",
+            legacy.display(),
+            e
+        ),
+    }
+}
+
+/// Move everything in `from` to a new directory `to`. `false` if there was
+/// nothing to move or `to` already exists.
+fn move_dir_contents(from: &Path, to: &Path) -> std::io::Result<bool> {
+    if !from.is_dir() || to.exists() {
+        return Ok(false);
+    }
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        // Renaming fails across file systems
+        if std::fs::rename(entry.path(), &target).is_err() {
+            std::fs::copy(entry.path(), &target)?;
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    // Only goes if empty, which it is unless something else put files there
+    let _ = std::fs::remove_dir(from);
+    Ok(true)
+}
+
+/// Load and save settings with the file at `path` for the rest of the run.
+/// Unlike the default file, it has to exist and be valid.
+pub fn use_path(path: &Path) -> Result<()> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    toml::from_str::<Config>(&contents)
+        .with_context(|| format!("Invalid config file {}", path.display()))?;
+    let _ = CONFIG_OVERRIDE.set(path.to_path_buf());
+    Ok(())
 }
 
 /// Load configuration from disk. Returns default if file is missing or invalid.
@@ -86,6 +471,7 @@ pub fn save(config: &Config) -> std::io::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use lado_core::test_support::TempDir;
 
     #[test]
     fn test_default_config() {
@@ -100,10 +486,26 @@ mod tests {
     fn test_serialize_deserialize() {
         let config = Config {
             ui_theme: "light".to_string(),
+            syntax_theme: Some("solarized-light".to_string()),
             font_size: 16,
             tab_width: 2,
             line_wrap_column: 120,
+            relative_timestamps: false,
+            tree_sort: "size".to_string(),
+            flat_file_list: true,
+            hide_deleted_files: true,
             panel_width: 300.0,
+            side_by_side_split: 0.6,
+            window_width: Some(1400.0),
+            window_height: Some(900.0),
+            window_x: Some(100.0),
+            window_y: Some(50.0),
+            window_maximized: true,
+            offline: true,
+            renderer: Renderer::Software,
+            windowing: Windowing::X11,
+            large_file_lines: 5000,
+            large_file_bytes: 0,
             key_unified: "u".to_string(),
             key_side_by_side: "s".to_string(),
             key_scroll_down: "j".to_string(),
@@ -112,6 +514,24 @@ mod tests {
             key_file_prev: "K".to_string(),
             key_prev_commit: "[".to_string(),
             key_next_commit: "]".to_string(),
+            repos: HashMap::from([("trobanga/lado".to_string(), PathBuf::from("/src/lado"))]),
+            exclude: vec!["vendor/**".to_string()],
+            semantic_diff: true,
+            normalize_line_endings: true,
+            language: Some("de".to_string()),
+            check_for_updates: true,
+            language_servers: HashMap::from([("rs".to_string(), "rust-analyzer".to_string())]),
+            https_proxy: Some("http://proxy.example.com:3128".to_string()),
+            no_proxy: Some("localhost,.internal".to_string()),
+            ca_bundle: Some(PathBuf::from("/etc/ssl/corp.pem")),
+            profiles: BTreeMap::from([(
+                "presentation".to_string(),
+                Profile {
+                    ui_theme: Some("light".to_string()),
+                    font_size: Some(20),
+                    ..Profile::default()
+                },
+            )]),
         };
 
         let toml_str = toml::to_string(&config).unwrap();
@@ -135,6 +555,118 @@ mod tests {
         assert_eq!(config.line_wrap_column, 100);
     }
 
+    #[test]
+    fn test_window_geometry_defaults_to_unset() {
+        let config: Config = toml::from_str("font_size = 12").unwrap();
+        assert_eq!(config.window_width, None);
+        assert_eq!(config.window_x, None);
+        assert!(!config.window_maximized);
+    }
+
+    #[test]
+    fn test_backend_options_parse_lowercase() {
+        let config: Config =
+            toml::from_str("renderer = \"femtovg\"\nwindowing = \"wayland\"").unwrap();
+        assert_eq!(config.renderer, Renderer::Femtovg);
+        assert_eq!(config.windowing, Windowing::Wayland);
+    }
+
+    #[test]
+    fn test_use_path_rejects_missing_or_invalid_files() {
+        let dir = TempDir::new("config");
+        assert!(use_path(&dir.path().join("no-such-config.toml")).is_err());
+
+        let invalid = dir.path().join("invalid-config.toml");
+        std::fs::write(&invalid, "font_size = \"big\"").unwrap();
+        let result = use_path(&invalid);
+        assert!(result.is_err());
+        // Still the default file
+        assert_ne!(config_path(), Some(invalid));
+    }
+
+    #[test]
+    fn test_profiles_table() {
+        let config: Config = toml::from_str(
+            "[profiles.presentation]\nui_theme = \"light\"\nfont_size = 20\n\n[profiles.daily]\nui_theme = \"dark\"",
+        )
+        .unwrap();
+        let names: Vec<&String> = config.profiles.keys().collect();
+        assert_eq!(names, ["daily", "presentation"]);
+        let presentation = &config.profiles["presentation"];
+        assert_eq!(presentation.font_size, Some(20));
+        assert_eq!(presentation.line_wrap_column, None);
+    }
+
+    #[test]
+    fn test_invalid_keys_are_reset() {
+        let mut config = Config {
+            key_scroll_down: "hyper+d".to_string(),
+            key_scroll_up: String::new(),
+            key_file_next: "shift+j".to_string(),
+            ..Config::default()
+        };
+        let problems = config.validate_keybindings();
+        assert_eq!(problems.len(), 2);
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_normalize_key() {
+        let normalized = |key| normalize_key(key);
+        assert_eq!(normalized("j").as_deref(), Some("j"));
+        assert_eq!(normalized("ctrl+shift+j").as_deref(), Some("Ctrl+J"));
+        assert_eq!(
+            normalized("shift+Alt+down").as_deref(),
+            Some("Alt+Shift+Down")
+        );
+        assert_eq!(normalized("Cmd+PageUp").as_deref(), Some("Meta+PageUp"));
+        assert_eq!(normalized("Ctrl++").as_deref(), Some("Ctrl++"));
+        assert_eq!(normalized("+").as_deref(), Some("+"));
+        assert_eq!(normalized(" ").as_deref(), Some("Space"));
+        assert_eq!(normalized("return").as_deref(), Some("Enter"));
+        assert_eq!(normalized("\u{F701}").as_deref(), Some("Down"));
+        assert_eq!(normalized("shift+1"), None);
+        assert_eq!(normalized("F13"), None);
+        assert_eq!(normalized(""), None);
+    }
+
+    #[test]
+    fn test_keybinding_conflicts() {
+        assert!(Config::default().keybinding_conflicts().is_empty());
+        let config = Config {
+            key_file_next: "j".to_string(),
+            key_next_commit: "v".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.keybinding_conflicts(),
+            [
+                "\"j\" is bound to Scroll Down and Next File",
+                "\"v\" is bound to Next Commit and Toggle Viewed",
+            ]
+        );
+        assert_eq!(config.key_taken_by("v", "Scroll Up"), Some("Next Commit"));
+        assert_eq!(config.key_taken_by("k", "Scroll Up"), None);
+        assert_eq!(config.key_taken_by("Ctrl+k", "Scroll Down"), None);
+    }
+
+    #[test]
+    fn test_move_dir_contents() {
+        let base = TempDir::new("migrate");
+        let (from, to) = (base.path().join("old"), base.path().join("new"));
+        std::fs::create_dir_all(&from).unwrap();
+        std::fs::write(from.join("config.toml"), "font_size = 18").unwrap();
+
+        let moved = move_dir_contents(&from, &to).unwrap();
+        let contents = std::fs::read_to_string(to.join("config.toml")).unwrap();
+        // Nothing left to move the second time
+        let moved_again = move_dir_contents(&from, &to).unwrap();
+
+        assert!(moved);
+        assert_eq!(contents, "font_size = 18");
+        assert!(!moved_again);
+    }
+
     #[test]
     fn test_invalid_toml_returns_default() {
         let invalid = "this is not valid toml {{{{";
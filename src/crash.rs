@@ -0,0 +1,170 @@
+//! Crash reports, so bug reports come with context.
+//!
+//! A panic writes what panicked, a backtrace, the versions involved and the
+//! last lines of the log (kept even without `-v`, see [`remember`]) to
+//! `crash-<time>.log` in lado's cache directory (`~/.cache/lado` on Linux).
+//! The next window lado opens points to the newest report it hasn't pointed
+//! to yet.
+
+use anyhow::{Context, Result};
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Lines of the log a report ends with
+const RECENT_LINES: usize = 200;
+
+/// Holds the name of the newest report that was pointed to
+const SEEN_FILE: &str = "crash-seen";
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|p| p.join("lado"))
+}
+
+/// Keep a line of the log for a crash report, dropping the oldest.
+pub fn remember(line: &str) {
+    // A panic while logging must not make the report fail too
+    let Ok(mut recent) = RECENT.lock() else {
+        return;
+    };
+    if recent.len() == RECENT_LINES {
+        recent.pop_front();
+    }
+    recent.push_back(line.trim_end().to_string());
+}
+
+/// Write a report for every panic, after the usual message.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        match write_report(info) {
+            Ok(path) => eprintln!("lado crashed, details are in {}", path.display()),
+            Err(e) => eprintln!("Warning: Could not write a crash report: {:#}", e),
+        }
+    }));
+}
+
+fn write_report(info: &PanicHookInfo<'_>) -> Result<PathBuf> {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("(no message)");
+    let location = info
+        .location()
+        .map_or_else(String::new, |l| format!("{}:{}", l.file(), l.line()));
+    let recent: Vec<String> = RECENT
+        .lock()
+        .map(|recent| recent.iter().cloned().collect())
+        .unwrap_or_default();
+    let text = report(
+        message,
+        &location,
+        &Backtrace::force_capture().to_string(),
+        &recent,
+    );
+
+    let dir = cache_dir().context("No cache directory")?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = dir.join(format!("crash-{}-{}.log", secs, std::process::id()));
+    std::fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// The text of a report
+fn report(message: &str, location: &str, backtrace: &str, recent: &[String]) -> String {
+    let thread = std::thread::current();
+    let mut text = String::new();
+    let _ = writeln!(text, "lado {} crashed", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(
+        text,
+        "os: {} {}, libgit2: {:?}",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        git2::Version::get().libgit2_version()
+    );
+    let _ = writeln!(
+        text,
+        "panicked in thread {} at {}:\n{}",
+        thread.name().unwrap_or("<unnamed>"),
+        location,
+        crate::logging::redact(message)
+    );
+    let _ = writeln!(text, "\nbacktrace:\n{}", backtrace.trim_end());
+    let _ = writeln!(text, "\nlast log lines:");
+    for line in recent {
+        let _ = writeln!(text, "{}", line);
+    }
+    text
+}
+
+/// The newest crash report not pointed to yet, which from now on counts as
+/// pointed to.
+pub fn take_unreported() -> Option<PathBuf> {
+    take_unreported_in(&cache_dir()?)
+}
+
+fn take_unreported_in(dir: &Path) -> Option<PathBuf> {
+    // Names start with the time, so the newest sorts last
+    let newest = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.starts_with("crash-") && name.ends_with(".log"))
+        .max()?;
+    let seen = dir.join(SEEN_FILE);
+    if std::fs::read_to_string(&seen).is_ok_and(|name| name == newest) {
+        return None;
+    }
+    if let Err(e) = std::fs::write(&seen, &newest) {
+        eprintln!("Warning: Could not save {}: {}", seen.display(), e);
+    }
+    Some(dir.join(newest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lado_core::test_support::TempDir;
+
+    #[test]
+    fn test_report() {
+        let recent = vec!["0.100s DEBUG github: running gh".to_string()];
+        let text = report(
+            "token ghp_abc123 expired",
+            "src/app.rs:10",
+            "0: lado::main",
+            &recent,
+        );
+        assert!(text.starts_with(&format!("lado {} crashed\n", env!("CARGO_PKG_VERSION"))));
+        assert!(text.contains("at src/app.rs:10:\ntoken ghp_*** expired\n"));
+        assert!(text.contains("\nbacktrace:\n0: lado::main\n"));
+        assert!(text.ends_with("\nlast log lines:\n0.100s DEBUG github: running gh\n"));
+    }
+
+    #[test]
+    fn test_take_unreported() {
+        let temp = TempDir::new("crash-test");
+        let dir = temp.path();
+        assert_eq!(take_unreported_in(dir), None);
+
+        std::fs::write(dir.join("crash-1700000000-1.log"), "old").unwrap();
+        std::fs::write(dir.join("crash-1800000000-2.log"), "new").unwrap();
+        assert_eq!(
+            take_unreported_in(dir),
+            Some(dir.join("crash-1800000000-2.log"))
+        );
+        // Only pointed to once
+        assert_eq!(take_unreported_in(dir), None);
+    }
+}
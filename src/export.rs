@@ -0,0 +1,454 @@
+//! Headless export (`--export html|pdf|patch`): the diff written to a file
+//! or stdout instead of shown in a window, e.g. as a CI artifact. The HTML
+//! and PDF reports are also what the window's Export menu saves.
+
+use crate::cli::{Args, DiffTarget};
+use crate::git::{
+    build_file_tree, DiffData, DiffHunk, DiffLineType, FileChange, FileStatus, FileTreeNode,
+};
+use crate::github::{self, FileComments, PrComment};
+use crate::highlighting::{HighlightedLine, Highlighter};
+use crate::pdf;
+use crate::target;
+use crate::timestamps;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+/// Format of `--export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Self-contained HTML page with syntax highlighting
+    Html,
+    /// Paginated PDF for printing, each page headed by what was reviewed
+    /// (only the `--file` if given)
+    Pdf,
+    /// Unified diff, as `git diff` prints it
+    Patch,
+}
+
+/// Compute the diff of the target and write it out in `format`.
+pub fn run(args: &Args, format: ExportFormat) -> Result<()> {
+    let target = DiffTarget::from_args(args);
+    let dir = std::env::current_dir()?;
+    let repo = target::open_repository(&dir, &target)?;
+    let mut data = target::load_diff(&dir, &repo, &target)?;
+
+    let output = match format {
+        ExportFormat::Patch => to_patch(&data).into_bytes(),
+        ExportFormat::Html | ExportFormat::Pdf => {
+            let mut config = crate::config::load();
+            args.overrides.apply(&mut config);
+            data.expand_tabs(config.tab_width as usize);
+            let title = target::title(&dir, &repo, &target)?;
+            let comments = match target {
+                DiffTarget::PullRequest(pr) => match github::get_pr_comments(&dir, pr) {
+                    Ok(comments) => Some(github::group_comments_by_file(comments)),
+                    Err(e) => {
+                        eprintln!("Warning: Could not fetch PR comments: {}", e);
+                        None
+                    }
+                },
+                _ => None,
+            };
+            let mut highlighter = Highlighter::new();
+            if format == ExportFormat::Pdf {
+                // Paper is white
+                highlighter.set_theme("light");
+                let header = pdf::Header::new(&repo, title, target::commits(&dir, &repo, &target)?);
+                let only_file = args.file_location().map(|(path, _)| path);
+                pdf::render(
+                    &data,
+                    only_file.as_deref(),
+                    &header,
+                    &highlighter,
+                    comments.as_ref(),
+                )
+            } else {
+                highlighter.set_theme(config.syntax_theme.as_ref().unwrap_or(&config.ui_theme));
+                to_html(
+                    &data,
+                    &title,
+                    &highlighter,
+                    &config.ui_theme,
+                    comments.as_ref(),
+                )
+                .into_bytes()
+            }
+        }
+    };
+
+    match &args.output {
+        Some(path) => std::fs::write(path, output)
+            .with_context(|| format!("Failed to write {}", path.display())),
+        None => Ok(std::io::stdout().write_all(&output)?),
+    }
+}
+
+/// The diff as a unified diff that `git apply` accepts.
+pub fn to_patch(data: &DiffData) -> String {
+    let mut out = String::new();
+    for file in &data.files {
+        let path = &file.path;
+        let (old, new) = match file.status {
+            FileStatus::Added => ("/dev/null".to_string(), format!("b/{}", path)),
+            FileStatus::Deleted => (format!("a/{}", path), "/dev/null".to_string()),
+            _ => (format!("a/{}", path), format!("b/{}", path)),
+        };
+        let _ = writeln!(out, "diff --git a/{} b/{}", path, path);
+        match file.status {
+            FileStatus::Added => out.push_str("new file mode 100644\n"),
+            FileStatus::Deleted => out.push_str("deleted file mode 100644\n"),
+            _ => {}
+        }
+        let hunks = data.file_hunks.get(path).map_or(&[][..], Vec::as_slice);
+        if hunks.is_empty() {
+            continue;
+        }
+        let _ = writeln!(out, "--- {}\n+++ {}", old, new);
+        for hunk in hunks {
+            let _ = writeln!(out, "{}", hunk.header.trim_end());
+            for line in &hunk.lines {
+                let prefix = match line.line_type {
+                    DiffLineType::Add => '+',
+                    DiffLineType::Remove => '-',
+                    _ => ' ',
+                };
+                let _ = writeln!(out, "{}{}", prefix, line.content);
+            }
+        }
+    }
+    out
+}
+
+/// Page colors per UI theme, the same as in ui/theme.slint (and those of
+/// `--print`):
+/// background, text, muted text, border, added, removed, hunk background,
+/// hunk text.
+pub fn page_colors(ui_theme: &str) -> [&'static str; 8] {
+    match ui_theme {
+        "light" => [
+            "#ffffff", "#1f2328", "#8c959f", "#d0d7de", "#e6ffec", "#ffebe9", "#f0f6fc", "#0550ae",
+        ],
+        "solarized-dark" => [
+            "#002b36", "#839496", "#586e75", "#586e75", "#073626", "#30262a", "#073642", "#268bd2",
+        ],
+        "solarized-light" => [
+            "#fdf6e3", "#556b72", "#96a7a9", "#e1dbcd", "#e4f0d0", "#fce8e4", "#eee8d5", "#268bd2",
+        ],
+        _ => [
+            "#1a1b1e", "#e4e4e7", "#6b6b73", "#2e2f33", "#1a2e1a", "#2e1a1a", "#252830", "#7090b0",
+        ],
+    }
+}
+
+/// The diff as one HTML page, highlighted like in the window: the file tree
+/// linking to each file, then the files with the review `comments` below
+/// the lines they are on.
+pub fn to_html(
+    data: &DiffData,
+    title: &str,
+    highlighter: &Highlighter,
+    ui_theme: &str,
+    comments: Option<&FileComments>,
+) -> String {
+    let [bg, fg, muted, border, add, remove, hunk_bg, hunk_fg] = page_colors(ui_theme);
+    let files: Vec<String> = data
+        .files
+        .par_iter()
+        .enumerate()
+        .map(|(index, file)| {
+            let hunks = data
+                .file_hunks
+                .get(&file.path)
+                .map_or(&[][..], Vec::as_slice);
+            let file_comments = comments
+                .and_then(|c| c.get(&file.path))
+                .map_or(&[][..], Vec::as_slice);
+            html_file(index, file, hunks, file_comments, highlighter)
+        })
+        .collect();
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ background: {bg}; color: {fg}; font-family: sans-serif; margin: 2em; }}
+section {{ border: 1px solid {border}; margin-bottom: 2em; }}
+h2 {{ font-size: 1em; margin: 0; padding: 0.5em; border-bottom: 1px solid {border}; }}
+.stat {{ color: {muted}; font-weight: normal; margin-left: 1em; }}
+table {{ border-collapse: collapse; width: 100%; font-family: monospace; }}
+td {{ padding: 0 0.5em; white-space: pre; vertical-align: top; }}
+td.num {{ color: {muted}; text-align: right; user-select: none; width: 1%; }}
+tr.add {{ background: {add}; }}
+tr.remove {{ background: {remove}; }}
+tr.hunk {{ background: {hunk_bg}; color: {hunk_fg}; }}
+a {{ color: {hunk_fg}; text-decoration: none; }}
+nav {{ border: 1px solid {border}; margin-bottom: 2em; padding: 0.5em 1em; font-family: monospace; }}
+nav ul {{ list-style: none; padding-left: 1.5em; margin: 0; }}
+nav > ul {{ padding-left: 0; }}
+.comment {{ border: 1px solid {border}; border-radius: 4px; margin: 0.3em 0; padding: 0.3em 0.6em; font-family: sans-serif; white-space: pre-wrap; }}
+.comment .meta {{ color: {muted}; font-size: 0.85em; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+"#,
+        title = escape(title),
+    );
+    let anchors: HashMap<&str, usize> = data
+        .files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| (file.path.as_str(), index))
+        .collect();
+    out.push_str("<nav>\n");
+    html_tree(&mut out, &build_file_tree(&data.files), &anchors);
+    out.push_str("</nav>\n");
+    for file in files {
+        out.push_str(&file);
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Name of the report file with `extension` for a diff titled `title`, e.g.
+/// `lado-PR-42-Fix-the-parser.html`
+pub fn report_file_name(title: &str, extension: &str) -> String {
+    let words: Vec<&str> = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    format!("lado-{}.{}", words.join("-"), extension)
+}
+
+/// The file tree as nested lists, each file linking to its section.
+fn html_tree(out: &mut String, nodes: &[FileTreeNode], anchors: &HashMap<&str, usize>) {
+    out.push_str("<ul>\n");
+    for node in nodes {
+        if node.is_folder {
+            let _ = writeln!(out, "<li>{}/", escape(&node.name));
+            html_tree(out, &node.children, anchors);
+            out.push_str("</li>\n");
+        } else if let Some(index) = anchors.get(node.path.as_str()) {
+            let _ = writeln!(
+                out,
+                "<li><a href=\"#file-{}\">{}</a></li>",
+                index,
+                escape(&node.name)
+            );
+        }
+    }
+    out.push_str("</ul>\n");
+}
+
+/// One file's `<section>`, `index` being its place in the diff.
+fn html_file(
+    index: usize,
+    file: &FileChange,
+    hunks: &[DiffHunk],
+    comments: &[PrComment],
+    highlighter: &Highlighter,
+) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<section id=\"file-{}\">\n<h2>{}<span class=\"stat\">{} +{} −{}</span></h2>\n<table>",
+        index,
+        escape(&file.path),
+        file.status.as_str(),
+        file.additions,
+        file.deletions
+    );
+
+    let source: String = hunks
+        .iter()
+        .flat_map(|h| &h.lines)
+        .map(|line| format!("{}\n", line.content))
+        .collect();
+    let mut highlighted = highlighter.highlight(&source, &file.path).into_iter();
+    let comment_index = github::index_comments_by_line(comments);
+    let num = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+    for hunk in hunks {
+        let _ = writeln!(
+            out,
+            "<tr class=\"hunk\"><td class=\"num\"></td><td class=\"num\"></td><td>{}</td></tr>",
+            escape(hunk.header.trim_end())
+        );
+        for line in &hunk.lines {
+            let class = match line.line_type {
+                DiffLineType::Add => "add",
+                DiffLineType::Remove => "remove",
+                _ => "context",
+            };
+            let code = match highlighted.next() {
+                Some(spans) => html_spans(&spans),
+                None => escape(&line.content),
+            };
+            let _ = writeln!(
+                out,
+                "<tr class=\"{}\"><td class=\"num\">{}</td><td class=\"num\">{}</td><td>{}</td></tr>",
+                class,
+                num(line.old_line_num),
+                num(line.new_line_num),
+                code
+            );
+
+            // The comments on this line, as the window shows them
+            let mut line_comments: Vec<&PrComment> = [
+                line.new_line_num.map(|n| (github::CommentSide::Right, n)),
+                line.old_line_num.map(|n| (github::CommentSide::Left, n)),
+            ]
+            .into_iter()
+            .flatten()
+            .filter_map(|key| comment_index.get(&key))
+            .flatten()
+            .copied()
+            .collect();
+            line_comments.sort_by_key(|c| (c.line, c.id));
+            if !line_comments.is_empty() {
+                out.push_str("<tr><td class=\"num\"></td><td class=\"num\"></td><td>");
+                for comment in line_comments {
+                    html_comment(&mut out, comment);
+                }
+                out.push_str("</td></tr>\n");
+            }
+        }
+    }
+    out.push_str("</table>\n");
+    // Comments on lines the diff no longer has
+    for comment in comments.iter().filter(|c| c.line.is_none()) {
+        html_comment(&mut out, comment);
+    }
+    out.push_str("</section>\n");
+    out
+}
+
+fn html_comment(out: &mut String, comment: &PrComment) {
+    let _ = writeln!(
+        out,
+        "<div class=\"comment\"><div class=\"meta\">{} · {}</div>{}</div>",
+        escape(&comment.author),
+        escape(&timestamps::absolute(&comment.created_at)),
+        escape(comment.body.trim_end())
+    );
+}
+
+fn html_spans(line: &HighlightedLine) -> String {
+    let mut out = String::new();
+    for span in &line.spans {
+        let _ = write!(
+            out,
+            "<span style=\"color:{}\">{}</span>",
+            span.color,
+            escape(&span.text)
+        );
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::parse_patch;
+
+    const PATCH: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,2 @@ mod a;
+ fn a() {}
+-fn b() {}
++fn b() -> u8 { 1 }
+diff --git a/notes.txt b/notes.txt
+new file mode 100644
+--- /dev/null
++++ b/notes.txt
+@@ -0,0 +1 @@
++<hello>
+";
+
+    #[test]
+    fn test_patch_roundtrip() {
+        let data = parse_patch(PATCH).unwrap();
+        assert_eq!(to_patch(&data), PATCH);
+    }
+
+    #[test]
+    fn test_html_escapes_content() {
+        let data = parse_patch(PATCH).unwrap();
+        let html = to_html(&data, "a & b", &Highlighter::new(), "dark", None);
+        assert!(html.contains("<title>a &amp; b</title>"));
+        assert!(html.contains("&lt;hello&gt;"));
+        assert!(html.contains("<tr class=\"add\">"));
+        assert!(!html.contains("<hello>"));
+    }
+
+    #[test]
+    fn test_report_file_name() {
+        assert_eq!(
+            report_file_name("PR #42: Fix the parser", "html"),
+            "lado-PR-42-Fix-the-parser.html"
+        );
+        assert_eq!(
+            report_file_name("main..feature/x", "pdf"),
+            "lado-main-feature-x.pdf"
+        );
+    }
+
+    #[test]
+    fn test_html_tree_and_comments() {
+        let data = parse_patch(PATCH).unwrap();
+        let comment = |id, line, body: &str| PrComment {
+            id,
+            in_reply_to_id: None,
+            path: "src/lib.rs".to_string(),
+            line,
+            side: github::CommentSide::Right,
+            body: body.to_string(),
+            author: "ada".to_string(),
+            created_at: "not a time".to_string(),
+            commit_id: String::new(),
+            original_commit_id: String::new(),
+            resolved: false,
+        };
+        let comments = github::group_comments_by_file(vec![
+            comment(1, Some(2), "Why <u8>?"),
+            comment(2, None, "Outdated"),
+        ]);
+        let html = to_html(&data, "t", &Highlighter::new(), "dark", Some(&comments));
+        assert!(html.contains("<li>src/\n<ul>\n<li><a href=\"#file-0\">lib.rs</a></li>"));
+        assert!(html.contains("<section id=\"file-1\">"));
+        assert!(html.contains("ada · not a time</div>Why &lt;u8&gt;?</div>"));
+        // After the line it is on, before the next file
+        let (line, comment, outdated) = (
+            html.find("<tr class=\"add\">").unwrap(),
+            html.find("Why &lt;u8").unwrap(),
+            html.find("Outdated").unwrap(),
+        );
+        assert!(line < comment && comment < outdated);
+        assert!(outdated < html.find("id=\"file-1\"").unwrap());
+    }
+}
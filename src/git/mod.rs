@@ -1,10 +0,0 @@
-mod diff;
-mod file_tree;
-mod repository;
-
-pub use diff::{CommentData, DiffData, DiffHunk, DiffLine, DiffLineType};
-pub use file_tree::{
-    build_file_tree, collect_folder_paths, collect_folder_paths_under, flatten_tree_with_state,
-    FileTreeNode, FlatFileEntry,
-};
-pub use repository::Repository;
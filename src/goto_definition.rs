@@ -0,0 +1,132 @@
+//! Go to definition: Ctrl+click on a symbol in the new version of a file
+//! asks the language server configured for its extension where the symbol
+//! is defined, e.g. in config.toml:
+//!
+//! ```toml
+//! [language_servers]
+//! rs = "rust-analyzer"
+//! go = "gopls"
+//! ```
+//!
+//! Servers start with the window, so they can index the project while the
+//! review begins, and each runs on a thread of its own.
+
+use crate::lsp::{LanguageServer, Location};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// A symbol to look up
+pub struct Query {
+    /// Absolute path of the file
+    pub path: PathBuf,
+    /// Content of the file the position refers to
+    pub text: String,
+    /// 0-based line
+    pub line: u32,
+    /// 0-based, in UTF-16 code units
+    pub character: u32,
+}
+
+type Reply = async_channel::Sender<Result<Vec<Location>>>;
+
+/// The configured language servers, stopped when dropped.
+pub struct Servers {
+    by_extension: HashMap<String, mpsc::Sender<(Query, Reply)>>,
+}
+
+impl Servers {
+    /// Start the servers of `commands` (by file extension) for the project
+    /// in `root`. `None` if there are none.
+    pub fn start(commands: &HashMap<String, String>, root: &Path) -> Option<Self> {
+        if commands.is_empty() {
+            return None;
+        }
+        // Extensions with the same command share its server
+        let mut by_command: HashMap<&str, mpsc::Sender<(Query, Reply)>> = HashMap::new();
+        let by_extension = commands
+            .iter()
+            .map(|(extension, command)| {
+                let sender = by_command
+                    .entry(command)
+                    .or_insert_with(|| spawn(command.clone(), root.to_path_buf()));
+                (
+                    extension.trim_start_matches('.').to_string(),
+                    sender.clone(),
+                )
+            })
+            .collect();
+        Some(Self { by_extension })
+    }
+
+    /// Look up where `query` is defined; the answer arrives on the returned
+    /// channel. `None` if no server handles the file.
+    pub fn definition(
+        &self,
+        query: Query,
+    ) -> Option<async_channel::Receiver<Result<Vec<Location>>>> {
+        let extension = query.path.extension().and_then(|e| e.to_str());
+        let sender = self.by_extension.get(extension?)?;
+        let (reply, answer) = async_channel::bounded(1);
+        sender.send((query, reply)).ok()?;
+        Some(answer)
+    }
+}
+
+fn spawn(command: String, root: PathBuf) -> mpsc::Sender<(Query, Reply)> {
+    let (sender, receiver) = mpsc::channel::<(Query, Reply)>();
+    std::thread::spawn(move || {
+        let mut server = match LanguageServer::start(&command, &root) {
+            Ok(server) => server,
+            Err(e) => {
+                let message = format!("{:#}", e);
+                eprintln!("Warning: {}", message);
+                for (_, reply) in receiver {
+                    let _ = reply.send_blocking(Err(anyhow!("{}", message)));
+                }
+                return;
+            }
+        };
+        // Ends when the `Servers` are dropped
+        for (query, reply) in receiver {
+            let locations =
+                server.definition(&query.path, &query.text, query.line, query.character);
+            // A closed channel means nobody waits for the answer anymore
+            let _ = reply.send_blocking(locations);
+        }
+    });
+    sender
+}
+
+/// The position, in UTF-16 code units as language servers count, of the
+/// character shown at `column` of `line` once its tabs are `tab_width`
+/// spaces. `None` past its end.
+pub fn utf16_column(line: &str, column: usize, tab_width: usize) -> Option<u32> {
+    let mut shown = 0;
+    let mut utf16 = 0;
+    for c in line.chars() {
+        shown += if c == '\t' { tab_width } else { 1 };
+        if shown > column {
+            return Some(utf16);
+        }
+        utf16 += c.len_utf16() as u32;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf16_column() {
+        assert_eq!(utf16_column("let x = 1;", 4, 4), Some(4));
+        // A tab shown as 4 spaces
+        assert_eq!(utf16_column("\tfoo()", 2, 4), Some(0));
+        assert_eq!(utf16_column("\tfoo()", 4, 4), Some(1));
+        // 𝒳 is two UTF-16 code units
+        assert_eq!(utf16_column("𝒳 + bar", 4, 4), Some(5));
+        assert_eq!(utf16_column("short", 5, 4), None);
+    }
+}
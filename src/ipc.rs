@@ -0,0 +1,456 @@
+//! `--serve`: one lado process for all windows, driven over a local socket.
+//! `lado <target>` run elsewhere hands its arguments (and working directory)
+//! over to the serving instance, which opens the diff in a new window instead
+//! of starting another process. Editor extensions use the same socket to
+//! drive lado as their review surface.
+//!
+//! Each diff gets a window of its own rather than a tab of one window: a
+//! window holds the state of one diff (its tree, comments, viewed marks and
+//! watcher), and the window manager already switches between windows. What
+//! is shared is the process, its caches and the single instance to talk to.
+//!
+//! The socket speaks JSON-RPC 2.0, one message per line, any number of them
+//! per connection. Methods:
+//!
+//! - `open {"cwd": "...", "args": ["main", "--file", "src/app.rs"]}`: open a
+//!   window as `lado main --file src/app.rs` in `cwd` would. Returns
+//!   `{"window": 1}`.
+//! - `goto {"file": "src/app.rs", "line": 120}`: show a file of the diff and
+//!   scroll to a line of its new side (`line` is optional, and `file` may be
+//!   `src/app.rs:120` as with `--file`).
+//! - `list-comments {}`: the PR's review comments, as plugins get them (plus
+//!   their `id`).
+//!
+//! `goto` and `list-comments` act on the newest open window, or the one
+//! given as `"window"`. Only Unix has the socket; elsewhere `--serve` is an
+//! error and every `lado` opens its own window.
+
+use crate::app::App;
+use crate::cli::Args;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Anything going wrong while handling a valid call
+const CALL_FAILED: i64 = -32000;
+
+thread_local! {
+    /// Windows of this process, by number; closed ones stay in place
+    static WINDOWS: RefCell<Vec<App>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    /// Absent for notifications, which get no answer
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenParams {
+    cwd: PathBuf,
+    /// Command line arguments, without the program name
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GotoParams {
+    file: String,
+    line: Option<u32>,
+    window: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WindowParams {
+    window: Option<usize>,
+}
+
+/// A JSON-RPC error
+#[derive(Debug)]
+struct Error {
+    code: i64,
+    message: String,
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        Error {
+            code: CALL_FAILED,
+            message: format!("{:#}", e),
+        }
+    }
+}
+
+/// Socket of the serving instance: in the runtime directory where there is
+/// one (`$XDG_RUNTIME_DIR`), or lado's config directory.
+fn socket_path() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .or_else(crate::config::config_dir)
+        .map(|dir| dir.join("lado.sock"))
+}
+
+/// Whether the serving instance can open `args`' diff: not for a patch on
+/// stdin or the PR picked with `lado pr list`, which only this process has.
+pub fn can_forward(args: &Args) -> bool {
+    let picked_pr =
+        matches!(&args.command, Some(crate::cli::Command::Pr(pr)) if pr.command.is_some());
+    !args.stdin && args.diff.target.as_deref() != Some("-") && !picked_pr
+}
+
+/// Have the serving instance open a window for `args` (the command line
+/// without the program name), run in the directory `-C` went into already.
+/// `false` if there is none listening.
+#[cfg(unix)]
+pub fn forward(args: Vec<String>) -> Result<bool> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let Some(path) = socket_path() else {
+        return Ok(false);
+    };
+    let Ok(mut stream) = UnixStream::connect(&path) else {
+        return Ok(false);
+    };
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "open",
+        "params": {"cwd": std::env::current_dir()?, "args": without_directory(args)},
+    });
+    writeln!(stream, "{}", request)?;
+
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    if answer.is_empty() {
+        return Err(anyhow!("The lado serving windows closed the connection"));
+    }
+    let answer: Value = serde_json::from_str(&answer)?;
+    match answer["error"]["message"].as_str() {
+        Some(message) => Err(anyhow!("{}", message)),
+        None => Ok(true),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn forward(_args: Vec<String>) -> Result<bool> {
+    Ok(false)
+}
+
+/// `args` without `-C`/`--repo`, which the working directory sent along
+/// with them already is
+#[cfg(unix)]
+fn without_directory(args: Vec<String>) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            kept.push(arg);
+            kept.extend(args.by_ref());
+        } else if arg == "-C" || arg == "--repo" {
+            args.next();
+        } else if !(arg.starts_with("-C") || arg.starts_with("--repo=")) {
+            kept.push(arg);
+        }
+    }
+    kept
+}
+
+/// The socket being served; removed when dropped
+pub struct Serving {
+    path: PathBuf,
+}
+
+impl Drop for Serving {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Listen for other invocations and editors, with `app` as the first
+/// window, until the returned [`Serving`] is dropped. Their calls are
+/// handled by the event loop.
+#[cfg(unix)]
+pub fn serve(app: &App) -> Result<Serving> {
+    use anyhow::Context;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let path = socket_path().ok_or_else(|| anyhow!("No directory for lado's socket"))?;
+    if UnixStream::connect(&path).is_ok() {
+        return Err(anyhow!(
+            "Another lado is already serving at {}",
+            path.display()
+        ));
+    }
+    // Left behind by a lado that didn't exit cleanly
+    let _ = std::fs::remove_file(&path);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("Failed to listen on {}", path.display()))?;
+    tracing::debug!(socket = %path.display(), "serving");
+    let serving = Serving { path };
+    WINDOWS.with(|windows| windows.borrow_mut().push(app.clone()));
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            // Editors keep their connection open
+            std::thread::spawn(move || {
+                let mut writer = &stream;
+                for line in BufReader::new(&stream).lines() {
+                    let Ok(line) = line else { break };
+                    if let Some(answer) = handle_line(&line, call_in_event_loop) {
+                        if writeln!(writer, "{}", answer).is_err() {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    Ok(serving)
+}
+
+#[cfg(not(unix))]
+pub fn serve(_app: &App) -> Result<Serving> {
+    Err(anyhow!("--serve is only supported on Unix"))
+}
+
+/// The answer to the JSON-RPC message `line`, with `call` handling valid
+/// calls; `None` for notifications.
+fn handle_line(
+    line: &str,
+    call: impl FnOnce(String, Value) -> Result<Value, Error>,
+) -> Option<Value> {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": {"code": PARSE_ERROR, "message": e.to_string()},
+            }))
+        }
+    };
+    let result = call(request.method, request.params);
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": e.code, "message": e.message},
+        }),
+    })
+}
+
+/// Handle a call on the event loop, where the windows live, and wait for it.
+fn call_in_event_loop(method: String, params: Value) -> Result<Value, Error> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slint::invoke_from_event_loop(move || {
+        let _ = sender.send(call(&method, params));
+    })
+    .map_err(anyhow::Error::from)?;
+    receiver
+        .recv()
+        .map_err(|_| anyhow!("lado is shutting down"))?
+}
+
+fn call(method: &str, params: Value) -> Result<Value, Error> {
+    match method {
+        "open" => open(parse(params)?),
+        "goto" => {
+            let params: GotoParams = parse(params)?;
+            let (file, line) = match (params.line, params.file.rsplit_once(':')) {
+                (None, Some((file, line))) if line.parse::<u32>().is_ok() => {
+                    (file.to_string(), line.parse().ok())
+                }
+                _ => (params.file, params.line),
+            };
+            window(params.window)?.goto(&file, line)?;
+            Ok(Value::Null)
+        }
+        "list-comments" => {
+            let params: WindowParams = parse(params)?;
+            let comments: Vec<Value> = window(params.window)?
+                .comments()
+                .iter()
+                .map(|comment| {
+                    let mut json = crate::plugins::comment_json(comment);
+                    json["id"] = comment.id.into();
+                    json
+                })
+                .collect();
+            Ok(comments.into())
+        }
+        _ => Err(Error {
+            code: METHOD_NOT_FOUND,
+            message: format!("Unknown method {:?}", method),
+        }),
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, Error> {
+    // Methods without required parameters may leave them out
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| Error {
+        code: INVALID_PARAMS,
+        message: e.to_string(),
+    })
+}
+
+/// Window `number`, or the newest open one.
+fn window(number: Option<usize>) -> Result<App> {
+    WINDOWS.with(|windows| {
+        let windows = windows.borrow();
+        match number {
+            Some(n) => windows
+                .get(n)
+                .cloned()
+                .ok_or_else(|| anyhow!("No window {}", n)),
+            None => windows
+                .iter()
+                .rev()
+                .find(|app| app.is_visible())
+                .cloned()
+                .ok_or_else(|| anyhow!("No window is open")),
+        }
+    })
+}
+
+/// Open a window for `params`, in its working directory.
+fn open(params: OpenParams) -> Result<Value, Error> {
+    use clap::Parser;
+
+    let mut args = Args::try_parse_from(std::iter::once("lado".to_string()).chain(params.args))
+        .map_err(|e| Error {
+            code: INVALID_PARAMS,
+            message: e.to_string(),
+        })?;
+    let config = crate::config::load();
+    args.overrides.use_profile(&config)?;
+    args.offline |= config.offline;
+
+    let dir = working_dir(&params.cwd, &args);
+    if !dir.is_dir() {
+        return Err(anyhow!("Failed to open {}", dir.display()).into());
+    }
+    let app = App::new(args, dir)?;
+    app.show()?;
+    let number = WINDOWS.with(|windows| {
+        let mut windows = windows.borrow_mut();
+        windows.push(app);
+        windows.len() - 1
+    });
+    Ok(json!({ "window": number }))
+}
+
+/// Where to open `args` sent from `cwd`: relative paths and the repository
+/// are those of the caller, in the caller's `-C` if given
+fn working_dir(cwd: &Path, args: &Args) -> PathBuf {
+    match &args.directory {
+        Some(dir) => cwd.join(dir),
+        None => cwd.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_can_forward() {
+        let forwards = |args: &[&str]| can_forward(&Args::parse_from(args));
+        assert!(forwards(&["lado", "main"]));
+        assert!(forwards(&["lado", "pr", "42"]));
+        assert!(!forwards(&["lado", "-"]));
+        assert!(!forwards(&["lado", "--stdin"]));
+        assert!(!forwards(&["lado", "pr", "list"]));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_forward_without_directory() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        for given in [
+            args(&["-C", "sub", "main"]),
+            args(&["-Csub", "main"]),
+            args(&["main", "--repo", "sub"]),
+            args(&["--repo=sub", "main"]),
+        ] {
+            let forwarded = without_directory(given);
+            assert_eq!(forwarded, args(&["main"]));
+
+            // The serving side opens it in the caller's directory only
+            let params: OpenParams = serde_json::from_value(
+                json!({"cwd": "/src/project/sub", "args": forwarded}),
+            )
+            .unwrap();
+            let parsed =
+                Args::parse_from(std::iter::once("lado".to_string()).chain(params.args));
+            assert_eq!(parsed.directory, None);
+            assert_eq!(parsed.diff.target.as_deref(), Some("main"));
+            assert_eq!(
+                working_dir(&params.cwd, &parsed),
+                Path::new("/src/project/sub")
+            );
+        }
+        // A request that still has `-C` goes into it once
+        let parsed = Args::parse_from(["lado", "-C", "sub", "main"]);
+        assert_eq!(
+            working_dir(Path::new("/src/project"), &parsed),
+            Path::new("/src/project/sub")
+        );
+        assert_eq!(
+            without_directory(args(&["--file", "x", "--", "-C"])),
+            args(&["--file", "x", "--", "-C"])
+        );
+    }
+
+    #[test]
+    fn test_handle_line() {
+        let answer = handle_line(
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "goto", "params": {"file": "a.rs"}}"#,
+            |method, params| {
+                assert_eq!((method.as_str(), &params["file"]), ("goto", &json!("a.rs")));
+                Ok(Value::Null)
+            },
+        );
+        assert_eq!(
+            answer,
+            Some(json!({"jsonrpc": "2.0", "id": 7, "result": null}))
+        );
+
+        let answer = handle_line(
+            r#"{"jsonrpc": "2.0", "id": "x", "method": "nope"}"#,
+            |m, p| call(&m, p),
+        )
+        .unwrap();
+        assert_eq!(answer["id"], "x");
+        assert_eq!(answer["error"]["code"], METHOD_NOT_FOUND);
+
+        // Notifications get no answer, broken messages do
+        let notification = r#"{"jsonrpc": "2.0", "method": "goto"}"#;
+        assert_eq!(handle_line(notification, |_, _| Ok(Value::Null)), None);
+        let answer = handle_line("{", |_, _| unreachable!()).unwrap();
+        assert_eq!(answer["error"]["code"], PARSE_ERROR);
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let error = call("goto", json!({"line": 3})).unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+}
@@ -0,0 +1,144 @@
+//! Guard against rendering huge file diffs by accident.
+//!
+//! A file whose diff is larger than the configured limits, or that is
+//! generated, is shown as a placeholder until the user asks for it. Once asked for, its rows are
+//! streamed into the view a batch at a time so the window stays responsive.
+
+use crate::git::{DiffHunk, DiffLineType};
+use std::collections::HashSet;
+
+/// Diff lines added to the view per event loop turn while streaming a file.
+pub const STREAM_LINES: usize = 2000;
+
+/// Size limits for rendering a file's diff without asking first.
+pub struct LargeFileGuard {
+    /// Changed (added + removed) lines; 0 = no limit
+    max_lines: usize,
+    /// Bytes of diff text; 0 = no limit
+    max_bytes: usize,
+    /// Files the user chose to load anyway
+    allowed: HashSet<String>,
+}
+
+impl LargeFileGuard {
+    pub fn new(max_lines: i32, max_bytes: i32) -> Self {
+        Self {
+            max_lines: max_lines.max(0) as usize,
+            max_bytes: max_bytes.max(0) as usize,
+            allowed: HashSet::new(),
+        }
+    }
+
+    /// Placeholder text for `path` if it is `generated` or over the limits,
+    /// and hasn't been loaded anyway.
+    pub fn notice(&self, path: &str, generated: bool, hunks: &[DiffHunk]) -> Option<String> {
+        if self.allowed.contains(path) {
+            return None;
+        }
+        if generated {
+            return Some("This file is generated — load anyway?".to_string());
+        }
+
+        let lines = hunks.iter().flat_map(|h| &h.lines);
+        let changed = lines
+            .clone()
+            .filter(|l| matches!(l.line_type, DiffLineType::Add | DiffLineType::Remove))
+            .count();
+        if self.max_lines > 0 && changed > self.max_lines {
+            return Some(format!(
+                "This file has {} changed lines — load anyway?",
+                format_count(changed)
+            ));
+        }
+
+        let bytes: usize = lines.map(|l| l.content.len()).sum();
+        if self.max_bytes > 0 && bytes > self.max_bytes {
+            return Some(format!(
+                "This file's diff is {:.1} MB — load anyway?",
+                bytes as f64 / 1_000_000.0
+            ));
+        }
+        None
+    }
+
+    /// Stop guarding `path` for the rest of the session.
+    pub fn allow(&mut self, path: &str) {
+        self.allowed.insert(path.to_string());
+    }
+}
+
+/// Format a count with thousands separators, e.g. `48,000`.
+fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffLine;
+
+    fn hunk(added: usize, content: &str) -> DiffHunk {
+        DiffHunk {
+            header: "@@ -0,0 +1 @@".to_string(),
+            old_start: 0,
+            old_lines: 0,
+            new_start: 1,
+            new_lines: added as u32,
+            lines: (0..added)
+                .map(|i| DiffLine {
+                    line_type: DiffLineType::Add,
+                    old_line_num: None,
+                    new_line_num: Some(i as u32 + 1),
+                    content: content.into(),
+                    comment: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(48_000), "48,000");
+        assert_eq!(format_count(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn test_notice_until_allowed() {
+        let mut guard = LargeFileGuard::new(10, 0);
+        let hunks = vec![hunk(48, "x")];
+
+        let notice = guard.notice("big.rs", false, &hunks).unwrap();
+        assert!(notice.contains("48 changed lines"));
+        assert!(guard.notice("big.rs", false, &[hunk(10, "x")]).is_none());
+
+        guard.allow("big.rs");
+        assert!(guard.notice("big.rs", false, &hunks).is_none());
+    }
+
+    #[test]
+    fn test_generated() {
+        let mut guard = LargeFileGuard::new(0, 0);
+        let notice = guard.notice("Cargo.lock", true, &[hunk(1, "x")]).unwrap();
+        assert!(notice.contains("generated"));
+        guard.allow("Cargo.lock");
+        assert!(guard.notice("Cargo.lock", true, &[hunk(1, "x")]).is_none());
+    }
+
+    #[test]
+    fn test_byte_limit() {
+        let guard = LargeFileGuard::new(0, 1000);
+        let long_line = "x".repeat(2000);
+        assert!(guard.notice("min.js", false, &[hunk(1, &long_line)]).is_some());
+        assert!(guard.notice("min.js", false, &[hunk(1, "short")]).is_none());
+    }
+}
@@ -0,0 +1,395 @@
+//! Viewport-scoped syntax highlighting.
+//!
+//! Files without a precomputed highlight are shown with plain-colored rows
+//! right away. Highlighting is then filled in chunk by chunk as rows scroll
+//! into view, so opening a large file never waits on the whole file.
+
+use crate::highlighting::Highlighter;
+use crate::models::{parse_hex_color, wrap_diff_line, DiffLineModel, TextSpanModel};
+use crate::{DiffLine, MainWindow, RowMetrics, TextSpan};
+use slint::{ComponentHandle, Model, ModelRc, VecModel};
+use std::ops::Range;
+use std::rc::Rc;
+
+/// Source lines highlighted together.
+pub const CHUNK_LINES: usize = 100;
+
+/// Preceding source lines fed to the highlighter so a chunk starts with
+/// roughly the right parser state (open strings, comments).
+const CONTEXT_LINES: usize = 50;
+
+/// Rows past either edge of the viewport that are highlighted ahead of time.
+const LOOKAHEAD_ROWS: usize = 60;
+
+/// Model rows showing one code line (add/remove/context). More than one when
+/// wrapped; the model is the only copy of the text.
+#[derive(Debug, Clone)]
+pub struct CodeRows {
+    rows: Range<usize>,
+    /// Whether the line is the added one of paired side-by-side rows, in
+    /// their `new_` fields
+    paired_new: bool,
+}
+
+impl CodeRows {
+    pub fn new(rows: Range<usize>) -> Self {
+        Self {
+            rows,
+            paired_new: false,
+        }
+    }
+
+    /// The added line of the paired rows `rows`
+    pub fn paired_new(rows: Range<usize>) -> Self {
+        Self {
+            rows,
+            paired_new: true,
+        }
+    }
+}
+
+/// Rows of a displayed file whose highlighting is still (partly) pending.
+pub struct LazyHighlight {
+    path: String,
+    model: Rc<VecModel<DiffLine>>,
+    /// Rows of each code line, in source order
+    rows: Vec<CodeRows>,
+    wrap_column: usize,
+    /// Whether each chunk of `CHUNK_LINES` code lines has been highlighted
+    done: Vec<bool>,
+}
+
+impl LazyHighlight {
+    /// `rows[i]` are the model rows showing the file's `i`th code line.
+    pub fn new(
+        path: &str,
+        model: Rc<VecModel<DiffLine>>,
+        rows: Vec<CodeRows>,
+        wrap_column: usize,
+    ) -> Self {
+        let chunks = rows.len().div_ceil(CHUNK_LINES);
+        Self {
+            path: path.to_string(),
+            model,
+            rows,
+            wrap_column,
+            done: vec![false; chunks],
+        }
+    }
+
+    /// Highlight everything near the viewport at `top..top + height`
+    /// (logical pixels of the diff list, whose rows are at `offsets`).
+    pub fn show_viewport(
+        &mut self,
+        top: f32,
+        height: f32,
+        offsets: &RowOffsets,
+        hl: &Highlighter,
+    ) {
+        if self.done.iter().all(|&d| d) {
+            return;
+        }
+        let first = offsets.row_at(top);
+        let last = offsets.row_at(top + height);
+        self.ensure_rows(
+            first.saturating_sub(LOOKAHEAD_ROWS),
+            last + LOOKAHEAD_ROWS,
+            hl,
+        );
+    }
+
+    /// Highlight all chunks touching model rows `first..=last`.
+    pub fn ensure_rows(&mut self, first: usize, last: usize, hl: &Highlighter) {
+        // Code line at or before each row edge
+        let line_at = |row: usize| {
+            self.rows
+                .partition_point(|r| r.rows.start <= row)
+                .saturating_sub(1)
+        };
+        let first_chunk = line_at(first) / CHUNK_LINES;
+        let last_chunk = line_at(last) / CHUNK_LINES;
+
+        for chunk in first_chunk..=last_chunk.min(self.done.len().saturating_sub(1)) {
+            if !self.done[chunk] {
+                self.highlight_chunk(chunk, hl);
+                self.done[chunk] = true;
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self, hl), fields(path = %self.path))]
+    fn highlight_chunk(&self, chunk: usize, hl: &Highlighter) {
+        let start = chunk * CHUNK_LINES;
+        let end = (start + CHUNK_LINES).min(self.rows.len());
+        let context_start = start.saturating_sub(CONTEXT_LINES);
+
+        let lines: Vec<String> = (context_start..end).map(|i| self.line_text(i)).collect();
+        let mut source = String::new();
+        for line in &lines {
+            source.push_str(line);
+            source.push('\n');
+        }
+        let highlighted = hl.highlight(&source, &self.path);
+
+        let chunk_lines = (context_start..end).zip(highlighted);
+        for (i, hl_line) in chunk_lines.skip(start - context_start) {
+            let spans = hl_line
+                .spans
+                .into_iter()
+                .map(|s| TextSpanModel::new(s.text, parse_hex_color(&s.color)))
+                .collect();
+            let code = &self.rows[i];
+            let mut model = self.line_model(code.rows.clone());
+            if code.paired_new {
+                model.new_spans = spans;
+            } else {
+                model.spans = spans;
+            }
+
+            // Only replace the rows if wrapping still yields the same layout
+            let rows = code.rows.clone();
+            let wrapped = wrap_diff_line(model, self.wrap_column);
+            if wrapped.len() == rows.len() {
+                for (row, line) in rows.zip(wrapped) {
+                    self.model.set_row_data(row, line.into());
+                }
+            }
+        }
+    }
+
+    /// Text of code line `i`
+    fn line_text(&self, i: usize) -> String {
+        let code = &self.rows[i];
+        let model = self.line_model(code.rows.clone());
+        if code.paired_new {
+            model.new_content
+        } else {
+            model.content
+        }
+    }
+
+    /// The line (or paired lines) at `rows`, reassembled from the model rows
+    /// it was wrapped into.
+    fn line_model(&self, rows: Range<usize>) -> DiffLineModel {
+        let first = self.model.row_data(rows.start).unwrap_or_default();
+        let mut model = DiffLineModel {
+            line_type: first.line_type.to_string(),
+            old_line_num: first.old_line_num.to_string(),
+            new_line_num: first.new_line_num.to_string(),
+            reviewed: first.reviewed,
+            paired: first.paired,
+            ..Default::default()
+        };
+        let spans = |spans: ModelRc<TextSpan>| {
+            spans
+                .iter()
+                .map(|s| TextSpanModel::new(s.text.to_string(), s.color))
+                .collect::<Vec<_>>()
+        };
+        for row in rows.filter_map(|r| self.model.row_data(r)) {
+            model.content.push_str(&row.content);
+            model.spans.extend(spans(row.spans));
+            model.new_content.push_str(&row.new_content);
+            model.new_spans.extend(spans(row.new_spans));
+        }
+        model
+    }
+}
+
+/// Heights of the rows of the diff views at a font size, from the
+/// `RowMetrics` they are laid out with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowHeights {
+    /// Of a code row
+    pub line: f32,
+    /// Of a comment row without images
+    pub comment: f32,
+    /// Added to a comment row for each image
+    pub comment_image: f32,
+}
+
+impl RowHeights {
+    /// The heights `window` lays rows out with at `font_size`
+    pub fn of(window: &MainWindow, font_size: f32) -> Self {
+        let metrics = window.global::<RowMetrics>();
+        Self {
+            line: font_size * metrics.get_line_height(),
+            comment: metrics.get_comment_height(),
+            comment_image: metrics.get_comment_image_height(),
+        }
+    }
+
+    pub fn row(&self, line: &DiffLine) -> f32 {
+        if line.line_type == "comment" {
+            self.comment + line.comment_images.row_count() as f32 * self.comment_image
+        } else {
+            self.line
+        }
+    }
+}
+
+/// Vertical offsets of the rows of a model, worked out once for it
+#[derive(Default)]
+pub struct RowOffsets {
+    lines: ModelRc<DiffLine>,
+    heights: Option<RowHeights>,
+    /// Offset of the bottom of each row
+    bottoms: Vec<f32>,
+}
+
+impl RowOffsets {
+    pub fn new(lines: ModelRc<DiffLine>, heights: RowHeights) -> Self {
+        let bottoms = lines
+            .iter()
+            .scan(0.0, |top, line| {
+                *top += heights.row(&line);
+                Some(*top)
+            })
+            .collect();
+        Self {
+            lines,
+            heights: Some(heights),
+            bottoms,
+        }
+    }
+
+    /// Whether these are the offsets of `lines` at `heights`. Rows are only
+    /// replaced by rows as high, so the offsets hold while the row count does.
+    pub fn are_of(&self, lines: &ModelRc<DiffLine>, heights: RowHeights) -> bool {
+        self.lines == *lines
+            && self.heights == Some(heights)
+            && self.bottoms.len() == lines.row_count()
+    }
+
+    /// Index of the row displayed at vertical offset `y`
+    pub fn row_at(&self, y: f32) -> usize {
+        self.bottoms
+            .partition_point(|&bottom| bottom <= y)
+            .min(self.bottoms.len().saturating_sub(1))
+    }
+
+    /// Vertical offset at which row `row` is displayed
+    pub fn offset(&self, row: usize) -> f32 {
+        row.checked_sub(1)
+            .and_then(|above| self.bottoms.get(above))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{DiffLine as GitDiffLine, DiffLineType};
+
+    fn plain_file(line_count: usize) -> LazyHighlight {
+        let lines: Vec<GitDiffLine> = (0..line_count)
+            .map(|i| GitDiffLine {
+                line_type: DiffLineType::Add,
+                old_line_num: None,
+                new_line_num: Some(i as u32 + 1),
+                content: format!("let x{} = {};", i, i).into(),
+                comment: None,
+            })
+            .collect();
+        let rows = (0..line_count).map(|i| CodeRows::new(i..i + 1)).collect();
+        let model = Rc::new(VecModel::from(
+            lines
+                .iter()
+                .map(|l| DiffLineModel::from(l).into())
+                .collect::<Vec<DiffLine>>(),
+        ));
+        LazyHighlight::new("test.rs", model, rows, 0)
+    }
+
+    fn has_spans(lazy: &LazyHighlight, row: usize) -> bool {
+        lazy.model.row_data(row).unwrap().spans.row_count() > 0
+    }
+
+    #[test]
+    fn test_only_chunks_in_view_are_highlighted() {
+        let mut lazy = plain_file(3 * CHUNK_LINES);
+        let hl = Highlighter::new();
+
+        lazy.ensure_rows(0, 0, &hl);
+        assert!(has_spans(&lazy, 0));
+        assert!(has_spans(&lazy, CHUNK_LINES - 1));
+        assert!(!has_spans(&lazy, CHUNK_LINES));
+
+        lazy.ensure_rows(2 * CHUNK_LINES + 5, 2 * CHUNK_LINES + 5, &hl);
+        assert!(has_spans(&lazy, 2 * CHUNK_LINES + 5));
+        assert!(!has_spans(&lazy, CHUNK_LINES));
+    }
+
+    #[test]
+    fn test_wrapped_line_keeps_its_text() {
+        let line = GitDiffLine {
+            line_type: DiffLineType::Context,
+            old_line_num: Some(1),
+            new_line_num: Some(1),
+            content: "let long_name = another_long_name;".into(),
+            comment: None,
+        };
+        let rows: Vec<DiffLine> = wrap_diff_line(DiffLineModel::from(&line), 10)
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let row_count = rows.len();
+        let model = Rc::new(VecModel::from(rows));
+        let line_rows = vec![CodeRows::new(0..row_count)];
+        let mut lazy = LazyHighlight::new("test.rs", model, line_rows, 10);
+
+        lazy.ensure_rows(0, 0, &Highlighter::new());
+        assert!(has_spans(&lazy, 0));
+        assert_eq!(lazy.line_text(0), &*line.content);
+        assert_eq!(lazy.model.row_data(0).unwrap().new_line_num, "1");
+    }
+
+    #[test]
+    fn test_paired_lines_are_highlighted_on_their_side() {
+        let mut model = DiffLineModel {
+            line_type: "remove".to_string(),
+            content: "let old = 1;".to_string(),
+            paired: true,
+            new_content: "let new = 2;".to_string(),
+            ..Default::default()
+        };
+        model.old_line_num = "1".to_string();
+        model.new_line_num = "1".to_string();
+        let model = Rc::new(VecModel::from(vec![DiffLine::from(model)]));
+        let rows = vec![CodeRows::new(0..1), CodeRows::paired_new(0..1)];
+        let mut lazy = LazyHighlight::new("test.rs", model, rows, 0);
+
+        assert_eq!(lazy.line_text(1), "let new = 2;");
+        lazy.ensure_rows(0, 0, &Highlighter::new());
+        let row = lazy.model.row_data(0).unwrap();
+        assert!(row.spans.row_count() > 0);
+        assert!(row.new_spans.row_count() > 0);
+        assert_eq!(row.content, "let old = 1;");
+        assert_eq!(row.new_content, "let new = 2;");
+    }
+
+    #[test]
+    fn test_row_offsets() {
+        let lazy = plain_file(10);
+        let heights = RowHeights {
+            line: 17.0,
+            comment: 80.0,
+            comment_image: 244.0,
+        };
+        let lines = ModelRc::from(Rc::clone(&lazy.model));
+        let offsets = RowOffsets::new(lines.clone(), heights);
+        assert_eq!(offsets.row_at(0.0), 0);
+        assert_eq!(offsets.row_at(35.0), 2);
+        assert_eq!(offsets.row_at(10_000.0), 9);
+        assert_eq!(offsets.offset(0), 0.0);
+        assert_eq!(offsets.offset(2), 34.0);
+
+        assert!(offsets.are_of(&lines, heights));
+        let larger = RowHeights { line: 20.0, ..heights };
+        assert!(!offsets.are_of(&lines, larger));
+        lazy.model.push(lazy.model.row_data(0).unwrap());
+        assert!(!offsets.are_of(&lines, heights));
+        assert!(!RowOffsets::default().are_of(&lines, heights));
+    }
+}
@@ -0,0 +1,333 @@
+//! Diagnostic logging for `-v`/`-vv` and `--log-file`.
+//!
+//! Prints the `tracing` events of lado (gh invocations and the like) and,
+//! when each span closes, how long it took: diffing, gh calls, highlighting.
+//! Anything that looks like a GitHub token is redacted, so logs can be
+//! attached to bug reports as they are. Without `-v` the debug log is only
+//! kept in memory, for crash reports.
+
+use crate::profile::Profiler;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+/// Install the logger for this run, writing where `-v` or `--log-file` ask
+/// (or nowhere) and feeding `profiler` too.
+pub fn install(
+    verbosity: u8,
+    log_file: Option<&Path>,
+    profiler: Option<Arc<Profiler>>,
+) -> Result<()> {
+    let out: Box<dyn Write + Send> = match log_file {
+        Some(path) => Box::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("Failed to create log file {}", path.display()))?,
+        ),
+        None if verbosity > 0 => Box::new(std::io::stderr()),
+        None => Box::new(std::io::sink()),
+    };
+    let logger = Logger::new(level_for(verbosity), out, profiler);
+    if tracing::subscriber::set_global_default(logger).is_err() {
+        eprintln!("Warning: Could not enable logging: a tracing subscriber is already set");
+    }
+    Ok(())
+}
+
+/// `-v` logs timings, `-vv` (and a bare `--log-file`, and crash reports)
+/// what happens in between, `-vvv` everything.
+fn level_for(verbosity: u8) -> Level {
+    match verbosity {
+        1 => Level::INFO,
+        0 | 2 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+/// Writes events and span timings as lines of text.
+pub struct Logger {
+    level: Level,
+    out: Mutex<Box<dyn Write + Send>>,
+    started: Instant,
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, OpenSpan>>,
+    /// Gets every span as well, for `--profile`
+    profiler: Option<Arc<Profiler>>,
+}
+
+struct OpenSpan {
+    /// `None` for spans only tracked for the profiler
+    line: Option<String>,
+    refs: usize,
+    entered: Option<Instant>,
+    busy: Duration,
+}
+
+impl Logger {
+    fn new(level: Level, out: Box<dyn Write + Send>, profiler: Option<Arc<Profiler>>) -> Self {
+        Self {
+            level,
+            out: Mutex::new(out),
+            started: Instant::now(),
+            next_id: AtomicU64::new(0),
+            spans: Mutex::new(HashMap::new()),
+            profiler,
+        }
+    }
+
+    fn logs(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.level
+    }
+
+    fn write_line(&self, level: &Level, text: &str) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let line = format!("{:>9.3}s {:<5} {}\n", elapsed, level, redact(text));
+        crate::crash::remember(&line);
+        // Logging must never take lado down
+        let _ = self.out.lock().unwrap().write_all(line.as_bytes());
+    }
+}
+
+impl Subscriber for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.logs(metadata) || (metadata.is_span() && self.profiler.is_some())
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = match &self.profiler {
+            Some(profiler) => profiler.new_span(span),
+            None => Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1),
+        };
+        let metadata = span.metadata();
+        let line = self.logs(metadata).then(|| {
+            let mut fields = Fields::default();
+            span.record(&mut fields);
+            format!(
+                "{}::{}{}",
+                short_target(metadata),
+                metadata.name(),
+                fields.text
+            )
+        });
+        self.spans.lock().unwrap().insert(
+            id.into_u64(),
+            OpenSpan {
+                line,
+                refs: 1,
+                entered: None,
+                busy: Duration::ZERO,
+            },
+        );
+        id
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        if let Some(line) = spans
+            .get_mut(&span.into_u64())
+            .and_then(|s| s.line.as_mut())
+        {
+            let mut fields = Fields::default();
+            values.record(&mut fields);
+            line.push_str(&fields.text);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        self.write_line(
+            metadata.level(),
+            &format!(
+                "{}: {}{}",
+                short_target(metadata),
+                fields.message,
+                fields.text
+            ),
+        );
+    }
+
+    fn enter(&self, span: &Id) {
+        if let Some(profiler) = &self.profiler {
+            profiler.enter(span);
+        }
+        if let Some(open) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            open.entered.get_or_insert_with(Instant::now);
+        }
+    }
+
+    fn exit(&self, span: &Id) {
+        if let Some(profiler) = &self.profiler {
+            profiler.exit(span);
+        }
+        if let Some(open) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            if let Some(entered) = open.entered.take() {
+                open.busy += entered.elapsed();
+            }
+        }
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(profiler) = &self.profiler {
+            profiler.clone_span(span);
+        }
+        if let Some(open) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            open.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        if let Some(profiler) = &self.profiler {
+            profiler.try_close(span.clone());
+        }
+        let id = span.into_u64();
+        let mut spans = self.spans.lock().unwrap();
+        let Some(open) = spans.get_mut(&id) else {
+            return false;
+        };
+        open.refs -= 1;
+        if open.refs > 0 {
+            return false;
+        }
+        let closed = spans.remove(&id);
+        drop(spans);
+        if let Some(OpenSpan {
+            line: Some(line),
+            busy,
+            ..
+        }) = closed
+        {
+            let took = format!("{} took {:.1}ms", line, busy.as_secs_f64() * 1000.0);
+            self.write_line(&Level::INFO, &took);
+        }
+        true
+    }
+}
+
+/// `lado_core::git::repository` -> `git::repository`
+fn short_target<'a>(metadata: &Metadata<'a>) -> &'a str {
+    let target = metadata.target();
+    target
+        .strip_prefix("lado::")
+        .or_else(|| target.strip_prefix("lado_core::"))
+        .unwrap_or(target)
+}
+
+/// The message and the other fields (as ` name=value`) of an event or span
+#[derive(Default)]
+struct Fields {
+    message: String,
+    text: String,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            let _ = write!(self.text, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            let _ = write!(self.text, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Prefixes of GitHub tokens (personal, OAuth, app and refresh tokens)
+const TOKEN_PREFIXES: [&str; 6] = ["ghp_", "gho_", "ghu_", "ghs_", "ghr_", "github_pat_"];
+
+/// `text` with the secret part of anything that looks like a token replaced
+/// by `***`.
+pub fn redact(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    loop {
+        let next = TOKEN_PREFIXES
+            .iter()
+            .filter_map(|prefix| rest.find(prefix).map(|at| (at, prefix.len())))
+            .chain(
+                ["Authorization: token ", "Bearer ", "token="]
+                    .iter()
+                    .filter_map(|prefix| rest.find(prefix).map(|at| (at, prefix.len()))),
+            )
+            .min();
+        let Some((at, prefix_len)) = next else {
+            out.push_str(rest);
+            return out;
+        };
+        let secret_start = at + prefix_len;
+        let secret_len = rest[secret_start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.'))
+            .unwrap_or(rest.len() - secret_start);
+        out.push_str(&rest[..secret_start]);
+        if secret_len > 0 {
+            out.push_str("***");
+        }
+        rest = &rest[secret_start + secret_len..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collects what the logger writes.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_logs_events_and_span_timings() {
+        let buffer = Buffer::default();
+        let logger = Logger::new(Level::DEBUG, Box::new(buffer.clone()), None);
+        tracing::subscriber::with_default(logger, || {
+            let _span = tracing::info_span!("diff", files = 3).entered();
+            tracing::debug!(command = "gh pr view 1", "running gh");
+            tracing::trace!("too detailed");
+        });
+
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("DEBUG logging::tests: running gh command=gh pr view 1"));
+        assert!(lines[1].contains("INFO  logging::tests::diff files=3 took "));
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("GH_TOKEN=ghp_abc123XYZ gh api"),
+            "GH_TOKEN=ghp_*** gh api"
+        );
+        assert_eq!(
+            redact("Authorization: token gho_secret, Bearer abc.def"),
+            "Authorization: token ***, Bearer ***"
+        );
+        assert_eq!(redact("no secrets here"), "no secrets here");
+    }
+}
@@ -1,20 +1,59 @@
 mod app;
+mod attachments;
+mod backend;
+mod check;
 mod cli;
+mod composer;
 mod config;
-mod git;
-mod github;
-mod highlighting;
+mod crash;
+mod export;
+mod goto_definition;
+// Only Unix has the socket
+#[cfg_attr(not(unix), allow(dead_code))]
+mod ipc;
+mod large_file;
+mod lazy_highlight;
+mod logging;
 mod models;
+mod pdf;
+mod plugins;
+mod pr_loader;
+mod pr_picker;
+mod print;
+mod profile;
+mod proxy;
+mod recent;
+mod scripting;
+mod scroll_markers;
+mod selection;
+mod session;
+mod summary;
+mod target;
+mod timestamps;
+mod tui;
 mod ui;
+mod update;
+mod url_handler;
 mod viewed_state;
+mod watcher;
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use clap::Parser;
+use lado_core::{
+    difftastic, encoding, exclude, folding, funcname, git, github, highlighting, lsp, mbox,
+    scm, search, semantic, sourcehut, todos, usages,
+};
 
 slint::include_modules!();
 
 fn main() -> Result<()> {
-    let args = cli::Args::parse();
+    crash::install_panic_hook();
+    let mut args = cli::Args::parse();
+    // What a `--serve` instance gets to open the window with
+    let mut forward_args: Vec<String> = std::env::args_os()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
 
     // Handle shell completion generation
     if let Some(shell) = args.completions {
@@ -22,8 +61,118 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    let app = app::App::new(args)?;
-    app.run()?;
+    // `-C` goes into the directory first, so paths given and gh calls are
+    // relative to it, as with git
+    if let Some(dir) = &args.directory {
+        std::env::set_current_dir(dir)
+            .with_context(|| format!("Failed to open {}", dir.display()))?;
+    }
+
+    config::migrate_legacy_dir();
+    if let Some(path) = &args.config {
+        config::use_path(path)?;
+    }
+
+    let config = config::load();
+    proxy::apply(&config);
+    args.overrides.use_profile(&config)?;
+    args.offline |= config.offline;
+    if args.offline && matches!(args.command, Some(cli::Command::Pr(_))) {
+        return Err(anyhow!("`lado pr` needs GitHub, which offline mode turns off"));
+    }
+
+    if args.register_url_handler {
+        return url_handler::register();
+    }
+
+    // A `lado://` link opens its PR in a local clone of the repository
+    if let Some(url) = args.diff.target.as_deref().and_then(url_handler::parse) {
+        if args.offline {
+            return Err(anyhow!("lado:// links open PRs, which need GitHub and offline mode turns off"));
+        }
+        url_handler::enter_repo(&url)?;
+        let number = url.number.to_string();
+        for arg in forward_args.iter_mut().filter(|arg| url_handler::parse(arg).is_some()) {
+            *arg = number.clone();
+        }
+        args.diff.target = Some(number);
+    }
+
+    // `lado recent` picks a target in the terminal and opens it in a window
+    if matches!(args.command, Some(cli::Command::Recent)) {
+        let repo = git::Repository::open_current_dir()?;
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| anyhow!("`lado recent` needs a working directory"))?;
+        let recent = recent::RecentTargets::load();
+        let targets = recent.for_repo(workdir);
+        if targets.is_empty() {
+            eprintln!("Nothing opened in this repository yet");
+            return Ok(());
+        }
+        let rows: Vec<String> = targets
+            .iter()
+            .map(|t| format!("{:<40} lado {}", t.title, t.args.join(" ")))
+            .collect();
+        if let Some(picked) = pr_picker::pick_row(&rows)? {
+            recent::open(workdir, &targets[picked])?;
+        }
+        return Ok(());
+    }
+
+    // `lado pr list` picks the PR in the terminal before opening the window
+    if let Some(cli::Command::Pr(pr)) = &mut args.command {
+        if pr.command.is_some() {
+            match pr_picker::pick()? {
+                Some(number) => pr.number = Some(number),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    let profiler = args
+        .profile
+        .then(|| std::sync::Arc::new(profile::Profiler::default()));
+    logging::install(args.verbose, args.log_file.as_deref(), profiler.clone())?;
+
+    if args.check {
+        // 1 means "changes" here, so failures get 2, as with diff(1)
+        let code = match check::run(&args) {
+            Ok(changed) => i32::from(changed),
+            Err(e) => {
+                eprintln!("Error: {:?}", e);
+                2
+            }
+        };
+        if let Some(profiler) = profiler {
+            profiler.print_summary();
+        }
+        std::process::exit(code);
+    }
+
+    if let Some(format) = args.export {
+        export::run(&args, format)?;
+    } else if args.print {
+        print::run(&args)?;
+    } else if args.tui || !tui::has_display() {
+        tui::run(&args)?;
+    } else if !args.serve && ipc::can_forward(&args) && ipc::forward(forward_args)? {
+        // A `--serve` instance opened the window
+    } else {
+        // The backend has to be chosen before the first window exists
+        backend::select(
+            args.renderer.unwrap_or(config.renderer),
+            args.windowing.unwrap_or(config.windowing),
+        )?;
+        let serve = args.serve;
+        let app = app::App::new(args, std::env::current_dir()?)?;
+        let _serving = if serve { Some(ipc::serve(&app)?) } else { None };
+        app.run()?;
+    }
+
+    if let Some(profiler) = profiler {
+        profiler.print_summary();
+    }
 
     Ok(())
 }
@@ -1,43 +1,129 @@
 use crate::github::PrCommit;
+use crate::timestamps;
 use crate::PrCommitEntry;
 
+/// Types of conventional commits (`feat: ...`, `fix(ui)!: ...`)
+pub const CONVENTIONAL_KINDS: [&str; 11] = [
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+
 /// Model for a PR commit entry in the UI
 pub struct PrCommitModel {
     pub sha: String,
     pub short_sha: String,
     pub summary: String,
+    /// Conventional commit type, e.g. `feat`; empty for other commits
+    pub kind: String,
+    /// The prefix the type comes in, e.g. `feat(ui)!`
+    pub tag: String,
     pub author: String,
+    /// When the commit was authored, in local time and relative to now
+    pub date: String,
+    pub relative_date: String,
     pub is_selected: bool,
 }
 
 impl From<&PrCommit> for PrCommitModel {
     fn from(commit: &PrCommit) -> Self {
         // Extract the first line of the commit message as the summary
-        let summary = commit
-            .message
-            .lines()
-            .next()
-            .unwrap_or("")
-            .to_string();
+        let summary = commit.message.lines().next().unwrap_or("");
+        let (kind, tag, summary) = match conventional_prefix(summary) {
+            Some((kind, tag, subject)) => (kind, tag, subject),
+            None => (String::new(), "", summary),
+        };
 
         Self {
             sha: commit.sha.clone(),
             short_sha: commit.short_sha.clone(),
-            summary,
+            summary: summary.to_string(),
+            kind,
+            tag: tag.to_string(),
             author: commit.author.clone(),
+            date: timestamps::absolute(&commit.authored_at),
+            relative_date: timestamps::relative(&commit.authored_at),
             is_selected: false,
         }
     }
 }
 
+/// Split a conventional commit summary into its type (lower case), prefix
+/// and subject: `Fix(ui)!: wrap` gives `fix`, `Fix(ui)!` and `wrap`.
+pub fn conventional_prefix(summary: &str) -> Option<(String, &str, &str)> {
+    let (tag, subject) = summary.split_once(": ")?;
+    let kind_end = tag
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(tag.len());
+    let (kind, rest) = tag.split_at(kind_end);
+    let rest = rest.strip_suffix('!').unwrap_or(rest);
+    let scope_ok =
+        rest.is_empty() || (rest.starts_with('(') && rest.ends_with(')') && !rest.contains(' '));
+    let kind = kind.to_ascii_lowercase();
+    (scope_ok && CONVENTIONAL_KINDS.contains(&kind.as_str()))
+        .then(|| (kind, tag, subject.trim_start()))
+}
+
+/// Whether `commit` matches what was typed into the commit list's filter box:
+/// part of its message or author, ignoring case, or the start of its SHA.
+pub fn matches_filter(commit: &PrCommit, filter: &str) -> bool {
+    let filter = filter.trim().to_lowercase();
+    filter.is_empty()
+        || commit.sha.starts_with(&filter)
+        || commit.message.to_lowercase().contains(&filter)
+        || commit.author.to_lowercase().contains(&filter)
+}
+
 impl From<PrCommitModel> for PrCommitEntry {
     fn from(model: PrCommitModel) -> Self {
         Self {
             sha: model.sha.into(),
             short_sha: model.short_sha.into(),
             summary: model.summary.into(),
+            kind: model.kind.into(),
+            tag: model.tag.into(),
             author: model.author.into(),
+            date: model.date.into(),
+            relative_date: model.relative_date.into(),
             is_selected: model.is_selected,
+            filtered_out: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conventional_prefix() {
+        assert_eq!(
+            conventional_prefix("feat: add folding"),
+            Some(("feat".to_string(), "feat", "add folding"))
+        );
+        assert_eq!(
+            conventional_prefix("Fix(ui)!: wrap long lines"),
+            Some(("fix".to_string(), "Fix(ui)!", "wrap long lines"))
+        );
+        assert_eq!(conventional_prefix("Add folding"), None);
+        assert_eq!(conventional_prefix("WIP: folding"), None);
+        assert_eq!(conventional_prefix("Merge branch 'main': conflicts"), None);
+        assert_eq!(conventional_prefix("fix(two words): typo"), None);
+    }
+
+    #[test]
+    fn test_matches_filter() {
+        let commit = PrCommit {
+            sha: "3650a3f0c1".to_string(),
+            short_sha: "3650a3f".to_string(),
+            parent_sha: None,
+            message: "Tag conventional commits\n\nFilter them by Type".to_string(),
+            author: "Ada Lovelace".to_string(),
+            authored_at: String::new(),
+        };
+        assert!(matches_filter(&commit, ""));
+        assert!(matches_filter(&commit, "  lovelace "));
+        assert!(matches_filter(&commit, "by type"));
+        assert!(matches_filter(&commit, "3650a"));
+        assert!(!matches_filter(&commit, "a3f0"));
+        assert!(!matches_filter(&commit, "grace"));
+    }
+}
@@ -1,10 +1,12 @@
+use crate::attachments;
 use crate::git::{DiffLine, DiffLineType};
 use crate::models::TextSpanModel;
 use crate::DiffLine as SlintDiffLine;
 use crate::TextSpan as SlintTextSpan;
-use slint::ModelRc;
+use slint::{ModelRc, SharedString};
 
 /// Model for a diff line in the UI
+#[derive(Default)]
 pub struct DiffLineModel {
     pub line_type: String,
     pub old_line_num: String,
@@ -15,11 +17,28 @@ pub struct DiffLineModel {
     /// same line_type for background coloring but suppress the sign and gutter
     /// numbers so the user can tell continuations from real code lines.
     pub is_continuation: bool,
+    /// Header or code row of a hunk marked reviewed, shown dimmed
+    pub reviewed: bool,
+    /// Function (or class, module …) around the hunk of a header row
+    pub hunk_context: String,
+    /// Commits that made the hunk of a header row, once asked for
+    pub hunk_commits: String,
+    /// Side-by-side row pairing the removed line above with an added one,
+    /// whose text is in the `new_` fields (`new_line_num` is its number)
+    pub paired: bool,
+    pub new_content: String,
+    pub new_spans: Vec<TextSpanModel>,
     // Comment fields
     pub comment_author: String,
     pub comment_body: String,
     pub comment_timestamp: String,
+    pub comment_relative_time: String,
     pub comment_is_reply: bool,
+    /// Id of the comment starting the thread, as text since Slint's ints
+    /// are too small for it
+    pub comment_thread: String,
+    /// URLs of the comment's image attachments
+    pub comment_images: Vec<String>,
 }
 
 impl From<&DiffLine> for DiffLineModel {
@@ -32,15 +51,7 @@ impl From<&DiffLine> for DiffLineModel {
             DiffLineType::Comment => "comment",
         };
 
-        let (author, body, timestamp, is_reply) = match &line.comment {
-            Some(c) => (
-                c.author.clone(),
-                c.body.clone(),
-                c.timestamp.clone(),
-                c.is_reply,
-            ),
-            None => (String::new(), String::new(), String::new(), false),
-        };
+        let comment = line.comment.as_deref().cloned().unwrap_or_default();
 
         Self {
             line_type: line_type.to_string(),
@@ -52,13 +63,25 @@ impl From<&DiffLine> for DiffLineModel {
                 .new_line_num
                 .map(|n| n.to_string())
                 .unwrap_or_default(),
-            content: line.content.clone(),
+            content: line.content.to_string(),
             spans: Vec::new(), // Spans populated later by highlighter
             is_continuation: false,
-            comment_author: author,
-            comment_body: body,
-            comment_timestamp: timestamp,
-            comment_is_reply: is_reply,
+            reviewed: false,
+            hunk_context: String::new(),
+            hunk_commits: String::new(),
+            paired: false,
+            new_content: String::new(),
+            new_spans: Vec::new(),
+            comment_author: comment.author,
+            comment_body: comment.body,
+            comment_timestamp: comment.timestamp,
+            comment_relative_time: comment.relative_time,
+            comment_is_reply: comment.is_reply,
+            comment_thread: match comment.thread_id {
+                0 => String::new(),
+                id => id.to_string(),
+            },
+            comment_images: comment.images,
         }
     }
 }
@@ -71,27 +94,77 @@ impl From<DiffLineModel> for SlintDiffLine {
             .into_iter()
             .map(SlintTextSpan::from)
             .collect();
-        let spans_model = ModelRc::new(slint::VecModel::from(slint_spans));
+        let new_spans: Vec<SlintTextSpan> = model
+            .new_spans
+            .into_iter()
+            .map(SlintTextSpan::from)
+            .collect();
+
+        // Images that weren't fetched or failed to decode are dropped rather
+        // than shown broken
+        let images: Vec<slint::Image> = model
+            .comment_images
+            .iter()
+            .filter_map(|url| attachments::image(url))
+            .collect();
 
         Self {
-            line_type: model.line_type.into(),
-            old_line_num: model.old_line_num.into(),
-            new_line_num: model.new_line_num.into(),
-            content: model.content.into(),
-            spans: spans_model,
+            line_type: interned(&model.line_type),
+            old_line_num: interned(&model.old_line_num),
+            new_line_num: interned(&model.new_line_num),
+            content: interned(&model.content),
+            spans: model_or_empty(slint_spans),
             is_continuation: model.is_continuation,
-            comment_author: model.comment_author.into(),
-            comment_body: model.comment_body.into(),
-            comment_timestamp: model.comment_timestamp.into(),
+            reviewed: model.reviewed,
+            hunk_context: model.hunk_context.into(),
+            hunk_commits: model.hunk_commits.into(),
+            paired: model.paired,
+            new_content: interned(&model.new_content),
+            new_spans: model_or_empty(new_spans),
+            comment_author: interned(&model.comment_author),
+            comment_body: interned(&model.comment_body),
+            comment_timestamp: interned(&model.comment_timestamp),
+            comment_relative_time: interned(&model.comment_relative_time),
             comment_is_reply: model.comment_is_reply,
+            comment_thread: model.comment_thread.into(),
+            comment_images: model_or_empty(images),
         }
     }
 }
 
+thread_local! {
+    /// Strings found in most rows (line types, empty fields), allocated once
+    /// and shared instead of once per row
+    static INTERNED: Vec<SharedString> = ["", "add", "remove", "context", "hunk", "comment"]
+        .into_iter()
+        .map(SharedString::from)
+        .collect();
+}
+
+/// `s` as a Slint string, shared if it is one of the common ones.
+fn interned(s: &str) -> SharedString {
+    if s.len() <= "context".len() {
+        if let Some(shared) = INTERNED.with(|i| i.iter().find(|i| i.as_str() == s).cloned()) {
+            return shared;
+        }
+    }
+    s.into()
+}
+
+/// Empty lists share the default model rather than allocating one per row.
+fn model_or_empty<T: Clone + 'static>(items: Vec<T>) -> ModelRc<T> {
+    if items.is_empty() {
+        ModelRc::default()
+    } else {
+        ModelRc::new(slint::VecModel::from(items))
+    }
+}
+
 /// Split a diff line into multiple visual rows that each fit within
 /// `wrap_column` characters. Only Add/Remove/Context lines wrap; Hunk headers
 /// and Comments pass through unchanged. Continuation rows preserve the
-/// background (line_type) but drop the line numbers and sign.
+/// background (line_type) but drop the line numbers and sign. Both lines of a
+/// paired side-by-side row wrap, and the row takes as many rows as the longer.
 pub fn wrap_diff_line(model: DiffLineModel, wrap_column: usize) -> Vec<DiffLineModel> {
     if wrap_column == 0 {
         return vec![model];
@@ -99,35 +172,59 @@ pub fn wrap_diff_line(model: DiffLineModel, wrap_column: usize) -> Vec<DiffLineM
     if !matches!(model.line_type.as_str(), "add" | "remove" | "context") {
         return vec![model];
     }
-    if model.content.chars().count() <= wrap_column {
+    let fits = |s: &str| s.chars().count() <= wrap_column;
+    if fits(&model.content) && fits(&model.new_content) {
         return vec![model];
     }
 
-    let chunks: Vec<(String, Vec<TextSpanModel>)> = if model.spans.is_empty() {
-        chunk_str(&model.content, wrap_column)
+    let old_chunks = chunk_text(&model.content, &model.spans, wrap_column);
+    let new_chunks = chunk_text(&model.new_content, &model.new_spans, wrap_column);
+    let row_count = old_chunks.len().max(new_chunks.len());
+    let (mut old_chunks, mut new_chunks) = (old_chunks.into_iter(), new_chunks.into_iter());
+
+    (0..row_count)
+        .map(|i| {
+            let (content, spans) = old_chunks.next().unwrap_or_default();
+            let (new_content, new_spans) = new_chunks.next().unwrap_or_default();
+            DiffLineModel {
+                line_type: model.line_type.clone(),
+                old_line_num: if i == 0 { model.old_line_num.clone() } else { String::new() },
+                new_line_num: if i == 0 { model.new_line_num.clone() } else { String::new() },
+                content,
+                spans,
+                is_continuation: i > 0,
+                reviewed: model.reviewed,
+                hunk_context: String::new(),
+                hunk_commits: String::new(),
+                paired: model.paired,
+                new_content,
+                new_spans,
+                comment_author: String::new(),
+                comment_body: String::new(),
+                comment_timestamp: String::new(),
+                comment_relative_time: String::new(),
+                comment_is_reply: false,
+                comment_thread: String::new(),
+                comment_images: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// `content` (highlighted as `spans`, if it is) in chunks of `n` characters
+fn chunk_text(
+    content: &str,
+    spans: &[TextSpanModel],
+    n: usize,
+) -> Vec<(String, Vec<TextSpanModel>)> {
+    if spans.is_empty() {
+        chunk_str(content, n)
             .into_iter()
             .map(|s| (s, Vec::new()))
             .collect()
     } else {
-        chunk_spans(&model.spans, wrap_column)
-    };
-
-    chunks
-        .into_iter()
-        .enumerate()
-        .map(|(i, (content, spans))| DiffLineModel {
-            line_type: model.line_type.clone(),
-            old_line_num: if i == 0 { model.old_line_num.clone() } else { String::new() },
-            new_line_num: if i == 0 { model.new_line_num.clone() } else { String::new() },
-            content,
-            spans,
-            is_continuation: i > 0,
-            comment_author: String::new(),
-            comment_body: String::new(),
-            comment_timestamp: String::new(),
-            comment_is_reply: false,
-        })
-        .collect()
+        chunk_spans(spans, n)
+    }
 }
 
 fn chunk_str(s: &str, n: usize) -> Vec<String> {
@@ -194,10 +291,19 @@ mod tests {
             content: content.to_string(),
             spans,
             is_continuation: false,
+            reviewed: false,
+            hunk_context: String::new(),
+            hunk_commits: String::new(),
+            paired: false,
+            new_content: String::new(),
+            new_spans: Vec::new(),
             comment_author: String::new(),
             comment_body: String::new(),
             comment_timestamp: String::new(),
+            comment_relative_time: String::new(),
             comment_is_reply: false,
+            comment_thread: String::new(),
+            comment_images: Vec::new(),
         }
     }
 
@@ -271,4 +377,38 @@ mod tests {
         assert!(out[1].is_continuation);
         assert!(out[2].is_continuation);
     }
+
+    #[test]
+    fn paired_row_wraps_both_lines() {
+        let mut m = model_with(&"o".repeat(15), vec![], "remove");
+        m.paired = true;
+        m.new_content = "n".repeat(5);
+        let out = wrap_diff_line(m, 10);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].content, "o".repeat(10));
+        assert_eq!(out[0].new_content, "nnnnn");
+        assert_eq!(out[1].content, "ooooo");
+        assert_eq!(out[1].new_content, "");
+        assert!(out[1].paired && out[1].is_continuation);
+
+        let mut m = model_with("old", vec![], "remove");
+        m.paired = true;
+        m.new_content = "n".repeat(25);
+        let out = wrap_diff_line(m, 10);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[2].content, "");
+        assert_eq!(out[2].new_content, "nnnnn");
+    }
+
+    #[test]
+    fn rows_share_common_strings() {
+        let a: SlintDiffLine = model_with("", vec![], "add").into();
+        let b: SlintDiffLine = model_with("x", vec![], "add").into();
+        assert_eq!(a.line_type.as_str().as_ptr(), b.line_type.as_str().as_ptr());
+        assert_eq!(
+            a.content.as_str().as_ptr(),
+            b.comment_body.as_str().as_ptr()
+        );
+        assert_eq!(b.content, "x");
+    }
 }
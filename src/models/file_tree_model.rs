@@ -9,8 +9,15 @@ pub struct FileEntryModel {
     pub is_folder: bool,
     pub is_expanded: bool,
     pub status: String,
+    pub additions: i32,
+    pub deletions: i32,
+    pub file_count: i32,
     pub comment_count: i32,
+    /// Of those, comments in threads not resolved yet
+    pub unresolved_count: i32,
+    pub todo_count: i32,
     pub viewed: bool,
+    pub generated: bool,
 }
 
 impl From<&FlatFileEntry> for FileEntryModel {
@@ -22,8 +29,14 @@ impl From<&FlatFileEntry> for FileEntryModel {
             is_folder: entry.is_folder,
             is_expanded: entry.is_expanded,
             status: entry.status.clone(),
+            additions: entry.additions as i32,
+            deletions: entry.deletions as i32,
+            file_count: entry.files as i32,
             comment_count: entry.comment_count,
+            unresolved_count: 0,
+            todo_count: 0,
             viewed: entry.viewed,
+            generated: false,
         }
     }
 }
@@ -37,8 +50,14 @@ impl From<FileEntryModel> for FileEntry {
             is_folder: model.is_folder,
             is_expanded: model.is_expanded,
             status: model.status.into(),
+            additions: model.additions,
+            deletions: model.deletions,
+            file_count: model.file_count,
             comment_count: model.comment_count,
+            unresolved_count: model.unresolved_count,
+            todo_count: model.todo_count,
             viewed: model.viewed,
+            generated: model.generated,
         }
     }
 }
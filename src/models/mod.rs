@@ -3,7 +3,7 @@ mod diff_model;
 mod file_tree_model;
 mod span_model;
 
-pub use commit_model::PrCommitModel;
+pub use commit_model::{matches_filter, PrCommitModel, CONVENTIONAL_KINDS};
 pub use diff_model::{wrap_diff_line, DiffLineModel};
 pub use file_tree_model::FileEntryModel;
 pub use span_model::{parse_hex_color, TextSpanModel};
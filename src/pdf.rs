@@ -0,0 +1,529 @@
+//! Paginated PDF of a diff (`--export pdf`), for printing or for archiving
+//! with a review: every page is headed by what was reviewed (repository,
+//! target, commits) and numbered, and each file starts on a page of its own.
+//!
+//! Written directly, with the fonts every PDF reader has built in (Courier
+//! and Helvetica), so characters outside Latin-1 show as `?`.
+
+use crate::git::{DiffData, DiffHunk, DiffLineType, FileChange, Repository};
+use crate::github::{self, FileComments, PrComment};
+use crate::highlighting::Highlighter;
+use crate::timestamps;
+use std::io::Write as _;
+
+/// What every page's header says
+pub struct Header {
+    /// Name of the repository, e.g. its folder
+    pub repository: String,
+    /// The diff's title, e.g. `PR #42`
+    pub title: String,
+    /// Base and head commit, e.g. `1a2b3c4..5d6e7f8`, if the diff has them
+    pub commits: Option<String>,
+    /// When the PDF was made
+    pub generated: String,
+}
+
+impl Header {
+    /// The header for the diff titled `title` of `repo`, from `base` to
+    /// `head` if it is made from commits, made now.
+    pub fn new(repo: &Repository, title: String, commits: Option<(git2::Oid, git2::Oid)>) -> Self {
+        let short = |oid: git2::Oid| oid.to_string().chars().take(7).collect::<String>();
+        Self {
+            repository: repo
+                .workdir()
+                .and_then(|dir| dir.file_name())
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+            title,
+            commits: commits.map(|(base, head)| format!("{}..{}", short(base), short(head))),
+            generated: timestamps::absolute(&chrono::Utc::now().to_rfc3339()),
+        }
+    }
+}
+
+// A4 in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 40.0;
+/// Where the rows below the page header start and have to end
+const BODY_TOP: f32 = PAGE_HEIGHT - MARGIN - 34.0;
+const BODY_BOTTOM: f32 = MARGIN + 16.0;
+
+const FONT_SIZE: f32 = 8.0;
+const LINE_HEIGHT: f32 = 10.5;
+/// Courier's characters are 0.6 em wide
+const CHAR_WIDTH: f32 = FONT_SIZE * 0.6;
+/// Old and new line numbers and the +/- marker in front of the code
+const GUTTER_CHARS: usize = 12;
+const CODE_CHARS: usize = ((PAGE_WIDTH - 2.0 * MARGIN) / CHAR_WIDTH) as usize - GUTTER_CHARS;
+
+const COURIER: &str = "F1";
+const HELVETICA: &str = "F2";
+const HELVETICA_BOLD: &str = "F3";
+
+type Rgb = (f32, f32, f32);
+const BLACK: Rgb = (0.12, 0.14, 0.16);
+const MUTED: Rgb = (0.45, 0.48, 0.52);
+const ADDED: Rgb = (0.90, 1.0, 0.93);
+const REMOVED: Rgb = (1.0, 0.92, 0.91);
+const HUNK: Rgb = (0.94, 0.96, 0.99);
+const HUNK_TEXT: Rgb = (0.02, 0.33, 0.68);
+const COMMENT: Rgb = (1.0, 0.98, 0.90);
+
+/// The files of `data` (only `only_file` if given) as a PDF, with the
+/// review `comments` below the lines they are on. `highlighter` should use
+/// a light theme, as the pages are white.
+pub fn render(
+    data: &DiffData,
+    only_file: Option<&str>,
+    header: &Header,
+    highlighter: &Highlighter,
+    comments: Option<&FileComments>,
+) -> Vec<u8> {
+    let mut layout = Layout {
+        header,
+        pages: Vec::new(),
+        y: 0.0,
+        file: String::new(),
+    };
+    let files = data
+        .files
+        .iter()
+        .filter(|file| only_file.is_none_or(|only| file.path == only));
+    for file in files {
+        let hunks = data
+            .file_hunks
+            .get(&file.path)
+            .map_or(&[][..], Vec::as_slice);
+        let file_comments = comments
+            .and_then(|c| c.get(&file.path))
+            .map_or(&[][..], Vec::as_slice);
+        layout.file(file, hunks, file_comments, highlighter);
+    }
+    if layout.pages.is_empty() {
+        layout.start_page();
+    }
+    layout.finish()
+}
+
+/// Lays out rows over pages, starting new ones as they fill
+struct Layout<'a> {
+    header: &'a Header,
+    /// Content stream of each page
+    pages: Vec<Vec<u8>>,
+    /// Top of the next row on the current page
+    y: f32,
+    /// File being laid out, named again on the pages it continues on
+    file: String,
+}
+
+impl Layout<'_> {
+    fn file(
+        &mut self,
+        file: &FileChange,
+        hunks: &[DiffHunk],
+        comments: &[PrComment],
+        highlighter: &Highlighter,
+    ) {
+        self.file.clear();
+        self.start_page();
+        self.file = file.path.clone();
+        let heading = format!(
+            "{}   {} +{} -{}",
+            file.path,
+            file.status.as_str(),
+            file.additions,
+            file.deletions
+        );
+        self.y -= 12.0;
+        self.text(MARGIN, self.y, HELVETICA_BOLD, 10.0, BLACK, &heading);
+        self.y -= 8.0;
+
+        let source: String = hunks
+            .iter()
+            .flat_map(|h| &h.lines)
+            .map(|line| format!("{}\n", line.content))
+            .collect();
+        let mut highlighted = highlighter.highlight(&source, &file.path).into_iter();
+        let comment_index = github::index_comments_by_line(comments);
+        for hunk in hunks {
+            // Not a header alone at the bottom of a page
+            self.make_room(2.0 * LINE_HEIGHT);
+            self.row(
+                Some(HUNK),
+                "",
+                &[(hunk.header.trim_end().to_string(), HUNK_TEXT)],
+            );
+            for line in &hunk.lines {
+                let (background, marker) = match line.line_type {
+                    DiffLineType::Add => (Some(ADDED), '+'),
+                    DiffLineType::Remove => (Some(REMOVED), '-'),
+                    _ => (None, ' '),
+                };
+                let spans: Vec<(String, Rgb)> = match highlighted.next() {
+                    Some(hl) => hl
+                        .spans
+                        .into_iter()
+                        .map(|span| (span.text, parse_color(&span.color).unwrap_or(BLACK)))
+                        .collect(),
+                    None => vec![(line.content.to_string(), BLACK)],
+                };
+                let number = |n: Option<u32>| n.map_or_else(String::new, |n| n.to_string());
+                let gutter = format!(
+                    "{:>5}{:>5} {}",
+                    number(line.old_line_num),
+                    number(line.new_line_num),
+                    marker
+                );
+                self.row(background, &gutter, &spans);
+
+                let mut line_comments: Vec<&PrComment> = [
+                    line.new_line_num.map(|n| (github::CommentSide::Right, n)),
+                    line.old_line_num.map(|n| (github::CommentSide::Left, n)),
+                ]
+                .into_iter()
+                .flatten()
+                .filter_map(|key| comment_index.get(&key))
+                .flatten()
+                .copied()
+                .collect();
+                line_comments.sort_by_key(|c| (c.line, c.id));
+                for comment in line_comments {
+                    self.comment(comment);
+                }
+            }
+        }
+        // Comments on lines the diff no longer has
+        for comment in comments.iter().filter(|c| c.line.is_none()) {
+            self.comment(comment);
+        }
+    }
+
+    fn comment(&mut self, comment: &PrComment) {
+        let meta = format!(
+            "{}, {}:",
+            comment.author,
+            timestamps::absolute(&comment.created_at)
+        );
+        self.row(Some(COMMENT), "", &[(meta, MUTED)]);
+        for line in comment.body.trim_end().lines() {
+            self.row(Some(COMMENT), "", &[(line.to_string(), BLACK)]);
+        }
+    }
+
+    /// A row with `gutter` in front of `spans`, wrapped onto more rows
+    /// where the spans are longer than a row
+    fn row(&mut self, background: Option<Rgb>, gutter: &str, spans: &[(String, Rgb)]) {
+        for (index, pieces) in wrap(spans, CODE_CHARS).into_iter().enumerate() {
+            self.make_room(LINE_HEIGHT);
+            self.y -= LINE_HEIGHT;
+            let baseline = self.y + (LINE_HEIGHT - FONT_SIZE) / 2.0 + 1.0;
+            if let Some(color) = background {
+                self.rect(
+                    MARGIN,
+                    self.y,
+                    PAGE_WIDTH - 2.0 * MARGIN,
+                    LINE_HEIGHT,
+                    color,
+                );
+            }
+            if index == 0 {
+                self.text(MARGIN, baseline, COURIER, FONT_SIZE, MUTED, gutter);
+            }
+            let mut x = MARGIN + GUTTER_CHARS as f32 * CHAR_WIDTH;
+            for (text, color) in pieces {
+                self.text(x, baseline, COURIER, FONT_SIZE, color, &text);
+                x += text.chars().count() as f32 * CHAR_WIDTH;
+            }
+        }
+    }
+
+    /// Start a new page unless `height` fits on this one
+    fn make_room(&mut self, height: f32) {
+        if self.pages.is_empty() || self.y - height < BODY_BOTTOM {
+            self.start_page();
+        }
+    }
+
+    fn start_page(&mut self) {
+        self.pages.push(Vec::new());
+        let header = self.header;
+        let top = PAGE_HEIGHT - MARGIN;
+        self.text(
+            MARGIN,
+            top - 10.0,
+            HELVETICA_BOLD,
+            11.0,
+            BLACK,
+            &header.title,
+        );
+        let details: Vec<&str> = [Some(header.repository.as_str()), header.commits.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter(|detail| !detail.is_empty())
+            .chain([header.generated.as_str()])
+            .collect();
+        self.text(
+            MARGIN,
+            top - 22.0,
+            HELVETICA,
+            8.0,
+            MUTED,
+            &details.join("  |  "),
+        );
+        self.rect(MARGIN, top - 28.0, PAGE_WIDTH - 2.0 * MARGIN, 0.5, MUTED);
+        self.y = BODY_TOP;
+        if !self.file.is_empty() {
+            let continued = format!("{} (continued)", self.file);
+            self.y -= 10.0;
+            self.text(MARGIN, self.y, HELVETICA, 8.0, MUTED, &continued);
+            self.y -= 4.0;
+        }
+    }
+
+    fn text(&mut self, x: f32, y: f32, font: &str, size: f32, (r, g, b): Rgb, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let page = self.pages.last_mut().expect("a page was started");
+        let _ = write!(
+            page,
+            "BT /{} {} Tf {:.3} {:.3} {:.3} rg {:.2} {:.2} Td ",
+            font, size, r, g, b, x, y
+        );
+        page.extend(pdf_string(text));
+        page.extend_from_slice(b" Tj ET\n");
+    }
+
+    fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, (r, g, b): Rgb) {
+        let page = self.pages.last_mut().expect("a page was started");
+        let _ = writeln!(
+            page,
+            "{:.3} {:.3} {:.3} rg {:.2} {:.2} {:.2} {:.2} re f",
+            r, g, b, x, y, width, height
+        );
+    }
+
+    /// Number the pages and write out the document.
+    fn finish(mut self) -> Vec<u8> {
+        let count = self.pages.len();
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            let number = format!("Page {} of {}", index + 1, count);
+            let x = PAGE_WIDTH - MARGIN - number.len() as f32 * 4.2;
+            let _ = write!(
+                page,
+                "BT /{} 8 Tf {:.3} {:.3} {:.3} rg {:.2} {:.2} Td ",
+                HELVETICA, MUTED.0, MUTED.1, MUTED.2, x, MARGIN
+            );
+            page.extend(pdf_string(&number));
+            page.extend_from_slice(b" Tj ET\n");
+        }
+        write_document(&self.pages)
+    }
+}
+
+/// Split `spans` into rows of at most `width` characters.
+fn wrap(spans: &[(String, Rgb)], width: usize) -> Vec<Vec<(String, Rgb)>> {
+    let mut rows = vec![Vec::new()];
+    let mut used = 0;
+    for (text, color) in spans {
+        let mut rest = text.as_str();
+        while !rest.is_empty() {
+            if used == width {
+                rows.push(Vec::new());
+                used = 0;
+            }
+            let end = rest
+                .char_indices()
+                .nth(width - used)
+                .map_or(rest.len(), |(at, _)| at);
+            let (piece, tail) = rest.split_at(end);
+            used += piece.chars().count();
+            rows.last_mut()
+                .expect("rows start with one")
+                .push((piece.to_string(), *color));
+            rest = tail;
+        }
+    }
+    rows
+}
+
+/// `#rrggbb` as PDF color components
+fn parse_color(hex: &str) -> Option<Rgb> {
+    let hex = hex.strip_prefix('#')?;
+    let component = |at: usize| {
+        let value = u8::from_str_radix(hex.get(at..at + 2)?, 16).ok()?;
+        Some(f32::from(value) / 255.0)
+    };
+    Some((component(0)?, component(2)?, component(4)?))
+}
+
+/// `text` as a PDF string in WinAnsiEncoding, which agrees with Latin-1 for
+/// the characters kept
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => out.extend_from_slice(&[b'\\', c as u8]),
+            '\t' => out.push(b' '),
+            // Highlighted lines end in theirs
+            '\n' | '\r' => {}
+            ' '..='~' | '\u{a0}'..='\u{ff}' => out.push(c as u32 as u8),
+            _ => out.push(b'?'),
+        }
+    }
+    out.push(b')');
+    out
+}
+
+/// The PDF file around the pages' content streams.
+fn write_document(pages: &[Vec<u8>]) -> Vec<u8> {
+    // Catalog, page tree and fonts, then each page and its content
+    let fonts = [
+        (COURIER, "Courier"),
+        (HELVETICA, "Helvetica"),
+        (HELVETICA_BOLD, "Helvetica-Bold"),
+    ];
+    let first_page = 3 + fonts.len();
+    let page_ids: Vec<usize> = (0..pages.len()).map(|i| first_page + 2 * i).collect();
+
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+    ];
+    for (_, name) in fonts {
+        objects.push(
+            format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                name
+            )
+            .into_bytes(),
+        );
+    }
+    let font_refs: Vec<String> = fonts
+        .iter()
+        .enumerate()
+        .map(|(i, (key, _))| format!("/{} {} 0 R", key, 3 + i))
+        .collect();
+    for (page, id) in pages.iter().zip(&page_ids) {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << {} >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                font_refs.join(" "),
+                id + 1
+            )
+            .into_bytes(),
+        );
+        let mut stream = format!("<< /Length {} >>\nstream\n", page.len()).into_bytes();
+        stream.extend_from_slice(page);
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        let _ = writeln!(out, "{} 0 obj", index + 1);
+        out.extend_from_slice(object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = out.len();
+    let _ = writeln!(out, "xref\n0 {}\n0000000000 65535 f ", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(out, "{:010} 00000 n ", offset);
+    }
+    let _ = writeln!(
+        out,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF",
+        objects.len() + 1,
+        xref
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::parse_patch;
+
+    fn header() -> Header {
+        Header {
+            repository: "lado".to_string(),
+            title: "PR #42".to_string(),
+            commits: Some("1a2b3c4..5d6e7f8".to_string()),
+            generated: "2024-06-15 12:00".to_string(),
+        }
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    #[test]
+    fn test_pages_and_structure() {
+        // A long file, then a short one that starts on a page of its own
+        let long: String = (1..=120).map(|n| format!("+line {}\n", n)).collect();
+        let patch = format!(
+            "diff --git a/long.txt b/long.txt\nnew file mode 100644\n--- /dev/null\n\
+             +++ b/long.txt\n@@ -0,0 +1,120 @@\n{}\
+             diff --git a/short.txt b/short.txt\n--- a/short.txt\n+++ b/short.txt\n\
+             @@ -1 +1 @@\n-old\n+new (ü → ü)\n",
+            long
+        );
+        let data = parse_patch(&patch).unwrap();
+        let pdf = render(&data, None, &header(), &Highlighter::new(), None);
+
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        assert!(contains(&pdf, b"/Count 3 "));
+        assert!(contains(&pdf, b"(Page 3 of 3)"));
+        assert!(contains(&pdf, b"(long.txt \\(continued\\))"));
+        assert!(contains(
+            &pdf,
+            b"(lado  |  1a2b3c4..5d6e7f8  |  2024-06-15 12:00)"
+        ));
+        // Latin-1 kept, the rest replaced
+        assert!(contains(&pdf, b"(new \\(\xfc ? \xfc\\))"));
+
+        // startxref points at the cross-reference table
+        let text = String::from_utf8_lossy(&pdf);
+        let start: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|rest| rest.lines().next())
+            .and_then(|offset| offset.parse().ok())
+            .unwrap();
+        assert!(pdf[start..].starts_with(b"xref\n"));
+
+        let only = render(
+            &data,
+            Some("short.txt"),
+            &header(),
+            &Highlighter::new(),
+            None,
+        );
+        assert!(contains(&only, b"/Count 1 "));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let spans = vec![("abcdef".to_string(), BLACK), ("gh".to_string(), MUTED)];
+        let rows = wrap(&spans, 4);
+        let texts: Vec<Vec<&str>> = rows
+            .iter()
+            .map(|row| row.iter().map(|(text, _)| text.as_str()).collect())
+            .collect();
+        assert_eq!(texts, [vec!["abcd"], vec!["ef", "gh"]]);
+        assert_eq!(wrap(&[], 4).len(), 1);
+    }
+}
@@ -0,0 +1,43 @@
+//! Stand-in for the plugin host when lado is built without the `plugins`
+//! feature: no plugin ever loads.
+
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+pub enum Plugin {}
+
+impl Plugin {
+    pub fn load(_path: &Path) -> Result<Self> {
+        Err(anyhow!(
+            "lado was built without plugin support (the `plugins` feature)"
+        ))
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {}
+    }
+
+    pub fn renders(&self) -> bool {
+        match *self {}
+    }
+
+    pub fn filters_comments(&self) -> bool {
+        match *self {}
+    }
+
+    pub fn annotates(&self) -> bool {
+        match *self {}
+    }
+
+    pub fn render(&self, _path: &str, _content: &[u8]) -> Result<Option<Vec<u8>>> {
+        match *self {}
+    }
+
+    pub fn keep_comment(&self, _json: &str) -> Result<bool> {
+        match *self {}
+    }
+
+    pub fn annotate(&self, _path: &str, _diff: &str) -> Result<Option<String>> {
+        match *self {}
+    }
+}
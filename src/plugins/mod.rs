@@ -0,0 +1,260 @@
+//! WebAssembly plugins from `plugins/` in lado's config directory
+//! (`~/.config/lado/plugins/*.wasm` on Linux), for formats and checks that
+//! don't belong in lado itself.
+//!
+//! A plugin is a module exporting its `memory`, `lado_alloc(len) -> ptr` (for
+//! the arguments lado passes in), `lado_free(ptr, len)` (which lado calls on
+//! those arguments and on results once it has read them) and any of these
+//! hooks:
+//!
+//! - `lado_render(path_ptr, path_len, data_ptr, data_len) -> u64`: file
+//!   renderer. Turns a file's contents into the text that is diffed (like
+//!   git's `textconv`), e.g. a notebook or SQLite database into readable
+//!   lines.
+//! - `lado_filter_comment(json_ptr, json_len) -> u32`: comment filter. Gets a
+//!   PR comment as JSON (`path`, `line`, `side`, `author`, `body`,
+//!   `created_at`); 0 hides it.
+//! - `lado_annotate(path_ptr, path_len, diff_ptr, diff_len) -> u64`:
+//!   annotation provider. Gets a file's unified diff and returns a JSON array
+//!   of `{"line": 12, "message": "..."}` for lines of the new side, shown
+//!   like review comments by the plugin.
+//!
+//! Hooks returning `u64` return `(ptr << 32) | len` of their result in
+//! memory from `lado_alloc`, or 0 for nothing. A call that runs for too long
+//! (about a billion instructions) fails, as a crashing one does. Running
+//! plugins needs lado built with the `plugins` feature.
+
+#[cfg(not(feature = "plugins"))]
+mod disabled;
+#[cfg(feature = "plugins")]
+mod wasm;
+
+#[cfg(not(feature = "plugins"))]
+use disabled::Plugin;
+#[cfg(feature = "plugins")]
+use wasm::Plugin;
+
+use crate::git::{DiffData, DiffHunk, DiffLineType, TextConv};
+use crate::github::{CommentSide, PrComment};
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+/// The plugins of this run, loaded on first use.
+pub fn loaded() -> &'static Plugins {
+    static PLUGINS: OnceLock<Plugins> = OnceLock::new();
+    PLUGINS.get_or_init(Plugins::load)
+}
+
+/// Loaded plugins, in file name order
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Arc<Plugin>>,
+}
+
+impl Plugins {
+    /// Load every `*.wasm` of the plugin directory, warning about (and
+    /// skipping) those that fail.
+    fn load() -> Self {
+        let Some(dir) = plugin_dir() else {
+            return Self::default();
+        };
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return Self::default();
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "wasm"))
+            .collect();
+        paths.sort();
+
+        let mut plugins = Vec::new();
+        for path in paths {
+            match Plugin::load(&path) {
+                Ok(plugin) => {
+                    tracing::debug!(plugin = plugin.name(), "loaded plugin");
+                    plugins.push(Arc::new(plugin));
+                }
+                Err(e) => eprintln!("Warning: Could not load plugin {}: {:#}", path.display(), e),
+            }
+        }
+        Self { plugins }
+    }
+
+    /// The file renderers as a `textconv` for the repository; the first
+    /// plugin that renders a file wins.
+    pub fn textconv(&self) -> Option<TextConv> {
+        if !self.plugins.iter().any(|p| p.renders()) {
+            return None;
+        }
+        let plugins = self.plugins.clone();
+        Some(Arc::new(move |path: &str, content: &[u8]| {
+            plugins.iter().filter(|p| p.renders()).find_map(|plugin| {
+                plugin.render(path, content).unwrap_or_else(|e| {
+                    eprintln!(
+                        "Warning: Plugin {} failed to render {}: {:#}",
+                        plugin.name(),
+                        path,
+                        e
+                    );
+                    None
+                })
+            })
+        }))
+    }
+
+    /// `comments` without those a comment filter hides.
+    pub fn filter_comments(&self, comments: Vec<PrComment>) -> Vec<PrComment> {
+        let filters: Vec<&Arc<Plugin>> = self
+            .plugins
+            .iter()
+            .filter(|p| p.filters_comments())
+            .collect();
+        if filters.is_empty() {
+            return comments;
+        }
+        comments
+            .into_iter()
+            .filter(|comment| {
+                let json = comment_json(comment).to_string();
+                filters.iter().all(|plugin| {
+                    // A broken filter shouldn't hide anything
+                    plugin.keep_comment(&json).unwrap_or_else(|e| {
+                        eprintln!(
+                            "Warning: Plugin {} failed to filter a comment: {:#}",
+                            plugin.name(),
+                            e
+                        );
+                        true
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Whether any plugin provides annotations.
+    pub fn annotates(&self) -> bool {
+        self.plugins.iter().any(|p| p.annotates())
+    }
+
+    /// The files of `data` with their hunks, as [`Plugins::annotate`] takes
+    /// them (on another thread).
+    pub fn annotated_files(data: &DiffData) -> Vec<(String, Vec<DiffHunk>)> {
+        data.files
+            .iter()
+            .filter_map(|file| {
+                let hunks = data.file_hunks.get(&file.path)?;
+                Some((file.path.clone(), hunks.clone()))
+            })
+            .collect()
+    }
+
+    /// The annotations of every annotation provider on `files` (paths with
+    /// their hunks), as comments by the plugin.
+    pub fn annotate(&self, files: &[(String, Vec<DiffHunk>)]) -> Vec<PrComment> {
+        let mut annotations = Vec::new();
+        for plugin in self.plugins.iter().filter(|p| p.annotates()) {
+            for (path, hunks) in files {
+                let mut diff = String::new();
+                for hunk in hunks {
+                    let _ = writeln!(diff, "{}", hunk.header.trim_end());
+                    for line in &hunk.lines {
+                        let prefix = match line.line_type {
+                            DiffLineType::Add => '+',
+                            DiffLineType::Remove => '-',
+                            _ => ' ',
+                        };
+                        let _ = writeln!(diff, "{}{}", prefix, line.content);
+                    }
+                }
+                let found = plugin.annotate(path, &diff).and_then(|json| {
+                    let Some(json) = json else {
+                        return Ok(Vec::new());
+                    };
+                    Ok(serde_json::from_str::<Vec<Annotation>>(&json)?)
+                });
+                match found {
+                    Ok(found) => {
+                        for a in found {
+                            annotations.push(PrComment {
+                                // Out of the way of GitHub's ids
+                                id: u64::MAX - annotations.len() as u64,
+                                in_reply_to_id: None,
+                                path: path.clone(),
+                                line: Some(a.line),
+                                side: CommentSide::Right,
+                                body: a.message,
+                                author: plugin.name().to_string(),
+                                created_at: String::new(),
+                                commit_id: String::new(),
+                                original_commit_id: String::new(),
+                                resolved: false,
+                            });
+                        }
+                    }
+                    Err(e) => eprintln!(
+                        "Warning: Plugin {} failed to annotate {}: {:#}",
+                        plugin.name(),
+                        path,
+                        e
+                    ),
+                }
+            }
+        }
+        annotations
+    }
+}
+
+/// An annotation as returned by `lado_annotate`
+#[derive(Deserialize)]
+struct Annotation {
+    line: u32,
+    message: String,
+}
+
+/// `comment` as plugins (and the editor API of `--serve`) get it
+pub fn comment_json(comment: &PrComment) -> serde_json::Value {
+    serde_json::json!({
+        "path": comment.path,
+        "line": comment.line,
+        "side": match comment.side {
+            CommentSide::Left => "left",
+            CommentSide::Right => "right",
+        },
+        "author": comment.author,
+        "body": comment.body,
+        "created_at": comment.created_at,
+    })
+}
+
+fn plugin_dir() -> Option<PathBuf> {
+    crate::config::config_dir().map(|p| p.join("plugins"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_json() {
+        let comment = PrComment {
+            id: 1,
+            in_reply_to_id: None,
+            path: "src/app.rs".to_string(),
+            line: Some(12),
+            side: CommentSide::Left,
+            body: "nit: \"typo\"".to_string(),
+            author: "ada".to_string(),
+            created_at: "2024-01-15T10:30:00Z".to_string(),
+            commit_id: "abc".to_string(),
+            original_commit_id: "abc".to_string(),
+            resolved: false,
+        };
+        let json = comment_json(&comment);
+        assert_eq!(json["path"], "src/app.rs");
+        assert_eq!(json["line"], 12);
+        assert_eq!(json["side"], "left");
+        assert_eq!(json["body"], "nit: \"typo\"");
+    }
+}
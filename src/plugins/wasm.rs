@@ -0,0 +1,307 @@
+//! The plugin host, on wasmtime. Plugins get no imports, so they can't touch
+//! files, the network or anything else outside their own memory. Each call
+//! gets a budget of fuel, so a plugin stuck in a loop fails the call rather
+//! than hanging lado.
+
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use wasmtime::{
+    Config, Engine, Instance, Memory, Module, Store, TypedFunc, WasmParams, WasmResults,
+};
+
+/// Fuel for one call into a plugin, arguments and results included: about a
+/// billion WebAssembly instructions, a second or so of work
+const FUEL_PER_CALL: u64 = 1_000_000_000;
+
+/// A loaded plugin; calls into it are serialized
+pub struct Plugin {
+    name: String,
+    instance: Mutex<Loaded>,
+    fuel: u64,
+    renders: bool,
+    filters_comments: bool,
+    annotates: bool,
+}
+
+struct Loaded {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<u32, u32>,
+    free: TypedFunc<(u32, u32), ()>,
+    render: Option<TypedFunc<(u32, u32, u32, u32), u64>>,
+    filter_comment: Option<TypedFunc<(u32, u32), u32>>,
+    annotate: Option<TypedFunc<(u32, u32, u32, u32), u64>>,
+}
+
+impl Plugin {
+    pub fn load(path: &Path) -> Result<Self> {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let wasm = std::fs::read(path)?;
+        Self::new(name, &wasm)
+    }
+
+    /// The plugin in `wasm`, a module in the binary or the text format
+    fn new(name: String, wasm: &[u8]) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, wasm)?;
+        let mut store = Store::new(&engine, ());
+        // Starting the module runs its start function, if any
+        store.set_fuel(FUEL_PER_CALL)?;
+        let instance = Instance::new(&mut store, &module, &[])?;
+
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| anyhow!("The plugin doesn't export `memory`"))?;
+        let alloc = instance
+            .get_typed_func(&mut store, "lado_alloc")
+            .context("The plugin doesn't export `lado_alloc(u32) -> u32`")?;
+        let free = instance
+            .get_typed_func(&mut store, "lado_free")
+            .context("The plugin doesn't export `lado_free(u32, u32)`")?;
+        let render = hook(&instance, &mut store, "lado_render")?;
+        let filter_comment = hook(&instance, &mut store, "lado_filter_comment")?;
+        let annotate = hook(&instance, &mut store, "lado_annotate")?;
+
+        Ok(Self {
+            name,
+            fuel: FUEL_PER_CALL,
+            renders: render.is_some(),
+            filters_comments: filter_comment.is_some(),
+            annotates: annotate.is_some(),
+            instance: Mutex::new(Loaded {
+                store,
+                memory,
+                alloc,
+                free,
+                render,
+                filter_comment,
+                annotate,
+            }),
+        })
+    }
+
+    /// File name without `.wasm`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn renders(&self) -> bool {
+        self.renders
+    }
+
+    pub fn filters_comments(&self) -> bool {
+        self.filters_comments
+    }
+
+    pub fn annotates(&self) -> bool {
+        self.annotates
+    }
+
+    /// `lado_render`; `None` if the plugin doesn't render this file.
+    pub fn render(&self, path: &str, content: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut loaded = self.lock()?;
+        let Some(render) = loaded.render.clone() else {
+            return Ok(None);
+        };
+        let (path_ptr, path_len) = loaded.pass(path.as_bytes())?;
+        let (data_ptr, data_len) = loaded.pass(content)?;
+        let packed = render.call(&mut loaded.store, (path_ptr, path_len, data_ptr, data_len))?;
+        loaded.free(path_ptr, path_len)?;
+        loaded.free(data_ptr, data_len)?;
+        loaded.result(packed)
+    }
+
+    /// `lado_filter_comment`; whether to show the comment.
+    pub fn keep_comment(&self, json: &str) -> Result<bool> {
+        let mut loaded = self.lock()?;
+        let Some(filter) = loaded.filter_comment.clone() else {
+            return Ok(true);
+        };
+        let (ptr, len) = loaded.pass(json.as_bytes())?;
+        let keep = filter.call(&mut loaded.store, (ptr, len))? != 0;
+        loaded.free(ptr, len)?;
+        Ok(keep)
+    }
+
+    /// `lado_annotate`; the JSON array of annotations, if any.
+    pub fn annotate(&self, path: &str, diff: &str) -> Result<Option<String>> {
+        let mut loaded = self.lock()?;
+        let Some(annotate) = loaded.annotate.clone() else {
+            return Ok(None);
+        };
+        let (path_ptr, path_len) = loaded.pass(path.as_bytes())?;
+        let (diff_ptr, diff_len) = loaded.pass(diff.as_bytes())?;
+        let packed = annotate.call(&mut loaded.store, (path_ptr, path_len, diff_ptr, diff_len))?;
+        loaded.free(path_ptr, path_len)?;
+        loaded.free(diff_ptr, diff_len)?;
+        loaded
+            .result(packed)?
+            .map(|bytes| String::from_utf8(bytes).context("Annotations aren't UTF-8"))
+            .transpose()
+    }
+
+    /// The plugin, refuelled for a call
+    fn lock(&self) -> Result<MutexGuard<'_, Loaded>> {
+        let mut loaded = self
+            .instance
+            .lock()
+            .map_err(|_| anyhow!("The plugin panicked in an earlier call"))?;
+        loaded.store.set_fuel(self.fuel)?;
+        Ok(loaded)
+    }
+}
+
+/// The export `name`, if there is one. Hooks are optional, but one with the
+/// wrong signature is a mistake.
+fn hook<P: WasmParams, R: WasmResults>(
+    instance: &Instance,
+    store: &mut Store<()>,
+    name: &str,
+) -> Result<Option<TypedFunc<P, R>>> {
+    instance
+        .get_func(&mut *store, name)
+        .map(|func| func.typed(&*store))
+        .transpose()
+        .with_context(|| format!("`{}` has the wrong signature", name))
+}
+
+impl Loaded {
+    /// Copy `bytes` into memory the plugin allocated for them.
+    fn pass(&mut self, bytes: &[u8]) -> Result<(u32, u32)> {
+        let len = u32::try_from(bytes.len()).context("Too large for the plugin")?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory.write(&mut self.store, ptr as usize, bytes)?;
+        Ok((ptr, len))
+    }
+
+    /// Hand memory from `lado_alloc` back to the plugin.
+    fn free(&mut self, ptr: u32, len: u32) -> Result<()> {
+        self.free.call(&mut self.store, (ptr, len))
+    }
+
+    /// The bytes a hook returned as `(ptr << 32) | len`, freed once read;
+    /// `None` for 0.
+    fn result(&mut self, packed: u64) -> Result<Option<Vec<u8>>> {
+        if packed == 0 {
+            return Ok(None);
+        }
+        let (ptr, len) = ((packed >> 32) as u32, (packed & 0xffff_ffff) as u32);
+        let mut bytes = vec![0; len as usize];
+        self.memory.read(&self.store, ptr as usize, &mut bytes)?;
+        self.free(ptr, len)?;
+        Ok(Some(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Echoes files as their rendering, keeps comments of more than 10
+    /// bytes of JSON and notes line 3 of every file. Its two pages of memory
+    /// fill up within a few calls unless lado frees what it is given.
+    const FIXTURE: &str = r#"
+(module
+  (memory (export "memory") 2 2)
+  (global $next (mut i32) (i32.const 1024))
+  (global $live (mut i32) (i32.const 0))
+  (data (i32.const 0) "[{\"line\": 3, \"message\": \"looks odd\"}]")
+  (func $alloc (export "lado_alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (if (i32.gt_u (i32.add (local.get $ptr) (local.get $len)) (i32.const 131072))
+      (then unreachable))
+    (global.set $next (i32.add (local.get $ptr) (local.get $len)))
+    (global.set $live (i32.add (global.get $live) (i32.const 1)))
+    (local.get $ptr))
+  (func (export "lado_free") (param $ptr i32) (param $len i32)
+    (global.set $live (i32.sub (global.get $live) (i32.const 1)))
+    (if (i32.eqz (global.get $live)) (then (global.set $next (i32.const 1024)))))
+  (func $pack (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len))))
+  (func (export "lado_render") (param i32 i32 i32 i32) (result i64)
+    (local $out i32)
+    (local.set $out (call $alloc (local.get 3)))
+    (memory.copy (local.get $out) (local.get 2) (local.get 3))
+    (call $pack (local.get $out) (local.get 3)))
+  (func (export "lado_filter_comment") (param i32 i32) (result i32)
+    (i32.gt_u (local.get 1) (i32.const 10)))
+  (func (export "lado_annotate") (param i32 i32 i32 i32) (result i64)
+    (local $out i32)
+    (local.set $out (call $alloc (i32.const 37)))
+    (memory.copy (local.get $out) (i32.const 0) (i32.const 37))
+    (call $pack (local.get $out) (i32.const 37))))
+"#;
+
+    fn plugin(wat: &str) -> Result<Plugin> {
+        Plugin::new("fixture".to_string(), wat.as_bytes())
+    }
+
+    #[test]
+    fn test_hooks() {
+        let plugin = plugin(FIXTURE).unwrap();
+        assert!(plugin.renders() && plugin.filters_comments() && plugin.annotates());
+
+        // 50 KiB in and out a call, so leaked arguments or results would
+        // run it out of memory by the second
+        let content = vec![b'x'; 50 * 1024];
+        for _ in 0..5 {
+            assert_eq!(plugin.render("a.ipynb", &content).unwrap(), Some(content.clone()));
+        }
+        assert!(plugin.keep_comment(r#"{"body": "keep me"}"#).unwrap());
+        assert!(!plugin.keep_comment("{}").unwrap());
+        assert_eq!(
+            plugin.annotate("a.rs", "@@ -1 +1 @@\n-a\n+b\n").unwrap().as_deref(),
+            Some(r#"[{"line": 3, "message": "looks odd"}]"#)
+        );
+    }
+
+    #[test]
+    fn test_runaway_plugin_runs_out_of_fuel() {
+        let mut plugin = plugin(
+            r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "lado_alloc") (param i32) (result i32) (i32.const 0))
+  (func (export "lado_free") (param i32 i32))
+  (func (export "lado_filter_comment") (param i32 i32) (result i32)
+    (loop $forever (br $forever))
+    (i32.const 1)))
+"#,
+        )
+        .unwrap();
+        plugin.fuel = 100_000;
+        assert!(plugin.keep_comment("{}").is_err());
+        // Refuelled, but still looping
+        assert!(plugin.keep_comment("{}").is_err());
+    }
+
+    #[test]
+    fn test_missing_exports() {
+        let error = |wat: &str| format!("{:#}", plugin(wat).err().unwrap());
+        assert!(error("(module)").contains("`memory`"));
+        assert!(error(r#"(module (memory (export "memory") 1))"#).contains("`lado_alloc"));
+        assert!(error(
+            r#"(module
+  (memory (export "memory") 1)
+  (func (export "lado_alloc") (param i32) (result i32) (i32.const 0)))"#
+        )
+        .contains("`lado_free"));
+        assert!(error(
+            r#"(module
+  (memory (export "memory") 1)
+  (func (export "lado_alloc") (param i32) (result i32) (i32.const 0))
+  (func (export "lado_free") (param i32 i32))
+  (func (export "lado_render") (result i32) (i32.const 0)))"#
+        )
+        .contains("`lado_render` has the wrong signature"));
+    }
+}
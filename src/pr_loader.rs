@@ -0,0 +1,99 @@
+//! Background loading of pull request data.
+//!
+//! Every `gh` call (and attachment download) runs on a worker thread so the
+//! window stays responsive on large PRs. Results are streamed back to the UI
+//! thread as [`PrEvent`]s in whatever order they complete. Sourcehut
+//! patchsets load the same way, as [`PatchsetEvent`]s.
+
+use crate::attachments;
+use crate::github::{self, PrComment, PrCommit, PrInfo};
+use crate::mbox::{Mail, Series};
+use crate::sourcehut::{self, PatchsetId};
+use anyhow::Result;
+use async_channel::{Receiver, Sender};
+use std::path::Path;
+
+/// A piece of PR data that finished loading.
+pub enum PrEvent {
+    Info(Result<PrInfo>),
+    Commits(Result<Vec<PrCommit>>),
+    Comments(Result<Vec<PrComment>>),
+}
+
+impl PrEvent {
+    /// Short name of the request this event answers, used for progress display.
+    pub fn label(&self) -> &'static str {
+        match self {
+            PrEvent::Info(_) => "info",
+            PrEvent::Commits(_) => "commits",
+            PrEvent::Comments(_) => "comments",
+        }
+    }
+}
+
+/// Labels of all events a loader produces, in display order.
+pub const ALL_LABELS: &[&str] = &["info", "commits", "comments"];
+
+/// Start fetching everything lado needs for `pr_number`, asking gh in `dir`
+/// (the window's repository).
+///
+/// The requests run concurrently; the receiver closes once all of them have
+/// reported back.
+pub fn spawn(dir: &Path, pr_number: u32) -> Receiver<PrEvent> {
+    let (tx, rx) = async_channel::unbounded();
+
+    let info_dir = dir.to_path_buf();
+    run(&tx, move || PrEvent::Info(github::get_pr_refs(&info_dir, pr_number)));
+    let commits_dir = dir.to_path_buf();
+    run(&tx, move || {
+        PrEvent::Commits(github::get_pr_commits(&commits_dir, pr_number))
+    });
+    let dir = dir.to_path_buf();
+    run(&tx, move || {
+        let comments = github::get_pr_comments(&dir, pr_number);
+        if let Ok(ref comments) = comments {
+            // Download screenshots up front so rendering only hits the disk cache
+            attachments::prefetch(comments);
+        }
+        PrEvent::Comments(comments)
+    });
+
+    rx
+}
+
+/// Run `work` on its own thread and send the result. A closed channel means
+/// the UI has gone away, so the result is simply dropped.
+fn run(tx: &Sender<PrEvent>, work: impl FnOnce() -> PrEvent + Send + 'static) {
+    let tx = tx.clone();
+    std::thread::spawn(move || {
+        let _ = tx.send_blocking(work());
+    });
+}
+
+/// A piece of a Sourcehut patchset that finished loading: its patches, then
+/// the replies in its thread.
+pub enum PatchsetEvent {
+    Series(Result<Series>),
+    /// With the cover letter, if only the thread had it
+    Replies(Result<(Vec<Mail>, Option<Mail>)>),
+}
+
+/// Start fetching the patchset `id`. The receiver closes once both events
+/// have been sent, or after a failed `Series`.
+pub fn spawn_patchset(id: PatchsetId) -> Receiver<PatchsetEvent> {
+    let (tx, rx) = async_channel::unbounded();
+    std::thread::spawn(move || {
+        let mut series = match sourcehut::get_series(&id) {
+            Ok(series) => series,
+            Err(e) => {
+                let _ = tx.send_blocking(PatchsetEvent::Series(Err(e)));
+                return;
+            }
+        };
+        let _ = tx.send_blocking(PatchsetEvent::Series(Ok(series.clone())));
+        let replies = sourcehut::get_replies(&id, &mut series);
+        let replies = replies.map(|replies| (replies, series.cover_letter));
+        let _ = tx.send_blocking(PatchsetEvent::Replies(replies));
+    });
+    rx
+}
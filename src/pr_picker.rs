@@ -0,0 +1,284 @@
+//! `lado pr list`: print the open PRs and pick one in the terminal (also
+//! used by `lado recent`).
+//!
+//! On a terminal the list becomes a picker (arrow keys or j/k, Enter to
+//! open, q or Esc to quit; outside Unix, a prompt for the number). Piped, it
+//! is only printed.
+
+use crate::github::{self, PrSummary};
+use anyhow::Result;
+use std::io::{self, IsTerminal, Read, Write};
+
+/// Print the open PRs and return the one picked, if any.
+pub fn pick() -> Result<Option<u32>> {
+    let prs = github::list_open_prs(&std::env::current_dir()?)?;
+    if prs.is_empty() {
+        eprintln!("No open pull requests");
+        return Ok(None);
+    }
+
+    let rows: Vec<String> = prs.iter().map(format_row).collect();
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        for row in &rows {
+            println!("{}", row);
+        }
+        return Ok(None);
+    }
+
+    #[cfg(unix)]
+    let picked = run_picker(&rows)?.map(|i| prs[i].number);
+    #[cfg(not(unix))]
+    let picked = prompt_for_number(&rows)?;
+    Ok(picked)
+}
+
+/// Print `rows` and return the index of the one picked, if any.
+pub fn pick_row(rows: &[String]) -> Result<Option<usize>> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        for row in rows {
+            println!("{}", row);
+        }
+        return Ok(None);
+    }
+
+    #[cfg(unix)]
+    let picked = run_picker(rows)?;
+    #[cfg(not(unix))]
+    let picked = prompt_for_row(rows)?;
+    Ok(picked)
+}
+
+/// `#123  Fix the thing                 alice       2024-01-15`
+fn format_row(pr: &PrSummary) -> String {
+    let title: String = if pr.title.chars().count() > 60 {
+        pr.title.chars().take(59).chain(['…']).collect()
+    } else {
+        pr.title.clone()
+    };
+    // Only the date of "2024-01-15T10:30:00Z"
+    let updated = pr.updated_at.split('T').next().unwrap_or_default();
+    format!(
+        "{:<6} {:<60}  {:<16} {}",
+        format!("#{}", pr.number),
+        title,
+        pr.author,
+        updated
+    )
+}
+
+/// A key press the picker reacts to
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Enter,
+    Quit,
+}
+
+/// Selection state of the picker
+#[cfg(unix)]
+struct Picker {
+    selected: usize,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl Picker {
+    /// Apply `key`; `Some` once the picker is done, holding the picked row
+    /// (`None` if the user quit).
+    fn handle(&mut self, key: Key) -> Option<Option<usize>> {
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(self.len - 1),
+            Key::Enter => return Some(Some(self.selected)),
+            Key::Quit => return Some(None),
+        }
+        None
+    }
+}
+
+#[cfg(unix)]
+fn run_picker(rows: &[String]) -> Result<Option<usize>> {
+    let mut picker = Picker {
+        selected: 0,
+        len: rows.len(),
+    };
+    let mut stdout = io::stdout();
+    let _raw = RawMode::enable()?;
+
+    draw(&mut stdout, rows, picker.selected, false)?;
+    let picked = loop {
+        let Some(key) = read_key()? else { continue };
+        if let Some(picked) = picker.handle(key) {
+            break picked;
+        }
+        draw(&mut stdout, rows, picker.selected, true)?;
+    };
+    writeln!(stdout)?;
+    Ok(picked)
+}
+
+/// Print the rows and read the PR number to open.
+#[cfg(not(unix))]
+fn prompt_for_number(rows: &[String]) -> Result<Option<u32>> {
+    for row in rows {
+        println!("{}", row);
+    }
+    print!("PR to open (empty to quit): #");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    let line = line.trim().trim_start_matches('#');
+    if line.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(line.parse()?))
+}
+
+/// Print the rows numbered from 1 and read the number of the one to open.
+#[cfg(not(unix))]
+fn prompt_for_row(rows: &[String]) -> Result<Option<usize>> {
+    for (i, row) in rows.iter().enumerate() {
+        println!("{:>2}. {}", i + 1, row);
+    }
+    print!("Number to open (empty to quit): ");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    match line.parse::<usize>() {
+        Ok(n) if (1..=rows.len()).contains(&n) => Ok(Some(n - 1)),
+        _ => Err(anyhow::anyhow!("Not one of the numbers: {}", line)),
+    }
+}
+
+/// Draw the rows with `selected` highlighted, over the previous drawing if
+/// `redraw`.
+#[cfg(unix)]
+fn draw(out: &mut impl Write, rows: &[String], selected: usize, redraw: bool) -> io::Result<()> {
+    if redraw {
+        // Back to the first row (the cursor sits at the end of the last one)
+        write!(out, "\r\x1b[{}A", rows.len() - 1)?;
+    }
+    for (i, row) in rows.iter().enumerate() {
+        if i > 0 {
+            write!(out, "\r\n")?;
+        }
+        if i == selected {
+            write!(out, "\x1b[2K\x1b[7m> {}\x1b[0m", row)?;
+        } else {
+            write!(out, "\x1b[2K  {}", row)?;
+        }
+    }
+    out.flush()
+}
+
+/// Next key press; `None` for anything the picker ignores.
+#[cfg(unix)]
+fn read_key() -> io::Result<Option<Key>> {
+    let mut stdin = io::stdin();
+    let mut byte = [0u8; 1];
+    if stdin.read(&mut byte)? == 0 {
+        return Ok(None);
+    }
+    let key = match byte[0] {
+        b'\r' | b'\n' => Key::Enter,
+        b'k' => Key::Up,
+        b'j' => Key::Down,
+        // q, Ctrl-C, Ctrl-D
+        b'q' | 3 | 4 => Key::Quit,
+        0x1b => {
+            // Arrow keys arrive as ESC [ A/B; a lone ESC times out
+            let mut seq = [0u8; 2];
+            if stdin.read(&mut seq)? < 2 {
+                return Ok(Some(Key::Quit));
+            }
+            match seq {
+                [b'[', b'A'] => Key::Up,
+                [b'[', b'B'] => Key::Down,
+                _ => return Ok(None),
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(key))
+}
+
+/// Unbuffered, unechoed terminal input for as long as this lives.
+#[cfg(unix)]
+struct RawMode {
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        // SAFETY: termios is plain data, filled in by tcgetattr before use
+        unsafe {
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = original;
+            // Ctrl-C is read as a key, so the terminal is always restored
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            // Reads give up after 0.1s, which tells a lone ESC from an arrow key
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 1;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { original })
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read in `enable`
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_picker_keys() {
+        let mut picker = Picker {
+            selected: 0,
+            len: 3,
+        };
+        assert_eq!(picker.handle(Key::Up), None);
+        assert_eq!(picker.selected, 0);
+        picker.handle(Key::Down);
+        picker.handle(Key::Down);
+        picker.handle(Key::Down);
+        assert_eq!(picker.selected, 2);
+        assert_eq!(picker.handle(Key::Enter), Some(Some(2)));
+        assert_eq!(picker.handle(Key::Quit), Some(None));
+    }
+
+    #[test]
+    fn test_format_row() {
+        let pr = PrSummary {
+            number: 42,
+            title: "x".repeat(80),
+            author: "alice".to_string(),
+            updated_at: "2024-01-15T10:30:00Z".to_string(),
+        };
+        let row = format_row(&pr);
+        assert!(row.starts_with("#42    xxx"));
+        assert!(row.contains("xxx… "));
+        assert!(row.ends_with("alice            2024-01-15"));
+    }
+}
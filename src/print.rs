@@ -0,0 +1,186 @@
+//! `--print`: the diff written to stdout with ANSI colors instead of shown
+//! in a window, highlighted as in the window and with added and removed
+//! lines on tinted backgrounds, to read in a pager (`lado main --print |
+//! less -R`). `NO_COLOR` leaves the colors out.
+
+use crate::cli::{Args, DiffTarget};
+use crate::export::page_colors;
+use crate::git::{DiffData, DiffHunk, DiffLineType, FileChange};
+use crate::highlighting::Highlighter;
+use crate::target;
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+const RESET: &str = "\x1b[0m";
+/// Fills the rest of the terminal line with the current background
+const CLEAR_LINE: &str = "\x1b[K";
+
+/// Compute the diff of the target and print it.
+pub fn run(args: &Args) -> Result<()> {
+    let target = DiffTarget::from_args(args);
+    let dir = std::env::current_dir()?;
+    let repo = target::open_repository(&dir, &target)?;
+    let mut data = target::load_diff(&dir, &repo, &target)?;
+    let mut config = crate::config::load();
+    args.overrides.apply(&mut config);
+    data.expand_tabs(config.tab_width as usize);
+
+    let output = if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        crate::export::to_patch(&data)
+    } else {
+        let mut highlighter = Highlighter::new();
+        highlighter.set_theme(config.syntax_theme.as_ref().unwrap_or(&config.ui_theme));
+        to_ansi(&data, &highlighter, &config.ui_theme)
+    };
+    match std::io::stdout().write_all(output.as_bytes()) {
+        // The pager quit before reading it all
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// The diff with 24-bit ANSI colors, in the colors of `ui_theme`.
+fn to_ansi(data: &DiffData, highlighter: &Highlighter, ui_theme: &str) -> String {
+    let colors = page_colors(ui_theme);
+    let mut out = String::new();
+    for (index, file) in data.files.iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        let hunks = data
+            .file_hunks
+            .get(&file.path)
+            .map_or(&[][..], Vec::as_slice);
+        ansi_file(&mut out, file, hunks, highlighter, &colors);
+    }
+    out
+}
+
+fn ansi_file(
+    out: &mut String,
+    file: &FileChange,
+    hunks: &[DiffHunk],
+    highlighter: &Highlighter,
+    colors: &[&str; 8],
+) {
+    let [_, _, muted, _, add, remove, hunk_bg, hunk_fg] = *colors;
+    let _ = writeln!(
+        out,
+        "\x1b[1m{}{}  {}{} +{} -{}{}",
+        file.path,
+        RESET,
+        fg(muted),
+        file.status.as_str(),
+        file.additions,
+        file.deletions,
+        RESET
+    );
+
+    let source: String = hunks
+        .iter()
+        .flat_map(|h| &h.lines)
+        .map(|line| format!("{}\n", line.content))
+        .collect();
+    let mut highlighted = highlighter.highlight(&source, &file.path).into_iter();
+    let num = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+    for hunk in hunks {
+        let _ = writeln!(
+            out,
+            "{}{}{}{}{}",
+            bg(hunk_bg),
+            fg(hunk_fg),
+            hunk.header.trim_end(),
+            CLEAR_LINE,
+            RESET
+        );
+        for line in &hunk.lines {
+            let background = match line.line_type {
+                DiffLineType::Add => bg(add),
+                DiffLineType::Remove => bg(remove),
+                _ => String::new(),
+            };
+            let marker = match line.line_type {
+                DiffLineType::Add => '+',
+                DiffLineType::Remove => '-',
+                _ => ' ',
+            };
+            let _ = write!(
+                out,
+                "{}{}{:>5} {:>5} {} ",
+                background,
+                fg(muted),
+                num(line.old_line_num),
+                num(line.new_line_num),
+                marker
+            );
+            match highlighted.next() {
+                Some(spans) => {
+                    for span in &spans.spans {
+                        let text = span.text.trim_end_matches(['\n', '\r']);
+                        let _ = write!(out, "{}{}", fg(&span.color), text);
+                    }
+                }
+                None => {
+                    // The terminal's own text color
+                    let _ = write!(out, "\x1b[39m{}", line.content);
+                }
+            }
+            let _ = writeln!(out, "{}{}", CLEAR_LINE, RESET);
+        }
+    }
+}
+
+/// Escape sequence for the foreground color `#rrggbb`; nothing if `hex`
+/// isn't one
+fn fg(hex: &str) -> String {
+    rgb(hex).map_or_else(String::new, |(r, g, b)| {
+        format!("\x1b[38;2;{};{};{}m", r, g, b)
+    })
+}
+
+/// Escape sequence for the background color `#rrggbb`
+fn bg(hex: &str) -> String {
+    rgb(hex).map_or_else(String::new, |(r, g, b)| {
+        format!("\x1b[48;2;{};{};{}m", r, g, b)
+    })
+}
+
+fn rgb(hex: &str) -> Option<(u8, u8, u8)> {
+    let hex = hex.strip_prefix('#')?;
+    let component = |at: usize| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok();
+    Some((component(0)?, component(2)?, component(4)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::parse_patch;
+
+    #[test]
+    fn test_to_ansi() {
+        let data = parse_patch(
+            "\
+diff --git a/notes.txt b/notes.txt
+--- a/notes.txt
++++ b/notes.txt
+@@ -1,2 +1,2 @@
+ same
+-old
++new
+",
+        )
+        .unwrap();
+        let ansi = to_ansi(&data, &Highlighter::new(), "dark");
+        let lines: Vec<&str> = ansi.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("\x1b[1mnotes.txt"));
+        // Added and removed lines on the theme's backgrounds
+        assert!(lines[3].starts_with("\x1b[48;2;46;26;26m"));
+        assert!(lines[4].starts_with("\x1b[48;2;26;46;26m"));
+        assert!(lines[4].contains("new"));
+        assert!(lines.iter().all(|line| line.ends_with(RESET)));
+        assert_eq!(fg("#ff8000"), "\x1b[38;2;255;128;0m");
+        assert_eq!(fg("red"), "");
+    }
+}
@@ -0,0 +1,185 @@
+//! Timing summary for `--profile`.
+//!
+//! Diff computation, `gh` calls, highlighting and model building run inside
+//! `tracing` spans. With `--profile`, this subscriber (fed by the logger
+//! `logging::install` sets up) adds up how long each kind of span was entered
+//! and prints a table on exit.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Collects per-span timings.
+#[derive(Default)]
+pub struct Profiler {
+    next_id: AtomicU64,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Spans that haven't been closed yet
+    spans: HashMap<u64, OpenSpan>,
+    /// Totals per span name
+    stats: HashMap<String, Stat>,
+}
+
+struct OpenSpan {
+    name: String,
+    /// Handles to the span still alive
+    refs: usize,
+    entered: Option<Instant>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stat {
+    pub calls: u32,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl Profiler {
+    /// Totals per span name, slowest first.
+    pub fn summary(&self) -> Vec<(String, Stat)> {
+        let state = self.state.lock().unwrap();
+        let mut stats: Vec<_> = state.stats.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        stats.sort_by_key(|(_, stat)| std::cmp::Reverse(stat.total));
+        stats
+    }
+
+    /// Print the timing table to stderr.
+    pub fn print_summary(&self) {
+        let stats = self.summary();
+        let width = stats.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        eprintln!(
+            "{:<width$}  {:>6}  {:>10}  {:>10}",
+            "span", "calls", "total", "max"
+        );
+        for (name, stat) in stats {
+            eprintln!(
+                "{:<width$}  {:>6}  {:>10}  {:>10}",
+                name,
+                stat.calls,
+                format_duration(stat.total),
+                format_duration(stat.max),
+            );
+        }
+    }
+}
+
+fn format_duration(d: Duration) -> String {
+    format!("{:.1}ms", d.as_secs_f64() * 1000.0)
+}
+
+/// `lado::git::repository` + `diff_files` -> `git::repository::diff_files`
+fn span_name(metadata: &Metadata<'_>) -> String {
+    let target = metadata.target();
+    let target = target.strip_prefix("lado::").unwrap_or(target);
+    if target == "lado" {
+        metadata.name().to_string()
+    } else {
+        format!("{}::{}", target, metadata.name())
+    }
+}
+
+impl Subscriber for Profiler {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_span()
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let name = span_name(span.metadata());
+        let mut state = self.state.lock().unwrap();
+        state.stats.entry(name.clone()).or_default().calls += 1;
+        state.spans.insert(
+            id,
+            OpenSpan {
+                name,
+                refs: 1,
+                entered: None,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, span: &Id) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(open) = state.spans.get_mut(&span.into_u64()) {
+            open.entered.get_or_insert_with(Instant::now);
+        }
+    }
+
+    fn exit(&self, span: &Id) {
+        let mut state = self.state.lock().unwrap();
+        let Some(open) = state.spans.get_mut(&span.into_u64()) else {
+            return;
+        };
+        let Some(entered) = open.entered.take() else {
+            return;
+        };
+        let elapsed = entered.elapsed();
+        let name = open.name.clone();
+        let stat = state.stats.entry(name).or_default();
+        stat.total += elapsed;
+        stat.max = stat.max.max(elapsed);
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        let mut state = self.state.lock().unwrap();
+        if let Some(open) = state.spans.get_mut(&span.into_u64()) {
+            open.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let id = span.into_u64();
+        let Some(open) = state.spans.get_mut(&id) else {
+            return false;
+        };
+        open.refs -= 1;
+        if open.refs == 0 {
+            state.spans.remove(&id);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_spans_are_counted_and_timed() {
+        let profiler = Arc::new(Profiler::default());
+        tracing::subscriber::with_default(Arc::clone(&profiler), || {
+            for _ in 0..3 {
+                let _span = tracing::info_span!("work").entered();
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        });
+
+        let summary = profiler.summary();
+        let (name, stat) = &summary[0];
+        assert_eq!(name, "profile::tests::work");
+        assert_eq!(stat.calls, 3);
+        assert!(stat.total >= Duration::from_millis(3));
+        assert!(stat.max <= stat.total);
+        // Closed spans are forgotten
+        assert!(profiler.state.lock().unwrap().spans.is_empty());
+    }
+}
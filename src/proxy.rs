@@ -0,0 +1,67 @@
+//! Proxy settings for what lado asks GitHub, for networks that can't reach
+//! it directly. Requests go through gh and curl, so the settings of
+//! config.toml are handed to them as the environment variables they read;
+//! those already set win.
+
+use crate::config::Config;
+
+/// Environment variables for `config`'s proxy settings, leaving out those
+/// `env` already has
+fn variables(config: &Config, env: impl Fn(&str) -> Option<String>) -> Vec<(&'static str, String)> {
+    let ca_bundle = config
+        .ca_bundle
+        .as_ref()
+        .map(|path| path.display().to_string());
+    // curl reads the lower-case names, gh either; SSL_CERT_FILE is gh's
+    let settings = [
+        (&["HTTPS_PROXY", "https_proxy"][..], &config.https_proxy),
+        (&["NO_PROXY", "no_proxy"], &config.no_proxy),
+        (&["SSL_CERT_FILE", "CURL_CA_BUNDLE"], &ca_bundle),
+    ];
+    let mut variables = Vec::new();
+    for (names, value) in settings {
+        let Some(value) = value.as_ref().filter(|value| !value.is_empty()) else {
+            continue;
+        };
+        if names.iter().any(|name| env(name).is_some()) {
+            continue;
+        }
+        variables.extend(names.iter().map(|name| (*name, value.clone())));
+    }
+    variables
+}
+
+/// Set the environment for `config`'s proxy settings. Call before any
+/// threads start.
+pub fn apply(config: &Config) {
+    for (name, value) in variables(config, |name| std::env::var(name).ok()) {
+        std::env::set_var(name, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_variables() {
+        let config = Config {
+            https_proxy: Some("http://proxy:3128".to_string()),
+            no_proxy: Some("localhost".to_string()),
+            ca_bundle: Some(PathBuf::from("/etc/ssl/corp.pem")),
+            ..Config::default()
+        };
+        let env = |name: &str| (name == "no_proxy").then(|| "example.com".to_string());
+        assert_eq!(
+            variables(&config, env),
+            [
+                ("HTTPS_PROXY", "http://proxy:3128".to_string()),
+                ("https_proxy", "http://proxy:3128".to_string()),
+                ("SSL_CERT_FILE", "/etc/ssl/corp.pem".to_string()),
+                ("CURL_CA_BUNDLE", "/etc/ssl/corp.pem".to_string()),
+            ]
+        );
+        assert!(variables(&Config::default(), |_| None).is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+//! Recently opened targets per repository, newest first, for the Recent
+//! menu of the toolbar and `lado recent`.
+
+use crate::cli::DiffTarget;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// How many targets are kept per repository
+const MAX_PER_REPO: usize = 10;
+
+/// A target as it was opened
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentTarget {
+    /// Title of the window, e.g. "PR #42"
+    pub title: String,
+    /// Arguments that open it again, e.g. `["pr", "42"]`
+    pub args: Vec<String>,
+}
+
+/// Persisted list: repository work directory -> recent targets
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecentTargets {
+    repos: HashMap<PathBuf, Vec<RecentTarget>>,
+}
+
+impl RecentTargets {
+    /// The targets opened in `repo`, newest first.
+    pub fn for_repo(&self, repo: &Path) -> &[RecentTarget] {
+        self.repos.get(repo).map_or(&[], Vec::as_slice)
+    }
+
+    /// Put `target` first in the list of `repo`.
+    pub fn push(&mut self, repo: &Path, target: RecentTarget) {
+        let targets = self.repos.entry(repo.to_path_buf()).or_default();
+        targets.retain(|t| t.args != target.args);
+        targets.insert(0, target);
+        targets.truncate(MAX_PER_REPO);
+    }
+
+    /// Load from disk. Returns default if missing or invalid.
+    pub fn load() -> Self {
+        let Some(path) = recent_path() else {
+            return Self::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Save to disk.
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = recent_path() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Could not determine config directory",
+            ));
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        std::fs::write(&path, contents)
+    }
+}
+
+/// Remember that `target` was opened in `repo`. Returns the other targets
+/// opened there, newest first.
+pub fn record(repo: &Path, target: &DiffTarget, title: &str) -> Vec<RecentTarget> {
+    let mut recent = RecentTargets::load();
+    let Some(args) = target_args(target) else {
+        return recent.for_repo(repo).to_vec();
+    };
+    recent.push(
+        repo,
+        RecentTarget {
+            title: title.to_string(),
+            args,
+        },
+    );
+    if let Err(e) = recent.save() {
+        eprintln!("Warning: Could not save recent targets: {}", e);
+    }
+    recent.for_repo(repo)[1..].to_vec()
+}
+
+/// Open `target` in another lado window.
+pub fn open(repo: &Path, target: &RecentTarget) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to find the lado executable")?;
+    std::process::Command::new(exe)
+        .args(&target.args)
+        .current_dir(repo)
+        .spawn()
+        .map_err(|e| anyhow!("Failed to open {}: {}", target.title, e))?;
+    Ok(())
+}
+
+/// The arguments that make lado open `target`; `None` for diffs that don't
+/// come from the repository.
+fn target_args(target: &DiffTarget) -> Option<Vec<String>> {
+    let args = match target {
+        DiffTarget::DefaultBranch => vec![],
+        // A number is only a ref when GitHub was off
+        DiffTarget::Ref(r) if matches!(DiffTarget::parse(Some(r)), DiffTarget::PullRequest(_)) => {
+            vec!["--offline".to_string(), r.clone()]
+        }
+        DiffTarget::Ref(r) => vec![r.clone()],
+        DiffTarget::PullRequest(pr) => vec!["pr".to_string(), pr.to_string()],
+        DiffTarget::Refs { base, head } => vec![base.clone(), head.clone()],
+        DiffTarget::MergeBase { base, head } => vec![format!("{}...{}", base, head)],
+        DiffTarget::Commit(rev) => vec!["show".to_string(), rev.clone()],
+        DiffTarget::Log(base) => std::iter::once("log".to_string())
+            .chain(base.clone())
+            .collect(),
+        DiffTarget::Mbox(path) => vec!["--mbox".to_string(), path.display().to_string()],
+        DiffTarget::Patchset(id) => vec!["srht".to_string(), id.url()],
+        DiffTarget::WorkingTree => vec!["--worktree".to_string()],
+        DiffTarget::Staged => vec!["--staged".to_string()],
+        // Not tied to a repository
+        DiffTarget::Stdin | DiffTarget::Files { .. } | DiffTarget::Dirs { .. } => return None,
+    };
+    Some(args)
+}
+
+fn recent_path() -> Option<PathBuf> {
+    crate::config::config_dir().map(|p| p.join("recent_targets.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recent(title: &str) -> RecentTarget {
+        RecentTarget {
+            title: title.to_string(),
+            args: vec![title.to_string()],
+        }
+    }
+
+    #[test]
+    fn test_push_moves_to_front_and_caps() {
+        let repo = Path::new("/src/lado");
+        let mut targets = RecentTargets::default();
+        for i in 0..12 {
+            targets.push(repo, recent(&i.to_string()));
+        }
+        targets.push(repo, recent("5"));
+        let titles: Vec<&str> = targets
+            .for_repo(repo)
+            .iter()
+            .map(|t| t.title.as_str())
+            .collect();
+        assert_eq!(titles, ["5", "11", "10", "9", "8", "7", "6", "4", "3", "2"]);
+        assert!(targets.for_repo(Path::new("/src/other")).is_empty());
+    }
+
+    #[test]
+    fn test_target_args() {
+        assert_eq!(
+            target_args(&DiffTarget::PullRequest(42)),
+            Some(vec!["pr".to_string(), "42".to_string()])
+        );
+        assert_eq!(
+            target_args(&DiffTarget::Ref("42".to_string())),
+            Some(vec!["--offline".to_string(), "42".to_string()])
+        );
+        assert_eq!(
+            target_args(&DiffTarget::Log(None)),
+            Some(vec!["log".to_string()])
+        );
+        assert_eq!(target_args(&DiffTarget::Stdin), None);
+    }
+}
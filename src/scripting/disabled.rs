@@ -0,0 +1,28 @@
+//! Stand-in for the script engine when lado is built without the
+//! `scripting` feature: scripts are only pointed out, never run.
+
+use super::{script_paths, Action, ScriptState};
+use async_channel::Receiver;
+use std::path::Path;
+
+pub struct Scripts;
+
+impl Scripts {
+    pub fn load(_dir: &Path) -> Self {
+        for path in script_paths() {
+            eprintln!(
+                "Warning: Not running script {}: lado was built without scripting (the `scripting` feature)",
+                path.display()
+            );
+        }
+        Self
+    }
+
+    pub fn on_open(&self, _state: ScriptState) -> Option<Receiver<Vec<Action>>> {
+        None
+    }
+
+    pub fn key(&self, _key: &str, _state: ScriptState) -> Option<Receiver<Vec<Action>>> {
+        None
+    }
+}
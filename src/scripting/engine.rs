@@ -0,0 +1,365 @@
+//! The script engine, on Rhai. Scripts run on a thread of their own, so a
+//! slow `run(command)` or a script stuck in a loop doesn't hold up the
+//! window.
+
+use super::{script_paths, Action, ScriptState};
+use async_channel::{Receiver, Sender};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, AST, INT};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use std::sync::mpsc;
+
+/// Operations a script function (or a script's top level) may take before
+/// it is stopped, a few seconds' worth
+const MAX_OPERATIONS: u64 = 50_000_000;
+
+/// A call for the scripts' thread, with where to send the actions to
+enum Call {
+    OnOpen(ScriptState, Sender<Vec<Action>>),
+    Key(String, ScriptState, Sender<Vec<Action>>),
+}
+
+/// The scripts, running on their own thread, and what they bound
+pub struct Scripts {
+    /// `None` without scripts, when there is no thread
+    calls: Option<mpsc::Sender<Call>>,
+    keys: HashSet<String>,
+    /// Some script defines `on_open`
+    opens: bool,
+}
+
+impl Scripts {
+    /// Start the scripts' thread, which compiles every script and runs its
+    /// top level (for `bind`). Their shell commands run in `dir`.
+    pub fn load(dir: &Path) -> Self {
+        Self::start(dir, script_paths(), MAX_OPERATIONS)
+    }
+
+    fn start(dir: &Path, paths: Vec<PathBuf>, max_operations: u64) -> Self {
+        if paths.is_empty() {
+            return Self {
+                calls: None,
+                keys: HashSet::new(),
+                opens: false,
+            };
+        }
+        let (calls, queue) = mpsc::channel();
+        let (bound, bindings) = mpsc::channel();
+        let dir = dir.to_path_buf();
+        std::thread::spawn(move || {
+            let loaded = Loaded::load(&dir, &paths, max_operations);
+            let _ = bound.send((loaded.keys.keys().cloned().collect(), loaded.opens()));
+            for call in queue {
+                match call {
+                    Call::OnOpen(state, reply) => {
+                        let _ = reply.send_blocking(loaded.on_open(&state));
+                    }
+                    Call::Key(key, state, reply) => {
+                        let _ = reply.send_blocking(loaded.key(&key, &state));
+                    }
+                }
+            }
+        });
+        // The top levels only bind keys, within the operation limit
+        let (keys, opens) = bindings.recv().unwrap_or_default();
+        Self {
+            calls: Some(calls),
+            keys,
+            opens,
+        }
+    }
+
+    /// Call `on_open` of every script that has one; `None` if none has.
+    /// The actions they ask for arrive on the returned channel.
+    pub fn on_open(&self, state: ScriptState) -> Option<Receiver<Vec<Action>>> {
+        if !self.opens {
+            return None;
+        }
+        self.send(|reply| Call::OnOpen(state, reply))
+    }
+
+    /// Call the function bound to `key`; `None` if no script bound it.
+    pub fn key(&self, key: &str, state: ScriptState) -> Option<Receiver<Vec<Action>>> {
+        if !self.keys.contains(key) {
+            return None;
+        }
+        let key = key.to_string();
+        self.send(|reply| Call::Key(key, state, reply))
+    }
+
+    fn send(
+        &self,
+        call: impl FnOnce(Sender<Vec<Action>>) -> Call,
+    ) -> Option<Receiver<Vec<Action>>> {
+        let (reply, actions) = async_channel::bounded(1);
+        self.calls.as_ref()?.send(call(reply)).ok()?;
+        Some(actions)
+    }
+}
+
+/// The compiled scripts on their thread
+struct Loaded {
+    engine: Engine,
+    scripts: Vec<AST>,
+    /// Key -> script and function it calls
+    keys: HashMap<String, (usize, String)>,
+    /// Filled by `hide` and `select` while a script function runs
+    actions: Rc<RefCell<Vec<Action>>>,
+}
+
+impl Loaded {
+    /// Compile the scripts at `paths` and run their top levels, warning
+    /// about (and skipping) those that fail.
+    fn load(dir: &Path, paths: &[PathBuf], max_operations: u64) -> Self {
+        let actions = Rc::new(RefCell::new(Vec::new()));
+        let bindings = Rc::new(RefCell::new(Vec::new()));
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_operations);
+        engine.on_print(|text| eprintln!("{}", text));
+        let hidden = Rc::clone(&actions);
+        engine.register_fn("hide", move |glob: &str| {
+            hidden.borrow_mut().push(Action::Hide(glob.to_string()));
+        });
+        let selected = Rc::clone(&actions);
+        engine.register_fn("select", move |path: &str| {
+            selected.borrow_mut().push(Action::Select(path.to_string()));
+        });
+        let dir = dir.to_path_buf();
+        engine.register_fn("run", move |command: &str| run(&dir, command));
+        let bound = Rc::clone(&bindings);
+        engine.register_fn("bind", move |key: &str, function: &str| {
+            bound
+                .borrow_mut()
+                .push((key.to_string(), function.to_string()));
+        });
+
+        let mut scripts = Vec::new();
+        let mut keys = HashMap::new();
+        for path in paths {
+            let loaded = engine
+                .compile_file(path.clone())
+                .and_then(|ast| engine.run_ast(&ast).map(|()| ast));
+            match loaded {
+                Ok(ast) => {
+                    for (key, function) in bindings.borrow_mut().drain(..) {
+                        keys.insert(key, (scripts.len(), function));
+                    }
+                    scripts.push(ast);
+                }
+                Err(e) => {
+                    eprintln!("Warning: Could not load script {}: {}", path.display(), e);
+                    bindings.borrow_mut().clear();
+                }
+            }
+        }
+        // Top levels only bind keys; there is nothing to act on yet
+        actions.borrow_mut().clear();
+
+        Self {
+            engine,
+            scripts,
+            keys,
+            actions,
+        }
+    }
+
+    /// Whether some script defines `on_open`.
+    fn opens(&self) -> bool {
+        (0..self.scripts.len()).any(|script| self.has_on_open(script))
+    }
+
+    fn has_on_open(&self, script: usize) -> bool {
+        self.scripts[script]
+            .iter_functions()
+            .any(|f| f.name == "on_open" && f.params.len() == 1)
+    }
+
+    /// Call `on_open` of every script that has one.
+    fn on_open(&self, state: &ScriptState) -> Vec<Action> {
+        let mut actions = Vec::new();
+        for script in 0..self.scripts.len() {
+            if self.has_on_open(script) {
+                actions.extend(self.call(script, "on_open", state));
+            }
+        }
+        actions
+    }
+
+    /// Call the function bound to `key`.
+    fn key(&self, key: &str, state: &ScriptState) -> Vec<Action> {
+        match self.keys.get(key) {
+            Some((script, function)) => self.call(*script, function, state),
+            None => Vec::new(),
+        }
+    }
+
+    fn call(&self, script: usize, function: &str, state: &ScriptState) -> Vec<Action> {
+        let result = self.engine.call_fn::<Dynamic>(
+            &mut Scope::new(),
+            &self.scripts[script],
+            function,
+            (to_dynamic(state),),
+        );
+        if let Err(e) = result {
+            eprintln!("Warning: Script function {} failed: {}", function, e);
+        }
+        std::mem::take(&mut *self.actions.borrow_mut())
+    }
+}
+
+/// `run(command)`: what a shell command run in `dir` printed to stdout.
+fn run(dir: &Path, command: &str) -> Result<String, Box<EvalAltResult>> {
+    #[cfg(windows)]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+    #[cfg(not(windows))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let output = shell
+        .arg(command)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", command, e))?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn to_dynamic(state: &ScriptState) -> Dynamic {
+    let files: Array = state
+        .files
+        .iter()
+        .map(|file| {
+            let mut map = Map::new();
+            map.insert("path".into(), file.path.clone().into());
+            map.insert("status".into(), file.status.as_str().into());
+            map.insert("additions".into(), (file.additions as INT).into());
+            map.insert("deletions".into(), (file.deletions as INT).into());
+            map.into()
+        })
+        .collect();
+    let mut map = Map::new();
+    map.insert("title".into(), state.title.clone().into());
+    map.insert(
+        "pr".into(),
+        state.pr.map_or(Dynamic::UNIT, |pr| (pr as INT).into()),
+    );
+    map.insert("selected".into(), state.selected.clone().into());
+    map.insert("files".into(), files.into());
+    map.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{FileChange, FileStatus};
+    use lado_core::test_support::TempDir;
+
+    fn state(pr: Option<u32>) -> ScriptState {
+        ScriptState {
+            title: "main".to_string(),
+            pr,
+            selected: String::new(),
+            files: vec![FileChange {
+                path: "src/app.rs".to_string(),
+                status: FileStatus::Modified,
+                additions: 3,
+                deletions: 1,
+                rename: None,
+                encoding: None,
+                generated: false,
+            }],
+        }
+    }
+
+    /// Scripts of `sources`, loaded in that order
+    fn scripts(dir: &TempDir, sources: &[&str], max_operations: u64) -> Scripts {
+        let paths: Vec<PathBuf> = sources
+            .iter()
+            .enumerate()
+            .map(|(i, source)| {
+                let path = dir.path().join(format!("{}.rhai", i));
+                std::fs::write(&path, source).unwrap();
+                path
+            })
+            .collect();
+        Scripts::start(dir.path(), paths, max_operations)
+    }
+
+    #[test]
+    fn test_hooks_and_keys() {
+        let dir = TempDir::new("scripts");
+        let scripts = scripts(
+            &dir,
+            &[
+                r#"
+                bind("t", "vendored");
+                fn on_open(diff) {
+                    if diff.pr != () { hide("*.lock"); }
+                    select(diff.files[0].path);
+                }
+                fn vendored(diff) { hide("vendor/**"); }
+                "#,
+                "this doesn't compile (",
+                r#"
+                fn on_open(diff) {
+                    let printed = run("echo " + diff.title);
+                    printed.trim();
+                    select(printed);
+                }
+                "#,
+            ],
+            MAX_OPERATIONS,
+        );
+
+        let on_open = scripts.on_open(state(Some(42))).unwrap().recv_blocking().unwrap();
+        assert_eq!(
+            on_open,
+            [
+                Action::Hide("*.lock".to_string()),
+                Action::Select("src/app.rs".to_string()),
+                Action::Select("main".to_string()),
+            ]
+        );
+        let on_open = scripts.on_open(state(None)).unwrap().recv_blocking().unwrap();
+        assert_eq!(on_open.len(), 2);
+
+        let key = scripts.key("t", state(None)).unwrap().recv_blocking().unwrap();
+        assert_eq!(key, [Action::Hide("vendor/**".to_string())]);
+        assert!(scripts.key("x", state(None)).is_none());
+    }
+
+    #[test]
+    fn test_without_scripts() {
+        let dir = TempDir::new("no-scripts");
+        let scripts = scripts(&dir, &[], MAX_OPERATIONS);
+        assert!(scripts.on_open(state(None)).is_none());
+        assert!(scripts.key("t", state(None)).is_none());
+
+        // Scripts binding keys only don't get `on_open` calls
+        let scripts = self::scripts(&dir, &[r#"bind("t", "f"); fn f(diff) {}"#], MAX_OPERATIONS);
+        assert!(scripts.on_open(state(None)).is_none());
+        assert!(scripts.key("t", state(None)).unwrap().recv_blocking().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_runaway_script_is_stopped() {
+        let dir = TempDir::new("runaway-script");
+        let scripts = scripts(
+            &dir,
+            &["fn on_open(diff) { hide(\"a\"); loop {} }"],
+            100_000,
+        );
+        // Stopped, with what it asked for before
+        let actions = scripts.on_open(state(None)).unwrap().recv_blocking().unwrap();
+        assert_eq!(actions, [Action::Hide("a".to_string())]);
+    }
+}
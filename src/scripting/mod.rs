@@ -0,0 +1,80 @@
+//! Rhai scripts from `scripts/` in lado's config directory
+//! (`~/.config/lado/scripts/*.rhai` on Linux), for automating reviews.
+//!
+//! A script can define `fn on_open(diff)`, called whenever a diff (or PR) is
+//! shown, and bind keys to its functions at the top level with
+//! `bind("t", "run_tests")`; those are called with the same `diff` when the
+//! key is pressed. `diff` is a map of `title`, `pr` (the PR number, or `()`),
+//! `selected` (path of the shown file) and `files`, each a map of `path`,
+//! `status`, `additions` and `deletions`.
+//!
+//! Besides Rhai's own functions, scripts can call:
+//!
+//! - `hide(glob)`: leave files out of the tree, like the `exclude` setting
+//! - `select(path)`: show a file
+//! - `run(command)`: run a shell command and get its output
+//!
+//! Scripts run on a thread of their own, so the window stays responsive
+//! while they work; what they ask for is carried out once they are done. A
+//! function (or top level) taking more than about fifty million operations
+//! is stopped.
+//!
+//! ```rhai
+//! bind("t", "run_tests");
+//!
+//! fn on_open(diff) {
+//!     if diff.pr != () { hide("*.lock"); }
+//! }
+//!
+//! fn run_tests(diff) {
+//!     print(run("cargo test --quiet"));
+//! }
+//! ```
+//!
+//! Running scripts needs lado built with the `scripting` feature.
+
+#[cfg(not(feature = "scripting"))]
+mod disabled;
+#[cfg(feature = "scripting")]
+mod engine;
+
+#[cfg(not(feature = "scripting"))]
+pub use disabled::Scripts;
+#[cfg(feature = "scripting")]
+pub use engine::Scripts;
+
+use crate::git::FileChange;
+use std::path::PathBuf;
+
+/// What a script asked lado to do
+#[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Hide(String),
+    Select(String),
+}
+
+/// What scripts get to see of the diff
+#[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+pub struct ScriptState {
+    pub title: String,
+    pub pr: Option<u32>,
+    pub selected: String,
+    pub files: Vec<FileChange>,
+}
+
+/// The scripts to load, in file name order.
+fn script_paths() -> Vec<PathBuf> {
+    let Some(dir) = crate::config::config_dir().map(|p| p.join("scripts")) else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "rhai"))
+        .collect();
+    paths.sort();
+    paths
+}
@@ -0,0 +1,108 @@
+//! Marks on the scroll bar beside the diff: where the shown file adds and
+//! removes lines and has comments, so a change far down a long file can be
+//! seen and jumped to.
+
+use crate::lazy_highlight::RowHeights;
+use crate::{DiffLine, ScrollMarker, ScrollOverview};
+use slint::{Model, ModelRc, VecModel};
+use std::rc::Rc;
+
+/// Runs of a kind closer together than this share of the file's height are
+/// marked as one, so a file with many small changes doesn't get thousands
+const MERGE_GAP: f32 = 0.002;
+
+/// The height of `lines` with rows of `heights`, where the scroll bar marks
+/// them and the length of the longest, which scrolling sideways goes up to.
+pub fn overview(lines: &impl Model<Data = DiffLine>, heights: RowHeights) -> ScrollOverview {
+    // Runs of rows of one kind, by offset from the top
+    let mut runs: Vec<(&'static str, f32, f32)> = Vec::new();
+    let mut height = 0.0;
+    let mut columns = 0;
+    for line in lines.iter() {
+        let row_height = heights.row(&line);
+        if line.line_type != "comment" {
+            columns = columns
+                .max(line.content.chars().count())
+                .max(line.new_content.chars().count());
+        }
+        let kind = match line.line_type.as_str() {
+            "add" => "add",
+            "remove" => "remove",
+            "comment" => "comment",
+            _ => {
+                height += row_height;
+                continue;
+            }
+        };
+        match runs.last_mut() {
+            Some((last, _, end)) if *last == kind && *end == height => *end += row_height,
+            _ => runs.push((kind, height, height + row_height)),
+        }
+        height += row_height;
+    }
+
+    let mut markers: Vec<ScrollMarker> = Vec::new();
+    for (kind, start, end) in runs {
+        let (start, end) = (start / height, end / height);
+        match markers.iter_mut().rev().find(|m| m.kind == kind) {
+            Some(marker) if start - (marker.start + marker.size) < MERGE_GAP => {
+                marker.size = end - marker.start;
+            }
+            _ => markers.push(ScrollMarker {
+                kind: kind.into(),
+                start,
+                size: end - start,
+            }),
+        }
+    }
+    ScrollOverview {
+        height,
+        columns: columns as i32,
+        markers: ModelRc::from(Rc::new(VecModel::from(markers))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DiffLineModel;
+
+    fn row(line_type: &str, content: &str) -> DiffLine {
+        DiffLineModel {
+            line_type: line_type.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_overview_marks_runs() {
+        let mut rows = vec![row("context", ""), row("add", "let x;"), row("add", "")];
+        rows.extend((0..96).map(|_| row("context", "")));
+        rows.push(row("remove", ""));
+        let lines = VecModel::from(rows);
+
+        let heights = RowHeights {
+            line: 17.0,
+            comment: 80.0,
+            comment_image: 244.0,
+        };
+        let overview = overview(&lines, heights);
+        assert_eq!(overview.height, 100.0 * 17.0);
+        assert_eq!(overview.columns, 6);
+        // In percent of the height
+        let markers: Vec<(String, i32, i32)> = overview
+            .markers
+            .iter()
+            .map(|m| {
+                let percent = |f: f32| (f * 100.0).round() as i32;
+                (m.kind.to_string(), percent(m.start), percent(m.size))
+            })
+            .collect();
+        assert_eq!(
+            markers,
+            [("add".to_string(), 1, 2), ("remove".to_string(), 99, 1)]
+        );
+    }
+}
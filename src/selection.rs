@@ -0,0 +1,343 @@
+//! Selecting text in the diff with the mouse: a double click selects the
+//! word under it, a triple click the whole line, and a shift+click extends
+//! the selection to the line clicked. Ctrl+C copies what is selected.
+//! Showing a match of the search selects it the same way.
+
+use crate::search::SearchHit;
+use crate::{DiffLine, DiffSelection};
+use slint::Model;
+use std::time::{Duration, Instant};
+
+/// Clicks on the same row closer together than this count as one gesture
+const MULTI_CLICK: Duration = Duration::from_millis(400);
+
+/// Selected rows of the shown file, or a word in one of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    /// Row the selection started at and the one it was extended to
+    pub anchor: usize,
+    pub head: usize,
+    /// Character columns of a selected word, end exclusive; `None` when
+    /// whole lines are selected
+    pub word: Option<(usize, usize)>,
+}
+
+impl Selection {
+    /// The selection the window shows, if any
+    pub fn from_shown(shown: &DiffSelection) -> Option<Self> {
+        let row = |row: i32| usize::try_from(row).ok();
+        let word = row(shown.start_column).zip(row(shown.end_column));
+        Some(Self {
+            anchor: row(shown.first_row)?,
+            head: row(shown.last_row)?,
+            word,
+        })
+    }
+
+    /// `selection` as the window shows it
+    pub fn shown(selection: Option<Self>) -> DiffSelection {
+        match selection {
+            Some(s) => {
+                let (start, end) = s.word.map_or((-1, -1), |(a, b)| (a as i32, b as i32));
+                DiffSelection {
+                    first_row: s.first_row() as i32,
+                    last_row: s.last_row() as i32,
+                    start_column: start,
+                    end_column: end,
+                }
+            }
+            None => DiffSelection {
+                first_row: -1,
+                last_row: -1,
+                start_column: -1,
+                end_column: -1,
+            },
+        }
+    }
+
+    /// The match `hit` of the search in `lines`, the rows shown for its
+    /// file, on the row the characters matched were wrapped into. A match on
+    /// the new side of a side-by-side row selects the whole row. `None` if
+    /// the line isn't shown, e.g. folded away.
+    pub fn of_match(lines: &impl Model<Data = DiffLine>, hit: &SearchHit) -> Option<Self> {
+        let number = |text: &str| text.parse::<u32>().ok();
+        let mut new_side = false;
+        let row = lines.iter().position(|l| {
+            if l.is_continuation || !matches!(l.line_type.as_str(), "add" | "remove" | "context") {
+                return false;
+            }
+            let (old, new) = (number(&l.old_line_num), number(&l.new_line_num));
+            if !l.paired {
+                return old == hit.old_line && new == hit.new_line;
+            }
+            new_side = hit.old_line.is_none() && new == hit.new_line;
+            new_side || (hit.new_line.is_none() && old == hit.old_line)
+        })?;
+        let line = Self {
+            anchor: row,
+            head: row,
+            word: None,
+        };
+        if new_side {
+            return Some(line);
+        }
+
+        // Columns past a wrapped row are on the rows continuing it
+        let (mut row, mut start) = (row, hit.start);
+        let len = loop {
+            let len = lines.row_data(row)?.content.chars().count();
+            let continued = lines.row_data(row + 1).is_some_and(|l| l.is_continuation);
+            if start < len || !continued {
+                break len;
+            }
+            start -= len;
+            row += 1;
+        };
+        Some(Self {
+            anchor: row,
+            head: row,
+            word: Some((start, (start + hit.end - hit.start).min(len))),
+        })
+    }
+
+    pub fn first_row(&self) -> usize {
+        self.anchor.min(self.head)
+    }
+
+    pub fn last_row(&self) -> usize {
+        self.anchor.max(self.head)
+    }
+
+    /// The selected text: the word, or the code of the selected lines (hunk
+    /// headers, comments and folds are left out). Rows a long line was
+    /// wrapped into are joined back together.
+    pub fn text(&self, lines: &impl Model<Data = DiffLine>) -> String {
+        if let Some((start, end)) = self.word {
+            let line = lines.row_data(self.anchor).unwrap_or_default();
+            return line.content.chars().skip(start).take(end - start).collect();
+        }
+        let mut text = String::new();
+        for row in self.first_row()..=self.last_row() {
+            let Some(line) = lines.row_data(row) else {
+                break;
+            };
+            if !matches!(line.line_type.as_str(), "add" | "remove" | "context") {
+                continue;
+            }
+            if !line.is_continuation && !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(&line.content);
+        }
+        text
+    }
+}
+
+/// What the clicks on the diff have selected so far
+#[derive(Debug, Default)]
+pub struct SelectionState {
+    /// File the rows are of
+    path: String,
+    /// Time and row of the last click, and how many came in a row
+    last_click: Option<(Instant, usize)>,
+    clicks: u32,
+    /// Row a shift+click extends from
+    anchor: Option<usize>,
+}
+
+impl SelectionState {
+    /// A click on `row` of `path` at character `column` of `content`, the
+    /// row's text, at time `now`. Returns the selection after it.
+    pub fn press(
+        &mut self,
+        path: &str,
+        row: usize,
+        column: usize,
+        content: &str,
+        shift: bool,
+        now: Instant,
+    ) -> Option<Selection> {
+        if path != self.path {
+            *self = Self {
+                path: path.to_string(),
+                ..Self::default()
+            };
+        }
+        let repeated = self
+            .last_click
+            .is_some_and(|(time, last)| last == row && now.duration_since(time) < MULTI_CLICK);
+        self.clicks = if repeated { self.clicks + 1 } else { 1 };
+        self.last_click = Some((now, row));
+
+        match (shift, self.anchor) {
+            (true, Some(anchor)) => Some(Selection {
+                anchor,
+                head: row,
+                word: None,
+            }),
+            _ => {
+                self.anchor = Some(row);
+                let line = Selection {
+                    anchor: row,
+                    head: row,
+                    word: None,
+                };
+                match self.clicks {
+                    1 => None,
+                    2 => word_at(content, column).map(|word| Selection {
+                        word: Some(word),
+                        ..line
+                    }),
+                    _ => Some(line),
+                }
+            }
+        }
+    }
+}
+
+/// Character columns (end exclusive) of the word at `column` of `line`: a
+/// run of letters, digits and `_`, or a single other character. `None` on
+/// whitespace and past the end.
+pub fn word_at(line: &str, column: usize) -> Option<(usize, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    let clicked = *chars.get(column)?;
+    if clicked.is_whitespace() {
+        return None;
+    }
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    if !is_word(&clicked) {
+        return Some((column, column + 1));
+    }
+    let start = chars[..column]
+        .iter()
+        .rposition(|c| !is_word(c))
+        .map_or(0, |i| i + 1);
+    let end = chars[column..]
+        .iter()
+        .position(|c| !is_word(c))
+        .map_or(chars.len(), |i| column + i);
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DiffLineModel;
+    use slint::VecModel;
+
+    fn numbered(line_type: &str, old: &str, new: &str, content: &str) -> DiffLineModel {
+        DiffLineModel {
+            line_type: line_type.to_string(),
+            old_line_num: old.to_string(),
+            new_line_num: new.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn row(line_type: &str, content: &str, is_continuation: bool) -> DiffLine {
+        DiffLineModel {
+            line_type: line_type.to_string(),
+            content: content.to_string(),
+            is_continuation,
+            ..Default::default()
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_word_at() {
+        let line = "let file_count = files.len();";
+        assert_eq!(word_at(line, 6), Some((4, 14)));
+        assert_eq!(word_at(line, 0), Some((0, 3)));
+        assert_eq!(word_at(line, 22), Some((22, 23)));
+        assert_eq!(word_at(line, 3), None);
+        assert_eq!(word_at(line, 100), None);
+    }
+
+    #[test]
+    fn test_clicks_select_word_line_and_range() {
+        let mut state = SelectionState::default();
+        let now = Instant::now();
+        let later = |ms| now + Duration::from_millis(ms);
+        let content = "let x = value;";
+
+        assert_eq!(state.press("a.rs", 3, 9, content, false, now), None);
+        let word = state.press("a.rs", 3, 9, content, false, later(100));
+        assert_eq!(word.unwrap().word, Some((8, 13)));
+        let line = state.press("a.rs", 3, 9, content, false, later(200));
+        assert_eq!((line.unwrap().first_row(), line.unwrap().word), (3, None));
+
+        // Shift+click extends from the row clicked first, upwards too
+        let range = state.press("a.rs", 1, 0, "", true, later(1000)).unwrap();
+        assert_eq!((range.first_row(), range.last_row()), (1, 3));
+        // A slow second click is a new first one
+        assert_eq!(state.press("a.rs", 1, 0, "", false, later(2000)), None);
+        // Another file starts over
+        assert_eq!(state.press("b.rs", 5, 0, "", true, later(2100)), None);
+    }
+
+    #[test]
+    fn test_selection_text() {
+        let lines = VecModel::from(vec![
+            row("hunk", "@@ -1,2 +1,2 @@", false),
+            row("remove", "let a = 1;", false),
+            row("add", "let a = ", false),
+            row("add", "2;", true),
+            row("comment", "nice", false),
+            row("context", "}", false),
+        ]);
+        let range = Selection {
+            anchor: 5,
+            head: 0,
+            word: None,
+        };
+        assert_eq!(range.text(&lines), "let a = 1;\nlet a = 2;\n}");
+        let word = Selection {
+            anchor: 1,
+            head: 1,
+            word: Some((4, 5)),
+        };
+        assert_eq!(word.text(&lines), "a");
+    }
+
+    #[test]
+    fn test_selection_of_match() {
+        let wrapped = DiffLineModel {
+            is_continuation: true,
+            ..numbered("add", "", "", "value;")
+        };
+        let paired = DiffLineModel {
+            paired: true,
+            new_content: "let b = 3;".to_string(),
+            ..numbered("remove", "4", "5", "let b = 2;")
+        };
+        let rows: Vec<DiffLine> = [
+            numbered("hunk", "", "", "@@ -1,4 +1,5 @@"),
+            numbered("context", "1", "1", "fn f() {"),
+            numbered("add", "", "2", "let a = "),
+            wrapped,
+            paired,
+        ]
+        .into_iter()
+        .map(Into::into)
+        .collect();
+        let lines = VecModel::from(rows);
+        let hit = |old, new, start, end| SearchHit {
+            path: "a.rs".to_string(),
+            old_line: old,
+            new_line: new,
+            start,
+            end,
+        };
+        let selected = |hit| Selection::of_match(&lines, &hit).map(|s| (s.anchor, s.word));
+
+        assert_eq!(selected(hit(Some(1), Some(1), 3, 4)), Some((1, Some((3, 4)))));
+        // On the row the line was wrapped into
+        assert_eq!(selected(hit(None, Some(2), 10, 14)), Some((3, Some((2, 6)))));
+        // Either side of a side-by-side row
+        assert_eq!(selected(hit(Some(4), None, 4, 5)), Some((4, Some((4, 5)))));
+        assert_eq!(selected(hit(None, Some(5), 4, 5)), Some((4, None)));
+        assert_eq!(selected(hit(Some(9), None, 0, 1)), None);
+    }
+}
@@ -0,0 +1,186 @@
+//! Statistics of the review session, for retrospectives on how reviews go:
+//! how long each file was shown and the session lasted, which files (and
+//! how many of their changed lines) were marked viewed, and how many review
+//! comments were written meanwhile.
+
+use crate::github::PrComment;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Files listed by the time spent on them, at most
+const LONGEST_FILES: usize = 5;
+
+/// What happened since the window opened
+pub struct Session {
+    started: Instant,
+    /// When, by the clock GitHub dates comments with
+    started_at: DateTime<Utc>,
+    /// File shown and since when
+    shown: Option<(String, Instant)>,
+    /// Time spent on each file before the one shown
+    time_on: HashMap<String, Duration>,
+    /// Files marked viewed in the session, with their changed lines
+    viewed: HashMap<String, usize>,
+}
+
+/// The session up to a moment, as the statistics panel shows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub elapsed: Duration,
+    pub files_viewed: usize,
+    pub lines_viewed: usize,
+    /// Files spent the longest on, longest first
+    pub longest: Vec<(String, Duration)>,
+}
+
+impl Session {
+    pub fn new(now: Instant, started_at: DateTime<Utc>) -> Self {
+        Self {
+            started: now,
+            started_at,
+            shown: None,
+            time_on: HashMap::new(),
+            viewed: HashMap::new(),
+        }
+    }
+
+    /// `path` is shown from `now` on, instead of the file shown before
+    pub fn show_file(&mut self, path: &str, now: Instant) {
+        if self.shown.as_ref().is_some_and(|(shown, _)| shown == path) {
+            return;
+        }
+        self.stop_showing(now);
+        self.shown = Some((path.to_string(), now));
+    }
+
+    fn stop_showing(&mut self, now: Instant) {
+        if let Some((path, since)) = self.shown.take() {
+            *self.time_on.entry(path).or_default() += now.saturating_duration_since(since);
+        }
+    }
+
+    /// `path`, with `lines` changed lines, was marked viewed; `None` if it
+    /// was unmarked again
+    pub fn set_viewed(&mut self, path: &str, lines: Option<usize>) {
+        match lines {
+            Some(lines) => self.viewed.insert(path.to_string(), lines),
+            None => self.viewed.remove(path),
+        };
+    }
+
+    pub fn stats(&self, now: Instant) -> Stats {
+        let mut time_on = self.time_on.clone();
+        if let Some((path, since)) = &self.shown {
+            *time_on.entry(path.clone()).or_default() += now.saturating_duration_since(*since);
+        }
+        let mut longest: Vec<(String, Duration)> = time_on.into_iter().collect();
+        // Ties by path, so the list doesn't shuffle
+        longest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        longest.truncate(LONGEST_FILES);
+        Stats {
+            elapsed: now.saturating_duration_since(self.started),
+            files_viewed: self.viewed.len(),
+            lines_viewed: self.viewed.values().sum(),
+            longest,
+        }
+    }
+
+    /// How many of `comments` `user` wrote since the session started
+    pub fn comments_written(&self, comments: &[PrComment], user: &str) -> usize {
+        comments
+            .iter()
+            .filter(|comment| comment.author == user)
+            .filter(|comment| {
+                DateTime::parse_from_rfc3339(&comment.created_at)
+                    .is_ok_and(|time| time.with_timezone(&Utc) >= self.started_at)
+            })
+            .count()
+    }
+}
+
+/// `duration` as the panel shows it, e.g. `1h 05m`, `12m 30s` or `45s`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes) = (seconds / 3600, seconds / 60 % 60);
+    if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::github::CommentSide;
+
+    #[test]
+    fn test_time_per_file() {
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        let mut session = Session::new(start, Utc::now());
+        session.show_file("a.rs", at(0));
+        session.show_file("b.rs", at(10));
+        // Showing the same file again keeps counting from when it was shown
+        session.show_file("b.rs", at(20));
+        session.show_file("a.rs", at(50));
+
+        let stats = session.stats(at(55));
+        assert_eq!(stats.elapsed, Duration::from_secs(55));
+        assert_eq!(
+            stats.longest,
+            [
+                ("b.rs".to_string(), Duration::from_secs(40)),
+                ("a.rs".to_string(), Duration::from_secs(15)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_viewed_files_and_lines() {
+        let mut session = Session::new(Instant::now(), Utc::now());
+        session.set_viewed("a.rs", Some(12));
+        session.set_viewed("b.rs", Some(3));
+        session.set_viewed("a.rs", None);
+        let stats = session.stats(Instant::now());
+        assert_eq!((stats.files_viewed, stats.lines_viewed), (1, 3));
+    }
+
+    #[test]
+    fn test_comments_written() {
+        let started_at = DateTime::parse_from_rfc3339("2024-01-15T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let session = Session::new(Instant::now(), started_at);
+        let comment = |author: &str, created_at: &str| PrComment {
+            id: 1,
+            in_reply_to_id: None,
+            path: "a.rs".to_string(),
+            line: Some(1),
+            side: CommentSide::Right,
+            body: String::new(),
+            author: author.to_string(),
+            created_at: created_at.to_string(),
+            commit_id: String::new(),
+            original_commit_id: String::new(),
+            resolved: false,
+        };
+        let comments = [
+            comment("me", "2024-01-15T10:30:00Z"),
+            comment("me", "2024-01-15T09:30:00Z"),
+            comment("someone", "2024-01-15T10:30:00Z"),
+            comment("me", "not a time"),
+        ];
+        assert_eq!(session.comments_written(&comments, "me"), 1);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(750)), "12m 30s");
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1h 05m");
+    }
+}
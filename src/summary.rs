@@ -0,0 +1,157 @@
+//! Markdown summary of a review, to paste into a PR description or a chat:
+//! which files were viewed, as a checklist, and the review comments, each
+//! under its file, line and the line it is on.
+
+use crate::git::{DiffData, DiffLineType};
+use crate::github::{CommentSide, PrComment};
+use std::fmt::Write as _;
+
+/// The summary of the review titled `title`: `viewed` says which files of
+/// `data` were marked viewed, `comments` are the ones to list. Generated
+/// files don't count towards the files viewed.
+pub fn markdown(
+    title: &str,
+    data: &DiffData,
+    viewed: impl Fn(&str) -> bool,
+    comments: &[PrComment],
+) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "## Review of {}\n", title);
+
+    let checked: Vec<bool> = data.files.iter().map(|file| viewed(&file.path)).collect();
+    // Generated files are listed, but not counted as left to review
+    let counted = data.files.iter().zip(&checked).filter(|(file, _)| !file.generated);
+    let _ = writeln!(
+        out,
+        "**{} of {} files viewed**\n",
+        counted.clone().filter(|(_, &viewed)| viewed).count(),
+        counted.count()
+    );
+    for (file, viewed) in data.files.iter().zip(checked) {
+        let _ = writeln!(
+            out,
+            "- [{}] `{}`{}",
+            if viewed { 'x' } else { ' ' },
+            file.path,
+            if file.generated { " (generated)" } else { "" }
+        );
+    }
+
+    let mut comments: Vec<&PrComment> = comments.iter().collect();
+    comments.sort_by(|a, b| (&a.path, a.line, a.id).cmp(&(&b.path, b.line, b.id)));
+    if !comments.is_empty() {
+        out.push_str("\n### Comments\n");
+    }
+    for comment in comments {
+        match comment.line {
+            Some(line) => {
+                let _ = writeln!(out, "\n**`{}:{}`**\n", comment.path, line);
+                if let Some(quote) = quoted_line(data, comment) {
+                    let _ = writeln!(out, "> {}\n", quote);
+                }
+            }
+            None => {
+                let _ = writeln!(out, "\n**`{}`**\n", comment.path);
+            }
+        }
+        let _ = writeln!(out, "{}", comment.body.trim_end());
+    }
+    out
+}
+
+/// The line of the diff `comment` is on, with its `+` or `-`
+fn quoted_line(data: &DiffData, comment: &PrComment) -> Option<String> {
+    let line = comment.line?;
+    data.file_hunks
+        .get(&comment.path)?
+        .iter()
+        .flat_map(|hunk| &hunk.lines)
+        .find(|diff_line| match comment.side {
+            CommentSide::Right => diff_line.new_line_num == Some(line),
+            CommentSide::Left => diff_line.old_line_num == Some(line),
+        })
+        .map(|diff_line| {
+            let marker = match diff_line.line_type {
+                DiffLineType::Add => '+',
+                DiffLineType::Remove => '-',
+                _ => ' ',
+            };
+            format!("`{}{}`", marker, diff_line.content.trim_end())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::parse_patch;
+
+    #[test]
+    fn test_markdown() {
+        let data = parse_patch(
+            "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,2 +1,2 @@
+ fn a() {}
+-fn b() {}
++fn b() -> u8 { 1 }
+diff --git a/notes.txt b/notes.txt
+--- a/notes.txt
++++ b/notes.txt
+@@ -1 +1 @@
+-x
++y
+diff --git a/Cargo.lock b/Cargo.lock
+--- a/Cargo.lock
++++ b/Cargo.lock
+@@ -1 +1 @@
+-version = 3
++version = 4
+",
+        )
+        .unwrap();
+        let comment = |id, line, side, body: &str| PrComment {
+            id,
+            in_reply_to_id: None,
+            path: "src/lib.rs".to_string(),
+            line,
+            side,
+            body: body.to_string(),
+            author: "ada".to_string(),
+            created_at: String::new(),
+            commit_id: String::new(),
+            original_commit_id: String::new(),
+            resolved: false,
+        };
+        let comments = [
+            comment(2, None, CommentSide::Right, "Outdated"),
+            comment(1, Some(2), CommentSide::Right, "Why u8?\n"),
+        ];
+        let summary = markdown("PR #42", &data, |path| path == "notes.txt", &comments);
+        assert_eq!(
+            summary,
+            "\
+## Review of PR #42
+
+**1 of 2 files viewed**
+
+- [ ] `src/lib.rs`
+- [x] `notes.txt`
+- [ ] `Cargo.lock` (generated)
+
+### Comments
+
+**`src/lib.rs`**
+
+Outdated
+
+**`src/lib.rs:2`**
+
+> `+fn b() -> u8 { 1 }`
+
+Why u8?
+"
+        );
+    }
+}
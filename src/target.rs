@@ -0,0 +1,331 @@
+//! Resolving a `DiffTarget` to the repository, title and commits to diff.
+//!
+//! Shared by the window and the headless modes (`--export`), which only
+//! differ in what they do with the diff.
+
+use crate::cli::DiffTarget;
+use crate::git::{parse_patch, DiffData, Repository};
+use crate::github;
+use crate::mbox::{self, Series};
+use crate::scm::{self, HgChanges, Scm};
+use crate::sourcehut;
+use anyhow::{anyhow, Context, Result};
+use git2::Oid;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
+/// The repository to diff in: the one `dir` is in, or an in-memory
+/// stand-in for diffs that don't come from git.
+///
+/// `dir` is where lado was run, or the window asked for over IPC: relative
+/// paths, gh and hg go by it rather than the process's working directory.
+pub fn open_repository(dir: &Path, target: &DiffTarget) -> Result<Repository> {
+    let mut repo = match target {
+        // Patches and files on disk can come from anywhere, in or out of a
+        // repository
+        DiffTarget::Stdin | DiffTarget::Files { .. } | DiffTarget::Dirs { .. } => {
+            Repository::in_memory()?
+        }
+        // Mercurial working copies are diffed by hg
+        _ if mercurial_root(dir, target).is_some() => Repository::in_memory()?,
+        // A patch series is committed, and the working tree's files are
+        // made blobs, without touching the repository
+        DiffTarget::Mbox(_) | DiffTarget::Patchset(_) | DiffTarget::WorkingTree => {
+            let repo = Repository::discover(dir)?;
+            repo.keep_new_objects_in_memory()?;
+            repo
+        }
+        _ => Repository::discover(dir)?,
+    };
+    // File renderer plugins decide what is diffed
+    if let Some(textconv) = crate::plugins::loaded().textconv() {
+        repo.set_textconv(textconv);
+    }
+    repo.set_normalize_line_endings(crate::config::load().normalize_line_endings);
+    Ok(repo)
+}
+
+/// Title describing what is diffed, e.g. "HEAD vs main".
+pub fn title(dir: &Path, repo: &Repository, target: &DiffTarget) -> Result<String> {
+    let title = match target {
+        DiffTarget::DefaultBranch | DiffTarget::Log(None) => match mercurial_root(dir, target) {
+            Some(_) => "HEAD vs default".to_string(),
+            None => format!("HEAD vs {}", repo.find_default_branch()?),
+        },
+        DiffTarget::Ref(r) | DiffTarget::Log(Some(r)) => format!("HEAD vs {}", r),
+        DiffTarget::PullRequest(pr) => format!("PR #{}", pr),
+        DiffTarget::Refs { base, head } => format!("{} vs {}", head, base),
+        DiffTarget::MergeBase { base, head } => format!("{} vs merge base with {}", head, base),
+        DiffTarget::Commit(rev) => format!("Commit {}", rev),
+        DiffTarget::Stdin => "Patch from stdin".to_string(),
+        DiffTarget::Mbox(path) => format!("Patch series {}", path.display()),
+        DiffTarget::Patchset(id) => format!("Patchset {}", id),
+        DiffTarget::WorkingTree => "Working tree vs HEAD".to_string(),
+        DiffTarget::Staged => "Staged vs HEAD".to_string(),
+        DiffTarget::Files { old, new } | DiffTarget::Dirs { old, new } => {
+            format!("{} vs {}", new.display(), old.display())
+        }
+    };
+    Ok(title)
+}
+
+/// Resolve the target to the (base, head) commits to diff. `None` for a PR,
+/// whose refs come from gh, or a diff not made from commits.
+pub fn commits(dir: &Path, repo: &Repository, target: &DiffTarget) -> Result<Option<(Oid, Oid)>> {
+    if mercurial_root(dir, target).is_some() {
+        return Ok(None);
+    }
+    let commits = match target {
+        DiffTarget::DefaultBranch | DiffTarget::Log(None) => {
+            let default_branch = repo.find_default_branch()?;
+            (repo.resolve_ref(&default_branch)?, repo.head_commit()?)
+        }
+        DiffTarget::Ref(ref_name) | DiffTarget::Log(Some(ref_name)) => {
+            (repo.resolve_ref(ref_name)?, repo.head_commit()?)
+        }
+        DiffTarget::Refs { base, head } => (repo.resolve_ref(base)?, repo.resolve_ref(head)?),
+        DiffTarget::MergeBase { base, head } => {
+            let head_oid = repo.resolve_ref(head)?;
+            let fork = repo
+                .merge_base(repo.resolve_ref(base)?, head_oid)
+                .with_context(|| format!("{} and {} have no common ancestor", base, head))?;
+            (fork, head_oid)
+        }
+        DiffTarget::Commit(rev) => {
+            let parent = repo
+                .resolve_ref(&format!("{}^", rev))
+                .with_context(|| format!("{} has no parent to diff against", rev))?;
+            (parent, repo.resolve_ref(rev)?)
+        }
+        DiffTarget::Mbox(path) => {
+            let (base, commits) = apply_series(repo, &read_series(&dir.join(path))?)?;
+            (base, commits.last().copied().unwrap_or(base))
+        }
+        DiffTarget::Patchset(id) => {
+            let (base, commits) = apply_series(repo, &sourcehut::get_series(id)?)?;
+            (base, commits.last().copied().unwrap_or(base))
+        }
+        DiffTarget::PullRequest(_)
+        | DiffTarget::Stdin
+        | DiffTarget::Files { .. }
+        | DiffTarget::Dirs { .. }
+        | DiffTarget::WorkingTree
+        | DiffTarget::Staged => return Ok(None),
+    };
+    Ok(Some(commits))
+}
+
+/// The diff of a target that isn't made from git commits: a patch on
+/// stdin, paths on disk, uncommitted changes or revisions of a Mercurial
+/// working copy. `None` for every other target.
+pub fn diff_without_commits(
+    dir: &Path,
+    repo: &Repository,
+    target: &DiffTarget,
+) -> Result<Option<DiffData>> {
+    let data = match target {
+        DiffTarget::Stdin => read_patch()?,
+        DiffTarget::Files { old, new } => repo.diff_file_paths(&dir.join(old), &dir.join(new))?,
+        DiffTarget::Dirs { old, new } => repo.diff_dirs(&dir.join(old), &dir.join(new))?,
+        _ => match mercurial_root(dir, target) {
+            Some(root) => scm::hg_diff(&root, &hg_changes(target)?)?,
+            None => match target {
+                DiffTarget::WorkingTree => repo.diff_workdir()?,
+                DiffTarget::Staged => repo.diff_index()?,
+                _ => return Ok(None),
+            },
+        },
+    };
+    Ok(Some(data))
+}
+
+/// Root of the Mercurial working copy `target` is diffed in, if `dir` is in
+/// one rather than in a git repository. Patches and files on disk need
+/// neither.
+fn mercurial_root(dir: &Path, target: &DiffTarget) -> Option<PathBuf> {
+    if matches!(
+        target,
+        DiffTarget::Stdin
+            | DiffTarget::Files { .. }
+            | DiffTarget::Dirs { .. }
+            | DiffTarget::Mbox(_)
+            | DiffTarget::Patchset(_)
+    ) {
+        return None;
+    }
+    match Scm::detect(dir)? {
+        (Scm::Mercurial, root) => Some(root),
+        (Scm::Git, _) => None,
+    }
+}
+
+/// What `target` diffs in a Mercurial working copy, where bookmarks,
+/// branches and tags name revisions as refs do in git, `.` is the working
+/// directory's parent and `default` the default branch.
+fn hg_changes(target: &DiffTarget) -> Result<HgChanges> {
+    // `lado show` defaults to git's name for it
+    let rev = |rev: &str| if rev == "HEAD" { "." } else { rev }.to_string();
+    let changes = match target {
+        DiffTarget::DefaultBranch | DiffTarget::Log(None) => {
+            HgChanges::Between("default".to_string(), rev("."))
+        }
+        DiffTarget::Ref(base) | DiffTarget::Log(Some(base)) => {
+            HgChanges::Between(rev(base), rev("."))
+        }
+        DiffTarget::Refs { base, head } => HgChanges::Between(rev(base), rev(head)),
+        DiffTarget::MergeBase { base, head } => HgChanges::Between(
+            format!("ancestor({}, {})", rev(base), rev(head)),
+            rev(head),
+        ),
+        DiffTarget::Commit(commit) => HgChanges::Change(rev(commit)),
+        DiffTarget::WorkingTree => HgChanges::WorkingDir,
+        DiffTarget::Staged => {
+            return Err(anyhow!(
+                "Mercurial has no staging area; diff the working directory with --worktree instead"
+            ))
+        }
+        DiffTarget::PullRequest(_) => {
+            return Err(anyhow!(
+                "Merge requests aren't supported in Mercurial repositories yet; diff their revisions instead"
+            ))
+        }
+        DiffTarget::Stdin
+        | DiffTarget::Files { .. }
+        | DiffTarget::Dirs { .. }
+        | DiffTarget::Mbox(_)
+        | DiffTarget::Patchset(_) => return Err(anyhow!("Not a diff of revisions")),
+    };
+    Ok(changes)
+}
+
+/// The patch series in the mbox at `path`.
+pub fn read_series(path: &Path) -> Result<Series> {
+    let text = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let series = mbox::parse(&String::from_utf8_lossy(&text));
+    if series.patches.is_empty() {
+        return Err(anyhow!("{} has no patches", path.display()));
+    }
+    Ok(series)
+}
+
+/// Commit `series` on top of its base: the `base-commit` it names if the
+/// repository has it, else HEAD. The base and the patches' commits.
+pub fn apply_series(repo: &Repository, series: &Series) -> Result<(Oid, Vec<Oid>)> {
+    let base = match series
+        .base_commit
+        .as_ref()
+        .and_then(|base| repo.resolve_ref(base).ok())
+    {
+        Some(base) => base,
+        None => repo.head_commit()?,
+    };
+    Ok((base, repo.apply_series(base, series)?))
+}
+
+/// Compute the whole diff up front, fetching a PR's refs with gh.
+pub fn load_diff(dir: &Path, repo: &Repository, target: &DiffTarget) -> Result<DiffData> {
+    if let Some(data) = diff_without_commits(dir, repo, target)? {
+        return Ok(data);
+    }
+    let (base, head) = match (target, commits(dir, repo, target)?) {
+        (_, Some(commits)) => commits,
+        (DiffTarget::PullRequest(pr), None) => {
+            let info = github::get_pr_refs(dir, *pr)?;
+            (
+                repo.resolve_ref(&info.base_ref)?,
+                repo.resolve_ref(&info.head_ref)?,
+            )
+        }
+        (_, None) => return Err(anyhow!("Nothing to diff")),
+    };
+    repo.diff_commits(base, head)
+}
+
+/// `target` diffed against `base` instead, for switching the base while
+/// lado is open. `None` for targets without a base to switch: a single
+/// commit, a PR (whose base is GitHub's), a patch series (applied to its own
+/// base) and diffs not made from commits.
+pub fn with_base(target: &DiffTarget, base: &str) -> Option<DiffTarget> {
+    let base = base.to_string();
+    match target {
+        DiffTarget::DefaultBranch | DiffTarget::Ref(_) => Some(DiffTarget::Ref(base)),
+        DiffTarget::Log(_) => Some(DiffTarget::Log(Some(base))),
+        DiffTarget::Refs { head, .. } => Some(DiffTarget::Refs {
+            base,
+            head: head.clone(),
+        }),
+        DiffTarget::MergeBase { head, .. } => Some(DiffTarget::MergeBase {
+            base,
+            head: head.clone(),
+        }),
+        DiffTarget::PullRequest(_)
+        | DiffTarget::Commit(_)
+        | DiffTarget::Stdin
+        | DiffTarget::Files { .. }
+        | DiffTarget::Dirs { .. }
+        | DiffTarget::Mbox(_)
+        | DiffTarget::Patchset(_)
+        | DiffTarget::WorkingTree
+        | DiffTarget::Staged => None,
+    }
+}
+
+/// Parse the unified diff on stdin.
+fn read_patch() -> Result<DiffData> {
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Err(anyhow!(
+            "Expected a patch on stdin, e.g. `git diff | lado -`"
+        ));
+    }
+    let mut patch = String::new();
+    stdin
+        .read_to_string(&mut patch)
+        .context("Failed to read the patch from stdin")?;
+    parse_patch(&patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_base() {
+        let base = |target| with_base(&target, "v1.0");
+        assert!(matches!(base(DiffTarget::DefaultBranch), Some(DiffTarget::Ref(r)) if r == "v1.0"));
+        assert!(matches!(
+            base(DiffTarget::Log(None)),
+            Some(DiffTarget::Log(Some(r))) if r == "v1.0"
+        ));
+        let refs = DiffTarget::Refs {
+            base: "main".to_string(),
+            head: "feature".to_string(),
+        };
+        assert!(matches!(
+            base(refs),
+            Some(DiffTarget::Refs { base, head }) if base == "v1.0" && head == "feature"
+        ));
+        let forked = DiffTarget::MergeBase {
+            base: "main".to_string(),
+            head: "feature".to_string(),
+        };
+        assert!(matches!(
+            base(forked),
+            Some(DiffTarget::MergeBase { base, head }) if base == "v1.0" && head == "feature"
+        ));
+        assert!(base(DiffTarget::PullRequest(7)).is_none());
+    }
+
+    #[test]
+    fn test_hg_changes() {
+        assert_eq!(
+            hg_changes(&DiffTarget::DefaultBranch).unwrap(),
+            HgChanges::Between("default".to_string(), ".".to_string())
+        );
+        assert_eq!(
+            hg_changes(&DiffTarget::Commit("HEAD".to_string())).unwrap(),
+            HgChanges::Change(".".to_string())
+        );
+        assert!(hg_changes(&DiffTarget::PullRequest(7)).is_err());
+    }
+}
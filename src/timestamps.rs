@@ -0,0 +1,90 @@
+//! Times of review comments, which GitHub gives in UTC as ISO 8601
+//! (`2024-01-15T10:30:00Z`), shown in local time or relative to now
+//! (`relative_timestamps`).
+
+use chrono::{DateTime, Local, Utc};
+
+/// `timestamp` in local time, e.g. `2024-01-15 11:30`. Left as is if it
+/// can't be parsed.
+pub fn absolute(timestamp: &str) -> String {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(time) => time
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+/// How long ago `timestamp` was, e.g. `3 hours ago`. Empty if it can't be
+/// parsed.
+pub fn relative(timestamp: &str) -> String {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(time) => relative_to(time.with_timezone(&Utc), Utc::now()),
+        Err(_) => String::new(),
+    }
+}
+
+/// `seconds` since the epoch (e.g. a commit's time) as GitHub writes
+/// times, for [`absolute`] and [`relative`]
+pub fn from_unix(seconds: i64) -> String {
+    DateTime::from_timestamp(seconds, 0).map_or_else(String::new, |time| time.to_rfc3339())
+}
+
+fn relative_to(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let ago = |count: i64, unit: &str| {
+        if count == 1 {
+            format!("1 {} ago", unit)
+        } else {
+            format!("{} {}s ago", count, unit)
+        }
+    };
+    let elapsed = now - time;
+    let days = elapsed.num_days();
+    if elapsed.num_minutes() < 1 {
+        // Also times ahead of a clock that is behind
+        "just now".to_string()
+    } else if elapsed.num_hours() < 1 {
+        ago(elapsed.num_minutes(), "minute")
+    } else if days < 1 {
+        ago(elapsed.num_hours(), "hour")
+    } else if days == 1 {
+        "yesterday".to_string()
+    } else if days < 30 {
+        ago(days, "day")
+    } else if days < 365 {
+        ago(days / 30, "month")
+    } else {
+        ago(days / 365, "year")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_relative_to() {
+        let now = DateTime::parse_from_rfc3339("2024-06-15T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let before = |elapsed: Duration| relative_to(now - elapsed, now);
+        assert_eq!(before(Duration::seconds(30)), "just now");
+        assert_eq!(before(Duration::seconds(-30)), "just now");
+        assert_eq!(before(Duration::minutes(1)), "1 minute ago");
+        assert_eq!(before(Duration::minutes(59)), "59 minutes ago");
+        assert_eq!(before(Duration::hours(3)), "3 hours ago");
+        assert_eq!(before(Duration::hours(30)), "yesterday");
+        assert_eq!(before(Duration::days(12)), "12 days ago");
+        assert_eq!(before(Duration::days(65)), "2 months ago");
+        assert_eq!(before(Duration::days(800)), "2 years ago");
+    }
+
+    #[test]
+    fn test_unparseable_and_unix() {
+        assert_eq!(absolute("yesterday-ish"), "yesterday-ish");
+        assert_eq!(relative("yesterday-ish"), "");
+        assert_eq!(from_unix(1_700_000_000), "2023-11-14T22:13:20+00:00");
+    }
+}
@@ -0,0 +1,501 @@
+//! Terminal UI, for when there is no display to open a window on (e.g. over
+//! SSH) or with `--tui`. Shows the same diff, file tree and viewed marks as
+//! the window, as a unified diff, and takes the scroll and file keys of the
+//! config file. PR comments are left out.
+
+use crate::cli::{Args, DiffTarget};
+use crate::config::Config;
+use crate::exclude::ExcludeFilter;
+use crate::git::{
+    build_file_tree, flatten_tree_with_state, DiffData, DiffLineType, FileTreeNode, FlatFileEntry,
+};
+use crate::target;
+use crate::viewed_state::{self, ViewedState};
+use anyhow::{anyhow, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+
+/// Lines the scroll keys move the diff by
+const SCROLL_LINES: usize = 3;
+
+/// Whether a window can be opened. Only X11 and Wayland sessions can be
+/// missing one; macOS and Windows always have a display.
+pub fn has_display() -> bool {
+    if cfg!(any(target_os = "macos", windows)) {
+        return true;
+    }
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|v| !v.is_empty()))
+}
+
+/// Show the diff of `args` in the terminal until the user quits.
+pub fn run(args: &Args) -> Result<()> {
+    if !io::stdout().is_terminal() {
+        return Err(anyhow!(
+            "No display to open a window on, and stdout isn't a terminal for the terminal UI"
+        ));
+    }
+    let mut config = crate::config::load();
+    args.overrides.apply(&mut config);
+    for problem in config.validate_keybindings() {
+        eprintln!("Warning: {}", problem);
+    }
+
+    let target = DiffTarget::from_args(args);
+    let dir = std::env::current_dir()?;
+    let repo = target::open_repository(&dir, &target)?;
+    let title = target::title(&dir, &repo, &target)?;
+    let mut data = target::load_diff(&dir, &repo, &target)?;
+    data.expand_tabs(config.tab_width.max(1) as usize);
+
+    let mut viewed = ViewedState::load();
+    let key = viewed_state::migrated_target_key(&mut viewed, repo.workdir(), &target);
+    let mut tui = Tui::new(title, data, &config, viewed, key);
+
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<()> {
+        loop {
+            terminal.draw(|frame| tui.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match tui.action(key) {
+                Some(Action::Quit) => return Ok(()),
+                Some(action) => tui.apply(action),
+                None => {}
+            }
+        }
+    })();
+    ratatui::restore();
+    result
+}
+
+/// What a key press does
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Quit,
+    /// Move the tree selection down (or up) a row, folders included
+    RowDown,
+    RowUp,
+    NextFile,
+    PrevFile,
+    ScrollDown(usize),
+    ScrollUp(usize),
+    ToggleFolder,
+    ToggleViewed,
+}
+
+/// The configurable keys, as in the window
+struct Keys {
+    scroll_down: String,
+    scroll_up: String,
+    file_next: String,
+    file_prev: String,
+}
+
+/// The keybinding `key` is a press of, named as in config.toml (see
+/// [`crate::config::normalize_key`])
+fn key_name(key: &KeyEvent) -> String {
+    let base = match key.code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Delete => "Delete".to_string(),
+        KeyCode::Insert => "Insert".to_string(),
+        _ => return String::new(),
+    };
+    // Shift picks the character of the others
+    let named = base.chars().count() > 1;
+    let mut name = String::new();
+    for (modifier, prefix) in [
+        (KeyModifiers::CONTROL, "Ctrl+"),
+        (KeyModifiers::ALT, "Alt+"),
+        (KeyModifiers::SUPER | KeyModifiers::META, "Meta+"),
+    ] {
+        if key.modifiers.intersects(modifier) {
+            name.push_str(prefix);
+        }
+    }
+    if named && key.modifiers.contains(KeyModifiers::SHIFT) {
+        name.push_str("Shift+");
+    }
+    name.push_str(&base);
+    name
+}
+
+struct Tui {
+    title: String,
+    data: DiffData,
+    tree: Vec<FileTreeNode>,
+    expanded: HashMap<String, bool>,
+    rows: Vec<FlatFileEntry>,
+    /// Row of the tree that is selected
+    selected: usize,
+    /// First line of the diff shown
+    scroll: usize,
+    /// Height of the diff at the last draw, for paging
+    page: usize,
+    keys: Keys,
+    viewed: ViewedState,
+    target_key: String,
+}
+
+impl Tui {
+    fn new(
+        title: String,
+        data: DiffData,
+        config: &Config,
+        viewed: ViewedState,
+        target_key: String,
+    ) -> Self {
+        let files = ExcludeFilter::new(&config.exclude).filter(&data.files).0;
+        let tree = build_file_tree(&files);
+        let mut tui = Self {
+            title,
+            data,
+            rows: flatten_tree_with_state(&tree, 0, &HashMap::new()),
+            tree,
+            expanded: HashMap::new(),
+            selected: 0,
+            scroll: 0,
+            page: 20,
+            keys: Keys {
+                scroll_down: config.key_scroll_down.clone(),
+                scroll_up: config.key_scroll_up.clone(),
+                file_next: config.key_file_next.clone(),
+                file_prev: config.key_file_prev.clone(),
+            },
+            viewed,
+            target_key,
+        };
+        // Open on the first file rather than its folder
+        if tui.rows.first().is_some_and(|row| row.is_folder) {
+            tui.apply(Action::NextFile);
+        }
+        tui
+    }
+
+    fn action(&self, key: KeyEvent) -> Option<Action> {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Some(Action::Quit);
+        }
+        // Configured keys first, as they may take over the fixed ones
+        let name = key_name(&key);
+        if name == self.keys.scroll_down {
+            return Some(Action::ScrollDown(SCROLL_LINES));
+        } else if name == self.keys.scroll_up {
+            return Some(Action::ScrollUp(SCROLL_LINES));
+        } else if name == self.keys.file_next {
+            return Some(Action::NextFile);
+        } else if name == self.keys.file_prev {
+            return Some(Action::PrevFile);
+        }
+        let action = match key.code {
+            KeyCode::Char('q') => Action::Quit,
+            KeyCode::Char('e') => Action::ToggleFolder,
+            KeyCode::Char('v') => Action::ToggleViewed,
+            KeyCode::Char(' ') => Action::ScrollDown(self.page),
+            KeyCode::Esc => Action::Quit,
+            KeyCode::Down => Action::RowDown,
+            KeyCode::Up => Action::RowUp,
+            KeyCode::Enter => Action::ToggleFolder,
+            KeyCode::PageDown => Action::ScrollDown(self.page),
+            KeyCode::PageUp => Action::ScrollUp(self.page),
+            _ => return None,
+        };
+        Some(action)
+    }
+
+    fn apply(&mut self, action: Action) {
+        let previous = self.selected;
+        match action {
+            Action::Quit => {}
+            Action::RowDown => {
+                self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1))
+            }
+            Action::RowUp => self.selected = self.selected.saturating_sub(1),
+            Action::NextFile => {
+                if let Some(i) =
+                    (self.selected + 1..self.rows.len()).find(|&i| !self.rows[i].is_folder)
+                {
+                    self.selected = i;
+                }
+            }
+            Action::PrevFile => {
+                if let Some(i) = (0..self.selected).rev().find(|&i| !self.rows[i].is_folder) {
+                    self.selected = i;
+                }
+            }
+            Action::ScrollDown(lines) => {
+                let max = self.diff_lines().len().saturating_sub(1);
+                self.scroll = (self.scroll + lines).min(max);
+            }
+            Action::ScrollUp(lines) => self.scroll = self.scroll.saturating_sub(lines),
+            Action::ToggleFolder => {
+                let Some(row) = self.rows.get(self.selected).filter(|r| r.is_folder) else {
+                    return;
+                };
+                self.expanded.insert(row.path.clone(), !row.is_expanded);
+                self.rows = flatten_tree_with_state(&self.tree, 0, &self.expanded);
+            }
+            Action::ToggleViewed => {
+                let Some(path) = self.selected_file().map(str::to_string) else {
+                    return;
+                };
+                if self.is_viewed(&path) {
+                    self.viewed.set_unviewed(&self.target_key, &path);
+                } else {
+                    let hash = self.hash(&path);
+                    self.viewed.set_viewed(&self.target_key, &path, hash);
+                }
+                if let Err(e) = self.viewed.save() {
+                    eprintln!("Warning: Failed to save viewed state: {}", e);
+                }
+            }
+        }
+        if self.selected != previous {
+            self.scroll = 0;
+        }
+    }
+
+    fn selected_file(&self) -> Option<&str> {
+        self.rows
+            .get(self.selected)
+            .filter(|row| !row.is_folder)
+            .map(|row| row.path.as_str())
+    }
+
+    fn hash(&self, path: &str) -> u64 {
+        self.data
+            .file_hunks
+            .get(path)
+            .map_or(0, |hunks| viewed_state::hash_diff_content(hunks))
+    }
+
+    fn is_viewed(&self, path: &str) -> bool {
+        self.viewed
+            .is_viewed(&self.target_key, path, self.hash(path))
+    }
+
+    /// Generated files don't count towards the files viewed
+    fn is_generated(&self, path: &str) -> bool {
+        self.data.files.iter().any(|f| f.path == path && f.generated)
+    }
+
+    /// The diff of the selected file, with line numbers
+    fn diff_lines(&self) -> Vec<Line<'static>> {
+        let Some(path) = self.selected_file() else {
+            return Vec::new();
+        };
+        let number = |n: Option<u32>| n.map_or(String::new(), |n| n.to_string());
+        let mut lines = Vec::new();
+        for hunk in self.data.shown_hunks(path) {
+            lines.push(Line::styled(
+                hunk.header.trim_end().to_string(),
+                Style::new().fg(Color::Cyan),
+            ));
+            for line in &hunk.lines {
+                let (sign, style) = match line.line_type {
+                    DiffLineType::Add => ('+', Style::new().fg(Color::Green)),
+                    DiffLineType::Remove => ('-', Style::new().fg(Color::Red)),
+                    _ => (' ', Style::new()),
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(
+                        format!(
+                            "{:>5} {:>5} ",
+                            number(line.old_line_num),
+                            number(line.new_line_num)
+                        ),
+                        Style::new().fg(Color::DarkGray),
+                    ),
+                    Span::styled(format!("{}{}", sign, line.content), style),
+                ]));
+            }
+        }
+        lines
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [tree_area, diff_area] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Min(0)]).areas(body);
+
+        let counted = self
+            .rows
+            .iter()
+            .filter(|row| !row.is_folder && !self.is_generated(&row.path));
+        let viewed = counted.clone().filter(|row| self.is_viewed(&row.path)).count();
+        let files = counted.count();
+        frame.render_widget(
+            Line::from(format!(" {}  ({}/{} viewed)", self.title, viewed, files))
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+            header,
+        );
+
+        let items: Vec<ListItem> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let indent = "  ".repeat(row.depth.max(0) as usize);
+                let (marker, style) = if row.is_folder {
+                    (if row.is_expanded { "▾" } else { "▸" }, Style::new())
+                } else {
+                    let color = match row.status.as_str() {
+                        "added" => Color::Green,
+                        "deleted" => Color::Red,
+                        "renamed" => Color::Blue,
+                        _ => Color::Yellow,
+                    };
+                    let marker = if self.is_viewed(&row.path) {
+                        "✓"
+                    } else {
+                        "•"
+                    };
+                    (marker, Style::new().fg(color))
+                };
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{}{} ", indent, marker)),
+                    Span::styled(row.name.clone(), style),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::new().borders(Borders::RIGHT))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, tree_area, &mut state);
+
+        self.page = diff_area.height.max(1) as usize;
+        let diff = Paragraph::new(self.diff_lines()).scroll((self.scroll as u16, 0));
+        frame.render_widget(diff, diff_area);
+
+        frame.render_widget(
+            Line::from(format!(
+                " ↑↓ files  {}/{} next/previous file  {}/{} scroll  e fold  v viewed  q quit",
+                self.keys.file_next,
+                self.keys.file_prev,
+                self.keys.scroll_down,
+                self.keys.scroll_up
+            ))
+            .style(Style::new().fg(Color::DarkGray)),
+            footer,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::parse_patch;
+
+    fn tui() -> Tui {
+        let data = parse_patch(
+            "\
+--- src/a.rs
++++ src/a.rs
+@@ -1 +1 @@
+-a
++A
+--- src/b.rs
++++ src/b.rs
+@@ -1 +1,2 @@
+ b
++c
+--- README.md
++++ README.md
+@@ -1 +1 @@
+-x
++y
+",
+        )
+        .unwrap();
+        Tui::new(
+            "test".to_string(),
+            data,
+            &Config::default(),
+            ViewedState::default(),
+            "test".to_string(),
+        )
+    }
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_file_keys_skip_folders() {
+        let mut tui = tui();
+        assert_eq!(tui.selected_file(), Some("src/a.rs"));
+        for path in ["src/b.rs", "README.md", "README.md"] {
+            tui.apply(tui.action(key('J')).unwrap());
+            assert_eq!(tui.selected_file(), Some(path));
+        }
+        tui.apply(Action::RowUp);
+        tui.apply(Action::RowUp);
+        tui.apply(Action::RowUp);
+        assert_eq!(tui.selected_file(), None);
+        tui.apply(tui.action(key('J')).unwrap());
+        assert_eq!(tui.selected_file(), Some("src/a.rs"));
+    }
+
+    #[test]
+    fn test_key_names() {
+        let with = |code, modifiers| key_name(&KeyEvent::new(code, modifiers));
+        assert_eq!(with(KeyCode::Char('J'), KeyModifiers::SHIFT), "J");
+        assert_eq!(with(KeyCode::Char('d'), KeyModifiers::CONTROL), "Ctrl+d");
+        assert_eq!(
+            with(KeyCode::Down, KeyModifiers::ALT | KeyModifiers::SHIFT),
+            "Alt+Shift+Down"
+        );
+        assert_eq!(with(KeyCode::Char(' '), KeyModifiers::NONE), "Space");
+    }
+
+    #[test]
+    fn test_toggle_folder() {
+        let mut tui = tui();
+        let rows = tui.rows.len();
+        tui.apply(Action::RowUp);
+        tui.apply(tui.action(key('e')).unwrap());
+        assert_eq!(tui.rows.len(), rows - 2);
+        tui.apply(Action::ToggleFolder);
+        assert_eq!(tui.rows.len(), rows);
+    }
+
+    #[test]
+    fn test_scroll_stops_at_last_line() {
+        let mut tui = tui();
+        tui.apply(Action::NextFile);
+        // Hunk header and two lines
+        tui.apply(tui.action(key('j')).unwrap());
+        assert_eq!(tui.scroll, 2);
+        tui.apply(tui.action(key('k')).unwrap());
+        assert_eq!(tui.scroll, 0);
+        assert_eq!(tui.action(key('x')), None);
+    }
+}
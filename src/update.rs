@@ -0,0 +1,121 @@
+//! Opt-in check for a newer lado (`check_for_updates = true` in
+//! config.toml), for installs outside package managers, which otherwise go
+//! stale unnoticed.
+//!
+//! Asks GitHub's releases API (with curl, no token needed) at most once a
+//! day and keeps the answer in lado's cache directory, so the notice stays
+//! until lado is updated.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/trobanga/lado/releases/latest";
+
+/// How long an answer is trusted before asking again
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A release, as GitHub describes it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Release {
+    /// Version, e.g. `v0.2.0`
+    #[serde(rename = "tag_name")]
+    pub tag: String,
+    /// Release page
+    #[serde(rename = "html_url")]
+    pub url: String,
+    /// Release notes (markdown)
+    #[serde(default)]
+    pub body: String,
+}
+
+fn cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|p| p.join("lado").join("latest-release.json"))
+}
+
+/// The latest release if it is newer than this lado. Blocks on the network
+/// when the last answer is older than [`CHECK_INTERVAL`].
+pub fn newer_release() -> Result<Option<Release>> {
+    let release = latest_release()?;
+    Ok(is_newer(&release.tag, env!("CARGO_PKG_VERSION")).then_some(release))
+}
+
+fn latest_release() -> Result<Release> {
+    let cache = cache_path();
+    if let Some(path) = &cache {
+        let fresh = std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age < CHECK_INTERVAL));
+        let cached = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok());
+        if let (true, Some(release)) = (fresh, cached) {
+            return Ok(release);
+        }
+    }
+
+    let output = Command::new("curl")
+        .args([
+            "-fsSL",
+            "--max-time",
+            "10",
+            "-H",
+            "Accept: application/vnd.github+json",
+            LATEST_RELEASE_URL,
+        ])
+        .output()
+        .context("Failed to execute curl. Is it installed?")?;
+    tracing::debug!(status = %output.status, "checked for a newer release");
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Update check failed: {}", stderr.trim()));
+    }
+    let release: Release =
+        serde_json::from_slice(&output.stdout).context("GitHub sent an invalid release")?;
+
+    if let Some(path) = cache {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Err(e) = std::fs::write(&path, serde_json::to_string(&release)?) {
+            tracing::debug!("could not cache the latest release: {}", e);
+        }
+    }
+    Ok(release)
+}
+
+/// Whether the version of `tag` (e.g. `v0.2.0`) is newer than `current`.
+/// Pre-release suffixes are ignored.
+fn is_newer(tag: &str, current: &str) -> bool {
+    let parse = |version: &str| -> Option<Vec<u64>> {
+        let version = version.trim_start_matches('v').split('-').next()?;
+        version.split('.').map(|part| part.parse().ok()).collect()
+    };
+    matches!((parse(tag), parse(current)), (Some(tag), Some(current)) if tag > current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("v0.2.0", "0.1.0"));
+        assert!(is_newer("0.10.0", "0.9.3"));
+        assert!(is_newer("v1.0.0-rc1", "0.9.0"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("v0.0.9", "0.1.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+    }
+
+    #[test]
+    fn test_parse_release() {
+        let json = r#"{"tag_name": "v0.2.0", "html_url": "https://github.com/trobanga/lado/releases/tag/v0.2.0",
+                       "body": "- Faster diffs", "draft": false}"#;
+        let release: Release = serde_json::from_str(json).unwrap();
+        assert_eq!(release.tag, "v0.2.0");
+        assert_eq!(release.body, "- Faster diffs");
+    }
+}
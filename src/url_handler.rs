@@ -0,0 +1,176 @@
+//! `lado://owner/repo/pull/123` links.
+//!
+//! `--register-url-handler` makes the desktop open such links with lado;
+//! opening one changes into a local clone of the repository and reviews the
+//! PR there. Clones are found by the `origin` of the current directory, or
+//! listed in the `repos` table of the config file.
+
+use crate::git::Repository;
+use anyhow::{anyhow, Context, Result};
+use std::path::PathBuf;
+
+const SCHEME: &str = "lado";
+
+/// A pull request link
+#[derive(Debug, PartialEq)]
+pub struct PrUrl {
+    pub owner: String,
+    pub repo: String,
+    pub number: u32,
+}
+
+impl PrUrl {
+    /// `owner/repo`, as used by GitHub
+    pub fn slug(&self) -> String {
+        format!("{}/{}", self.owner, self.repo)
+    }
+}
+
+/// Parse `lado://owner/repo/pull/123`; `None` for anything else.
+pub fn parse(url: &str) -> Option<PrUrl> {
+    let rest = url.strip_prefix(SCHEME)?.strip_prefix("://")?;
+    // Launchers may add a trailing slash, and links a query or fragment
+    let rest = rest.split(['?', '#']).next().unwrap_or(rest);
+    let parts: Vec<&str> = rest.split('/').filter(|p| !p.is_empty()).collect();
+    match parts.as_slice() {
+        [owner, repo, "pull" | "pulls", number] => Some(PrUrl {
+            owner: owner.to_string(),
+            repo: repo.trim_end_matches(".git").to_string(),
+            number: number.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+/// Change into a local clone of the link's repository.
+pub fn enter_repo(url: &PrUrl) -> Result<()> {
+    let slug = url.slug();
+    let is_clone = Repository::open_current_dir()
+        .ok()
+        .and_then(|repo| repo.remote_url("origin"))
+        .is_some_and(|origin| remote_matches(&origin, &slug));
+    if is_clone {
+        return Ok(());
+    }
+
+    let path = crate::config::load().repos.get(&slug).cloned().ok_or_else(|| {
+        anyhow!(
+            "No local clone of {} known; add it to config.toml as\n\n[repos]\n\"{}\" = \"/path/to/clone\"",
+            slug,
+            slug
+        )
+    })?;
+    std::env::set_current_dir(&path).with_context(|| format!("Failed to open {}", path.display()))
+}
+
+/// Whether `remote` (https or ssh) points at `owner/repo`.
+fn remote_matches(remote: &str, slug: &str) -> bool {
+    let remote = remote.trim_end_matches('/').trim_end_matches(".git");
+    remote
+        .strip_suffix(slug)
+        .is_some_and(|host| host.ends_with('/') || host.ends_with(':'))
+}
+
+/// Make lado the desktop's handler for `lado://` links.
+pub fn register() -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to find the lado executable")?;
+    register_for(exe)?;
+    println!("lado now opens {}:// links", SCHEME);
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn register_for(exe: PathBuf) -> Result<()> {
+    use std::process::Command;
+
+    let dir = dirs::data_dir()
+        .ok_or_else(|| anyhow!("Could not determine data directory"))?
+        .join("applications");
+    std::fs::create_dir_all(&dir)?;
+    let desktop_file = "lado-url-handler.desktop";
+    let contents = format!(
+        "[Desktop Entry]\nType=Application\nName=lado\nExec=\"{}\" %u\nMimeType=x-scheme-handler/{};\nNoDisplay=true\nTerminal=false\n",
+        exe.display(),
+        SCHEME
+    );
+    std::fs::write(dir.join(desktop_file), contents)
+        .context("Failed to write the desktop entry")?;
+
+    let status = Command::new("xdg-mime")
+        .args([
+            "default",
+            desktop_file,
+            &format!("x-scheme-handler/{}", SCHEME),
+        ])
+        .status()
+        .context("Failed to execute xdg-mime. Is xdg-utils installed?")?;
+    if !status.success() {
+        return Err(anyhow!("xdg-mime failed to register the handler"));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn register_for(exe: PathBuf) -> Result<()> {
+    use std::process::Command;
+
+    let key = format!(r"HKCU\Software\Classes\{}", SCHEME);
+    let command = format!("\"{}\" \"%1\"", exe.display());
+    let entries = [
+        (key.clone(), None, format!("URL:{} Protocol", SCHEME)),
+        (key.clone(), Some("URL Protocol"), String::new()),
+        (format!(r"{}\shell\open\command", key), None, command),
+    ];
+    for (key, value, data) in entries {
+        let mut reg = Command::new("reg");
+        reg.args(["add", &key, "/f", "/d", &data]);
+        match value {
+            Some(name) => reg.args(["/v", name]),
+            None => reg.arg("/ve"),
+        };
+        let status = reg.status().context("Failed to execute reg")?;
+        if !status.success() {
+            return Err(anyhow!("reg failed to write {}", key));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(all(unix, not(target_os = "macos")), windows)))]
+fn register_for(_exe: PathBuf) -> Result<()> {
+    // macOS only takes URL schemes from the Info.plist of an app bundle
+    Err(anyhow!(
+        "Registering a URL handler isn't supported on this platform"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let expected = PrUrl {
+            owner: "trobanga".to_string(),
+            repo: "lado".to_string(),
+            number: 123,
+        };
+        assert_eq!(parse("lado://trobanga/lado/pull/123"), Some(expected));
+        assert_eq!(
+            parse("lado://trobanga/lado/pull/123/?from=chat").map(|u| u.number),
+            Some(123)
+        );
+        assert_eq!(parse("lado://trobanga/lado"), None);
+        assert_eq!(parse("lado://trobanga/lado/pull/abc"), None);
+        assert_eq!(parse("main"), None);
+    }
+
+    #[test]
+    fn test_remote_matches() {
+        let matches = |remote| remote_matches(remote, "trobanga/lado");
+        assert!(matches("https://github.com/trobanga/lado.git"));
+        assert!(matches("git@github.com:trobanga/lado"));
+        assert!(!matches("https://github.com/someone/not-lado"));
+        assert!(!matches("https://github.com/xtrobanga/lado"));
+    }
+}
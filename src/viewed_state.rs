@@ -1,19 +1,32 @@
 //! Persistence for per-file "viewed" state.
 //!
-//! Viewed state is keyed by diff target (branch, commit, PR number) and
-//! stores a content hash per file. If the hash matches on load, the file
+//! Viewed state is keyed by repository and diff target (branch, commit, PR
+//! number) and stores a content hash per file. If the hash matches on load, the file
 //! is considered still viewed. If the diff content changed, the hash
 //! won't match and the file reverts to unviewed.
+//!
+//! Single hunks can be marked reviewed the same way, by the hash of their
+//! lines alone: a hunk stays reviewed across refreshes that move it but
+//! leave its lines as they were.
+//!
+//! The folders of the file tree opened or closed are kept per target too,
+//! so the tree looks as it was left when the diff is reviewed again.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::{DefaultHasher, Hash, Hasher};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Persisted viewed state: diff_target_key -> (file_path -> content_hash)
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ViewedState {
     targets: HashMap<String, HashMap<String, u64>>,
+    /// diff_target_key -> (file_path -> hashes of the reviewed hunks)
+    #[serde(default)]
+    hunks: HashMap<String, HashMap<String, HashSet<u64>>>,
+    /// diff_target_key -> (folder_path -> expanded)
+    #[serde(default)]
+    folders: HashMap<String, HashMap<String, bool>>,
 }
 
 impl ViewedState {
@@ -41,6 +54,54 @@ impl ViewedState {
         }
     }
 
+    /// Check if the hunk of a file with this hash was marked reviewed.
+    pub fn is_hunk_reviewed(&self, target_key: &str, file_path: &str, hunk_hash: u64) -> bool {
+        self.hunks
+            .get(target_key)
+            .and_then(|files| files.get(file_path))
+            .is_some_and(|hashes| hashes.contains(&hunk_hash))
+    }
+
+    /// Mark a hunk of a file as reviewed or not.
+    pub fn set_hunk_reviewed(
+        &mut self,
+        target_key: &str,
+        file_path: &str,
+        hunk_hash: u64,
+        reviewed: bool,
+    ) {
+        let files = self.hunks.entry(target_key.to_string()).or_default();
+        let hashes = files.entry(file_path.to_string()).or_default();
+        if reviewed {
+            hashes.insert(hunk_hash);
+        } else {
+            hashes.remove(&hunk_hash);
+        }
+    }
+
+    /// The folders of the file tree opened or closed for a target.
+    pub fn folder_state(&self, target_key: &str) -> HashMap<String, bool> {
+        self.folders.get(target_key).cloned().unwrap_or_default()
+    }
+
+    /// Remember which folders of the file tree are open for a target.
+    pub fn set_folder_state(&mut self, target_key: &str, state: &HashMap<String, bool>) {
+        self.folders.insert(target_key.to_string(), state.clone());
+    }
+
+    /// Move what is kept under `legacy_key` to `key` where `key` has nothing
+    /// yet, so marks and folders saved under an older form of the key aren't
+    /// lost. Returns whether anything moved.
+    pub fn migrate_key(&mut self, legacy_key: &str, key: &str) -> bool {
+        if legacy_key == key {
+            return false;
+        }
+        let mut moved = move_entry(&mut self.targets, legacy_key, key);
+        moved |= move_entry(&mut self.hunks, legacy_key, key);
+        moved |= move_entry(&mut self.folders, legacy_key, key);
+        moved
+    }
+
     /// Load from disk. Returns default if missing or invalid.
     pub fn load() -> Self {
         let Some(path) = state_path() else {
@@ -82,17 +143,81 @@ pub fn hash_diff_content(hunks: &[crate::git::DiffHunk]) -> u64 {
     hasher.finish()
 }
 
+/// Compute a content hash for a hunk's lines. The header is left out, as
+/// its line numbers change when lines above the hunk do.
+pub fn hash_hunk(hunk: &crate::git::DiffHunk) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for line in &hunk.lines {
+        std::mem::discriminant(&line.line_type).hash(&mut hasher);
+        line.content.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
 /// Derive a stable key from the DiffTarget for persistence.
 pub fn target_key(target: &crate::cli::DiffTarget) -> String {
     match target {
         crate::cli::DiffTarget::DefaultBranch => "default-branch".to_string(),
         crate::cli::DiffTarget::Ref(r) => format!("ref:{r}"),
         crate::cli::DiffTarget::PullRequest(n) => format!("pr:{n}"),
+        crate::cli::DiffTarget::Refs { base, head } => format!("refs:{base}..{head}"),
+        crate::cli::DiffTarget::MergeBase { base, head } => format!("refs:{base}...{head}"),
+        crate::cli::DiffTarget::Commit(c) => format!("commit:{c}"),
+        // Same diff as the plain target, so the same viewed marks
+        crate::cli::DiffTarget::Log(None) => "default-branch".to_string(),
+        crate::cli::DiffTarget::Log(Some(r)) => format!("ref:{r}"),
+        crate::cli::DiffTarget::Stdin => "stdin".to_string(),
+        crate::cli::DiffTarget::Files { old, new } => {
+            format!("files:{}..{}", old.display(), new.display())
+        }
+        crate::cli::DiffTarget::Dirs { old, new } => {
+            format!("dirs:{}..{}", old.display(), new.display())
+        }
+        crate::cli::DiffTarget::Mbox(path) => format!("mbox:{}", path.display()),
+        crate::cli::DiffTarget::Patchset(id) => format!("srht:{}", id.url()),
+        crate::cli::DiffTarget::WorkingTree => "worktree".to_string(),
+        crate::cli::DiffTarget::Staged => "staged".to_string(),
+    }
+}
+
+/// Key of `target` in the repository checked out at `repo_root`, so the
+/// same branch in two repositories keeps its own marks. Diffs not made in a
+/// repository are keyed by the target alone.
+pub fn repo_target_key(repo_root: Option<&Path>, target: &crate::cli::DiffTarget) -> String {
+    match repo_root {
+        Some(root) => format!("{}#{}", root.display(), target_key(target)),
+        None => target_key(target),
+    }
+}
+
+/// [`repo_target_key`] of `target`, taking over in `state` what was kept for
+/// it by the target alone, as it was before keys named the repository.
+pub fn migrated_target_key(
+    state: &mut ViewedState,
+    repo_root: Option<&Path>,
+    target: &crate::cli::DiffTarget,
+) -> String {
+    let key = repo_target_key(repo_root, target);
+    state.migrate_key(&target_key(target), &key);
+    key
+}
+
+/// Move `map`'s entry from `from` to `to` unless `to` has one already.
+fn move_entry<V>(map: &mut HashMap<String, V>, from: &str, to: &str) -> bool {
+    if map.contains_key(to) {
+        return false;
+    }
+    match map.remove(from) {
+        Some(value) => {
+            map.insert(to.to_string(), value);
+            true
+        }
+        None => false,
     }
 }
 
 fn state_path() -> Option<PathBuf> {
-    dirs::config_dir().map(|p| p.join("lado").join("viewed_state.json"))
+    crate::config::config_dir().map(|p| p.join("viewed_state.json"))
 }
 
 #[cfg(test)]
@@ -137,11 +262,85 @@ mod tests {
         assert!(loaded.is_viewed("pr:42", "README.md", 555));
     }
 
+    #[test]
+    fn test_hunk_reviewed() {
+        let mut state = ViewedState::default();
+        state.set_hunk_reviewed("pr:42", "src/app.rs", 7, true);
+        assert!(state.is_hunk_reviewed("pr:42", "src/app.rs", 7));
+        assert!(!state.is_hunk_reviewed("pr:42", "src/app.rs", 8));
+        assert!(!state.is_hunk_reviewed("pr:43", "src/app.rs", 7));
+
+        // State saved before hunks were tracked still loads
+        let loaded: ViewedState = serde_json::from_str(r#"{"targets":{}}"#).unwrap();
+        assert!(!loaded.is_hunk_reviewed("pr:42", "src/app.rs", 7));
+
+        state.set_hunk_reviewed("pr:42", "src/app.rs", 7, false);
+        assert!(!state.is_hunk_reviewed("pr:42", "src/app.rs", 7));
+    }
+
+    #[test]
+    fn test_folder_state() {
+        let mut state = ViewedState::default();
+        let folders = HashMap::from([("src".to_string(), false), ("ui".to_string(), true)]);
+        state.set_folder_state("pr:42", &folders);
+
+        let json = serde_json::to_string(&state).unwrap();
+        let loaded: ViewedState = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.folder_state("pr:42"), folders);
+        assert!(loaded.folder_state("pr:43").is_empty());
+    }
+
     #[test]
     fn test_target_key_variants() {
         use crate::cli::DiffTarget;
         assert_eq!(target_key(&DiffTarget::DefaultBranch), "default-branch");
         assert_eq!(target_key(&DiffTarget::Ref("feature".into())), "ref:feature");
         assert_eq!(target_key(&DiffTarget::PullRequest(42)), "pr:42");
+        let refs = DiffTarget::Refs {
+            base: "main".into(),
+            head: "feature".into(),
+        };
+        assert_eq!(target_key(&refs), "refs:main..feature");
+        let dirs = DiffTarget::Dirs {
+            old: "v1".into(),
+            new: "v2".into(),
+        };
+        assert_eq!(target_key(&dirs), "dirs:v1..v2");
+    }
+
+    #[test]
+    fn test_repo_target_key() {
+        use crate::cli::DiffTarget;
+        let target = DiffTarget::Ref("main".into());
+        let here = repo_target_key(Some(Path::new("/src/lado")), &target);
+        let there = repo_target_key(Some(Path::new("/src/other")), &target);
+        assert_eq!(here, "/src/lado#ref:main");
+        assert_ne!(here, there);
+        assert_eq!(repo_target_key(None, &DiffTarget::Stdin), "stdin");
+    }
+
+    #[test]
+    fn test_migrated_target_key() {
+        use crate::cli::DiffTarget;
+        let target = DiffTarget::Ref("main".into());
+        let folders = HashMap::from([("src".to_string(), false)]);
+        let mut state = ViewedState::default();
+        state.set_viewed("ref:main", "src/app.rs", 1);
+        state.set_hunk_reviewed("ref:main", "src/app.rs", 2, true);
+        state.set_folder_state("ref:main", &folders);
+
+        let key = migrated_target_key(&mut state, Some(Path::new("/src/lado")), &target);
+        assert_eq!(key, "/src/lado#ref:main");
+        assert!(state.is_viewed(&key, "src/app.rs", 1));
+        assert!(state.is_hunk_reviewed(&key, "src/app.rs", 2));
+        assert_eq!(state.folder_state(&key), folders);
+        assert!(!state.is_viewed("ref:main", "src/app.rs", 1));
+
+        // Marks made under the new key win over whatever is left under the
+        // legacy one
+        state.set_viewed("ref:main", "src/app.rs", 3);
+        let key = migrated_target_key(&mut state, Some(Path::new("/src/lado")), &target);
+        assert!(state.is_viewed(&key, "src/app.rs", 1));
+        assert!(state.is_viewed("ref:main", "src/app.rs", 3));
     }
 }
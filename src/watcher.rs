@@ -0,0 +1,226 @@
+//! Refreshing the diff when what it is made from changes, so lado never
+//! shows a stale review and can stay open as a live view of what is being
+//! changed.
+//!
+//! Diffs of files on disk (`--files`, `--dirs`) are watched for changes of
+//! those files. Diffs of commits are watched for HEAD moving and refs
+//! changing (a commit, checkout or fetch), not the working tree, which
+//! doesn't change them; uncommitted changes (`--worktree`, `--staged`) for
+//! that and the working tree or index changing too. Bursts of events (an editor saving, a fetch
+//! updating many refs) trigger a single refresh once things are quiet for
+//! [`DEBOUNCE`]. `--no-watch` turns this off, leaving the toolbar's refresh
+//! button.
+
+use crate::cli::DiffTarget;
+use anyhow::Result;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Quiet time after the last change before refreshing
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Something to watch
+#[derive(Debug, PartialEq)]
+enum Watch {
+    /// Files, through their directory: editors save by replacing the file,
+    /// which ends a watch on the file itself
+    Files { dir: PathBuf, files: Vec<PathBuf> },
+    /// Everything in a directory tree
+    Dir(PathBuf),
+    /// HEAD and the refs of a repository, and its index if `index`
+    Refs { git_dir: PathBuf, index: bool },
+}
+
+impl Watch {
+    fn dirs(&self) -> Vec<(PathBuf, RecursiveMode)> {
+        match self {
+            Watch::Files { dir, .. } => vec![(dir.clone(), RecursiveMode::NonRecursive)],
+            Watch::Dir(dir) => vec![(dir.clone(), RecursiveMode::Recursive)],
+            // Not the objects, which a fetch writes thousands of
+            Watch::Refs { git_dir, .. } => vec![
+                (git_dir.clone(), RecursiveMode::NonRecursive),
+                (git_dir.join("refs"), RecursiveMode::Recursive),
+            ],
+        }
+    }
+
+    /// Whether a change of `path` can change the diff
+    fn matters(&self, path: &Path) -> bool {
+        match self {
+            Watch::Files { files, .. } => files.iter().any(|f| f == path),
+            Watch::Dir(dir) => path.strip_prefix(dir).is_ok_and(|inner| {
+                // Repositories inside it change on every git command
+                !inner
+                    .components()
+                    .any(|c| c == Component::Normal(".git".as_ref()))
+            }),
+            Watch::Refs { git_dir, index } => path.strip_prefix(git_dir).is_ok_and(|inner| {
+                // Refs are written to a lock file first
+                let locked = inner.extension().is_some_and(|ext| ext == "lock");
+                !locked
+                    && (inner == Path::new("HEAD")
+                        || (*index && inner == Path::new("index"))
+                        || inner == Path::new("packed-refs")
+                        || inner.starts_with("refs"))
+            }),
+        }
+    }
+}
+
+/// What to watch for `target`, diffed in the repository at `git_dir` with
+/// its working tree at `workdir`
+fn watches(target: &DiffTarget, git_dir: &Path, workdir: Option<&Path>) -> Vec<Watch> {
+    match target {
+        DiffTarget::Files { old, new } => [old, new]
+            .into_iter()
+            .filter_map(|file| {
+                let file = std::path::absolute(file).ok()?;
+                Some(Watch::Files {
+                    dir: file.parent()?.to_path_buf(),
+                    files: vec![file],
+                })
+            })
+            .collect(),
+        DiffTarget::Dirs { old, new } => [old, new]
+            .into_iter()
+            .filter_map(|dir| Some(Watch::Dir(std::path::absolute(dir).ok()?)))
+            .collect(),
+        // A patch series is read (or fetched) once
+        DiffTarget::Stdin | DiffTarget::Mbox(_) | DiffTarget::Patchset(_) => Vec::new(),
+        DiffTarget::WorkingTree => workdir
+            .map(|workdir| Watch::Dir(workdir.to_path_buf()))
+            .into_iter()
+            .chain([Watch::Refs {
+                git_dir: git_dir.to_path_buf(),
+                index: false,
+            }])
+            .collect(),
+        _ => vec![Watch::Refs {
+            git_dir: git_dir.to_path_buf(),
+            index: matches!(target, DiffTarget::Staged),
+        }],
+    }
+}
+
+/// Call `on_change` (on a thread of its own) whenever what `target` is
+/// diffed from changes, for as long as the returned watcher lives. `None` if
+/// there is nothing to watch.
+pub fn watch(
+    target: &DiffTarget,
+    git_dir: &Path,
+    workdir: Option<&Path>,
+    on_change: impl Fn() + Send + 'static,
+) -> Result<Option<RecommendedWatcher>> {
+    let mut watches = watches(target, git_dir, workdir);
+    // The in-memory stand-in for a Mercurial working copy has no refs
+    watches.retain(|watch| !matches!(watch, Watch::Refs { git_dir, .. } if !git_dir.is_dir()));
+    if watches.is_empty() {
+        return Ok(None);
+    }
+
+    let (sender, receiver) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    for (dir, mode) in watches.iter().flat_map(Watch::dirs) {
+        watcher.watch(&dir, mode)?;
+    }
+
+    let relevant = move |event: notify::Result<Event>| match event {
+        Ok(event) => {
+            !event.kind.is_access()
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| watches.iter().any(|watch| watch.matters(path)))
+        }
+        Err(e) => {
+            tracing::debug!("watch error: {}", e);
+            false
+        }
+    };
+    std::thread::spawn(move || {
+        // Ends when the watcher, and with it the sender, is dropped
+        while let Ok(event) = receiver.recv() {
+            if !relevant(event) {
+                continue;
+            }
+            loop {
+                match receiver.recv_timeout(DEBOUNCE) {
+                    Ok(_) => continue,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            tracing::debug!("diffed files or refs changed");
+            on_change();
+        }
+    });
+    Ok(Some(watcher))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIT_DIR: &str = "/src/lado/.git";
+
+    fn matters(watches: &[Watch], path: &str) -> bool {
+        watches.iter().any(|watch| watch.matters(Path::new(path)))
+    }
+
+    #[test]
+    fn test_watch_files() {
+        let target = DiffTarget::Files {
+            old: PathBuf::from("/tmp/a/old.rs"),
+            new: PathBuf::from("/tmp/b/new.rs"),
+        };
+        let files = watches(&target, Path::new(GIT_DIR), None);
+        assert_eq!(
+            files[1],
+            Watch::Files {
+                dir: PathBuf::from("/tmp/b"),
+                files: vec![PathBuf::from("/tmp/b/new.rs")],
+            }
+        );
+        assert!(matters(&files, "/tmp/b/new.rs"));
+        assert!(!matters(&files, "/tmp/b/other.rs"));
+
+        let target = DiffTarget::Dirs {
+            old: PathBuf::from("/tmp/old"),
+            new: PathBuf::from("/tmp/new"),
+        };
+        let dirs = watches(&target, Path::new(GIT_DIR), None);
+        assert!(matters(&dirs, "/tmp/new/src/lib.rs"));
+        assert!(!matters(&dirs, "/tmp/new/.git/index"));
+        assert!(!matters(&dirs, "/tmp/newer/lib.rs"));
+    }
+
+    #[test]
+    fn test_watch_refs() {
+        assert!(watches(&DiffTarget::Stdin, Path::new(GIT_DIR), None).is_empty());
+
+        let refs = watches(&DiffTarget::Ref("main".to_string()), Path::new(GIT_DIR), None);
+        assert!(matters(&refs, "/src/lado/.git/HEAD"));
+        assert!(matters(&refs, "/src/lado/.git/packed-refs"));
+        assert!(matters(&refs, "/src/lado/.git/refs/remotes/origin/main"));
+        assert!(!matters(&refs, "/src/lado/.git/refs/heads/main.lock"));
+        assert!(!matters(&refs, "/src/lado/.git/index"));
+        assert!(!matters(&refs, "/src/lado/src/main.rs"));
+    }
+
+    #[test]
+    fn test_watch_uncommitted() {
+        let workdir = Path::new("/src/lado");
+        let target = DiffTarget::WorkingTree;
+        let worktree = watches(&target, Path::new(GIT_DIR), Some(workdir));
+        assert!(matters(&worktree, "/src/lado/src/main.rs"));
+        assert!(matters(&worktree, "/src/lado/.git/HEAD"));
+        assert!(!matters(&worktree, "/src/lado/.git/objects/ab/cdef"));
+
+        let staged = watches(&DiffTarget::Staged, Path::new(GIT_DIR), Some(workdir));
+        assert!(matters(&staged, "/src/lado/.git/index"));
+        assert!(!matters(&staged, "/src/lado/.git/index.lock"));
+        assert!(!matters(&staged, "/src/lado/src/main.rs"));
+    }
+}